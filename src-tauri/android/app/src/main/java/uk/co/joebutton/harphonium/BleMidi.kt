@@ -0,0 +1,130 @@
+package uk.co.joebutton.harphonium
+
+import android.Manifest
+import android.annotation.SuppressLint
+import android.bluetooth.BluetoothDevice
+import android.bluetooth.BluetoothManager
+import android.bluetooth.le.ScanCallback
+import android.bluetooth.le.ScanFilter
+import android.bluetooth.le.ScanResult
+import android.bluetooth.le.ScanSettings
+import android.content.Context
+import android.content.pm.PackageManager
+import android.media.midi.MidiDevice
+import android.media.midi.MidiDeviceInfo
+import android.media.midi.MidiManager
+import android.media.midi.MidiOutputPort
+import android.media.midi.MidiReceiver
+import android.os.Build
+import android.os.Handler
+import android.os.Looper
+import android.os.ParcelUuid
+
+// Bluetooth LE MIDI controllers. Scans for devices advertising the BLE MIDI
+// service and opens them through MidiManager, which unwraps the BLE packets
+// into plain MIDI bytes. Those go straight to the shared parser in Rust
+// (src-tauri/src/audio/midi_android.rs). Devices are known by their address
+class BleMidi(private val activity: MainActivity) {
+    companion object {
+        val MIDI_SERVICE: ParcelUuid = ParcelUuid.fromString("03B80E5A-EDE8-4B33-A751-6CE34EC4C700")
+        const val PERMISSION_REQUEST = 1918
+    }
+
+    private val midiManager = activity.getSystemService(Context.MIDI_SERVICE) as MidiManager?
+    private val bluetooth =
+        (activity.getSystemService(Context.BLUETOOTH_SERVICE) as BluetoothManager?)?.adapter
+    private val handler = Handler(Looper.getMainLooper())
+    private val found = mutableMapOf<String, BluetoothDevice>()
+    private val open = mutableMapOf<String, Pair<MidiDevice, MidiOutputPort>>()
+    private var scanning = false
+
+    private val scanCallback = object : ScanCallback() {
+        @SuppressLint("MissingPermission")
+        override fun onScanResult(callbackType: Int, result: ScanResult) {
+            val device = result.device
+            if (found.put(device.address, device) == null) {
+                activity.nativeMidiDeviceAdded(device.address, device.name ?: device.address)
+            }
+        }
+    }
+
+    // Bluetooth devices that drop out, e.g. switched off or out of range
+    private val deviceCallback = object : MidiManager.DeviceCallback() {
+        override fun onDeviceRemoved(info: MidiDeviceInfo) {
+            val device = info.properties.getParcelable<BluetoothDevice>(
+                MidiDeviceInfo.PROPERTY_BLUETOOTH_DEVICE
+            ) ?: return
+            close(device.address)
+            found.remove(device.address)
+            activity.nativeMidiDeviceRemoved(device.address)
+        }
+    }
+
+    init {
+        midiManager?.registerDeviceCallback(deviceCallback, handler)
+    }
+
+    private fun permissions(): Array<String> =
+        if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.S) {
+            arrayOf(Manifest.permission.BLUETOOTH_SCAN, Manifest.permission.BLUETOOTH_CONNECT)
+        } else {
+            arrayOf(Manifest.permission.ACCESS_FINE_LOCATION)
+        }
+
+    // Look for devices for `seconds`. Without the permissions this asks for
+    // them instead, and the scan has to be asked for again once granted
+    @SuppressLint("MissingPermission")
+    fun scan(seconds: Int) {
+        val missing = permissions().filter {
+            activity.checkSelfPermission(it) != PackageManager.PERMISSION_GRANTED
+        }
+        if (missing.isNotEmpty()) {
+            activity.requestPermissions(missing.toTypedArray(), PERMISSION_REQUEST)
+            return
+        }
+        val scanner = bluetooth?.bluetoothLeScanner ?: return
+        if (scanning) return
+        val filter = ScanFilter.Builder().setServiceUuid(MIDI_SERVICE).build()
+        val settings = ScanSettings.Builder()
+            .setScanMode(ScanSettings.SCAN_MODE_LOW_LATENCY)
+            .build()
+        scanner.startScan(listOf(filter), settings, scanCallback)
+        scanning = true
+        handler.postDelayed({
+            scanner.stopScan(scanCallback)
+            scanning = false
+        }, seconds * 1000L)
+    }
+
+    // Open a device found by the scan, sending its bytes to Rust as `source`
+    fun connect(address: String, source: Int) {
+        val device = found[address]
+        val manager = midiManager
+        if (device == null || manager == null) {
+            activity.nativeMidiDeviceFailed(address)
+            return
+        }
+        manager.openBluetoothDevice(device, { midiDevice ->
+            // The device's output port is our input
+            val port = midiDevice?.openOutputPort(0)
+            if (midiDevice == null || port == null) {
+                midiDevice?.close()
+                activity.nativeMidiDeviceFailed(address)
+                return@openBluetoothDevice
+            }
+            port.connect(object : MidiReceiver() {
+                override fun onSend(msg: ByteArray, offset: Int, count: Int, timestamp: Long) {
+                    activity.nativeMidiData(source, msg, offset, count)
+                }
+            })
+            open[address] = Pair(midiDevice, port)
+        }, handler)
+    }
+
+    fun close(address: String) {
+        open.remove(address)?.let { (device, port) ->
+            port.close()
+            device.close()
+        }
+    }
+}
@@ -1,14 +1,82 @@
 package uk.co.joebutton.harphonium
 
+import android.content.Intent
+import android.media.midi.MidiDevice
 import android.os.Bundle
+import androidx.core.content.FileProvider
 import androidx.core.view.WindowCompat
 import androidx.core.view.WindowInsetsCompat
 import androidx.core.view.WindowInsetsControllerCompat
+import java.io.File
 
 class MainActivity : TauriActivity() {
+    // Native audio entry points (see src-tauri/src/jni_bridge.rs). These skip
+    // Tauri IPC so the touch layer can drive expression parameters directly.
+    external fun nativePlayNote(frequency: Float)
+    external fun nativeStopNote()
+    external fun nativeNoteOn(pointerId: Int, frequency: Float, velocity: Float)
+    external fun nativeNoteOff(pointerId: Int)
+    external fun nativeSetFrequency(frequency: Float)
+    external fun nativeSetMasterVolume(volume: Float)
+    external fun nativeSetAttack(attack: Float)
+    external fun nativeSetDecay(decay: Float)
+    external fun nativeSetSustain(sustain: Float)
+    external fun nativeSetRelease(release: Float)
+    external fun nativeSetDelayTime(delayTime: Float)
+    external fun nativeSetDelayFeedback(delayFeedback: Float)
+    external fun nativeSetDelayMix(delayMix: Float)
+    external fun nativeSetFilterCutoff(cutoff: Float)
+    external fun nativeSetFilterResonance(resonance: Float)
+
+    // MIDI devices, see src-tauri/src/audio/midi_android.rs
+    external fun nativeMidiDeviceAdded(id: String, name: String)
+    external fun nativeMidiDeviceRemoved(id: String)
+    external fun nativeMidiDeviceFailed(id: String)
+    external fun nativeMidiData(source: Int, data: ByteArray, offset: Int, count: Int)
+    external fun nativeOpenUsbMidi(id: String, source: Int, device: MidiDevice): Boolean
+
+    private val bleMidi by lazy { BleMidi(this) }
+    private var usbMidi: UsbMidi? = null
+
+    // Called from Rust (src-tauri/src/export.rs) to hand an exported file to
+    // another app. Files are shared from the cache through the FileProvider
+    fun shareAudioFile(path: String, mimeType: String) {
+        val uri = FileProvider.getUriForFile(this, "$packageName.fileprovider", File(path))
+        val intent = Intent(Intent.ACTION_SEND).apply {
+            type = mimeType
+            putExtra(Intent.EXTRA_STREAM, uri)
+            addFlags(Intent.FLAG_GRANT_READ_URI_PERMISSION)
+        }
+        runOnUiThread {
+            startActivity(Intent.createChooser(intent, null))
+        }
+    }
+
+    // Called from Rust (src-tauri/src/audio/midi_android.rs), on any thread
+    fun scanBluetoothMidi(seconds: Int) {
+        runOnUiThread { bleMidi.scan(seconds) }
+    }
+
+    fun connectMidiDevice(id: String, source: Int) {
+        runOnUiThread {
+            if (UsbMidi.owns(id)) usbMidi?.connect(id, source) else bleMidi.connect(id, source)
+        }
+    }
+
+    fun disconnectMidiDevice(id: String) {
+        runOnUiThread {
+            if (UsbMidi.owns(id)) usbMidi?.close(id) else bleMidi.close(id)
+        }
+    }
+
     override fun onCreate(savedInstanceState: Bundle?) {
         super.onCreate(savedInstanceState)
 
+        // USB MIDI devices are reported as they're plugged in (AMidi needs Android 10)
+        if (android.os.Build.VERSION.SDK_INT >= android.os.Build.VERSION_CODES.Q) {
+            usbMidi = UsbMidi(this)
+        }
+
         // Allow content to extend under the system bars
         WindowCompat.setDecorFitsSystemWindows(window, false)
 
@@ -0,0 +1,85 @@
+package uk.co.joebutton.harphonium
+
+import android.content.Context
+import android.media.midi.MidiDevice
+import android.media.midi.MidiDeviceInfo
+import android.media.midi.MidiManager
+import android.os.Handler
+import android.os.Looper
+
+// USB MIDI devices. Reports them to Rust as they're plugged in and out, and
+// opens them when asked; the open device is handed to Rust, which reads it
+// through AMidi (src-tauri/src/audio/amidi.rs) without coming back through
+// Java for each message. Needs Android 10
+class UsbMidi(private val activity: MainActivity) {
+    companion object {
+        private const val PREFIX = "usb:"
+
+        fun owns(id: String) = id.startsWith(PREFIX)
+    }
+
+    private val midiManager = activity.getSystemService(Context.MIDI_SERVICE) as MidiManager?
+    private val handler = Handler(Looper.getMainLooper())
+    private val found = mutableMapOf<String, MidiDeviceInfo>()
+    private val open = mutableMapOf<String, MidiDevice>()
+
+    private fun idOf(info: MidiDeviceInfo) = "$PREFIX${info.id}"
+
+    private fun nameOf(info: MidiDeviceInfo): String =
+        info.properties.getString(MidiDeviceInfo.PROPERTY_NAME)
+            ?: info.properties.getString(MidiDeviceInfo.PROPERTY_PRODUCT)
+            ?: "USB MIDI ${info.id}"
+
+    private fun added(info: MidiDeviceInfo) {
+        // Only devices that send us MIDI
+        if (info.type != MidiDeviceInfo.TYPE_USB || info.outputPortCount == 0) return
+        val id = idOf(info)
+        if (found.put(id, info) == null) {
+            activity.nativeMidiDeviceAdded(id, nameOf(info))
+        }
+    }
+
+    private val deviceCallback = object : MidiManager.DeviceCallback() {
+        override fun onDeviceAdded(info: MidiDeviceInfo) = added(info)
+
+        override fun onDeviceRemoved(info: MidiDeviceInfo) {
+            val id = idOf(info)
+            if (found.remove(id) == null) return
+            // Rust stops reading before the device is closed under it
+            activity.nativeMidiDeviceRemoved(id)
+            close(id)
+        }
+    }
+
+    init {
+        midiManager?.let { manager ->
+            // Devices already plugged in, once the activity has finished
+            // setting up, as Rust may connect them straight away
+            handler.post { manager.devices.forEach { added(it) } }
+            manager.registerDeviceCallback(deviceCallback, handler)
+        }
+    }
+
+    // Open a device, handing it to Rust to read as `source`
+    fun connect(id: String, source: Int) {
+        val info = found[id]
+        val manager = midiManager
+        if (info == null || manager == null) {
+            activity.nativeMidiDeviceFailed(id)
+            return
+        }
+        manager.openDevice(info, { device ->
+            if (device == null) {
+                activity.nativeMidiDeviceFailed(id)
+            } else if (activity.nativeOpenUsbMidi(id, source, device)) {
+                open[id] = device
+            } else {
+                device.close()
+            }
+        }, handler)
+    }
+
+    fun close(id: String) {
+        open.remove(id)?.close()
+    }
+}
@@ -0,0 +1,104 @@
+// Benchmarks for the audio callback's work: FunDSPSynth::fill_buffer with a
+// few notes held, across buffer sizes, waveforms and effect setups. Run with
+// `cargo bench`; on Android, build the bench for the device's target and run
+// the binary there, phone CPUs are where dropouts happen first
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use harphonium_lib::{AudioEvent, FunDSPSynth, Patch, Waveform};
+
+const SAMPLE_RATE: f32 = 48000.0;
+const BUFFER_SIZES: [usize; 3] = [64, 256, 1024];
+/// A chord, so the benchmarks include some voice allocation
+const NOTES: [f32; 4] = [220.0, 277.18, 329.63, 440.0];
+
+/// Effect setups, from the bare voices to everything on
+fn effect_setups() -> Vec<(&'static str, Patch)> {
+    let dry = Patch {
+        delay_mix: 0.0,
+        ..Patch::default()
+    };
+    vec![
+        ("dry", dry),
+        ("default", Patch::default()),
+        (
+            "everything",
+            Patch {
+                delay_mix: 0.4,
+                delay_feedback: 0.6,
+                unison_voices: 4,
+                ring_mod_mix: 0.3,
+                vibrato_depth: 20.0,
+                filter_lfo_depth: 1.0,
+                stereo_width: 1.5,
+                soft_clip: true,
+                soft_clip_drive: 0.5,
+                ..Patch::default()
+            },
+        ),
+    ]
+}
+
+/// A synth with `patch` loaded and the chord held, past its attack
+fn playing_synth(patch: &Patch) -> FunDSPSynth {
+    // Driven directly, nothing comes through the queue
+    let (_producer, consumer) = rtrb::RingBuffer::new(1);
+    let mut synth = FunDSPSynth::new(SAMPLE_RATE, consumer).unwrap();
+    synth.apply_patch(patch);
+    for (note_id, &frequency) in NOTES.iter().enumerate() {
+        synth.handle_event(AudioEvent::PlayNote {
+            note_id: note_id as u32,
+            frequency,
+            velocity: 0.8,
+        });
+    }
+    let mut warm_up = vec![0.0; SAMPLE_RATE as usize / 10];
+    synth.fill_buffer(&mut warm_up);
+    synth
+}
+
+fn buffer_sizes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("buffer_size");
+    for size in BUFFER_SIZES {
+        let mut synth = playing_synth(&Patch::default());
+        let mut buffer = vec![0.0; size];
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| synth.fill_buffer(&mut buffer))
+        });
+    }
+    group.finish();
+}
+
+fn waveforms(c: &mut Criterion) {
+    let mut group = c.benchmark_group("waveform");
+    for waveform in [
+        Waveform::Sine,
+        Waveform::Square,
+        Waveform::Sawtooth,
+        Waveform::Triangle,
+    ] {
+        let mut synth = playing_synth(&Patch {
+            waveform,
+            ..Patch::default()
+        });
+        let mut buffer = vec![0.0; 256];
+        group.throughput(Throughput::Elements(256));
+        group.bench_function(format!("{:?}", waveform).to_lowercase(), |b| {
+            b.iter(|| synth.fill_buffer(&mut buffer))
+        });
+    }
+    group.finish();
+}
+
+fn effects(c: &mut Criterion) {
+    let mut group = c.benchmark_group("effects");
+    for (name, patch) in effect_setups() {
+        let mut synth = playing_synth(&patch);
+        let mut buffer = vec![0.0; 256];
+        group.throughput(Throughput::Elements(256));
+        group.bench_function(name, |b| b.iter(|| synth.fill_buffer(&mut buffer)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, buffer_sizes, waveforms, effects);
+criterion_main!(benches);
@@ -1,5 +1,8 @@
 fn main() {
-    tauri_build::build();
+    // The web build is just the synth, there's no Tauri app to configure
+    if std::env::var("CARGO_CFG_TARGET_ARCH").as_deref() != Ok("wasm32") {
+        tauri_build::build();
+    }
 
     // Add Android-specific build configuration to fix __cxa_pure_virtual issue
     if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("android") {
@@ -0,0 +1,165 @@
+// USB MIDI input through the NDK's AMidi, which reads a device's bytes
+// straight from the MIDI service rather than through a Java callback per
+// message. The activity opens the device (see UsbMidi.kt) and hands it over.
+// libamidi is only there from Android 10, so it's loaded when first needed
+// rather than linked, which would stop the app starting on older versions
+use super::midi::{queue_events, MidiParser};
+use jni::objects::JObject;
+use jni::JNIEnv;
+use libloading::Library;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often the port is checked for bytes. AMidi has no blocking read
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+const AMIDI_OPCODE_DATA: i32 = 1;
+
+#[repr(C)]
+struct AMidiDevice {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct AMidiOutputPort {
+    _private: [u8; 0],
+}
+
+/// The AMidi functions we use, from libamidi.so
+struct AMidi {
+    device_from_java: unsafe extern "C" fn(
+        *mut jni::sys::JNIEnv,
+        jni::sys::jobject,
+        *mut *mut AMidiDevice,
+    ) -> i32,
+    device_release: unsafe extern "C" fn(*const AMidiDevice) -> i32,
+    output_port_open:
+        unsafe extern "C" fn(*const AMidiDevice, i32, *mut *mut AMidiOutputPort) -> i32,
+    output_port_receive: unsafe extern "C" fn(
+        *const AMidiOutputPort,
+        *mut i32,
+        *mut u8,
+        usize,
+        *mut usize,
+        *mut i64,
+    ) -> isize,
+    output_port_close: unsafe extern "C" fn(*const AMidiOutputPort),
+    _library: Library,
+}
+
+static AMIDI: OnceLock<Result<AMidi, String>> = OnceLock::new();
+
+unsafe fn symbol<T: Copy>(library: &Library, name: &[u8]) -> Result<T, String> {
+    library
+        .get::<T>(name)
+        .map(|symbol| *symbol)
+        .map_err(|e| e.to_string())
+}
+
+fn amidi() -> Result<&'static AMidi, String> {
+    AMIDI
+        .get_or_init(|| unsafe {
+            let library = Library::new("libamidi.so")
+                .map_err(|e| format!("USB MIDI needs Android 10 or later: {}", e))?;
+            Ok(AMidi {
+                device_from_java: symbol(&library, b"AMidiDevice_fromJava\0")?,
+                device_release: symbol(&library, b"AMidiDevice_release\0")?,
+                output_port_open: symbol(&library, b"AMidiOutputPort_open\0")?,
+                output_port_receive: symbol(&library, b"AMidiOutputPort_receive\0")?,
+                output_port_close: symbol(&library, b"AMidiOutputPort_close\0")?,
+                _library: library,
+            })
+        })
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+/// The device and port, owned by the reading thread
+struct Handles {
+    device: *mut AMidiDevice,
+    port: *mut AMidiOutputPort,
+}
+
+// AMidi handles may be used from any thread
+unsafe impl Send for Handles {}
+
+/// One USB device being read. Dropping it stops the reading and closes the
+/// port, releasing the device's notes
+pub struct UsbMidiInput {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UsbMidiInput {
+    /// Read the device's first output port (its MIDI out, our input) on a
+    /// thread of its own, parsing as `source`. `device` is the Java MidiDevice
+    pub fn open(env: &mut JNIEnv, device: &JObject, source: u32) -> Result<Self, String> {
+        let amidi = amidi()?;
+        let mut midi_device = std::ptr::null_mut();
+        let status =
+            unsafe { (amidi.device_from_java)(env.get_raw(), device.as_raw(), &mut midi_device) };
+        if status != 0 {
+            return Err(format!("AMidiDevice_fromJava failed ({})", status));
+        }
+        let mut port = std::ptr::null_mut();
+        let status = unsafe { (amidi.output_port_open)(midi_device, 0, &mut port) };
+        if status != 0 {
+            unsafe { (amidi.device_release)(midi_device) };
+            return Err(format!("AMidiOutputPort_open failed ({})", status));
+        }
+
+        let handles = Handles {
+            device: midi_device,
+            port,
+        };
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let thread = std::thread::spawn(move || {
+            // The whole struct, not its (non-Send) fields
+            let handles = handles;
+            let mut parser = MidiParser::new(source);
+            let mut buffer = [0u8; 256];
+            while thread_running.load(Ordering::Relaxed) {
+                loop {
+                    let (mut opcode, mut len, mut timestamp) = (0, 0, 0);
+                    let received = unsafe {
+                        (amidi.output_port_receive)(
+                            handles.port,
+                            &mut opcode,
+                            buffer.as_mut_ptr(),
+                            buffer.len(),
+                            &mut len,
+                            &mut timestamp,
+                        )
+                    };
+                    if received <= 0 {
+                        break;
+                    }
+                    if opcode == AMIDI_OPCODE_DATA {
+                        queue_events(parser.feed(&buffer[..len.min(buffer.len())]));
+                    }
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            queue_events(parser.release_all());
+            unsafe {
+                (amidi.output_port_close)(handles.port);
+                (amidi.device_release)(handles.device);
+            }
+        });
+        Ok(UsbMidiInput {
+            running,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for UsbMidiInput {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -1,164 +1,587 @@
 // Android audio implementation using oboe with FunDSP integration
+use super::input::InputProducer;
+use super::stats::STATS;
 use super::synthesis::FunDSPSynth;
+use super::{emit_event, AudioConfigInfo, AudioDeviceInfo, DeviceChangedEvent, StreamSettings};
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info, warn};
 
 use oboe::{
-    AudioOutputCallback, AudioOutputStreamSafe, AudioStreamBuilder, DataCallbackResult,
-    PerformanceMode, SharingMode, AudioStreamSafe, AudioStream, AudioStreamBase
+    AudioInputCallback, AudioInputStreamSafe, AudioOutputCallback, AudioOutputStreamSafe,
+    AudioStream, AudioStreamAsync, AudioStreamBase, AudioStreamBuilder, AudioStreamSafe,
+    DataCallbackResult, Input, InputPreset, Output, PerformanceMode, SharingMode,
 };
-use std::cell::Cell;
-
-#[inline]
-pub fn enable_flush_denormals() {
-    // --- AArch64 (ARMv8, 64-bit): FPCR (FZ=bit24, FZ16=bit19) ---
-    #[cfg(target_arch = "aarch64")]
-    unsafe {
-        let mut fpcr: u64;
-        core::arch::asm!("mrs {0}, fpcr", out(reg) fpcr);
-        fpcr |= 1 << 24; // FZ
-        fpcr |= 1 << 19; // FZ16 (harmless if unused)
-        core::arch::asm!("msr fpcr, {0}", in(reg) fpcr);
-    }
-
-    // --- ARMv7/AArch32 (32-bit): FPSCR (FZ=bit24) ---
-    // Gate on any VFP generation that guarantees VMRS/VMSR exist.
-    #[cfg(all(
-        target_arch = "arm",
-        any(
-            target_feature = "vfp2",
-            target_feature = "vfp3",
-            target_feature = "vfp4",
-            target_feature = "fp-armv8"
-        )
-    ))]
-    unsafe {
-        let mut fpscr: u32;
-        core::arch::asm!("vmrs {0}, fpscr", out(reg) fpscr);
-        fpscr |= 1 << 24; // FZ
-        core::arch::asm!("vmsr fpscr, {0}", in(reg) fpscr);
-    }
-
-    // Optional: on other targets do nothing (keeps non-ARM builds happy).
-    #[cfg(not(any(target_arch = "aarch64", target_arch = "arm")))]
-    {
-        // no-op
-    }
+
+/// How often to retry opening the stream after a disconnect, if the first attempt failed
+const REOPEN_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Requests handled by the stream keeper thread
+enum StreamCommand {
+    Reconfigure {
+        settings: StreamSettings,
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    GetConfig {
+        reply: mpsc::Sender<Option<AudioConfigInfo>>,
+    },
+    Pause {
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    /// Resume a paused stream, or reopen it after a shutdown
+    Resume {
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    /// Close the stream and release the device
+    Shutdown {
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    /// Sent from the oboe error callback once the stream has been closed under
+    /// us, e.g. when Bluetooth headphones connect or the device is unplugged
+    Disconnected { error: String },
+    /// Open the microphone, feeding `input`
+    StartInput {
+        input: InputProducer,
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    StopInput {
+        reply: mpsc::Sender<Result<(), String>>,
+    },
 }
 
-thread_local! {
-    static DENORMALS_ENABLED: Cell<bool> = Cell::new(false);
+/// Handle to the thread that owns the output stream
+pub struct StreamController {
+    commands: mpsc::Sender<StreamCommand>,
+    /// Settings most recently requested by the user
+    settings: Mutex<StreamSettings>,
 }
 
-#[inline]
-pub fn enable_denormals_once_per_thread() {
-    DENORMALS_ENABLED.with(|flag| {
-        if !flag.get() {
-            enable_flush_denormals();
-            flag.set(true);
+impl StreamController {
+    pub fn select_device(&self, _name: Option<String>) -> Result<(), String> {
+        Err("Output device selection is not supported on Android".to_string())
+    }
+
+    pub fn select_host(&self, _name: Option<String>) -> Result<(), String> {
+        Err("Audio host selection is not supported on Android".to_string())
+    }
+
+    pub fn list_devices(&self) -> Result<Vec<AudioDeviceInfo>, String> {
+        Err("Output device selection is not supported on Android".to_string())
+    }
+
+    pub fn list_hosts(&self) -> Result<Vec<String>, String> {
+        Ok(vec!["Oboe".to_string()])
+    }
+
+    pub fn list_input_devices(&self) -> Result<Vec<AudioDeviceInfo>, String> {
+        Err("Input device selection is not supported on Android".to_string())
+    }
+
+    /// Start recording from the default input (needs the RECORD_AUDIO
+    /// permission) into `input`, replacing any input already running
+    pub fn start_input(
+        &self,
+        device_name: Option<String>,
+        input: InputProducer,
+    ) -> Result<(), String> {
+        if device_name.is_some() {
+            return Err("Input device selection is not supported on Android".to_string());
         }
-    });
+        self.request(|reply| StreamCommand::StartInput { input, reply })
+    }
+
+    pub fn stop_input(&self) -> Result<(), String> {
+        self.request(|reply| StreamCommand::StopInput { reply })
+    }
+
+    /// Request a sample rate, or 48kHz if `None`
+    pub fn set_sample_rate(&self, sample_rate: Option<u32>) -> Result<(), String> {
+        self.reconfigure(|settings| settings.sample_rate = sample_rate)
+    }
+
+    /// Request frames per callback, or the low latency default if `None`
+    pub fn set_buffer_size(&self, buffer_size: Option<u32>) -> Result<(), String> {
+        self.reconfigure(|settings| settings.buffer_size = buffer_size)
+    }
+
+    /// Ask oboe for an exclusive (MMAP) stream. Oboe falls back to shared
+    /// mode by itself if the device can't do it
+    pub fn set_exclusive_mode(&self, exclusive: bool) -> Result<(), String> {
+        self.reconfigure(|settings| settings.exclusive_mode = exclusive)
+    }
+
+    /// Oboe already runs low latency callbacks at real-time priority
+    pub fn set_realtime_priority(&self, _enabled: bool) -> Result<(), String> {
+        Err("Audio thread priority is managed by Oboe on Android".to_string())
+    }
+
+    /// What oboe actually gave us, if a stream is open
+    pub fn get_config(&self) -> Option<AudioConfigInfo> {
+        let (reply, response) = mpsc::channel();
+        self.commands
+            .send(StreamCommand::GetConfig { reply })
+            .ok()?;
+        response.recv().ok().flatten()
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        self.request(|reply| StreamCommand::Pause { reply })
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        self.request(|reply| StreamCommand::Resume { reply })
+    }
+
+    pub fn shutdown(&self) -> Result<(), String> {
+        self.request(|reply| StreamCommand::Shutdown { reply })
+    }
+
+    fn request(
+        &self,
+        command: impl FnOnce(mpsc::Sender<Result<(), String>>) -> StreamCommand,
+    ) -> Result<(), String> {
+        let (reply, response) = mpsc::channel();
+        self.commands
+            .send(command(reply))
+            .map_err(|_| "Audio stream thread has stopped".to_string())?;
+        response
+            .recv()
+            .map_err(|_| "Audio stream thread has stopped".to_string())?
+    }
+
+    fn reconfigure(&self, update: impl FnOnce(&mut StreamSettings)) -> Result<(), String> {
+        let mut current = self.settings.lock().unwrap();
+        let mut settings = current.clone();
+        update(&mut settings);
+
+        self.request(|reply| StreamCommand::Reconfigure {
+            settings: settings.clone(),
+            reply,
+        })?;
+
+        *current = settings;
+        Ok(())
+    }
 }
 
-pub fn start_audio_stream(
+/// State shared by the mono and stereo callbacks; never block in RT thread
+struct CallbackState {
     synth: Arc<Mutex<FunDSPSynth>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Initializing Android audio engine with Oboe - CALLBACK MODE");
+    /// Lets the error callback tell the stream keeper thread to rebuild the stream
+    keeper: mpsc::Sender<StreamCommand>,
+    /// Oboe's running xrun count for this stream, as of the last callback
+    xruns: i32,
+}
+
+impl CallbackState {
+    /// Run `fill` against the synth. Returns false if the synth was locked,
+    /// in which case the caller should output silence
+    fn render(&self, fill: impl FnOnce(&mut FunDSPSynth)) -> bool {
+        // Generate audio using FunDSP synthesis without locking if unavailable
+        match self.synth.try_lock() {
+            Ok(mut synth_guard) => {
+                fill(&mut synth_guard);
+                true
+            }
+            // Silence on contention to avoid glitches / priority inversion
+            Err(_) => {
+                STATS.record_lock_contention();
+                false
+            }
+        }
+    }
+
+    /// Add any new xruns oboe has seen on this stream to the stats
+    fn count_xruns(&mut self, stream: &dyn AudioOutputStreamSafe) {
+        if let Ok(xruns) = stream.get_xrun_count() {
+            if xruns > self.xruns {
+                STATS.record_underruns((xruns - self.xruns) as u64);
+            }
+            self.xruns = xruns;
+        }
+    }
 
-    // Create callback handler; never block in RT thread
-    struct AudioCallback {
-        synth: Arc<Mutex<FunDSPSynth>>,
+    fn closed(&self, error: oboe::Error) {
+        // Oboe has already closed the stream; we can't reopen it from this
+        // callback, so hand over to the keeper thread
+        warn!("⚠️ Oboe stream closed: {}", error);
+        let _ = self.keeper.send(StreamCommand::Disconnected {
+            error: error.to_string(),
+        });
     }
+}
 
-    impl AudioOutputCallback for AudioCallback {
-        type FrameType = (f32, oboe::Mono); // Correct frame type for mono
+struct MonoCallback(CallbackState);
 
-        fn on_audio_ready(
-            &mut self,
-            _stream: &mut dyn AudioOutputStreamSafe,
-            frames: &mut [f32],
-        ) -> DataCallbackResult {
-            enable_denormals_once_per_thread();
+impl AudioOutputCallback for MonoCallback {
+    type FrameType = (f32, oboe::Mono);
 
-            // Generate audio using FunDSP synthesis without locking if unavailable
-            match self.synth.try_lock() {
-                Ok(mut synth_guard) => {
-                    synth_guard.fill_buffer(frames);
-                }
-                Err(_) => {
-                    // Fill with silence on contention to avoid glitches / priority inversion
-                    // println!("⚠️ Audio synthesis locked, outputting silence");
-                    frames.fill(0.0);
-                }
-            }
-            DataCallbackResult::Continue
+    fn on_audio_ready(
+        &mut self,
+        stream: &mut dyn AudioOutputStreamSafe,
+        frames: &mut [f32],
+    ) -> DataCallbackResult {
+        self.0.count_xruns(stream);
+        if !self.0.render(|synth| synth.fill_buffer(frames)) {
+            frames.fill(0.0);
+        }
+        DataCallbackResult::Continue
+    }
+
+    fn on_error_after_close(
+        &mut self,
+        _stream: &mut dyn AudioOutputStreamSafe,
+        error: oboe::Error,
+    ) {
+        self.0.closed(error);
+    }
+}
+
+struct StereoCallback(CallbackState);
+
+impl AudioOutputCallback for StereoCallback {
+    type FrameType = (f32, oboe::Stereo);
+
+    fn on_audio_ready(
+        &mut self,
+        stream: &mut dyn AudioOutputStreamSafe,
+        frames: &mut [(f32, f32)],
+    ) -> DataCallbackResult {
+        self.0.count_xruns(stream);
+        if !self.0.render(|synth| synth.fill_stereo(frames)) {
+            frames.fill((0.0, 0.0));
         }
+        DataCallbackResult::Continue
+    }
+
+    fn on_error_after_close(
+        &mut self,
+        _stream: &mut dyn AudioOutputStreamSafe,
+        error: oboe::Error,
+    ) {
+        self.0.closed(error);
     }
+}
+
+struct InputCallback(InputProducer);
+
+impl AudioInputCallback for InputCallback {
+    type FrameType = (f32, oboe::Mono);
+
+    fn on_audio_ready(
+        &mut self,
+        _stream: &mut dyn AudioInputStreamSafe,
+        frames: &[f32],
+    ) -> DataCallbackResult {
+        self.0.push_interleaved(frames, 1);
+        DataCallbackResult::Continue
+    }
+}
 
-    println!("🚀 Android audio using FunDSP synthesis (Shared mode)");
+type InputStream = AudioStreamAsync<Input, InputCallback>;
+
+/// Open the microphone at the output's sample rate, unprocessed where the
+/// device allows, since it's going through our own effects
+fn open_input_stream(sample_rate: u32, input: InputProducer) -> Result<InputStream, String> {
     let mut stream = AudioStreamBuilder::default()
+        .set_input()
         .set_format::<f32>()
         .set_channel_count::<oboe::Mono>()
-        .set_sample_rate(48000)
-        .set_frames_per_callback(32)
+        .set_sample_rate(sample_rate as i32)
+        .set_performance_mode(PerformanceMode::LowLatency)
+        .set_input_preset(InputPreset::Unprocessed)
+        .set_callback(InputCallback(input))
+        .open_stream()
+        .map_err(|e| {
+            format!(
+                "Failed to open audio input (is microphone access allowed?): {}",
+                e
+            )
+        })?;
+    stream.start().map_err(|e| e.to_string())?;
+    info!("🎤 Oboe input stream: {} Hz", sample_rate);
+    Ok(stream)
+}
+
+/// An open oboe stream. Stereo is preferred, mono is the fallback for
+/// devices that won't give us two channels
+enum OutputStream {
+    Stereo(AudioStreamAsync<Output, StereoCallback>),
+    Mono(AudioStreamAsync<Output, MonoCallback>),
+}
+
+impl OutputStream {
+    fn start(&mut self) -> Result<(), oboe::Error> {
+        match self {
+            OutputStream::Stereo(s) => s.start(),
+            OutputStream::Mono(s) => s.start(),
+        }
+    }
+
+    fn stop(&mut self) -> Result<(), oboe::Error> {
+        match self {
+            OutputStream::Stereo(s) => s.stop(),
+            OutputStream::Mono(s) => s.stop(),
+        }
+    }
+
+    fn pause(&mut self) -> Result<(), oboe::Error> {
+        match self {
+            OutputStream::Stereo(s) => s.pause(),
+            OutputStream::Mono(s) => s.pause(),
+        }
+    }
+
+    fn config(&self) -> AudioConfigInfo {
+        let (sample_rate, frames_per_callback, channels, sharing_mode, performance_mode) =
+            match self {
+                OutputStream::Stereo(s) => (
+                    s.get_sample_rate(),
+                    s.get_frames_per_callback(),
+                    2,
+                    s.get_sharing_mode(),
+                    s.get_performance_mode(),
+                ),
+                OutputStream::Mono(s) => (
+                    s.get_sample_rate(),
+                    s.get_frames_per_callback(),
+                    1,
+                    s.get_sharing_mode(),
+                    s.get_performance_mode(),
+                ),
+            };
+        AudioConfigInfo {
+            host_name: "Oboe".to_string(),
+            device_name: "Android audio output".to_string(),
+            sample_rate: sample_rate as u32,
+            engine_sample_rate: sample_rate as u32,
+            buffer_size: Some(frames_per_callback as u32),
+            channels,
+            exclusive_mode: sharing_mode == SharingMode::Exclusive,
+            // Low latency streams get a real-time callback thread from the system
+            realtime_priority: performance_mode == PerformanceMode::LowLatency,
+        }
+    }
+}
+
+fn open_stream(
+    synth: &Arc<Mutex<FunDSPSynth>>,
+    settings: &StreamSettings,
+    keeper: &mpsc::Sender<StreamCommand>,
+) -> Result<OutputStream, oboe::Error> {
+    let sample_rate = settings.sample_rate.unwrap_or(48000) as i32;
+    let frames_per_callback = settings.buffer_size.unwrap_or(32) as i32;
+    let sharing_mode = if settings.exclusive_mode {
+        SharingMode::Exclusive
+    } else {
+        SharingMode::Shared
+    };
+    let state = || CallbackState {
+        synth: synth.clone(),
+        keeper: keeper.clone(),
+        xruns: 0,
+    };
+
+    let stereo = AudioStreamBuilder::default()
+        .set_format::<f32>()
+        .set_channel_count::<oboe::Stereo>()
+        .set_sample_rate(sample_rate)
+        .set_frames_per_callback(frames_per_callback)
         .set_performance_mode(PerformanceMode::LowLatency)
-        .set_sharing_mode(SharingMode::Shared)
-        .set_callback(AudioCallback {
-            synth: synth.clone(),
-        })
-        .open_stream()?;
+        .set_sharing_mode(sharing_mode)
+        .set_callback(StereoCallback(state()))
+        .open_stream();
 
-    let actual_sample_rate = stream.get_sample_rate() as f32;
-    let actual_callback_size = stream.get_frames_per_callback();
+    let mut stream = match stereo {
+        Ok(stream) => OutputStream::Stereo(stream),
+        Err(e) => {
+            warn!("⚠️ Stereo stream unavailable ({}), falling back to mono", e);
+            let mono = AudioStreamBuilder::default()
+                .set_format::<f32>()
+                .set_channel_count::<oboe::Mono>()
+                .set_sample_rate(sample_rate)
+                .set_frames_per_callback(frames_per_callback)
+                .set_performance_mode(PerformanceMode::LowLatency)
+                .set_sharing_mode(sharing_mode)
+                .set_callback(MonoCallback(state()))
+                .open_stream()?;
+            OutputStream::Mono(mono)
+        }
+    };
+
+    let config = stream.config();
 
     // Align backend sample rate to device stream
     if let Ok(mut s) = synth.lock() {
-        s.set_sample_rate(actual_sample_rate);
+        s.set_sample_rate(config.sample_rate as f32);
     }
 
-    println!(
-        "🎯 Oboe CALLBACK stream: {} Hz, {} frames per callback",
-        actual_sample_rate as i32, actual_callback_size
+    info!(
+        "🎯 Oboe CALLBACK stream: {} Hz, {} channels, {} frames per callback",
+        config.sample_rate,
+        config.channels,
+        config.buffer_size.unwrap_or_default()
     );
 
     stream.start()?;
-    println!("🔥 Android CALLBACK audio stream started");
+    Ok(stream)
+}
+
+pub fn start_audio_stream(
+    synth: Arc<Mutex<FunDSPSynth>>,
+) -> Result<StreamController, Box<dyn std::error::Error>> {
+    info!("Initializing Android audio engine with Oboe - CALLBACK MODE");
+    info!("🚀 Android audio using FunDSP synthesis (Shared mode)");
+
+    let (commands, command_receiver) = mpsc::channel();
 
-    // Keep stream alive in a background thread
+    let mut stream = Some(open_stream(&synth, &StreamSettings::default(), &commands)?);
+    info!("🔥 Android CALLBACK audio stream started");
+
+    // The stream keeper thread owns the stream. It sleeps until it gets a
+    // lifecycle request or a disconnect from the error callback.
+    let keeper = commands.clone();
     std::thread::spawn(move || {
-        println!("🔧 Callback mode stream keeper thread started");
-        let mut old_xrun_count = 0;
+        info!("🔧 Callback mode stream keeper thread started");
+        let mut settings = StreamSettings::default();
+        // Set while the user has paused or shut down audio, so we don't restart it behind their back
+        let mut suspended = false;
+        // Set when a rebuild after a disconnect failed, so we retry periodically
+        let mut reopen_pending = false;
+        let mut input_stream: Option<InputStream> = None;
         loop {
-            match stream.get_state() {
-                oboe::StreamState::Started => {
-                    std::thread::sleep(std::time::Duration::from_secs(5));
+            let command = if reopen_pending {
+                match command_receiver.recv_timeout(REOPEN_RETRY_INTERVAL) {
+                    Ok(command) => Some(command),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
                 }
-                oboe::StreamState::Paused => {
-                    println!("⚠️ Stream paused, attempting to restart...");
-                    let _ = stream.start();
-                    std::thread::sleep(std::time::Duration::from_secs(1));
+            } else {
+                match command_receiver.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => break,
                 }
-                oboe::StreamState::Stopped => {
-                    println!("⚠️ Stream stopped, attempting to restart...");
-                    let _ = stream.start();
-                    std::thread::sleep(std::time::Duration::from_secs(1));
+            };
+
+            match command {
+                None | Some(StreamCommand::Disconnected { .. }) => {
+                    if let Some(StreamCommand::Disconnected { error }) = &command {
+                        info!("🔌 Audio stream disconnected ({}), rebuilding", error);
+                    }
+                    // The old stream is already closed, drop our handle to it
+                    drop(stream.take());
+                    if suspended {
+                        reopen_pending = false;
+                        continue;
+                    }
+                    match open_stream(&synth, &settings, &keeper) {
+                        Ok(new_stream) => {
+                            emit_event(
+                                "audio-device-changed",
+                                DeviceChangedEvent {
+                                    device_name: new_stream.config().device_name,
+                                    reason: "stream_disconnected",
+                                },
+                            );
+                            stream = Some(new_stream);
+                            reopen_pending = false;
+                        }
+                        Err(e) => {
+                            if !reopen_pending {
+                                error!("Failed to reopen audio stream: {}", e);
+                            }
+                            reopen_pending = true;
+                        }
+                    }
                 }
-                _ => {
-                    std::thread::sleep(std::time::Duration::from_secs(1));
+                Some(StreamCommand::Reconfigure {
+                    settings: requested,
+                    reply,
+                }) => {
+                    if let Some(old) = stream.as_mut() {
+                        let _ = old.stop();
+                    }
+                    let result = match open_stream(&synth, &requested, &keeper) {
+                        Ok(new_stream) => {
+                            stream = Some(new_stream);
+                            settings = requested;
+                            suspended = false;
+                            reopen_pending = false;
+                            Ok(())
+                        }
+                        Err(e) => {
+                            error!("Failed to reconfigure audio stream: {}", e);
+                            if let Some(old) = stream.as_mut().filter(|_| !suspended) {
+                                let _ = old.start();
+                            }
+                            Err(e.to_string())
+                        }
+                    };
+                    let _ = reply.send(result);
                 }
-            }
-            match stream.get_xrun_count() {
-                Ok(count) => {
-                    if count != old_xrun_count {
-                        println!("⚠️ XRUN detected! Count: {}", count);
-                        old_xrun_count = count;
+                Some(StreamCommand::GetConfig { reply }) => {
+                    let _ = reply.send(stream.as_ref().map(OutputStream::config));
+                }
+                Some(StreamCommand::Pause { reply }) => {
+                    suspended = true;
+                    let result = match stream.as_mut() {
+                        Some(s) => s.pause().map_err(|e| e.to_string()),
+                        None => Ok(()),
+                    };
+                    let _ = reply.send(result);
+                }
+                Some(StreamCommand::Resume { reply }) => {
+                    let result = if let Some(s) = stream.as_mut() {
+                        s.start().map_err(|e| e.to_string())
+                    } else {
+                        match open_stream(&synth, &settings, &keeper) {
+                            Ok(new_stream) => {
+                                stream = Some(new_stream);
+                                Ok(())
+                            }
+                            Err(e) => Err(e.to_string()),
+                        }
+                    };
+                    suspended = result.is_err();
+                    reopen_pending = false;
+                    let _ = reply.send(result);
+                }
+                Some(StreamCommand::Shutdown { reply }) => {
+                    suspended = true;
+                    reopen_pending = false;
+                    if let Some(mut old) = input_stream.take() {
+                        let _ = old.stop();
+                    }
+                    if let Some(mut old) = stream.take() {
+                        let _ = old.stop();
                     }
+                    info!("🛑 Android audio stream closed");
+                    let _ = reply.send(Ok(()));
                 }
-                Err(e) => {
-                    println!("⚠️ Failed to get XRUN count: {}", e);
+                Some(StreamCommand::StartInput { input, reply }) => {
+                    if let Some(mut old) = input_stream.take() {
+                        let _ = old.stop();
+                    }
+                    let result = match stream.as_ref() {
+                        Some(s) => open_input_stream(s.config().sample_rate, input)
+                            .map(|new_stream| input_stream = Some(new_stream)),
+                        None => Err("No audio stream running".to_string()),
+                    };
+                    let _ = reply.send(result);
+                }
+                Some(StreamCommand::StopInput { reply }) => {
+                    if let Some(mut old) = input_stream.take() {
+                        let _ = old.stop();
+                    }
+                    let _ = reply.send(Ok(()));
                 }
             }
         }
     });
 
-    Ok(())
+    Ok(StreamController {
+        commands,
+        settings: Mutex::new(StreamSettings::default()),
+    })
 }
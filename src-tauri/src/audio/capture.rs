@@ -0,0 +1,158 @@
+// Performance capture: every event sent to the synth, stamped with the
+// output sample it took effect at, so a performance can be replayed or
+// re-rendered offline
+use super::patch::Patch;
+use super::synthesis::{AudioEvent, FunDSPSynth};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Version of the exported log format
+const LOG_VERSION: u32 = 1;
+/// Frames rendered per step when re-rendering offline
+const RENDER_CHUNK: usize = 256;
+
+/// Frames the live synth has rendered since startup. Events are applied at
+/// the start of the next buffer, so this is when a queued event takes effect
+pub static SAMPLE_CLOCK: AtomicU64 = AtomicU64::new(0);
+
+/// The capture in progress, if any
+static RECORDING: Mutex<Option<PerformanceLog>> = Mutex::new(None);
+/// The most recently finished (or imported) capture
+static LAST_CAPTURE: Mutex<Option<PerformanceLog>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedEvent {
+    /// Frames since the start of the capture, at the log's sample rate
+    pub sample: u64,
+    pub event: AudioEvent,
+}
+
+/// A captured performance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerformanceLog {
+    pub version: u32,
+    pub sample_rate: f32,
+    /// The sound when capture started, so replays start from the same place
+    pub initial_patch: Patch,
+    pub events: Vec<CapturedEvent>,
+    /// Clock value when capture started. Only meaningful while recording
+    #[serde(skip)]
+    started_at: u64,
+}
+
+/// Start capturing events, discarding any capture in progress
+pub fn start_capture(sample_rate: f32, initial_patch: Patch) {
+    *RECORDING.lock().unwrap() = Some(PerformanceLog {
+        version: LOG_VERSION,
+        sample_rate,
+        initial_patch,
+        events: Vec::new(),
+        started_at: SAMPLE_CLOCK.load(Ordering::Relaxed),
+    });
+}
+
+/// Finish capturing. Returns the number of events captured
+pub fn stop_capture() -> Result<usize, String> {
+    let log = RECORDING
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| "Not capturing".to_string())?;
+    let count = log.events.len();
+    *LAST_CAPTURE.lock().unwrap() = Some(log);
+    Ok(count)
+}
+
+/// Record an event on its way to the synth. Does nothing unless capturing.
+/// Called from the event queue, never the audio thread
+pub fn record(event: &AudioEvent) {
+    if let Some(log) = RECORDING.lock().unwrap().as_mut() {
+        let now = SAMPLE_CLOCK.load(Ordering::Relaxed);
+        // Timed events are logged for when they'll take effect
+        let (sample, event) = match event {
+            AudioEvent::At { frame, event } => ((*frame).max(now), event.as_ref()),
+            _ => (now, event),
+        };
+        log.events.push(CapturedEvent {
+            sample: sample.saturating_sub(log.started_at),
+            event: event.clone(),
+        });
+    }
+}
+
+/// Copy of the last finished capture
+pub fn last_capture() -> Result<PerformanceLog, String> {
+    LAST_CAPTURE
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| "No performance captured".to_string())
+}
+
+pub fn export_capture(path: &str) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&last_capture()?).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Load a log exported earlier, making it the last capture
+pub fn import_capture(path: &str) -> Result<usize, String> {
+    let json =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let log: PerformanceLog =
+        serde_json::from_str(&json).map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+    if log.version > LOG_VERSION {
+        return Err(format!(
+            "Performance log version {} is newer than this app supports ({})",
+            log.version, LOG_VERSION
+        ));
+    }
+    let count = log.events.len();
+    *LAST_CAPTURE.lock().unwrap() = Some(log);
+    Ok(count)
+}
+
+/// Re-render a performance with a fresh synth at `sample_rate`, which can
+/// be higher than the rate it was played at. `tail_secs` of extra audio are
+/// rendered after the last event to let releases and echoes ring out
+pub fn render_offline(
+    log: &PerformanceLog,
+    sample_rate: f32,
+    tail_secs: f32,
+) -> Result<Vec<(f32, f32)>, Box<dyn std::error::Error>> {
+    // The offline synth is driven directly, it gets nothing through its queue
+    let (_producer, consumer) = rtrb::RingBuffer::new(1);
+    let mut synth = FunDSPSynth::new(sample_rate, consumer)?;
+    synth.apply_patch(&log.initial_patch);
+    // Events land between chunks, so chunks have to be rendered as they come
+    synth.set_fixed_blocks(false);
+
+    let scale = sample_rate as f64 / log.sample_rate as f64;
+    let mut events = log.events.clone();
+    events.sort_by_key(|e| e.sample);
+    let end = events
+        .last()
+        .map(|e| (e.sample as f64 * scale) as usize)
+        .unwrap_or(0)
+        + (tail_secs * sample_rate) as usize;
+
+    let mut output = vec![(0.0, 0.0); end];
+    let mut events = events.into_iter().peekable();
+    let mut position = 0;
+    while position < end {
+        while let Some(event) = events.next_if(|e| (e.sample as f64 * scale) as usize <= position) {
+            synth.handle_event(event.event);
+        }
+        let next_event = events
+            .peek()
+            .map(|e| (e.sample as f64 * scale) as usize)
+            .unwrap_or(end);
+        let chunk_end = next_event
+            .min(position + RENDER_CHUNK)
+            .min(end)
+            .max(position + 1);
+        synth.fill_stereo(&mut output[position..chunk_end]);
+        position = chunk_end;
+    }
+    Ok(output)
+}
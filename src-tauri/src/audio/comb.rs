@@ -0,0 +1,130 @@
+// Tuned comb resonator: a feedback delay as long as one cycle of a pitch,
+// so the signal rings at that pitch and its harmonics like a plucked
+// string. It can follow the keyboard, ringing at whatever was played last
+use fundsp::hacker::{shared, AudioNode, Frame, U2};
+
+/// Lowest pitch the resonator rings at, which sets its buffer length
+const MIN_FREQUENCY: f32 = 20.0;
+/// How quickly the pitch follows a new note, seconds
+const RETUNE_TIME: f32 = 0.005;
+
+/// Stereo comb resonator. Rings at `note` (Hz) shifted by `transpose`
+/// semitones while `key_track` is on, at `frequency` (Hz) otherwise.
+/// `decay` is the seconds the ringing takes to fall by 60dB, `damping`
+/// (0.0 to 1.0) how much faster the highs die away
+#[derive(Clone)]
+pub struct CombNode {
+    key_track: shared::Shared,
+    frequency: shared::Shared,
+    transpose: shared::Shared,
+    decay: shared::Shared,
+    damping: shared::Shared,
+    note: shared::Shared,
+    buffer: [Vec<f32>; 2],
+    write: usize,
+    /// Loop low pass state, per channel
+    low: [f32; 2],
+    /// The period (samples) after retuning
+    period: f32,
+    sample_rate: f32,
+}
+
+impl CombNode {
+    pub fn new(
+        key_track: shared::Shared,
+        frequency: shared::Shared,
+        transpose: shared::Shared,
+        decay: shared::Shared,
+        damping: shared::Shared,
+        note: shared::Shared,
+    ) -> Self {
+        let mut node = CombNode {
+            key_track,
+            frequency,
+            transpose,
+            decay,
+            damping,
+            note,
+            buffer: [Vec::new(), Vec::new()],
+            write: 0,
+            low: [0.0; 2],
+            period: 0.0,
+            sample_rate: 48000.0,
+        };
+        node.allocate();
+        node
+    }
+
+    fn allocate(&mut self) {
+        let len = (self.sample_rate / MIN_FREQUENCY).ceil() as usize + 2;
+        self.buffer = [vec![0.0; len], vec![0.0; len]];
+        self.write = 0;
+        self.period = self.target_period();
+    }
+
+    fn target_period(&self) -> f32 {
+        let frequency = if self.key_track.value() >= 0.5 {
+            self.note.value() * (self.transpose.value() / 12.0).exp2()
+        } else {
+            self.frequency.value()
+        };
+        let max = self.buffer[0].len() as f32 - 2.0;
+        (self.sample_rate / frequency.max(MIN_FREQUENCY)).clamp(1.0, max)
+    }
+
+    /// `delay` samples ago on `channel`, interpolated
+    fn read(&self, channel: usize, delay: f32) -> f32 {
+        let buffer = &self.buffer[channel];
+        let len = buffer.len();
+        let position = self.write as f32 + len as f32 - delay;
+        let index = position.floor();
+        let fraction = position - index;
+        let a = buffer[index as usize % len];
+        let b = buffer[(index as usize + 1) % len];
+        a + (b - a) * fraction
+    }
+}
+
+impl AudioNode for CombNode {
+    const ID: u64 = 0x4861_7270_436f_6d62;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.buffer
+            .iter_mut()
+            .for_each(|b| b.iter_mut().for_each(|s| *s = 0.0));
+        self.low = [0.0; 2];
+        self.period = self.target_period();
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+        self.allocate();
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let target = self.target_period();
+        self.period += (target - self.period) / (RETUNE_TIME * self.sample_rate);
+        // Feedback for a fall of 60dB over `decay` seconds
+        let loop_seconds = self.period / self.sample_rate;
+        let decay = self.decay.value().max(0.01);
+        let feedback = 10f32.powf(-3.0 * loop_seconds / decay).min(0.999);
+        let damping = self.damping.value().clamp(0.0, 1.0) * 0.9;
+        // The loop's low pass delays the lows a little, so the line is read
+        // that much short to stay in tune
+        let delay = (self.period - damping / (1.0 - damping)).max(1.0);
+        let len = self.buffer[0].len();
+        let mut output = [0.0; 2];
+        for channel in 0..2 {
+            let delayed = self.read(channel, delay);
+            self.low[channel] += (1.0 - damping) * (delayed - self.low[channel]);
+            let ringing = input[channel] + feedback * self.low[channel];
+            self.buffer[channel][self.write] = ringing;
+            // Unity gain at the resonant peak, however long the decay
+            output[channel] = ringing * (1.0 - feedback);
+        }
+        self.write = (self.write + 1) % len;
+        output.into()
+    }
+}
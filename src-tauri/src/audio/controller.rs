@@ -0,0 +1,216 @@
+// Controller mode: notes played on Harphonium, and changes to its pitch,
+// filter and volume, are sent out over MIDI so it can play a hardware synth.
+// The internal synth can keep playing along or be muted. Notes from MIDI
+// inputs aren't sent back out, so a device that's both can't loop
+use super::midi::is_midi_input_note;
+use super::mirror::PARAM_MIRROR;
+use super::synthesis::{AudioEvent, AudioEventResult};
+use super::voice::DEFAULT_PITCH_BEND_RANGE;
+use super::AudioError;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tracing::warn;
+
+const CC_VOLUME: u8 = 7;
+/// "Brightness", which synths usually map to filter cutoff
+const CC_BRIGHTNESS: u8 = 74;
+const CC_ALL_NOTES_OFF: u8 = 123;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControllerSettings {
+    pub enabled: bool,
+    /// MIDI channel to send on, 1 to 16
+    pub channel: u8,
+    /// Only send notes, leaving the internal synth silent
+    pub mute_synth: bool,
+    /// MIDI output to send to, or None for the first there is
+    pub output: Option<String>,
+}
+
+impl ControllerSettings {
+    const DEFAULT: ControllerSettings = ControllerSettings {
+        enabled: false,
+        channel: 1,
+        mute_synth: false,
+        output: None,
+    };
+}
+
+impl Default for ControllerSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+struct Controller {
+    settings: ControllerSettings,
+    /// Notes sent and not yet released, by note id, oldest first
+    notes: Vec<(u32, u8)>,
+    /// Last pitch bend sent, 14 bit
+    bend: u16,
+}
+
+static CONTROLLER: Mutex<Controller> = Mutex::new(Controller {
+    settings: ControllerSettings::DEFAULT,
+    notes: Vec::new(),
+    bend: 8192,
+});
+
+/// The nearest MIDI note to `frequency`, and how many semitones it's off by
+fn nearest_note(frequency: f32) -> (u8, f32) {
+    let note = 69.0 + 12.0 * (frequency.max(1.0) / 440.0).log2();
+    let nearest = note.round().clamp(0.0, 127.0);
+    (nearest as u8, note - nearest)
+}
+
+fn pitch_bend_range() -> f32 {
+    match PARAM_MIRROR.answer(&AudioEvent::GetPitchBendRange) {
+        Some(AudioEventResult::ValueF32(semitones)) => semitones,
+        _ => DEFAULT_PITCH_BEND_RANGE,
+    }
+}
+
+impl Controller {
+    fn status(&self, kind: u8) -> u8 {
+        kind | (self.settings.channel.clamp(1, 16) - 1)
+    }
+
+    fn control_change(&self, controller: u8, value: f32) -> Vec<u8> {
+        let value = (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+        vec![self.status(0xb0), controller, value]
+    }
+
+    /// A pitch bend message, if `bend` (-1.0 to 1.0) isn't the one sent last
+    fn pitch_bend(&mut self, bend: f32, messages: &mut Vec<Vec<u8>>) {
+        let value = ((bend.clamp(-1.0, 1.0) + 1.0) * 8192.0)
+            .round()
+            .min(16383.0) as u16;
+        if value != self.bend {
+            self.bend = value;
+            messages.push(vec![
+                self.status(0xe0),
+                (value & 0x7f) as u8,
+                (value >> 7) as u8,
+            ]);
+        }
+    }
+
+    fn release(&mut self, note_id: u32, messages: &mut Vec<Vec<u8>>) {
+        if let Some(index) = self.notes.iter().position(|&(id, _)| id == note_id) {
+            let (_, note) = self.notes.remove(index);
+            messages.push(vec![self.status(0x80), note, 0]);
+        }
+    }
+
+    fn release_all(&mut self, messages: &mut Vec<Vec<u8>>) {
+        for (_, note) in std::mem::take(&mut self.notes) {
+            messages.push(vec![self.status(0x80), note, 0]);
+        }
+    }
+
+    /// The MIDI messages for one event. Notes off the equal tempered grid
+    /// bend the channel to their pitch, as do slides of the newest note
+    fn messages(&mut self, event: &AudioEvent, messages: &mut Vec<Vec<u8>>) {
+        match *event {
+            AudioEvent::PlayNote {
+                note_id,
+                frequency,
+                velocity,
+            } if !is_midi_input_note(note_id) => {
+                self.release(note_id, messages);
+                let (note, offset) = nearest_note(frequency);
+                self.pitch_bend(offset / pitch_bend_range(), messages);
+                let velocity = (velocity.clamp(0.0, 1.0) * 127.0).round().max(1.0) as u8;
+                messages.push(vec![self.status(0x90), note, velocity]);
+                self.notes.push((note_id, note));
+            }
+            AudioEvent::NoteOff { note_id } if !is_midi_input_note(note_id) => {
+                self.release(note_id, messages)
+            }
+            AudioEvent::NoteOffAll => {
+                self.release_all(messages);
+                messages.push(self.control_change(CC_ALL_NOTES_OFF, 0.0));
+            }
+            AudioEvent::SetFrequency { frequency } => {
+                if let Some(&(_, note)) = self.notes.last() {
+                    let semitones = 12.0 * (frequency.max(1.0) / 440.0).log2() + 69.0 - note as f32;
+                    self.pitch_bend(semitones / pitch_bend_range(), messages);
+                }
+            }
+            AudioEvent::SetPitchBend { bend } => self.pitch_bend(bend, messages),
+            AudioEvent::SetFilterCutoff { cutoff } => {
+                // Log scale over the audible range
+                let position = (cutoff.clamp(20.0, 20000.0) / 20.0).log10() / 3.0;
+                messages.push(self.control_change(CC_BRIGHTNESS, position));
+            }
+            AudioEvent::SetMasterVolume { volume } => {
+                messages.push(self.control_change(CC_VOLUME, volume))
+            }
+            // Sent straight away, not at the frame asked for
+            AudioEvent::At { ref event, .. } => self.messages(event, messages),
+            _ => {}
+        }
+    }
+}
+
+/// Whether the synth should skip `event` because controller mode plays it instead
+fn muted(event: &AudioEvent) -> bool {
+    match event {
+        AudioEvent::PlayNote { note_id, .. } | AudioEvent::NoteOff { note_id } => {
+            !is_midi_input_note(*note_id)
+        }
+        AudioEvent::SetFrequency { .. } => true,
+        AudioEvent::At { event, .. } => muted(event),
+        _ => false,
+    }
+}
+
+/// Send an event out over MIDI if controller mode is on. Returns true if
+/// the synth is muted and shouldn't play it
+pub fn echo(event: &AudioEvent) -> bool {
+    let mut controller = CONTROLLER.lock().unwrap();
+    if !controller.settings.enabled {
+        return false;
+    }
+    let mut messages = Vec::new();
+    controller.messages(event, &mut messages);
+    let mute = controller.settings.mute_synth && muted(event);
+    drop(controller);
+    for message in messages {
+        if let Err(e) = super::midi_ports::send_midi(&message) {
+            warn!("Failed to send MIDI: {}", e);
+            break;
+        }
+    }
+    mute
+}
+
+/// Change controller mode. Turning it on opens the MIDI output, failing if
+/// it can't; turning it off releases the notes sent and closes the output
+pub fn set_controller_settings(settings: ControllerSettings) -> Result<(), AudioError> {
+    let settings = ControllerSettings {
+        channel: settings.channel.clamp(1, 16),
+        ..settings
+    };
+    let mut controller = CONTROLLER.lock().unwrap();
+    // The old channel's notes are released, whatever happens next
+    let mut messages = Vec::new();
+    controller.release_all(&mut messages);
+    for message in messages {
+        let _ = super::midi_ports::send_midi(&message);
+    }
+    if settings.enabled {
+        super::midi_ports::open_midi_output(settings.output.as_deref())
+            .map_err(AudioError::Device)?;
+    } else {
+        super::midi_ports::close_midi_output();
+    }
+    controller.bend = 8192;
+    controller.settings = settings;
+    Ok(())
+}
+
+pub fn controller_settings() -> ControllerSettings {
+    CONTROLLER.lock().unwrap().settings.clone()
+}
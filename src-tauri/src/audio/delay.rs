@@ -0,0 +1,213 @@
+// The delay's feedback path. Repeats go back through a tone stage, so each
+// one comes round darker and thinner than the last, like an analog echo,
+// rather than piling up highs until the limiter grabs them. The delay line
+// itself is a clean digital one or a tape loop, see `DelayMode`
+use fundsp::hacker::{shared, AudioNode, Frame, U2};
+use std::f32::consts::TAU;
+
+/// Longest delay time, seconds
+pub const MAX_DELAY_TIME: f32 = 5.0;
+/// Wow: slow pitch drift from the tape speed wandering. Rate (Hz) and depth
+/// (seconds of delay time)
+const WOW_RATE: f32 = 0.5;
+const WOW_DEPTH: f32 = 0.0012;
+/// Flutter: faster, shallower wobble from the capstan
+const FLUTTER_RATE: f32 = 6.3;
+const FLUTTER_DEPTH: f32 = 0.00005;
+/// How hard the record head is driven. Quiet repeats pass cleanly, loud
+/// ones squash as they go round
+const TAPE_DRIVE: f32 = 1.8;
+/// How quickly the tape's delay time follows the control, seconds. Changing
+/// it bends the repeats' pitch, like moving a tape echo's heads
+const TAPE_TIME_GLIDE: f32 = 0.3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DelayMode {
+    /// Clean repeats, exactly the time set
+    #[default]
+    Digital,
+    /// Wow, flutter and saturation in the loop, for lo-fi echoes
+    Tape,
+}
+
+impl DelayMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DelayMode::Digital => "digital",
+            DelayMode::Tape => "tape",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "digital" => Some(DelayMode::Digital),
+            "tape" => Some(DelayMode::Tape),
+            _ => None,
+        }
+    }
+}
+
+/// Lowest and highest tone control settings. At the ends they're out of
+/// the way
+pub const MIN_DELAY_CUT: f32 = 20.0;
+pub const MAX_DELAY_CUT: f32 = 20000.0;
+
+/// Stereo one pole high pass into one pole low pass, gentle as tape and
+/// bucket brigade echoes are. Takes the high pass (`low_cut`) and low pass
+/// (`high_cut`) frequencies in Hz
+#[derive(Clone)]
+pub struct FeedbackToneNode {
+    low_cut: shared::Shared,
+    high_cut: shared::Shared,
+    /// Per channel: the high pass's low end, taken off the signal, and the
+    /// low pass's state
+    low: [f32; 2],
+    high: [f32; 2],
+    sample_rate: f32,
+}
+
+impl FeedbackToneNode {
+    pub fn new(low_cut: shared::Shared, high_cut: shared::Shared) -> Self {
+        FeedbackToneNode {
+            low_cut,
+            high_cut,
+            low: [0.0; 2],
+            high: [0.0; 2],
+            sample_rate: 48000.0,
+        }
+    }
+
+    fn coefficient(&self, frequency: f32) -> f32 {
+        1.0 - (-TAU * frequency.min(self.sample_rate * 0.45) / self.sample_rate).exp()
+    }
+}
+
+impl AudioNode for FeedbackToneNode {
+    const ID: u64 = 0x4861_7270_4563_686f;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.low = [0.0; 2];
+        self.high = [0.0; 2];
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let low_cut = self.low_cut.value();
+        let high_cut = self.high_cut.value();
+        let low_coefficient = self.coefficient(low_cut);
+        let high_coefficient = self.coefficient(high_cut);
+        let mut output = [0.0; 2];
+        // The filters keep running when set out of the way, so turning
+        // them back in doesn't thump
+        for channel in 0..2 {
+            let mut sample = input[channel];
+            self.low[channel] += low_coefficient * (sample - self.low[channel]);
+            if low_cut > MIN_DELAY_CUT {
+                sample -= self.low[channel];
+            }
+            self.high[channel] += high_coefficient * (sample - self.high[channel]);
+            if high_cut < MAX_DELAY_CUT {
+                sample = self.high[channel];
+            }
+            output[channel] = sample;
+        }
+        output.into()
+    }
+}
+
+/// Stereo tape loop. The delay time, read from `time` (seconds), glides
+/// rather than jumps and wanders with wow and flutter, the two channels
+/// slightly apart. What goes in is saturated on its way onto the tape
+#[derive(Clone)]
+pub struct TapeDelayNode {
+    time: shared::Shared,
+    buffer: [Vec<f32>; 2],
+    write: usize,
+    /// The delay time after gliding, seconds
+    glide: f32,
+    /// Wow and flutter phases, 0.0 to 1.0
+    wow: f32,
+    flutter: f32,
+    sample_rate: f32,
+}
+
+impl TapeDelayNode {
+    pub fn new(time: shared::Shared) -> Self {
+        let mut node = TapeDelayNode {
+            glide: time.value(),
+            time,
+            buffer: [Vec::new(), Vec::new()],
+            write: 0,
+            wow: 0.0,
+            flutter: 0.0,
+            sample_rate: 48000.0,
+        };
+        node.allocate();
+        node
+    }
+
+    fn allocate(&mut self) {
+        let seconds = MAX_DELAY_TIME + WOW_DEPTH + FLUTTER_DEPTH;
+        let len = (seconds * self.sample_rate).ceil() as usize + 2;
+        self.buffer = [vec![0.0; len], vec![0.0; len]];
+        self.write = 0;
+    }
+
+    /// `delay` samples ago on `channel`, interpolated
+    fn read(&self, channel: usize, delay: f32) -> f32 {
+        let buffer = &self.buffer[channel];
+        let len = buffer.len();
+        let position = self.write as f32 + len as f32 - delay.clamp(1.0, len as f32 - 2.0);
+        let index = position.floor();
+        let fraction = position - index;
+        let a = buffer[index as usize % len];
+        let b = buffer[(index as usize + 1) % len];
+        a + (b - a) * fraction
+    }
+}
+
+impl AudioNode for TapeDelayNode {
+    const ID: u64 = 0x4861_7270_5461_7065;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.buffer
+            .iter_mut()
+            .for_each(|b| b.iter_mut().for_each(|s| *s = 0.0));
+        self.glide = self.time.value();
+        self.wow = 0.0;
+        self.flutter = 0.0;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+        self.allocate();
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let target = self.time.value().clamp(0.0, MAX_DELAY_TIME);
+        self.glide += (target - self.glide) / (TAPE_TIME_GLIDE * self.sample_rate);
+        self.wow = (self.wow + WOW_RATE / self.sample_rate) % 1.0;
+        self.flutter = (self.flutter + FLUTTER_RATE / self.sample_rate) % 1.0;
+        let len = self.buffer[0].len();
+        let mut output = [0.0; 2];
+        for channel in 0..2 {
+            // The right channel runs a quarter cycle behind, which widens
+            // the repeats a little
+            let offset = channel as f32 * 0.25;
+            let wobble = WOW_DEPTH * (0.5 - 0.5 * (TAU * (self.wow + offset)).cos())
+                + FLUTTER_DEPTH * (0.5 - 0.5 * (TAU * (self.flutter + offset)).cos());
+            self.buffer[channel][self.write] = (input[channel] * TAPE_DRIVE).tanh() / TAPE_DRIVE;
+            output[channel] = self.read(channel, (self.glide + wobble) * self.sample_rate);
+        }
+        self.write = (self.write + 1) % len;
+        output.into()
+    }
+}
@@ -0,0 +1,117 @@
+// Denormal protection. As reverb and delay tails decay they pass through
+// denormal (subnormal) floats, which many CPUs handle in microcode at up to
+// 100x the cost, enough to blow the audio deadline. While rendering, the
+// FPU is told to flush them to zero instead
+#[cfg(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    all(target_arch = "arm", target_feature = "vfp2")
+))]
+use std::arch::asm;
+
+/// MXCSR flush-to-zero and denormals-are-zero
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const FLUSH_BITS: u64 = (1 << 15) | (1 << 6);
+/// FPCR flush-to-zero, for single and half precision
+#[cfg(target_arch = "aarch64")]
+const FLUSH_BITS: u64 = (1 << 24) | (1 << 19);
+/// FPSCR flush-to-zero. NEON always flushes, but scalar VFP code doesn't.
+/// Every later VFP version implies vfp2
+#[cfg(all(target_arch = "arm", target_feature = "vfp2"))]
+const FLUSH_BITS: u64 = 1 << 24;
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    all(target_arch = "arm", target_feature = "vfp2")
+)))]
+const FLUSH_BITS: u64 = 0;
+
+/// Flushes denormals to zero on this thread until dropped, then puts the
+/// FPU back how it was. Does nothing on other architectures (wasm can't be
+/// told to)
+pub struct DenormalGuard {
+    previous: u64,
+}
+
+impl DenormalGuard {
+    pub fn new() -> Self {
+        let previous = read_control();
+        write_control(previous | FLUSH_BITS);
+        DenormalGuard { previous }
+    }
+}
+
+impl Drop for DenormalGuard {
+    fn drop(&mut self) {
+        write_control(self.previous);
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn read_control() -> u64 {
+    let mut csr: u32 = 0;
+    // SAFETY: stores MXCSR to a local; SSE is baseline on every x86 we build for
+    unsafe {
+        asm!("stmxcsr [{}]", in(reg) std::ptr::addr_of_mut!(csr), options(nostack, preserves_flags))
+    };
+    csr as u64
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn write_control(value: u64) {
+    let csr = value as u32;
+    // SAFETY: only the FTZ/DAZ bits differ from what was read back
+    unsafe {
+        asm!("ldmxcsr [{}]", in(reg) std::ptr::addr_of!(csr), options(nostack, preserves_flags))
+    };
+}
+
+#[cfg(target_arch = "aarch64")]
+fn read_control() -> u64 {
+    let fpcr: u64;
+    // SAFETY: reading FPCR has no side effects
+    unsafe { asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack, preserves_flags)) };
+    fpcr
+}
+
+#[cfg(target_arch = "aarch64")]
+fn write_control(value: u64) {
+    // SAFETY: only the FZ and FZ16 bits differ from what was read back
+    unsafe { asm!("msr fpcr, {}", in(reg) value, options(nomem, nostack, preserves_flags)) };
+}
+
+#[cfg(all(target_arch = "arm", target_feature = "vfp2"))]
+fn read_control() -> u64 {
+    let fpscr: u32;
+    // SAFETY: reading FPSCR has no side effects
+    unsafe { asm!("vmrs {}, fpscr", out(reg) fpscr, options(nomem, nostack, preserves_flags)) };
+    fpscr as u64
+}
+
+#[cfg(all(target_arch = "arm", target_feature = "vfp2"))]
+fn write_control(value: u64) {
+    // SAFETY: only the FZ bit differs from what was read back
+    unsafe {
+        asm!("vmsr fpscr, {}", in(reg) value as u32, options(nomem, nostack, preserves_flags))
+    };
+}
+
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    all(target_arch = "arm", target_feature = "vfp2")
+)))]
+fn read_control() -> u64 {
+    0
+}
+
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    all(target_arch = "arm", target_feature = "vfp2")
+)))]
+fn write_control(_value: u64) {}
@@ -1,59 +1,717 @@
 // Desktop audio implementation using cpal with FunDSP integration
+use super::input::InputProducer;
+use super::priority::promote_current_thread;
+use super::stats::STATS;
 use super::synthesis::FunDSPSynth;
+use super::{emit_event, AudioConfigInfo, AudioDeviceInfo, DeviceChangedEvent, StreamSettings};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info, warn};
 
-pub fn start_audio_stream(
-    synth: Arc<Mutex<FunDSPSynth>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let host = cpal::default_host();
-    let device = host
+/// How often the stream thread checks for default device changes and stream errors
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Requests handled by the stream thread. cpal streams aren't Send, so the
+/// stream lives on its own thread and is driven through these.
+enum StreamCommand {
+    Reconfigure {
+        settings: StreamSettings,
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    GetConfig {
+        reply: mpsc::Sender<Option<AudioConfigInfo>>,
+    },
+    Pause {
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    /// Resume a paused stream, or reopen it after a shutdown
+    Resume {
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    /// Close the stream and release the device
+    Shutdown {
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    /// Open an input stream on the named device (or the default), feeding `input`
+    StartInput {
+        device_name: Option<String>,
+        input: InputProducer,
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    StopInput {
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+}
+
+/// Handle to the thread that owns the output stream
+pub struct StreamController {
+    commands: mpsc::Sender<StreamCommand>,
+    /// Settings most recently requested by the user
+    settings: Mutex<StreamSettings>,
+}
+
+impl StreamController {
+    /// Rebuild the stream on the named output device, or the default device if `None`
+    pub fn select_device(&self, name: Option<String>) -> Result<(), String> {
+        self.reconfigure(|settings| settings.device_name = name)
+    }
+
+    /// Switch audio host (e.g. ALSA or JACK on Linux), or the platform default
+    /// if `None`. Device names differ between hosts, so this goes back to the
+    /// host's default device
+    pub fn select_host(&self, name: Option<String>) -> Result<(), String> {
+        self.reconfigure(|settings| {
+            settings.host_name = name;
+            settings.device_name = None;
+        })
+    }
+
+    /// List the output devices offered by the current host
+    pub fn list_devices(&self) -> Result<Vec<AudioDeviceInfo>, String> {
+        let host_name = self.settings.lock().unwrap().host_name.clone();
+        let host = find_host(host_name.as_deref())?;
+        let default_name = host.default_output_device().and_then(|d| d.name().ok());
+        let devices = host.output_devices().map_err(|e| e.to_string())?;
+        Ok(devices
+            .filter_map(|d| d.name().ok())
+            .map(|name| AudioDeviceInfo {
+                is_default: Some(&name) == default_name.as_ref(),
+                name,
+            })
+            .collect())
+    }
+
+    /// List the input devices offered by the current host
+    pub fn list_input_devices(&self) -> Result<Vec<AudioDeviceInfo>, String> {
+        let host_name = self.settings.lock().unwrap().host_name.clone();
+        let host = find_host(host_name.as_deref())?;
+        let default_name = host.default_input_device().and_then(|d| d.name().ok());
+        let devices = host.input_devices().map_err(|e| e.to_string())?;
+        Ok(devices
+            .filter_map(|d| d.name().ok())
+            .map(|name| AudioDeviceInfo {
+                is_default: Some(&name) == default_name.as_ref(),
+                name,
+            })
+            .collect())
+    }
+
+    /// Start recording from an input device (the default if `None`) into
+    /// `input`, replacing any input already running
+    pub fn start_input(
+        &self,
+        device_name: Option<String>,
+        input: InputProducer,
+    ) -> Result<(), String> {
+        self.request(|reply| StreamCommand::StartInput {
+            device_name,
+            input,
+            reply,
+        })
+    }
+
+    pub fn stop_input(&self) -> Result<(), String> {
+        self.request(|reply| StreamCommand::StopInput { reply })
+    }
+
+    /// List the audio hosts compiled in and available on this system
+    pub fn list_hosts(&self) -> Result<Vec<String>, String> {
+        Ok(cpal::available_hosts()
+            .into_iter()
+            .map(|id| id.name().to_string())
+            .collect())
+    }
+
+    /// Request a sample rate, or the device default if `None`
+    pub fn set_sample_rate(&self, sample_rate: Option<u32>) -> Result<(), String> {
+        self.reconfigure(|settings| settings.sample_rate = sample_rate)
+    }
+
+    /// Request a buffer size in frames, or the device default if `None`
+    pub fn set_buffer_size(&self, buffer_size: Option<u32>) -> Result<(), String> {
+        self.reconfigure(|settings| settings.buffer_size = buffer_size)
+    }
+
+    /// Ask for exclusive use of the device (WASAPI only). Falls back to a
+    /// shared stream if the device refuses; `get_config` says which we got
+    pub fn set_exclusive_mode(&self, exclusive: bool) -> Result<(), String> {
+        self.reconfigure(|settings| settings.exclusive_mode = exclusive)
+    }
+
+    /// Run the audio callback at real-time priority. Takes effect when the
+    /// stream is rebuilt; `get_config` reports whether the OS allowed it
+    pub fn set_realtime_priority(&self, enabled: bool) -> Result<(), String> {
+        self.reconfigure(|settings| settings.realtime_priority = enabled)
+    }
+
+    /// What the device actually gave us, if a stream is running
+    pub fn get_config(&self) -> Option<AudioConfigInfo> {
+        let (reply, response) = mpsc::channel();
+        self.commands
+            .send(StreamCommand::GetConfig { reply })
+            .ok()?;
+        response.recv().ok().flatten()
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        self.request(|reply| StreamCommand::Pause { reply })
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        self.request(|reply| StreamCommand::Resume { reply })
+    }
+
+    pub fn shutdown(&self) -> Result<(), String> {
+        self.request(|reply| StreamCommand::Shutdown { reply })
+    }
+
+    fn request(
+        &self,
+        command: impl FnOnce(mpsc::Sender<Result<(), String>>) -> StreamCommand,
+    ) -> Result<(), String> {
+        let (reply, response) = mpsc::channel();
+        self.commands
+            .send(command(reply))
+            .map_err(|_| "Audio stream thread has stopped".to_string())?;
+        response
+            .recv()
+            .map_err(|_| "Audio stream thread has stopped".to_string())?
+    }
+
+    fn reconfigure(&self, update: impl FnOnce(&mut StreamSettings)) -> Result<(), String> {
+        let mut current = self.settings.lock().unwrap();
+        let mut settings = current.clone();
+        update(&mut settings);
+
+        self.request(|reply| StreamCommand::Reconfigure {
+            settings: settings.clone(),
+            reply,
+        })?;
+
+        *current = settings;
+        Ok(())
+    }
+}
+
+/// The underlying output stream
+enum OutputStream {
+    Shared(cpal::Stream),
+    #[cfg(target_os = "windows")]
+    Exclusive(super::wasapi_exclusive::ExclusiveStream),
+}
+
+impl OutputStream {
+    fn play(&self) -> Result<(), String> {
+        match self {
+            OutputStream::Shared(stream) => stream.play().map_err(|e| e.to_string()),
+            #[cfg(target_os = "windows")]
+            OutputStream::Exclusive(stream) => {
+                stream.play();
+                Ok(())
+            }
+        }
+    }
+
+    fn pause(&self) -> Result<(), String> {
+        match self {
+            OutputStream::Shared(stream) => stream.pause().map_err(|e| e.to_string()),
+            #[cfg(target_os = "windows")]
+            OutputStream::Exclusive(stream) => {
+                stream.pause();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A running stream and the configuration it ended up with
+struct ActiveStream {
+    stream: OutputStream,
+    config: AudioConfigInfo,
+    /// Frames delivered in the most recent callback, written by the audio thread
+    callback_frames: Arc<AtomicU32>,
+    /// Set by the error callback, e.g. when the device is unplugged
+    failed: Arc<AtomicBool>,
+    /// Set once the callback thread has been promoted to real-time priority
+    realtime: Arc<AtomicBool>,
+}
+
+impl ActiveStream {
+    fn config(&self) -> AudioConfigInfo {
+        let frames = self.callback_frames.load(Ordering::Relaxed);
+        AudioConfigInfo {
+            realtime_priority: self.realtime.load(Ordering::Relaxed),
+            buffer_size: if frames > 0 {
+                Some(frames)
+            } else {
+                self.config.buffer_size
+            },
+            ..self.config.clone()
+        }
+    }
+}
+
+/// Find an audio host by name (case insensitive), or the platform default if `None`
+fn find_host(name: Option<&str>) -> Result<cpal::Host, String> {
+    match name {
+        None => Ok(cpal::default_host()),
+        Some(name) => {
+            let id = cpal::available_hosts()
+                .into_iter()
+                .find(|id| id.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("Audio host not available: {}", name))?;
+            cpal::host_from_id(id).map_err(|e| e.to_string())
+        }
+    }
+}
+
+fn default_output_device_name(settings: &StreamSettings) -> Option<String> {
+    find_host(settings.host_name.as_deref())
+        .ok()?
         .default_output_device()
-        .ok_or("No output device available")?;
+        .and_then(|d| d.name().ok())
+}
 
-    let config = device.default_output_config()?;
-    let config: cpal::StreamConfig = config.into();
+fn find_output_device(host: &cpal::Host, name: Option<&str>) -> Result<cpal::Device, String> {
+    match name {
+        None => host
+            .default_output_device()
+            .ok_or_else(|| "No output device available".to_string()),
+        Some(name) => host
+            .output_devices()
+            .map_err(|e| e.to_string())?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+            .ok_or_else(|| format!("Output device not found: {}", name)),
+    }
+}
+
+fn find_input_device(host: &cpal::Host, name: Option<&str>) -> Result<cpal::Device, String> {
+    match name {
+        None => host
+            .default_input_device()
+            .ok_or_else(|| "No input device available".to_string()),
+        Some(name) => host
+            .input_devices()
+            .map_err(|e| e.to_string())?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+            .ok_or_else(|| format!("Input device not found: {}", name)),
+    }
+}
+
+/// Open an input stream at the synth's sample rate, so its frames can be
+/// mixed straight in
+fn build_input_stream(
+    settings: &StreamSettings,
+    device_name: Option<&str>,
+    sample_rate: u32,
+    mut input: InputProducer,
+) -> Result<cpal::Stream, String> {
+    let host = find_host(settings.host_name.as_deref())?;
+    let device = find_input_device(&host, device_name)?;
+    let supported = device
+        .supported_input_configs()
+        .map_err(|e| e.to_string())?
+        .filter(|c| c.sample_format() == cpal::SampleFormat::F32)
+        .find(|c| c.min_sample_rate().0 <= sample_rate && sample_rate <= c.max_sample_rate().0)
+        .map(|c| c.with_sample_rate(cpal::SampleRate(sample_rate)))
+        .ok_or_else(|| {
+            format!(
+                "Input device doesn't support {} Hz, the synth's sample rate",
+                sample_rate
+            )
+        })?;
+    let config: cpal::StreamConfig = supported.into();
+    let channels = config.channels as usize;
+    info!(
+        "🎤 Desktop audio input: {} ({} Hz, {} channels)",
+        device.name().unwrap_or_default(),
+        sample_rate,
+        channels
+    );
+    let stream = device
+        .build_input_stream(
+            &config,
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                input.push_interleaved(data, channels);
+            },
+            move |err| error!("Desktop audio input error: {}", err),
+            None,
+        )
+        .map_err(|e| e.to_string())?;
+    stream.play().map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+/// Pick a stream config for the device honouring the requested sample rate
+/// and buffer size. Also returns the rate the synth should render at: if the
+/// device can't do the rate asked for, it runs at its default and the synth
+/// is resampled to that
+fn choose_config(
+    device: &cpal::Device,
+    settings: &StreamSettings,
+) -> Result<(cpal::StreamConfig, u32), Box<dyn std::error::Error>> {
+    let default_config = device.default_output_config()?;
+    let (supported, engine_rate) = match settings.sample_rate {
+        None => {
+            let rate = default_config.sample_rate().0;
+            (default_config, rate)
+        }
+        Some(rate) => {
+            let supported = device
+                .supported_output_configs()?
+                .filter(|c| c.channels() == default_config.channels())
+                .find(|c| c.min_sample_rate().0 <= rate && rate <= c.max_sample_rate().0)
+                .map(|c| c.with_sample_rate(cpal::SampleRate(rate)));
+            match supported {
+                Some(supported) => (supported, rate),
+                None => {
+                    warn!(
+                        "⚠️ Sample rate {} Hz is not supported by this device, resampling to {} Hz",
+                        rate,
+                        default_config.sample_rate().0
+                    );
+                    (default_config, rate)
+                }
+            }
+        }
+    };
+
+    let buffer_size = match (settings.buffer_size, supported.buffer_size()) {
+        (None, _) => cpal::BufferSize::Default,
+        (Some(frames), cpal::SupportedBufferSize::Range { min, max }) => {
+            cpal::BufferSize::Fixed(frames.clamp(*min, *max))
+        }
+        (Some(frames), cpal::SupportedBufferSize::Unknown) => cpal::BufferSize::Fixed(frames),
+    };
+
+    let mut config: cpal::StreamConfig = supported.into();
+    config.buffer_size = buffer_size;
+    Ok((config, engine_rate))
+}
+
+fn build_stream(
+    synth: &Arc<Mutex<FunDSPSynth>>,
+    settings: &StreamSettings,
+) -> Result<ActiveStream, Box<dyn std::error::Error>> {
+    let host = find_host(settings.host_name.as_deref())?;
+
+    #[cfg(target_os = "windows")]
+    if settings.exclusive_mode && host.id() == cpal::HostId::Wasapi {
+        match build_exclusive_stream(synth, settings) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => warn!("⚠️ Exclusive mode unavailable ({}), using shared mode", e),
+        }
+    }
+
+    let device = find_output_device(&host, settings.device_name.as_deref())?;
+    let (config, engine_rate) = choose_config(&device, settings)?;
 
     let sample_rate = config.sample_rate.0 as f32;
-    println!(
-        "🎵 Desktop audio: {} Hz, {} channels",
-        sample_rate, config.channels
+    let device_name = device.name().unwrap_or_default();
+    info!(
+        "🎵 Desktop audio: {} via {} ({} Hz, {} channels, {:?} buffer)",
+        device_name,
+        host.id().name(),
+        sample_rate,
+        config.channels,
+        config.buffer_size
     );
-    println!("🚀 Desktop audio using FunDSP synthesis (no fallback)");
 
-    // Align backend sample rate to device
+    // Align backend sample rate to device, resampling if it couldn't give us
+    // the rate asked for
     if let Ok(mut s) = synth.lock() {
-        s.set_sample_rate(sample_rate);
+        s.set_sample_rate(engine_rate as f32);
+        s.set_output_sample_rate(sample_rate);
     }
 
+    let callback_frames = Arc::new(AtomicU32::new(0));
+    let frames_counter = callback_frames.clone();
+    let failed = Arc::new(AtomicBool::new(false));
+    let failed_flag = failed.clone();
+    let realtime = Arc::new(AtomicBool::new(false));
+    let realtime_flag = realtime.clone();
+    let mut promote_pending = settings.realtime_priority;
+    let synth = synth.clone();
+    let channels = config.channels as usize;
+    let stream_sample_rate = config.sample_rate.0;
+    let mut last_callback: Option<cpal::StreamInstant> = None;
     let stream = device.build_output_stream(
         &config,
-        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+        move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+            let frames = (data.len() / channels) as u32;
+            frames_counter.store(frames, Ordering::Relaxed);
+
+            // cpal doesn't report xruns, so infer them: if this callback came
+            // more than two buffers after the last one, the device ran dry
+            let now = info.timestamp().callback;
+            if let Some(gap) = last_callback.and_then(|last| now.duration_since(&last)) {
+                let buffer_secs = frames as f32 / stream_sample_rate as f32;
+                if gap.as_secs_f32() > 2.0 * buffer_secs {
+                    STATS.record_underruns(1);
+                }
+            }
+            last_callback = Some(now);
+
+            // We only find out which thread we're on once called, so promote
+            // from the first callback. This may make a syscall, but only the once
+            if promote_pending {
+                promote_pending = false;
+                let promoted = promote_current_thread(frames, stream_sample_rate);
+                realtime_flag.store(promoted, Ordering::Relaxed);
+            }
             // Fill buffer with FunDSP samples, but never block RT thread
             match synth.try_lock() {
                 Ok(mut synth_guard) => {
-                    for frame in data.chunks_mut(config.channels as usize) {
-                        synth_guard.fill_buffer(frame);
-                    }
+                    synth_guard.fill_interleaved(data, channels);
                 }
                 Err(_) => {
                     // On contention, output silence this cycle
+                    STATS.record_lock_contention();
                     for s in data.iter_mut() {
                         *s = 0.0;
                     }
                 }
             }
         },
-        |err| eprintln!("Desktop audio stream error: {}", err),
+        move |err| {
+            error!("Desktop audio stream error: {}", err);
+            failed_flag.store(true, Ordering::Relaxed);
+        },
         None,
     )?;
 
     stream.play()?;
 
-    println!("🎯 Desktop audio stream started");
+    Ok(ActiveStream {
+        stream: OutputStream::Shared(stream),
+        config: AudioConfigInfo {
+            host_name: host.id().name().to_string(),
+            device_name,
+            sample_rate: config.sample_rate.0,
+            engine_sample_rate: engine_rate,
+            buffer_size: match config.buffer_size {
+                cpal::BufferSize::Fixed(frames) => Some(frames),
+                cpal::BufferSize::Default => None,
+            },
+            channels: config.channels,
+            exclusive_mode: false,
+            realtime_priority: false,
+        },
+        callback_frames,
+        failed,
+        realtime,
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn build_exclusive_stream(
+    synth: &Arc<Mutex<FunDSPSynth>>,
+    settings: &StreamSettings,
+) -> Result<ActiveStream, String> {
+    let callback_frames = Arc::new(AtomicU32::new(0));
+    let failed = Arc::new(AtomicBool::new(false));
+    let realtime = Arc::new(AtomicBool::new(false));
+    let (stream, config) = super::wasapi_exclusive::open(
+        synth,
+        settings,
+        callback_frames.clone(),
+        failed.clone(),
+        realtime.clone(),
+    )?;
+
+    info!(
+        "🎵 Desktop audio: {} via WASAPI exclusive ({} Hz, {} channels, {} frame buffer)",
+        config.device_name, config.sample_rate, config.channels, config.buffer_size
+    );
+
+    if let Ok(mut s) = synth.lock() {
+        s.set_sample_rate(config.sample_rate as f32);
+    }
+
+    Ok(ActiveStream {
+        stream: OutputStream::Exclusive(stream),
+        config: AudioConfigInfo {
+            host_name: "WASAPI".to_string(),
+            device_name: config.device_name,
+            sample_rate: config.sample_rate,
+            engine_sample_rate: config.sample_rate,
+            buffer_size: Some(config.buffer_size),
+            channels: config.channels,
+            exclusive_mode: true,
+            realtime_priority: false,
+        },
+        callback_frames,
+        failed,
+        realtime,
+    })
+}
+
+/// Work out whether the stream needs rebuilding, and why
+fn needs_rebuild(active: &Option<ActiveStream>, settings: &StreamSettings) -> Option<&'static str> {
+    match active {
+        None => Some("stream_reopened"),
+        Some(a) if a.failed.load(Ordering::Relaxed) => Some("stream_error"),
+        // Only follow the default device if the user hasn't picked one
+        Some(a) if settings.device_name.is_none() => match default_output_device_name(settings) {
+            Some(name) if name != a.config.device_name => Some("default_device_changed"),
+            _ => None,
+        },
+        Some(_) => None,
+    }
+}
+
+pub fn start_audio_stream(
+    synth: Arc<Mutex<FunDSPSynth>>,
+) -> Result<StreamController, Box<dyn std::error::Error>> {
+    info!("🚀 Desktop audio using FunDSP synthesis (no fallback)");
+
+    let (commands, command_receiver) = mpsc::channel();
+    let (started, start_result) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut settings = StreamSettings::default();
+        let mut active = match build_stream(&synth, &settings) {
+            Ok(stream) => {
+                let _ = started.send(Ok(()));
+                Some(stream)
+            }
+            Err(e) => {
+                let _ = started.send(Err(e.to_string()));
+                return;
+            }
+        };
+
+        // Set while the user has paused or shut down audio, so we don't restart it behind their back
+        let mut suspended = false;
+        let mut input_stream: Option<cpal::Stream> = None;
+
+        loop {
+            let command = match command_receiver.recv_timeout(DEVICE_POLL_INTERVAL) {
+                Ok(command) => command,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if suspended {
+                        continue;
+                    }
+                    if let Some(reason) = needs_rebuild(&active, &settings) {
+                        // Synth state lives in the shared FunDSPSynth, so only the stream is rebuilt
+                        drop(active.take());
+                        match build_stream(&synth, &settings) {
+                            Ok(new_stream) => {
+                                info!("🔌 Audio stream rebuilt ({})", reason);
+                                emit_event(
+                                    "audio-device-changed",
+                                    DeviceChangedEvent {
+                                        device_name: new_stream.config.device_name.clone(),
+                                        reason,
+                                    },
+                                );
+                                active = Some(new_stream);
+                            }
+                            Err(e) => {
+                                // Try again next time round, e.g. once a device is plugged in
+                                if reason != "stream_reopened" {
+                                    error!("Failed to rebuild audio stream: {}", e);
+                                }
+                            }
+                        }
+                    }
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+
+            match command {
+                StreamCommand::Reconfigure {
+                    settings: requested,
+                    reply,
+                } => {
+                    // Close the old stream first, some backends won't open a busy device
+                    drop(active.take());
+                    let result = match build_stream(&synth, &requested) {
+                        Ok(new_stream) => {
+                            active = Some(new_stream);
+                            settings = requested;
+                            suspended = false;
+                            Ok(())
+                        }
+                        Err(e) => {
+                            error!("Failed to reconfigure audio stream: {}", e);
+                            // Fall back to whatever we were using before
+                            active = build_stream(&synth, &settings).ok();
+                            Err(e.to_string())
+                        }
+                    };
+                    let _ = reply.send(result);
+                }
+                StreamCommand::GetConfig { reply } => {
+                    let _ = reply.send(active.as_ref().map(|a| a.config()));
+                }
+                StreamCommand::Pause { reply } => {
+                    suspended = true;
+                    let result = match &active {
+                        Some(a) => a.stream.pause(),
+                        None => Ok(()),
+                    };
+                    let _ = reply.send(result);
+                }
+                StreamCommand::Resume { reply } => {
+                    let result = if let Some(a) = &active {
+                        a.stream.play()
+                    } else {
+                        match build_stream(&synth, &settings) {
+                            Ok(new_stream) => {
+                                active = Some(new_stream);
+                                Ok(())
+                            }
+                            Err(e) => Err(e.to_string()),
+                        }
+                    };
+                    suspended = result.is_err();
+                    let _ = reply.send(result);
+                }
+                StreamCommand::Shutdown { reply } => {
+                    suspended = true;
+                    drop(input_stream.take());
+                    drop(active.take());
+                    info!("🛑 Desktop audio stream closed");
+                    let _ = reply.send(Ok(()));
+                }
+                StreamCommand::StartInput {
+                    device_name,
+                    input,
+                    reply,
+                } => {
+                    drop(input_stream.take());
+                    let result = match &active {
+                        Some(a) => build_input_stream(
+                            &settings,
+                            device_name.as_deref(),
+                            a.config.engine_sample_rate,
+                            input,
+                        )
+                        .map(|stream| input_stream = Some(stream)),
+                        None => Err("No audio stream running".to_string()),
+                    };
+                    let _ = reply.send(result);
+                }
+                StreamCommand::StopInput { reply } => {
+                    drop(input_stream.take());
+                    let _ = reply.send(Ok(()));
+                }
+            }
+        }
+        drop(input_stream);
+        drop(active);
+    });
 
-    // Keep the stream alive by leaking it (in production, you'd want proper lifecycle management)
-    std::mem::forget(stream);
+    start_result.recv()??;
+    info!("🎯 Desktop audio stream started");
 
-    Ok(())
+    Ok(StreamController {
+        commands,
+        settings: Mutex::new(StreamSettings::default()),
+    })
 }
@@ -0,0 +1,157 @@
+// Drum synthesis for backing beats: a kick, snare and hi-hat, each made from
+// scratch (swept sine and filtered noise) so no samples need shipping
+use fundsp::hacker::{shared, AudioNode, Frame, U0, U1};
+use std::f32::consts::TAU;
+
+/// Below this the hit is inaudible and the drum stops running
+const SILENCE: f32 = 0.0001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DrumKind {
+    Kick,
+    Snare,
+    Hat,
+}
+
+impl DrumKind {
+    pub const ALL: [DrumKind; 3] = [DrumKind::Kick, DrumKind::Snare, DrumKind::Hat];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DrumKind::Kick => "kick",
+            DrumKind::Snare => "snare",
+            DrumKind::Hat => "hat",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "kick" => Some(DrumKind::Kick),
+            "snare" => Some(DrumKind::Snare),
+            "hat" | "hihat" => Some(DrumKind::Hat),
+            _ => None,
+        }
+    }
+}
+
+/// Controls for one drum in the graph. Bumping the trigger starts a hit
+pub struct DrumTrigger {
+    trigger: shared::Shared,
+    velocity: shared::Shared,
+}
+
+impl DrumTrigger {
+    pub fn hit(&self, velocity: f32) {
+        self.velocity.set_value(velocity.clamp(0.0, 1.0));
+        self.trigger.set_value(self.trigger.value() + 1.0);
+    }
+}
+
+/// Create a drum node and the trigger that plays it
+pub fn drum(kind: DrumKind) -> (DrumNode, DrumTrigger) {
+    let trigger = shared(0.0);
+    let velocity = shared(1.0);
+    let node = DrumNode {
+        kind,
+        trigger: trigger.clone(),
+        velocity: velocity.clone(),
+        last_trigger: 0.0,
+        active: false,
+        time: 0.0,
+        phase: 0.0,
+        level: 0.0,
+        noise: 0x1234_5678,
+        last_noise: 0.0,
+        sample_rate: 48000.0,
+    };
+    (node, DrumTrigger { trigger, velocity })
+}
+
+#[derive(Clone)]
+pub struct DrumNode {
+    kind: DrumKind,
+    trigger: shared::Shared,
+    velocity: shared::Shared,
+    last_trigger: f32,
+    active: bool,
+    /// Seconds since the hit
+    time: f32,
+    phase: f32,
+    /// Velocity of the current hit
+    level: f32,
+    /// xorshift state for the noise source
+    noise: u32,
+    /// Previous noise sample, for the hi-hat's high pass
+    last_noise: f32,
+    sample_rate: f32,
+}
+
+impl DrumNode {
+    fn white_noise(&mut self) -> f32 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        let t = self.time;
+        let (output, envelope) = match self.kind {
+            DrumKind::Kick => {
+                // Pitch drops quickly from 150Hz to 45Hz
+                let frequency = 45.0 + 105.0 * (-t / 0.04).exp();
+                self.phase = (self.phase + frequency / self.sample_rate).fract();
+                let envelope = (-t / 0.35).exp();
+                ((self.phase * TAU).sin() * envelope, envelope)
+            }
+            DrumKind::Snare => {
+                self.phase = (self.phase + 185.0 / self.sample_rate).fract();
+                let body = (self.phase * TAU).sin() * (-t / 0.1).exp() * 0.5;
+                let envelope = (-t / 0.15).exp();
+                (body + self.white_noise() * envelope * 0.6, envelope)
+            }
+            DrumKind::Hat => {
+                let noise = self.white_noise();
+                let bright = noise - self.last_noise;
+                self.last_noise = noise;
+                let envelope = (-t / 0.05).exp();
+                (bright * envelope * 0.4, envelope)
+            }
+        };
+        self.time += 1.0 / self.sample_rate;
+        if envelope < SILENCE {
+            self.active = false;
+        }
+        output * self.level
+    }
+}
+
+impl AudioNode for DrumNode {
+    const ID: u64 = 0x4861_7270_4472_756d;
+    type Inputs = U0;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.active = false;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn tick(&mut self, _input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let trigger = self.trigger.value();
+        if trigger != self.last_trigger {
+            self.last_trigger = trigger;
+            self.active = true;
+            self.time = 0.0;
+            self.phase = 0.0;
+            self.level = self.velocity.value();
+        }
+        if !self.active {
+            return [0.0].into();
+        }
+        [self.next_sample()].into()
+    }
+}
@@ -0,0 +1,159 @@
+// The effect chain: which effects the synth runs through, in what order.
+// The order is part of the patch, as filter-then-delay and delay-then-filter
+// are different sounds. Every effect has a wet/dry mix, crossfaded after it
+use super::slots::MAX_EFFECT_SLOTS;
+use fundsp::hacker::{An, AudioNode, AudioUnit, Frame, Net, NodeId, U2, U5};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::f32::consts::FRAC_PI_2;
+
+/// Names of the insert slots, as used in the chain
+const SLOT_NAMES: [&str; MAX_EFFECT_SLOTS] = ["slot_0", "slot_1", "slot_2", "slot_3"];
+
+/// An effect in the chain: one of the built in ones, or an insert slot
+/// hosting whatever effect it's been given (see `slots`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Filter,
+    Delay,
+    /// Insert slot, from 0
+    Slot(usize),
+}
+
+impl Effect {
+    pub const COUNT: usize = 2 + MAX_EFFECT_SLOTS;
+    pub const ALL: [Effect; Effect::COUNT] = [
+        Effect::Filter,
+        Effect::Delay,
+        Effect::Slot(0),
+        Effect::Slot(1),
+        Effect::Slot(2),
+        Effect::Slot(3),
+    ];
+
+    pub fn index(&self) -> usize {
+        match self {
+            Effect::Filter => 0,
+            Effect::Delay => 1,
+            Effect::Slot(slot) => 2 + slot,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Effect::Filter => "filter",
+            Effect::Delay => "delay",
+            Effect::Slot(slot) => SLOT_NAMES[*slot],
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "filter" => Some(Effect::Filter),
+            "delay" => Some(Effect::Delay),
+            name => SLOT_NAMES
+                .iter()
+                .position(|slot| *slot == name)
+                .map(Effect::Slot),
+        }
+    }
+}
+
+impl Serialize for Effect {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Effect {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Effect::from_str(&name).ok_or_else(|| D::Error::custom(format!("unknown effect: {}", name)))
+    }
+}
+
+/// Every effect, once each, first to last. Serialized as a list of names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EffectOrder([Effect; Effect::COUNT]);
+
+impl EffectOrder {
+    /// The order given, with repeats dropped and any effects left out
+    /// added at the end in their default order. Lists from before an effect
+    /// existed so still give a whole chain
+    pub fn new(effects: &[Effect]) -> Self {
+        let mut order = Vec::with_capacity(Effect::COUNT);
+        for &effect in effects.iter().chain(EffectOrder::default().0.iter()) {
+            if !order.contains(&effect) {
+                order.push(effect);
+            }
+        }
+        let mut effects = EffectOrder::default().0;
+        effects.copy_from_slice(&order);
+        EffectOrder(effects)
+    }
+
+    pub fn effects(&self) -> &[Effect] {
+        &self.0
+    }
+}
+
+impl Default for EffectOrder {
+    /// The order the synth has always had, echoes going through the filter,
+    /// then the insert slots
+    fn default() -> Self {
+        EffectOrder([
+            Effect::Delay,
+            Effect::Filter,
+            Effect::Slot(0),
+            Effect::Slot(1),
+            Effect::Slot(2),
+            Effect::Slot(3),
+        ])
+    }
+}
+
+impl Serialize for EffectOrder {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EffectOrder {
+    /// Names this version doesn't know, from a newer one, are skipped
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        let effects: Vec<Effect> = names.iter().filter_map(|n| Effect::from_str(n)).collect();
+        Ok(EffectOrder::new(&effects))
+    }
+}
+
+/// Equal-power crossfade from a dry stereo pair to a wet one, so a half
+/// mix is as loud as either end. Takes (dry left, dry right, wet left,
+/// wet right, mix), mix being 0.0 for all dry to 1.0 for all wet
+#[derive(Clone, Default)]
+pub struct DryWetNode;
+
+impl AudioNode for DryWetNode {
+    const ID: u64 = 0x4861_7270_4472_7957;
+    type Inputs = U5;
+    type Outputs = U2;
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let angle = input[4].clamp(0.0, 1.0) * FRAC_PI_2;
+        let (wet, dry) = angle.sin_cos();
+        [
+            input[0] * dry + input[2] * wet,
+            input[1] * dry + input[3] * wet,
+        ]
+        .into()
+    }
+}
+
+/// Add a wet/dry crossfade to `net`, its mix read from `mix`. The dry and
+/// wet inputs are left for the chain to wire
+pub fn push_dry_wet(net: &mut Net, mix: Box<dyn AudioUnit + Send>) -> NodeId {
+    let dry_wet_nodeid = net.push(Box::new(An(DryWetNode)));
+    let mix_nodeid = net.push(mix);
+    net.connect(mix_nodeid, 0, dry_wet_nodeid, 4);
+    dry_wet_nodeid
+}
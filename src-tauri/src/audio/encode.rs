@@ -0,0 +1,143 @@
+// Formats rendered and recorded audio can be saved in. WAV is written as
+// it comes; FLAC and OGG are encoded from the whole take at once
+use super::wav::{read_stereo_wav, to_int, write_stereo_wav, WavFormat};
+use serde::{Deserialize, Serialize};
+use std::num::{NonZeroU32, NonZeroU8};
+use std::path::Path;
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+/// FLAC is written at CD bit depth, about half the size of 24-bit
+const FLAC_BITS: usize = 16;
+/// Vorbis quality, -0.1 to 1.0. 0.6 is around 190kbps for stereo
+const OGG_QUALITY: f32 = 0.6;
+/// Frames handed to the Vorbis encoder at a time
+const OGG_BLOCK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Wav16,
+    Wav24,
+    /// 32-bit float WAV, lossless but big
+    #[default]
+    WavFloat,
+    /// Lossless, 16-bit
+    Flac,
+    /// Ogg Vorbis, lossy
+    Ogg,
+}
+
+impl ExportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Wav16 => "wav16",
+            ExportFormat::Wav24 => "wav24",
+            ExportFormat::WavFloat => "wav_float",
+            ExportFormat::Flac => "flac",
+            ExportFormat::Ogg => "ogg",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "wav16" => Some(ExportFormat::Wav16),
+            "wav24" => Some(ExportFormat::Wav24),
+            "wav_float" | "wav32" | "wav" => Some(ExportFormat::WavFloat),
+            "flac" => Some(ExportFormat::Flac),
+            "ogg" | "vorbis" => Some(ExportFormat::Ogg),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Wav16 | ExportFormat::Wav24 | ExportFormat::WavFloat => "wav",
+            ExportFormat::Flac => "flac",
+            ExportFormat::Ogg => "ogg",
+        }
+    }
+
+    /// The WAV format, or None for the compressed formats
+    pub fn wav_format(&self) -> Option<WavFormat> {
+        match self {
+            ExportFormat::Wav16 => Some(WavFormat::Int16),
+            ExportFormat::Wav24 => Some(WavFormat::Int24),
+            ExportFormat::WavFloat => Some(WavFormat::Float32),
+            ExportFormat::Flac | ExportFormat::Ogg => None,
+        }
+    }
+}
+
+/// Write stereo frames to `path` in `format`
+pub fn write_stereo(
+    path: &str,
+    sample_rate: u32,
+    frames: &[(f32, f32)],
+    format: ExportFormat,
+) -> Result<(), String> {
+    match format {
+        ExportFormat::Flac => write_flac(path, sample_rate, frames),
+        ExportFormat::Ogg => write_ogg(path, sample_rate, frames),
+        _ => write_stereo_wav(path, sample_rate, frames, format.wav_format().unwrap()),
+    }
+}
+
+/// Re-encode a WAV file as `format` at `dest`
+pub fn transcode_wav(source: &Path, dest: &Path, format: ExportFormat) -> Result<(), String> {
+    let (sample_rate, frames) = read_stereo_wav(&source.to_string_lossy())?;
+    write_stereo(&dest.to_string_lossy(), sample_rate, &frames, format)
+}
+
+fn write_flac(path: &str, sample_rate: u32, frames: &[(f32, f32)]) -> Result<(), String> {
+    use flacenc::component::BitRepr;
+    use flacenc::error::Verify;
+
+    let samples: Vec<i32> = frames
+        .iter()
+        .flat_map(|&(left, right)| {
+            [
+                to_int(left, FLAC_BITS as u32),
+                to_int(right, FLAC_BITS as u32),
+            ]
+        })
+        .collect();
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| format!("Bad FLAC settings: {:?}", e))?;
+    let source =
+        flacenc::source::MemSource::from_samples(&samples, 2, FLAC_BITS, sample_rate as usize);
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| format!("Failed to encode {}: {:?}", path, e))?;
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| format!("Failed to encode {}: {:?}", path, e))?;
+    std::fs::write(path, sink.as_slice()).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+fn write_ogg(path: &str, sample_rate: u32, frames: &[(f32, f32)]) -> Result<(), String> {
+    let file =
+        std::fs::File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let sample_rate =
+        NonZeroU32::new(sample_rate).ok_or_else(|| "Sample rate can't be 0".to_string())?;
+    let channels = NonZeroU8::new(2).unwrap();
+    let error = |e: vorbis_rs::VorbisError| format!("Failed to encode {}: {}", path, e);
+    let mut encoder = VorbisEncoderBuilder::new(sample_rate, channels, file)
+        .map_err(error)?
+        .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+            target_quality: OGG_QUALITY,
+        })
+        .build()
+        .map_err(error)?;
+    let mut left = Vec::with_capacity(OGG_BLOCK);
+    let mut right = Vec::with_capacity(OGG_BLOCK);
+    for block in frames.chunks(OGG_BLOCK) {
+        left.clear();
+        right.clear();
+        left.extend(block.iter().map(|&(l, _)| l));
+        right.extend(block.iter().map(|&(_, r)| r));
+        encoder.encode_audio_block([&left, &right]).map_err(error)?;
+    }
+    encoder.finish().map_err(error)?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+// Multi-stage envelope: delay, attack, hold, decay, sustain, release, with
+// an option to loop the attack / hold / decay stages while the note is held
+use super::synthesis::AdsrParams;
+use fundsp::hacker::{AudioNode, Frame, U1};
+
+/// Which envelope the voices use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvelopeMode {
+    /// The classic four stage envelope
+    #[default]
+    Adsr,
+    /// Adds a delay before the attack and a hold at full level after it
+    Dahdsr,
+    /// DAHDSR, cycling attack / hold / decay for as long as the note is held
+    Looping,
+}
+
+impl EnvelopeMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnvelopeMode::Adsr => "adsr",
+            EnvelopeMode::Dahdsr => "dahdsr",
+            EnvelopeMode::Looping => "looping",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "adsr" => Some(EnvelopeMode::Adsr),
+            "dahdsr" => Some(EnvelopeMode::Dahdsr),
+            "looping" | "loop" => Some(EnvelopeMode::Looping),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Idle,
+    Delay,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// Gate in, level out. Times are baked in; voices rebuild the node when they change
+#[derive(Clone)]
+pub struct DahdsrNode {
+    params: AdsrParams,
+    stage: Stage,
+    /// Seconds into the current stage
+    time: f32,
+    level: f32,
+    /// Level the current stage started from, so retriggers and releases don't jump
+    start_level: f32,
+    sample_rate: f32,
+}
+
+impl DahdsrNode {
+    pub fn new(params: AdsrParams) -> Self {
+        DahdsrNode {
+            params,
+            stage: Stage::Idle,
+            time: 0.0,
+            level: 0.0,
+            start_level: 0.0,
+            sample_rate: 48000.0,
+        }
+    }
+
+    fn enter(&mut self, stage: Stage) {
+        self.stage = stage;
+        self.time = 0.0;
+        self.start_level = self.level;
+    }
+
+    /// Progress through a stage of `length` seconds, 0.0 to 1.0
+    fn progress(&self, length: f32) -> f32 {
+        if length > 0.0 {
+            (self.time / length).min(1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+impl AudioNode for DahdsrNode {
+    const ID: u64 = 0x4861_7270_4461_6864;
+    type Inputs = U1;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.stage = Stage::Idle;
+        self.level = 0.0;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let gate = input[0] > 0.5;
+        let held = !matches!(self.stage, Stage::Idle | Stage::Release);
+        if gate && !held {
+            self.enter(Stage::Delay);
+        } else if !gate && held {
+            self.enter(Stage::Release);
+        }
+
+        let p = self.params;
+        match self.stage {
+            Stage::Idle => self.level = 0.0,
+            Stage::Delay => {
+                if self.time >= p.delay {
+                    self.enter(Stage::Attack);
+                }
+            }
+            Stage::Attack => {
+                let t = self.progress(p.attack);
+                self.level = self.start_level + (1.0 - self.start_level) * t;
+                if t >= 1.0 {
+                    self.enter(Stage::Hold);
+                }
+            }
+            Stage::Hold => {
+                if self.time >= p.hold {
+                    self.enter(Stage::Decay);
+                }
+            }
+            Stage::Decay => {
+                let t = self.progress(p.decay);
+                self.level = 1.0 + (p.sustain - 1.0) * t;
+                if t >= 1.0 {
+                    if p.mode == EnvelopeMode::Looping {
+                        self.enter(Stage::Attack);
+                    } else {
+                        self.enter(Stage::Sustain);
+                    }
+                }
+            }
+            Stage::Sustain => self.level = p.sustain,
+            Stage::Release => {
+                let t = self.progress(p.release);
+                self.level = self.start_level * (1.0 - t);
+                if t >= 1.0 {
+                    self.enter(Stage::Idle);
+                }
+            }
+        }
+        self.time += 1.0 / self.sample_rate;
+        [self.level].into()
+    }
+}
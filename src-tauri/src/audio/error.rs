@@ -0,0 +1,90 @@
+// Errors returned to the frontend. They serialize as `{ kind, message }`, so
+// the UI can tell "audio engine not initialized" apart from a bad argument
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AudioError {
+    #[error("Audio engine not initialized")]
+    NotInitialized,
+    #[error("Failed to acquire synth lock")]
+    Lock,
+    #[error("Event queue full")]
+    QueueFull,
+    #[error("Unexpected result from the audio engine")]
+    UnexpectedResult,
+    /// A string argument that doesn't name anything we know, e.g. a drum
+    #[error("Unknown {kind}: {value}")]
+    InvalidValue { kind: &'static str, value: String },
+    /// Lists the waveforms we do have, so the UI can offer them
+    #[error("Unknown waveform: {value}, expected one of {}", .valid.join(", "))]
+    InvalidWaveform {
+        value: String,
+        valid: Vec<&'static str>,
+    },
+    /// The synth rejected an event
+    #[error("{0}")]
+    Engine(String),
+    /// Opening, configuring or controlling the output stream failed
+    #[error("{0}")]
+    Device(String),
+    #[error("{0}")]
+    Capture(String),
+    #[error("{0}")]
+    Sample(String),
+    #[error("{0}")]
+    Preset(String),
+    /// A pattern or song edit that doesn't fit the arrangement
+    #[error("{0}")]
+    Sequencer(String),
+    #[error("{0}")]
+    Project(String),
+    /// Recording the output to WAV
+    #[error("{0}")]
+    Recording(String),
+    /// Saving or sharing exported audio
+    #[error("{0}")]
+    Export(String),
+    /// Saving app settings
+    #[error("{0}")]
+    Settings(String),
+}
+
+impl AudioError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AudioError::NotInitialized => "not_initialized",
+            AudioError::Lock => "lock",
+            AudioError::QueueFull => "queue_full",
+            AudioError::UnexpectedResult => "unexpected_result",
+            AudioError::InvalidValue { .. } => "invalid_value",
+            AudioError::InvalidWaveform { .. } => "invalid_waveform",
+            AudioError::Engine(_) => "engine",
+            AudioError::Device(_) => "device",
+            AudioError::Capture(_) => "capture",
+            AudioError::Sample(_) => "sample",
+            AudioError::Preset(_) => "preset",
+            AudioError::Sequencer(_) => "sequencer",
+            AudioError::Project(_) => "project",
+            AudioError::Recording(_) => "recording",
+            AudioError::Export(_) => "export",
+            AudioError::Settings(_) => "settings",
+        }
+    }
+}
+
+impl Serialize for AudioError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let valid = match self {
+            AudioError::InvalidWaveform { valid, .. } => Some(valid),
+            _ => None,
+        };
+        let mut state = serializer.serialize_struct("AudioError", 2 + valid.is_some() as usize)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let Some(valid) = valid {
+            state.serialize_field("valid", valid)?;
+        }
+        state.end()
+    }
+}
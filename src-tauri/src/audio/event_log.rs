@@ -0,0 +1,178 @@
+// Event log: every event queued for the synth, written to a JSONL file as it
+// arrives and replayable through the live engine later, for reproducing
+// glitches reported from the field. Unlike a performance capture it's
+// written line by line as it goes, so it survives a crash
+use super::capture::SAMPLE_CLOCK;
+use super::synthesis::AudioEvent;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Version of the log format, in the header line
+const LOG_VERSION: u32 = 1;
+/// How far ahead of the synth's clock replayed events are queued
+const REPLAY_LOOKAHEAD_SECS: f64 = 0.1;
+const REPLAY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The first line of a log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LogHeader {
+    version: u32,
+    sample_rate: f32,
+}
+
+/// Every line after the header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LogEntry {
+    /// Frames since the log started, at the header's sample rate
+    frame: u64,
+    event: AudioEvent,
+}
+
+struct EventLogger {
+    path: String,
+    writer: LineWriter<File>,
+    started_at: u64,
+    count: usize,
+}
+
+static LOGGER: Mutex<Option<EventLogger>> = Mutex::new(None);
+
+/// Start logging to `path`, replacing any log in progress
+pub fn start_event_log(path: &str, sample_rate: f32) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut writer = LineWriter::new(file);
+    let header = LogHeader {
+        version: LOG_VERSION,
+        sample_rate,
+    };
+    write_line(&mut writer, &header).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    info!("📝 Logging events to {}", path);
+    *LOGGER.lock().unwrap() = Some(EventLogger {
+        path: path.to_string(),
+        writer,
+        started_at: SAMPLE_CLOCK.load(Ordering::Relaxed),
+        count: 0,
+    });
+    Ok(())
+}
+
+/// Finish the log in progress. Returns the number of events logged
+pub fn stop_event_log() -> Result<usize, String> {
+    let mut logger = LOGGER
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| "Not logging events".to_string())?;
+    logger
+        .writer
+        .flush()
+        .map_err(|e| format!("Failed to write {}: {}", logger.path, e))?;
+    info!("📝 Logged {} events to {}", logger.count, logger.path);
+    Ok(logger.count)
+}
+
+fn write_line(writer: &mut impl Write, value: &impl Serialize) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writer.write_all(b"\n")
+}
+
+/// Log an event on its way to the synth. Does nothing unless logging.
+/// Called from the event queue, never the audio thread
+pub fn record(event: &AudioEvent) {
+    let mut logger = LOGGER.lock().unwrap();
+    let logger = match logger.as_mut() {
+        Some(logger) => logger,
+        None => return,
+    };
+    let now = SAMPLE_CLOCK.load(Ordering::Relaxed);
+    // Timed events are logged for when they'll take effect, as frames from
+    // another run's clock mean nothing on replay
+    let (frame, event) = match event {
+        AudioEvent::At { frame, event } => ((*frame).max(now), event.as_ref()),
+        _ => (now, event),
+    };
+    let entry = LogEntry {
+        frame: frame.saturating_sub(logger.started_at),
+        event: event.clone(),
+    };
+    match write_line(&mut logger.writer, &entry) {
+        Ok(()) => logger.count += 1,
+        Err(e) => error!("Failed to write {}: {}", logger.path, e),
+    }
+}
+
+/// Read a log written by `start_event_log`
+fn read_event_log(path: &str) -> Result<(LogHeader, Vec<LogEntry>), String> {
+    let file = File::open(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut lines = BufReader::new(file).lines().enumerate().peekable();
+    let parse_error = |line: usize, e: &dyn std::fmt::Display| {
+        format!("Failed to parse {} line {}: {}", path, line + 1, e)
+    };
+    let header: LogHeader = match lines.next() {
+        Some((n, line)) => {
+            let line = line.map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            serde_json::from_str(&line).map_err(|e| parse_error(n, &e))?
+        }
+        None => return Err(format!("{} is empty", path)),
+    };
+    if header.version > LOG_VERSION {
+        return Err(format!(
+            "Event log version {} is newer than this app supports ({})",
+            header.version, LOG_VERSION
+        ));
+    }
+    let mut entries = Vec::new();
+    while let Some((n, line)) = lines.next() {
+        let line = line.map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            // A log cut short by a crash can end mid-line
+            Err(e) if lines.peek().is_none() => {
+                warn!("Ignoring the unfinished last line of {}: {}", path, e)
+            }
+            Err(e) => return Err(parse_error(n, &e)),
+        }
+    }
+    Ok((header, entries))
+}
+
+/// Replay the log at `path` through the live synth, starting now, with the
+/// events' original timing. Events are queued a little ahead of time,
+/// wrapped in `AudioEvent::At`. Returns the number of events once the log
+/// has been read, replaying in the background
+pub fn replay_event_log(path: &str, sample_rate: f32) -> Result<usize, String> {
+    let (header, mut entries) = read_event_log(path)?;
+    entries.sort_by_key(|e| e.frame);
+    let count = entries.len();
+    let scale = sample_rate as f64 / header.sample_rate as f64;
+    let lookahead = (REPLAY_LOOKAHEAD_SECS * sample_rate as f64) as u64;
+    let path = path.to_string();
+    info!("▶️ Replaying {} events from {}", count, path);
+
+    std::thread::spawn(move || {
+        let origin = SAMPLE_CLOCK.load(Ordering::Relaxed) + lookahead;
+        for entry in entries {
+            let frame = origin + (entry.frame as f64 * scale) as u64;
+            while SAMPLE_CLOCK.load(Ordering::Relaxed) + lookahead < frame {
+                std::thread::sleep(REPLAY_INTERVAL);
+            }
+            let event = AudioEvent::At {
+                frame,
+                event: Box::new(entry.event),
+            };
+            if let Err(e) = super::queue_audio_event(event) {
+                error!("Failed to replay event from {}: {}", path, e);
+            }
+        }
+        info!("⏹️ Finished replaying {}", path);
+    });
+    Ok(count)
+}
@@ -0,0 +1,123 @@
+// Settings for the queue that carries events from the UI to the audio thread.
+// They're saved in the app's config directory and read at startup, as the
+// queue can't be resized once the audio is running
+use super::synthesis::AudioEvent;
+use rtrb::Consumer;
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use tauri::Manager;
+#[cfg(not(target_arch = "wasm32"))]
+use tracing::warn;
+
+/// Slots in the event queue unless configured otherwise. Fast slider drags
+/// can queue a few hundred events between buffers
+pub const DEFAULT_EVENT_QUEUE_CAPACITY: usize = 512;
+/// Upper limit on the configured capacity, well past anything useful
+const MAX_EVENT_QUEUE_CAPACITY: usize = 65536;
+#[cfg(not(target_arch = "wasm32"))]
+const CONFIG_FILE: &str = "event_queue.json";
+
+/// Slots in the note lane, which carries notes and the other events that
+/// can't be coalesced (splits, layers, key zones, ...), in send order. They
+/// arrive at playing speed, so this never fills while the audio runs
+pub const NOTE_QUEUE_CAPACITY: usize = 256;
+
+/// What to do with an event when the queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Refuse the new event, returning `AudioError::QueueFull`
+    #[default]
+    Reject,
+    /// Discard the oldest queued event to make room. The newest value of a
+    /// dragged parameter always arrives, but a queued note event can be lost
+    DropOldest,
+}
+
+impl OverflowPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverflowPolicy::Reject => "reject",
+            OverflowPolicy::DropOldest => "drop_oldest",
+        }
+    }
+}
+
+/// The audio thread's end of the event queue. The audio thread only tries
+/// the lock; it's taken elsewhere just to drop the oldest event when full
+pub type SharedConsumer = Arc<Mutex<Consumer<AudioEvent>>>;
+
+/// Passed to `initialize_audio_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventQueueConfig {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for EventQueueConfig {
+    fn default() -> Self {
+        EventQueueConfig {
+            capacity: DEFAULT_EVENT_QUEUE_CAPACITY,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+impl EventQueueConfig {
+    /// With the capacity brought into range
+    pub fn clamped(self) -> Self {
+        EventQueueConfig {
+            capacity: self.capacity.clamp(1, MAX_EVENT_QUEUE_CAPACITY),
+            ..self
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn config_path() -> Result<PathBuf, String> {
+    let app = super::APP_HANDLE
+        .get()
+        .ok_or_else(|| "No app handle".to_string())?;
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(CONFIG_FILE))
+}
+
+/// The saved settings, or the defaults if none have been saved or they
+/// can't be read
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_config() -> EventQueueConfig {
+    let path = match config_path() {
+        Ok(path) => path,
+        Err(_) => return EventQueueConfig::default(),
+    };
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return EventQueueConfig::default(),
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            return EventQueueConfig::default();
+        }
+    };
+    match serde_json::from_str::<EventQueueConfig>(&json) {
+        Ok(config) => config.clamped(),
+        Err(e) => {
+            warn!("Failed to parse {}: {}", path.display(), e);
+            EventQueueConfig::default()
+        }
+    }
+}
+
+/// Save settings for the next startup
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_config(config: EventQueueConfig) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&config.clamped()).map_err(|e| e.to_string())?;
+    let path = config_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
@@ -0,0 +1,114 @@
+// Envelope follower on the audio input, as a modulation source: the input's
+// level can open the filter or gate the voices' amplitude
+use fundsp::hacker::{shared, AudioNode, Frame, U2};
+use serde::{Deserialize, Serialize};
+
+/// Follower times, quick to open and smooth on the way down
+const FOLLOWER_ATTACK: f32 = 0.010;
+const FOLLOWER_RELEASE: f32 = 0.150;
+/// A mic at normal levels peaks around a quarter of full scale, so the
+/// envelope is scaled up to reach 1.0
+const FOLLOWER_SENSITIVITY: f32 = 4.0;
+/// Octaves the cutoff moves at full amount and full envelope
+const CUTOFF_RANGE_OCTAVES: f32 = 4.0;
+
+/// What the input envelope modulates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModDestination {
+    /// Amount -1.0 to 1.0: the cutoff moves up (or down) by up to 4 octaves
+    FilterCutoff,
+    /// Amount 0.0 to 1.0: the voices are only as loud as the input, like a
+    /// tremolo following the input. The audio input itself isn't affected
+    Amplitude,
+}
+
+impl ModDestination {
+    pub const ALL: [ModDestination; 2] = [ModDestination::FilterCutoff, ModDestination::Amplitude];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModDestination::FilterCutoff => "filter_cutoff",
+            ModDestination::Amplitude => "amplitude",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "filter_cutoff" | "cutoff" => Some(ModDestination::FilterCutoff),
+            "amplitude" | "amp" => Some(ModDestination::Amplitude),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Amounts outside this are clamped
+    pub fn range(self) -> (f32, f32) {
+        match self {
+            ModDestination::FilterCutoff => (-1.0, 1.0),
+            ModDestination::Amplitude => (0.0, 1.0),
+        }
+    }
+}
+
+/// Follows the level of its (stereo) input. Outputs are a multiplier for
+/// the filter cutoff and a gain for the voices, from the amounts for each
+/// `ModDestination`
+#[derive(Clone)]
+pub struct FollowerNode {
+    amounts: [shared::Shared; 2],
+    envelope: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl FollowerNode {
+    pub fn new(amounts: [shared::Shared; 2]) -> Self {
+        let mut node = FollowerNode {
+            amounts,
+            envelope: 0.0,
+            attack: 0.0,
+            release: 0.0,
+        };
+        node.set_sample_rate(48000.0);
+        node
+    }
+}
+
+impl AudioNode for FollowerNode {
+    const ID: u64 = 0x4861_7270_466f_6c6c;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        let sample_rate = sample_rate as f32;
+        self.attack = 1.0 - (-1.0 / (FOLLOWER_ATTACK * sample_rate)).exp();
+        self.release = 1.0 - (-1.0 / (FOLLOWER_RELEASE * sample_rate)).exp();
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let level = input[0].abs().max(input[1].abs());
+        let rate = if level > self.envelope {
+            self.attack
+        } else {
+            self.release
+        };
+        self.envelope += rate * (level - self.envelope);
+        let envelope = (self.envelope * FOLLOWER_SENSITIVITY).min(1.0);
+
+        let cutoff_amount = self.amounts[ModDestination::FilterCutoff.index()].value();
+        let amplitude_amount = self.amounts[ModDestination::Amplitude.index()].value();
+        [
+            (envelope * cutoff_amount * CUTOFF_RANGE_OCTAVES).exp2(),
+            1.0 - amplitude_amount * (1.0 - envelope),
+        ]
+        .into()
+    }
+}
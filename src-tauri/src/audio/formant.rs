@@ -0,0 +1,77 @@
+// Formant filter: three band passes at the resonances of a sung vowel, so
+// a plain waveform comes out sounding like it's saying "ah" or "oo". The
+// vowel morphs smoothly from A through E, I and O to U
+use super::svf::{Svf, SvfCoefficients};
+use fundsp::hacker::{shared, AudioNode, Frame, U2};
+
+/// Formant frequencies (Hz) and gains (linear) for each vowel, A E I O U,
+/// from a tenor voice
+const VOWELS: [[(f32, f32); 3]; 5] = [
+    [(650.0, 1.0), (1080.0, 0.501), (2650.0, 0.447)],
+    [(400.0, 1.0), (1700.0, 0.2), (2600.0, 0.251)],
+    [(290.0, 1.0), (1870.0, 0.178), (2800.0, 0.126)],
+    [(400.0, 1.0), (800.0, 0.316), (2600.0, 0.251)],
+    [(350.0, 1.0), (600.0, 0.1), (2700.0, 0.141)],
+];
+/// Narrow band passes take most of the level away, this brings it back
+const MAKEUP_GAIN: f32 = 2.0;
+
+/// Stereo formant filter. Takes `vowel`, 0.0 (A) to 4.0 (U) with the vowels
+/// between blended, and `resonance`, 0.0 to 1.0, how narrow and ringing
+/// the formants are
+#[derive(Clone)]
+pub struct FormantNode {
+    vowel: shared::Shared,
+    resonance: shared::Shared,
+    filters: [[Svf; 3]; 2],
+    sample_rate: f32,
+}
+
+impl FormantNode {
+    pub fn new(vowel: shared::Shared, resonance: shared::Shared) -> Self {
+        FormantNode {
+            vowel,
+            resonance,
+            filters: [[Svf::default(); 3]; 2],
+            sample_rate: 48000.0,
+        }
+    }
+}
+
+impl AudioNode for FormantNode {
+    const ID: u64 = 0x4861_7270_466f_726d;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.filters.iter_mut().flatten().for_each(Svf::reset);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let vowel = self.vowel.value().clamp(0.0, 4.0);
+        let from = (vowel.floor() as usize).min(3);
+        let blend = vowel - from as f32;
+        let q = 4.0 + self.resonance.value().clamp(0.0, 1.0) * 16.0;
+        let mut formants = [(SvfCoefficients::new(1000.0, q, self.sample_rate), 0.0); 3];
+        for (formant, (a, b)) in formants
+            .iter_mut()
+            .zip(VOWELS[from].iter().zip(&VOWELS[from + 1]))
+        {
+            let frequency = a.0 + (b.0 - a.0) * blend;
+            let gain = a.1 + (b.1 - a.1) * blend;
+            *formant = (SvfCoefficients::new(frequency, q, self.sample_rate), gain);
+        }
+        let mut output = [0.0; 2];
+        for channel in 0..2 {
+            for (filter, (coefficients, gain)) in self.filters[channel].iter_mut().zip(&formants) {
+                output[channel] += filter.bandpass(input[channel], coefficients) * gain;
+            }
+            output[channel] *= MAKEUP_GAIN;
+        }
+        output.into()
+    }
+}
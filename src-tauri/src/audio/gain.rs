@@ -0,0 +1,66 @@
+// Gain staging in dB: conversions, and the trims along the signal path
+use serde::{Deserialize, Serialize};
+
+/// Anything at or below this is treated as silence
+pub const SILENCE_DB: f32 = -60.0;
+
+/// Trims range, in dB
+pub const MIN_TRIM_DB: f32 = -24.0;
+pub const MAX_TRIM_DB: f32 = 12.0;
+
+pub fn db_to_gain(db: f32) -> f32 {
+    if db <= SILENCE_DB {
+        0.0
+    } else {
+        10f32.powf(db / 20.0)
+    }
+}
+
+pub fn gain_to_db(gain: f32) -> f32 {
+    if gain <= 0.0 {
+        SILENCE_DB
+    } else {
+        (20.0 * gain.log10()).max(SILENCE_DB)
+    }
+}
+
+/// Points in the signal path with a gain trim
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GainStage {
+    /// The voices, summed, before any effects
+    Oscillator,
+    /// Into the filter, wherever it is in the effect chain
+    PreFilter,
+    /// The synth after its effects, before the drums join
+    PostFx,
+}
+
+impl GainStage {
+    pub const ALL: [GainStage; 3] = [
+        GainStage::Oscillator,
+        GainStage::PreFilter,
+        GainStage::PostFx,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GainStage::Oscillator => "oscillator",
+            GainStage::PreFilter => "pre_filter",
+            GainStage::PostFx => "post_fx",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "oscillator" | "osc" => Some(GainStage::Oscillator),
+            "pre_filter" => Some(GainStage::PreFilter),
+            "post_fx" => Some(GainStage::PostFx),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
@@ -0,0 +1,314 @@
+// Game controller input: buttons play notes or chords, analog sticks and
+// triggers bend the pitch or sweep the filter. A thread polls every
+// connected controller through gilrs; the mapping can be changed at any time
+use super::error::AudioError;
+use super::synthesis::AudioEvent;
+use gilrs::{Axis, Button, EventType, Gilrs};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(4);
+/// Stick travel ignored around the centre, where worn sticks rest
+const AXIS_DEAD_ZONE: f32 = 0.1;
+/// Each button plays its own note ids, clear of the UI's, the computer
+/// keyboard's, pitch tracking's and the scheduler's
+const GAMEPAD_NOTE_ID_BASE: u32 = 0x7ffd_0000;
+/// Most notes a button's chord can have
+const MAX_CHORD_NOTES: usize = 16;
+
+/// What pressing a button does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ButtonAction {
+    Note { frequency: f32 },
+    Chord { frequencies: Vec<f32> },
+}
+
+/// What moving an axis does. Sticks rest at 0.0 and reach -1.0 to 1.0
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AxisAction {
+    /// Bend the pitch, across the pitch bend range
+    PitchBend,
+    /// Sweep the filter cutoff from `min` (axis at -1.0) to `max` (at
+    /// 1.0), in Hz. This sets the patch's cutoff, so suits triggers and
+    /// sticks that stay put better than sticks that spring back
+    FilterCutoff { min: f32, max: f32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ButtonBinding {
+    /// e.g. "south", "dpad_up", "right_trigger", see `button_from_str`
+    pub button: String,
+    pub action: ButtonAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AxisBinding {
+    /// e.g. "left_stick_x", see `axis_from_str`
+    pub axis: String,
+    pub action: AxisAction,
+}
+
+/// Which controls do what. Unmapped controls do nothing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadMapping {
+    pub buttons: Vec<ButtonBinding>,
+    pub axes: Vec<AxisBinding>,
+    /// Velocity of every note, 0.0 to 1.0
+    pub velocity: f32,
+}
+
+impl Default for GamepadMapping {
+    /// Face buttons play a C major arpeggio, the d-pad the I, IV, V and vi
+    /// chords; the left stick bends the pitch and the right one sweeps the filter
+    fn default() -> Self {
+        let note = |button: &str, frequency: f32| ButtonBinding {
+            button: button.to_string(),
+            action: ButtonAction::Note { frequency },
+        };
+        let chord = |button: &str, frequencies: &[f32]| ButtonBinding {
+            button: button.to_string(),
+            action: ButtonAction::Chord {
+                frequencies: frequencies.to_vec(),
+            },
+        };
+        GamepadMapping {
+            buttons: vec![
+                note("south", 261.63),
+                note("east", 329.63),
+                note("west", 392.00),
+                note("north", 523.25),
+                chord("dpad_up", &[261.63, 329.63, 392.00]),
+                chord("dpad_right", &[349.23, 440.00, 523.25]),
+                chord("dpad_down", &[392.00, 493.88, 587.33]),
+                chord("dpad_left", &[220.00, 261.63, 329.63]),
+            ],
+            axes: vec![
+                AxisBinding {
+                    axis: "left_stick_x".to_string(),
+                    action: AxisAction::PitchBend,
+                },
+                AxisBinding {
+                    axis: "right_stick_y".to_string(),
+                    action: AxisAction::FilterCutoff {
+                        min: 200.0,
+                        max: 8000.0,
+                    },
+                },
+            ],
+            velocity: 0.8,
+        }
+    }
+}
+
+const BUTTONS: [(&str, Button); 19] = [
+    ("south", Button::South),
+    ("east", Button::East),
+    ("north", Button::North),
+    ("west", Button::West),
+    ("c", Button::C),
+    ("z", Button::Z),
+    ("left_trigger", Button::LeftTrigger),
+    ("left_trigger2", Button::LeftTrigger2),
+    ("right_trigger", Button::RightTrigger),
+    ("right_trigger2", Button::RightTrigger2),
+    ("select", Button::Select),
+    ("start", Button::Start),
+    ("mode", Button::Mode),
+    ("left_thumb", Button::LeftThumb),
+    ("right_thumb", Button::RightThumb),
+    ("dpad_up", Button::DPadUp),
+    ("dpad_down", Button::DPadDown),
+    ("dpad_left", Button::DPadLeft),
+    ("dpad_right", Button::DPadRight),
+];
+
+const AXES: [(&str, Axis); 6] = [
+    ("left_stick_x", Axis::LeftStickX),
+    ("left_stick_y", Axis::LeftStickY),
+    ("left_z", Axis::LeftZ),
+    ("right_stick_x", Axis::RightStickX),
+    ("right_stick_y", Axis::RightStickY),
+    ("right_z", Axis::RightZ),
+];
+
+fn button_from_str(s: &str) -> Option<Button> {
+    let s = s.to_lowercase();
+    BUTTONS.iter().find(|(name, _)| *name == s).map(|&(_, b)| b)
+}
+
+fn axis_from_str(s: &str) -> Option<Axis> {
+    let s = s.to_lowercase();
+    AXES.iter().find(|(name, _)| *name == s).map(|&(_, a)| a)
+}
+
+/// A mapping with the names looked up, as the polling thread uses it
+struct ResolvedMapping {
+    buttons: Vec<(Button, ButtonAction)>,
+    axes: Vec<(Axis, AxisAction)>,
+    velocity: f32,
+}
+
+/// The mapping as set, and looked up. None for the default mapping
+static MAPPING: Mutex<Option<(GamepadMapping, ResolvedMapping)>> = Mutex::new(None);
+
+fn resolve(mapping: &GamepadMapping) -> Result<ResolvedMapping, AudioError> {
+    let buttons = mapping
+        .buttons
+        .iter()
+        .map(|binding| {
+            let button = button_from_str(&binding.button).ok_or(AudioError::InvalidValue {
+                kind: "gamepad button",
+                value: binding.button.clone(),
+            })?;
+            Ok((button, binding.action.clone()))
+        })
+        .collect::<Result<_, AudioError>>()?;
+    let axes = mapping
+        .axes
+        .iter()
+        .map(|binding| {
+            let axis = axis_from_str(&binding.axis).ok_or(AudioError::InvalidValue {
+                kind: "gamepad axis",
+                value: binding.axis.clone(),
+            })?;
+            Ok((axis, binding.action))
+        })
+        .collect::<Result<_, AudioError>>()?;
+    Ok(ResolvedMapping {
+        buttons,
+        axes,
+        velocity: mapping.velocity.clamp(0.0, 1.0),
+    })
+}
+
+/// Replace the mapping. Fails, leaving the mapping as it was, if it names a
+/// control gilrs doesn't know
+pub fn set_gamepad_mapping(mapping: GamepadMapping) -> Result<(), AudioError> {
+    let resolved = resolve(&mapping)?;
+    *MAPPING.lock().unwrap() = Some((mapping, resolved));
+    Ok(())
+}
+
+pub fn gamepad_mapping() -> GamepadMapping {
+    match MAPPING.lock().unwrap().as_ref() {
+        Some((mapping, _)) => mapping.clone(),
+        None => GamepadMapping::default(),
+    }
+}
+
+fn note_id(button_index: usize, note: usize) -> u32 {
+    GAMEPAD_NOTE_ID_BASE + (button_index * MAX_CHORD_NOTES + note) as u32
+}
+
+/// The events for one controller event
+fn events_for(mapping: &ResolvedMapping, event: &EventType) -> Vec<AudioEvent> {
+    match *event {
+        EventType::ButtonPressed(button, _) | EventType::ButtonReleased(button, _) => {
+            let pressed = matches!(event, EventType::ButtonPressed(..));
+            let (index, action) = match mapping.buttons.iter().position(|(b, _)| *b == button) {
+                Some(index) => (index, &mapping.buttons[index].1),
+                None => return Vec::new(),
+            };
+            let frequencies = match action {
+                ButtonAction::Note { frequency } => std::slice::from_ref(frequency),
+                ButtonAction::Chord { frequencies } => frequencies.as_slice(),
+            };
+            frequencies
+                .iter()
+                .take(MAX_CHORD_NOTES)
+                .enumerate()
+                .map(|(note, &frequency)| {
+                    let note_id = note_id(index, note);
+                    if pressed {
+                        AudioEvent::PlayNote {
+                            note_id,
+                            frequency,
+                            velocity: mapping.velocity,
+                        }
+                    } else {
+                        AudioEvent::NoteOff { note_id }
+                    }
+                })
+                .collect()
+        }
+        EventType::AxisChanged(axis, value, _) => {
+            let value = if value.abs() < AXIS_DEAD_ZONE {
+                0.0
+            } else {
+                value.clamp(-1.0, 1.0)
+            };
+            mapping
+                .axes
+                .iter()
+                .filter(|(a, _)| *a == axis)
+                .map(|(_, action)| match *action {
+                    AxisAction::PitchBend => AudioEvent::SetPitchBend { bend: value },
+                    AxisAction::FilterCutoff { min, max } => {
+                        let (min, max) = (min.max(20.0), max.max(20.0));
+                        AudioEvent::SetFilterCutoff {
+                            cutoff: min * (max / min).powf((value + 1.0) / 2.0),
+                        }
+                    }
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Start polling the controllers. Controllers plugged in later are picked
+/// up as they connect
+pub fn start_gamepad_input() {
+    std::thread::spawn(|| {
+        let mut gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(e) => {
+                warn!("No game controller support: {}", e);
+                return;
+            }
+        };
+        for (_, gamepad) in gilrs.gamepads() {
+            info!("🎮 Game controller: {}", gamepad.name());
+        }
+        // The default mapping only names controls we know
+        let default_mapping = match resolve(&GamepadMapping::default()) {
+            Ok(mapping) => mapping,
+            Err(e) => {
+                warn!("Bad default game controller mapping: {}", e);
+                return;
+            }
+        };
+        loop {
+            while let Some(event) = gilrs.next_event() {
+                match event.event {
+                    EventType::Connected => {
+                        info!(
+                            "🎮 Game controller connected: {}",
+                            gilrs.gamepad(event.id).name()
+                        )
+                    }
+                    EventType::Disconnected => {
+                        info!("🎮 Game controller disconnected");
+                        let _ = super::queue_audio_event(AudioEvent::SetPitchBend { bend: 0.0 });
+                    }
+                    _ => {}
+                }
+                let events = match MAPPING.lock().unwrap().as_ref() {
+                    Some((_, mapping)) => events_for(mapping, &event.event),
+                    None => events_for(&default_mapping, &event.event),
+                };
+                for audio_event in events {
+                    if let Err(e) = super::queue_audio_event(audio_event) {
+                        warn!("Dropped game controller event: {}", e);
+                    }
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
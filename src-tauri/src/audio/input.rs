@@ -0,0 +1,73 @@
+// Audio input (mic or line-in), fed into the synth ahead of the delay, so
+// Harphonium can be used as an effects processor. The input stream pushes
+// stereo frames into a lock-free ring and the synth pulls them out as it
+// renders. A mono copy goes to the analysis thread (tuner etc.)
+use rtrb::{Consumer, Producer, RingBuffer};
+
+/// Room for a few input callbacks' worth, in case the input and output
+/// callbacks run out of step
+const INPUT_CAPACITY: usize = 8192;
+/// Room for a couple of analysis passes at 48kHz
+const ANALYSIS_CAPACITY: usize = 16384;
+/// If more than this many frames build up (the two devices' clocks drift,
+/// or output stalled for a while) the oldest are dropped to keep latency down
+const MAX_BACKLOG: usize = 2048;
+
+/// The input stream's end
+pub struct InputProducer {
+    producer: Producer<(f32, f32)>,
+    analysis: Producer<f32>,
+}
+
+impl InputProducer {
+    /// Push interleaved frames. Mono input goes to both sides, and any
+    /// channels past the second are ignored. Drops frames if the synth isn't
+    /// keeping up, rather than blocking the input callback
+    pub fn push_interleaved(&mut self, data: &[f32], channels: usize) {
+        if channels == 0 {
+            return;
+        }
+        for frame in data.chunks_exact(channels) {
+            let left = frame[0];
+            let right = if channels > 1 { frame[1] } else { left };
+            // Each ring drops frames by itself when its reader falls behind
+            let _ = self.producer.push((left, right));
+            let _ = self.analysis.push(0.5 * (left + right));
+        }
+    }
+}
+
+/// The synth's end
+pub struct InputConsumer {
+    consumer: Consumer<(f32, f32)>,
+}
+
+impl InputConsumer {
+    /// Fill `left` and `right` with the next input frames, padding with
+    /// silence if the input hasn't caught up
+    pub fn read(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let backlog = self.consumer.slots();
+        if backlog > MAX_BACKLOG {
+            if let Ok(chunk) = self.consumer.read_chunk(backlog - MAX_BACKLOG / 2) {
+                chunk.commit_all();
+            }
+        }
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            let (left, right) = self.consumer.pop().unwrap_or((0.0, 0.0));
+            *l = left;
+            *r = right;
+        }
+    }
+}
+
+/// Create an input ring, returning the input stream's end, the synth's end
+/// and the analysis thread's (mono) end
+pub fn input_ring() -> (InputProducer, InputConsumer, Consumer<f32>) {
+    let (producer, consumer) = RingBuffer::new(INPUT_CAPACITY);
+    let (analysis, analysis_consumer) = RingBuffer::new(ANALYSIS_CAPACITY);
+    (
+        InputProducer { producer, analysis },
+        InputConsumer { consumer },
+        analysis_consumer,
+    )
+}
@@ -0,0 +1,89 @@
+// Instrument modes: the ways the UI can be played, each with the playing
+// settings that suit it. Switching mode applies its profile; the settings
+// can still be changed one by one afterwards
+use super::mono::NotePriority;
+use super::schedule::StrumSettings;
+use super::smoothing::Smoothing;
+use super::voice::{StealPolicy, DEFAULT_FREQUENCY_SMOOTHING};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstrumentMode {
+    /// Piano-style keys: polyphonic, notes start when they're pressed
+    #[default]
+    Keyboard,
+    /// Violin-style fingerboard: one voice, gliding legato between notes
+    Fretless,
+    /// Strummed chords, on the beat
+    Harp,
+}
+
+impl InstrumentMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstrumentMode::Keyboard => "keyboard",
+            InstrumentMode::Fretless => "fretless",
+            InstrumentMode::Harp => "harp",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "keyboard" | "keys" | "piano" => Some(InstrumentMode::Keyboard),
+            "fretless" | "violin" => Some(InstrumentMode::Fretless),
+            "harp" | "strum" => Some(InstrumentMode::Harp),
+            _ => None,
+        }
+    }
+
+    /// The settings this mode starts with
+    pub fn profile(&self) -> InstrumentProfile {
+        match self {
+            InstrumentMode::Keyboard => InstrumentProfile {
+                mono: false,
+                note_priority: NotePriority::Last,
+                steal_policy: StealPolicy::Oldest,
+                quantize: 0,
+                strum_settings: StrumSettings::default(),
+                frequency_smoothing: DEFAULT_FREQUENCY_SMOOTHING,
+            },
+            InstrumentMode::Fretless => InstrumentProfile {
+                mono: true,
+                note_priority: NotePriority::Last,
+                steal_policy: StealPolicy::Oldest,
+                quantize: 0,
+                strum_settings: StrumSettings::default(),
+                // Long enough to hide a finger wobbling on the fingerboard
+                frequency_smoothing: Smoothing::new(0.02, 0.02),
+            },
+            InstrumentMode::Harp => InstrumentProfile {
+                mono: false,
+                note_priority: NotePriority::Last,
+                // Re-plucking a string takes over its own voice
+                steal_policy: StealPolicy::SamePitch,
+                // Sixteenths
+                quantize: 4,
+                strum_settings: StrumSettings {
+                    interval_ms: 40.0,
+                    velocity_start: 0.9,
+                    velocity_end: 0.7,
+                    hold_ms: 1500.0,
+                },
+                frequency_smoothing: DEFAULT_FREQUENCY_SMOOTHING,
+            },
+        }
+    }
+}
+
+/// Playing settings applied by `FunDSPSynth::set_instrument_mode`
+#[derive(Debug, Clone, Copy)]
+pub struct InstrumentProfile {
+    pub mono: bool,
+    pub note_priority: NotePriority,
+    pub steal_policy: StealPolicy,
+    /// See `FunDSPSynth::set_quantize`
+    pub quantize: u32,
+    pub strum_settings: StrumSettings,
+    pub frequency_smoothing: Smoothing,
+}
@@ -0,0 +1,288 @@
+// Jam sync: devices on the same network play in time. One is the master
+// and broadcasts its tempo and whether the sequencer is running; the others
+// follow, changing tempo and starting and stopping with it. Discovery is just
+// UDP broadcast, so there's nothing to set up beyond picking a session name
+// both sides share. Starts arrive a network hop late, a few ms on a LAN
+use super::error::AudioError;
+use super::mirror::PARAM_MIRROR;
+use super::synthesis::{AudioEvent, AudioEventResult};
+use serde::{Deserialize, Serialize};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Version of the packet format. Packets from other versions are ignored
+const JAM_VERSION: u32 = 1;
+/// How often the master repeats its state, so followers that join late or
+/// miss a packet catch up
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(250);
+/// A master unheard for this long is gone
+const MASTER_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long the follower waits on the socket before checking it's still wanted
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JamRole {
+    Off,
+    /// Broadcast this device's transport
+    Master,
+    /// Follow the master's transport
+    Follower,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JamSettings {
+    pub role: JamRole,
+    /// Only devices with the same session name play together
+    pub session: String,
+    /// UDP port, the same on every device
+    pub port: u16,
+}
+
+impl Default for JamSettings {
+    fn default() -> Self {
+        JamSettings {
+            role: JamRole::Off,
+            session: "harphonium".to_string(),
+            port: 47100,
+        }
+    }
+}
+
+/// How a jam is going, for the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct JamStatus {
+    pub role: JamRole,
+    /// The master being followed, if one's been heard lately
+    pub master: Option<String>,
+    pub tempo: f32,
+    pub playing: bool,
+}
+
+/// What the master broadcasts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JamPacket {
+    version: u32,
+    session: String,
+    tempo: f32,
+    playing: bool,
+    /// The pattern it started from
+    pattern: usize,
+}
+
+struct Jam {
+    settings: JamSettings,
+    /// The master's socket, for sending changes as they happen
+    socket: Option<UdpSocket>,
+    tempo: f32,
+    playing: bool,
+    pattern: usize,
+    /// The master followed and when it was last heard
+    master: Option<(SocketAddr, Instant)>,
+    /// Tells the jam's thread to stop
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+static JAM: Mutex<Option<Jam>> = Mutex::new(None);
+
+fn current_tempo() -> f32 {
+    match PARAM_MIRROR.answer(&AudioEvent::GetTempo) {
+        Some(AudioEventResult::ValueF32(tempo)) => tempo,
+        _ => 120.0,
+    }
+}
+
+impl Jam {
+    fn packet(&self) -> Vec<u8> {
+        let packet = JamPacket {
+            version: JAM_VERSION,
+            session: self.settings.session.clone(),
+            tempo: self.tempo,
+            playing: self.playing,
+            pattern: self.pattern,
+        };
+        serde_json::to_vec(&packet).unwrap_or_default()
+    }
+
+    fn broadcast(&self) {
+        if let Some(socket) = &self.socket {
+            let destination = SocketAddr::from(([255, 255, 255, 255], self.settings.port));
+            if let Err(e) = socket.send_to(&self.packet(), destination) {
+                warn!("Failed to send jam sync: {}", e);
+            }
+        }
+    }
+
+    /// Catch up with a packet from the master, returning the events that
+    /// takes. They're queued once the jam is unlocked, as queueing comes
+    /// back through `record`
+    fn follow(&mut self, packet: JamPacket, from: SocketAddr) -> Vec<AudioEvent> {
+        let joined = self.master.map_or(true, |(master, _)| master != from);
+        if joined {
+            info!("🥁 Following the jam master at {}", from);
+        }
+        self.master = Some((from, Instant::now()));
+        let mut events = Vec::new();
+        if (packet.tempo - self.tempo).abs() > 0.001 {
+            events.push(AudioEvent::SetTempo { bpm: packet.tempo });
+        }
+        // Joining mid-song waits for the master's next start, as starting
+        // now would be out of step. So the first packet from a master only
+        // takes its state, and play waits for it to stop and start again
+        if packet.playing && !self.playing && !joined {
+            events.push(AudioEvent::SequencerPlay {
+                pattern: packet.pattern,
+            });
+        } else if !packet.playing && self.playing {
+            events.push(AudioEvent::SequencerStop);
+        }
+        self.tempo = packet.tempo;
+        self.playing = packet.playing;
+        self.pattern = packet.pattern;
+        events
+    }
+}
+
+/// As master, pass on transport changes as they're queued
+pub fn record(event: &AudioEvent) {
+    let mut jam = JAM.lock().unwrap();
+    let jam = match jam.as_mut() {
+        Some(jam) if jam.settings.role == JamRole::Master => jam,
+        _ => return,
+    };
+    match *event {
+        AudioEvent::SetTempo { bpm } => jam.tempo = bpm,
+        AudioEvent::SequencerPlay { pattern } => {
+            jam.playing = true;
+            jam.pattern = pattern;
+        }
+        AudioEvent::SequencerStop => jam.playing = false,
+        _ => return,
+    }
+    jam.broadcast();
+}
+
+fn run_master(running: Arc<AtomicBool>) {
+    while running.load(Ordering::Relaxed) {
+        if let Some(jam) = JAM.lock().unwrap().as_ref() {
+            jam.broadcast();
+        }
+        std::thread::sleep(HEARTBEAT_INTERVAL);
+    }
+}
+
+fn run_follower(socket: UdpSocket, running: Arc<AtomicBool>) {
+    let mut buffer = [0u8; 1024];
+    while running.load(Ordering::Relaxed) {
+        let received = socket.recv_from(&mut buffer);
+        let mut jam = JAM.lock().unwrap();
+        let jam = match jam.as_mut() {
+            Some(jam) => jam,
+            None => return,
+        };
+        if let Some((master, heard)) = jam.master {
+            if heard.elapsed() > MASTER_TIMEOUT {
+                info!("🥁 Lost the jam master at {}", master);
+                jam.master = None;
+            }
+        }
+        let (len, from) = match received {
+            Ok(received) => received,
+            // Timed out, go round again
+            Err(_) => continue,
+        };
+        let events = match serde_json::from_slice::<JamPacket>(&buffer[..len]) {
+            Ok(packet)
+                if packet.version == JAM_VERSION && packet.session == jam.settings.session =>
+            {
+                jam.follow(packet, from)
+            }
+            _ => continue,
+        };
+        drop(jam);
+        for event in events {
+            if let Err(e) = super::queue_audio_event(event) {
+                warn!("Dropped jam sync event: {}", e);
+            }
+        }
+    }
+}
+
+/// Start or stop jamming, or switch role or session. Fails, leaving jam
+/// sync off, if the socket can't be opened
+pub fn set_jam_settings(settings: JamSettings) -> Result<(), AudioError> {
+    // The old thread has to be gone before a new one binds the port. It
+    // takes the lock itself, so it's waited for without it
+    let old = JAM.lock().unwrap().take();
+    if let Some(mut old) = old {
+        old.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = old.thread.take() {
+            let _ = thread.join();
+        }
+    }
+    let device_error = |e: std::io::Error| AudioError::Device(e.to_string());
+    let running = Arc::new(AtomicBool::new(true));
+    let mut new = Jam {
+        settings: settings.clone(),
+        socket: None,
+        tempo: current_tempo(),
+        playing: false,
+        pattern: 0,
+        master: None,
+        running: running.clone(),
+        thread: None,
+    };
+    match settings.role {
+        JamRole::Off => {}
+        JamRole::Master => {
+            let socket = UdpSocket::bind("0.0.0.0:0").map_err(device_error)?;
+            socket.set_broadcast(true).map_err(device_error)?;
+            new.socket = Some(socket);
+            info!("🥁 Jam master for session {}", settings.session);
+            new.thread = Some(std::thread::spawn(move || run_master(running)));
+        }
+        JamRole::Follower => {
+            let socket = UdpSocket::bind(("0.0.0.0", settings.port)).map_err(device_error)?;
+            socket
+                .set_read_timeout(Some(RECEIVE_TIMEOUT))
+                .map_err(device_error)?;
+            info!("🥁 Following session {}", settings.session);
+            new.thread = Some(std::thread::spawn(move || run_follower(socket, running)));
+        }
+    }
+    *JAM.lock().unwrap() = Some(new);
+    Ok(())
+}
+
+pub fn jam_settings() -> JamSettings {
+    match JAM.lock().unwrap().as_ref() {
+        Some(jam) => jam.settings.clone(),
+        None => JamSettings::default(),
+    }
+}
+
+pub fn jam_status() -> JamStatus {
+    match JAM.lock().unwrap().as_ref() {
+        Some(jam) => JamStatus {
+            role: jam.settings.role,
+            master: jam
+                .master
+                .filter(|(_, heard)| heard.elapsed() <= MASTER_TIMEOUT)
+                .map(|(master, _)| master.to_string()),
+            tempo: jam.tempo,
+            playing: jam.playing,
+        },
+        None => JamStatus {
+            role: JamRole::Off,
+            master: None,
+            tempo: current_tempo(),
+            playing: false,
+        },
+    }
+}
@@ -0,0 +1,83 @@
+// Dual-layer patches: a second patch played on every note alongside the
+// main one, each with its own level and place in the stereo field
+use super::patch::Patch;
+use super::voice::{VoiceControls, MAX_ENVELOPE_SMOOTHING, MAX_GATE_SMOOTHING};
+use serde::{Deserialize, Serialize};
+
+/// Level and stereo position of one layer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerMix {
+    /// 0.0 = silent, 1.0 = unchanged
+    pub level: f32,
+    /// -1.0 = left, 1.0 = right
+    pub pan: f32,
+}
+
+impl Default for LayerMix {
+    fn default() -> Self {
+        LayerMix {
+            level: 1.0,
+            pan: 0.0,
+        }
+    }
+}
+
+impl LayerMix {
+    pub fn clamped(&self) -> Self {
+        LayerMix {
+            level: self.level.clamp(0.0, 1.0),
+            pan: self.pan.clamp(-1.0, 1.0),
+        }
+    }
+
+    /// Left and right gains. Equal power, scaled so the centre is the level
+    /// itself, as for voice pan
+    pub fn gains(&self) -> (f32, f32) {
+        let angle = (self.pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+        let level = self.level.clamp(0.0, 1.0) * std::f32::consts::SQRT_2;
+        (angle.cos() * level, angle.sin() * level)
+    }
+}
+
+/// The second layer and how the two layers are mixed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    /// The layered sound. Its oscillator, envelope, ring mod and vibrato are
+    /// its own; the filter, delay and everything after them are shared with
+    /// the main patch
+    pub patch: Patch,
+    pub mix: LayerMix,
+    /// The main patch's level and position while layered
+    #[serde(default)]
+    pub main_mix: LayerMix,
+}
+
+/// Point a layer's voice controls at a patch's values, clamped as the
+/// synth's setters would
+pub fn apply_layer_controls(controls: &VoiceControls, patch: &Patch) {
+    controls
+        .ring_mix
+        .set_value(patch.ring_mod_mix.clamp(0.0, 1.0));
+    controls
+        .ring_ratio
+        .set_value(patch.ring_mod_ratio.clamp(0.1, 16.0));
+    controls
+        .vibrato_rate
+        .set_value(patch.vibrato_rate.clamp(0.1, 20.0));
+    controls
+        .vibrato_depth
+        .set_value(patch.vibrato_depth.clamp(0.0, 200.0));
+    controls
+        .vibrato_delay
+        .set_value(patch.vibrato_delay.clamp(0.0, 5.0));
+    controls
+        .vibrato_shape
+        .set_value(patch.vibrato_shape.to_f32());
+    controls
+        .gate_smoothing
+        .set(patch.gate_smoothing.clamped(MAX_GATE_SMOOTHING));
+    controls
+        .envelope_smoothing
+        .set(patch.envelope_smoothing.clamped(MAX_ENVELOPE_SMOOTHING));
+}
@@ -0,0 +1,267 @@
+// Low frequency oscillators, and the per-voice vibrato built on one
+use fundsp::hacker::{shared, AudioNode, Frame, U1, U2};
+use std::f32::consts::TAU;
+
+/// Time vibrato takes to fade in once its onset delay has passed
+const VIBRATO_FADE_SECS: f32 = 0.2;
+
+/// LFO waveforms
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    Square,
+    Saw,
+    /// A new random level each cycle
+    SampleHold,
+}
+
+impl LfoShape {
+    const ALL: [LfoShape; 5] = [
+        LfoShape::Sine,
+        LfoShape::Triangle,
+        LfoShape::Square,
+        LfoShape::Saw,
+        LfoShape::SampleHold,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LfoShape::Sine => "sine",
+            LfoShape::Triangle => "triangle",
+            LfoShape::Square => "square",
+            LfoShape::Saw => "saw",
+            LfoShape::SampleHold => "sample_hold",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "sine" => Some(LfoShape::Sine),
+            "triangle" => Some(LfoShape::Triangle),
+            "square" => Some(LfoShape::Square),
+            "saw" | "sawtooth" => Some(LfoShape::Saw),
+            "sample_hold" | "random" => Some(LfoShape::SampleHold),
+            _ => None,
+        }
+    }
+
+    /// Stored in a shared var so the audio thread can follow changes
+    pub fn to_f32(self) -> f32 {
+        self as u8 as f32
+    }
+
+    pub fn from_f32(value: f32) -> Self {
+        Self::ALL
+            .get(value as usize)
+            .copied()
+            .unwrap_or(LfoShape::Sine)
+    }
+}
+
+/// The LFOs whose shape can be chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LfoTarget {
+    Vibrato,
+    Filter,
+}
+
+impl LfoTarget {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "vibrato" => Some(LfoTarget::Vibrato),
+            "filter" => Some(LfoTarget::Filter),
+            _ => None,
+        }
+    }
+}
+
+/// A free running LFO: call `next` once per sample
+#[derive(Clone)]
+pub struct Lfo {
+    /// Position in the cycle, 0.0 to 1.0
+    phase: f32,
+    /// Current sample & hold level
+    held: f32,
+    rng: fastrand::Rng,
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        Lfo {
+            phase: 0.0,
+            held: 0.0,
+            // Seeded, so offline renders come out the same every time
+            rng: fastrand::Rng::with_seed(0x4c46_4f00),
+        }
+    }
+}
+
+impl Lfo {
+    /// Advance by one sample at `rate` Hz and return the new value (-1.0 to 1.0)
+    pub fn next(&mut self, rate: f32, sample_rate: f32, shape: LfoShape) -> f32 {
+        let phase = self.phase + rate / sample_rate;
+        if phase >= 1.0 {
+            self.held = self.rng.f32() * 2.0 - 1.0;
+        }
+        self.phase = phase.fract();
+        match shape {
+            LfoShape::Sine => (self.phase * TAU).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            LfoShape::Square => {
+                if self.phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::Saw => 2.0 * self.phase - 1.0,
+            LfoShape::SampleHold => self.held,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+}
+
+/// Vibrato: takes (frequency, gate) and puts out the frequency with an LFO
+/// applied. The LFO restarts on each note and fades in after the onset delay
+#[derive(Clone)]
+pub struct VibratoNode {
+    /// LFO speed in Hz
+    rate: shared::Shared,
+    /// Pitch swing either side of the note, in cents
+    depth: shared::Shared,
+    /// Seconds from note on before the vibrato starts
+    delay: shared::Shared,
+    /// An `LfoShape` as f32
+    shape: shared::Shared,
+    lfo: Lfo,
+    gate_on: bool,
+    /// Seconds since the gate opened
+    time: f32,
+    sample_rate: f32,
+}
+
+impl VibratoNode {
+    pub fn new(
+        rate: shared::Shared,
+        depth: shared::Shared,
+        delay: shared::Shared,
+        shape: shared::Shared,
+    ) -> Self {
+        VibratoNode {
+            rate,
+            depth,
+            delay,
+            shape,
+            lfo: Lfo::default(),
+            gate_on: false,
+            time: 0.0,
+            sample_rate: 48000.0,
+        }
+    }
+}
+
+impl AudioNode for VibratoNode {
+    const ID: u64 = 0x4861_7270_5669_6272;
+    type Inputs = U2;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.lfo.reset();
+        self.gate_on = false;
+        self.time = 0.0;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let (frequency, gate) = (input[0], input[1]);
+        if gate > 0.0 && !self.gate_on {
+            self.lfo.reset();
+            self.time = 0.0;
+        }
+        self.gate_on = gate > 0.0;
+
+        let depth = self.depth.value();
+        if depth <= 0.0 {
+            return [frequency].into();
+        }
+        // Keep going through the release, so a note doesn't go straight as it fades
+        self.time += 1.0 / self.sample_rate;
+        let fade = ((self.time - self.delay.value()) / VIBRATO_FADE_SECS).clamp(0.0, 1.0);
+        let shape = LfoShape::from_f32(self.shape.value());
+        let lfo = self.lfo.next(self.rate.value(), self.sample_rate, shape);
+        let cents = lfo * depth * fade;
+        [frequency * (cents / 1200.0).exp2()].into()
+    }
+}
+
+/// Filter LFO: takes the cutoff and sweeps it up and down by `depth`
+/// octaves. With `sync_beats` above zero the rate follows the tempo, one
+/// cycle every that many beats
+#[derive(Clone)]
+pub struct CutoffLfoNode {
+    rate: shared::Shared,
+    depth: shared::Shared,
+    sync_beats: shared::Shared,
+    tempo: shared::Shared,
+    /// An `LfoShape` as f32
+    shape: shared::Shared,
+    lfo: Lfo,
+    sample_rate: f32,
+}
+
+impl CutoffLfoNode {
+    pub fn new(
+        rate: shared::Shared,
+        depth: shared::Shared,
+        sync_beats: shared::Shared,
+        tempo: shared::Shared,
+        shape: shared::Shared,
+    ) -> Self {
+        CutoffLfoNode {
+            rate,
+            depth,
+            sync_beats,
+            tempo,
+            shape,
+            lfo: Lfo::default(),
+            sample_rate: 48000.0,
+        }
+    }
+}
+
+impl AudioNode for CutoffLfoNode {
+    const ID: u64 = 0x4861_7270_4366_4c66;
+    type Inputs = U1;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.lfo.reset();
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let beats = self.sync_beats.value();
+        let rate = if beats > 0.0 {
+            self.tempo.value() / 60.0 / beats
+        } else {
+            self.rate.value()
+        };
+        // Keep the LFO running at zero depth so it stays in phase with the tempo
+        let shape = LfoShape::from_f32(self.shape.value());
+        let lfo = self.lfo.next(rate, self.sample_rate, shape);
+        [input[0] * (lfo * self.depth.value()).exp2()].into()
+    }
+}
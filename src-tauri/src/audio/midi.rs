@@ -0,0 +1,178 @@
+// MIDI input, whatever the transport: raw MIDI bytes from a device are parsed
+// into synth events here. Each platform's device code feeds this
+use super::synthesis::AudioEvent;
+use serde::Serialize;
+use tracing::warn;
+
+/// Most MIDI inputs open at once. Each has its own block of note ids
+pub const MAX_MIDI_SOURCES: u32 = 16;
+/// Each device's notes play their own note ids, clear of the UI's, the
+/// game controller's, the computer keyboard's, pitch tracking's and the scheduler's
+const MIDI_NOTE_ID_BASE: u32 = 0x7ffc_0000;
+const CC_SOSTENUTO: u8 = 66;
+const CC_ALL_SOUND_OFF: u8 = 120;
+const CC_ALL_NOTES_OFF: u8 = 123;
+
+/// A MIDI input as reported to the frontend
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MidiDeviceInfo {
+    pub name: String,
+    /// Whether its notes are being played
+    pub connected: bool,
+}
+
+/// Send a device's events to the synth
+pub fn queue_events(events: Vec<AudioEvent>) {
+    for event in events {
+        if let Err(e) = super::queue_audio_event(event) {
+            warn!("Dropped MIDI event: {}", e);
+        }
+    }
+}
+
+/// Turns one device's byte stream into synth events. Copes with running
+/// status and with messages split across reads, and skips SysEx and
+/// real-time messages
+pub struct MidiParser {
+    source: u32,
+    status: Option<u8>,
+    data: [u8; 2],
+    len: usize,
+    in_sysex: bool,
+    /// Which notes are down, by channel, so they can be released when the
+    /// device goes away
+    held: [[bool; 128]; 16],
+}
+
+/// Data bytes that follow a status byte
+fn data_len(status: u8) -> usize {
+    match status {
+        0x80..=0xbf | 0xe0..=0xef | 0xf2 => 2,
+        0xc0..=0xdf | 0xf1 | 0xf3 => 1,
+        _ => 0,
+    }
+}
+
+/// Whether a note id is one of a MIDI input's
+pub fn is_midi_input_note(note_id: u32) -> bool {
+    (MIDI_NOTE_ID_BASE..MIDI_NOTE_ID_BASE + MAX_MIDI_SOURCES * 16 * 128).contains(&note_id)
+}
+
+fn note_frequency(note: u8) -> f32 {
+    440.0 * 2.0f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+impl MidiParser {
+    /// `source` (below `MAX_MIDI_SOURCES`) picks the device's note ids
+    pub fn new(source: u32) -> Self {
+        MidiParser {
+            source: source % MAX_MIDI_SOURCES,
+            status: None,
+            data: [0; 2],
+            len: 0,
+            in_sysex: false,
+            held: [[false; 128]; 16],
+        }
+    }
+
+    fn note_id(&self, channel: usize, note: u8) -> u32 {
+        MIDI_NOTE_ID_BASE + (self.source * 16 + channel as u32) * 128 + note as u32
+    }
+
+    /// The events for the next bytes from the device
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<AudioEvent> {
+        let mut events = Vec::new();
+        for &byte in bytes {
+            match byte {
+                // Real-time messages can turn up anywhere, even mid-message
+                0xf8..=0xff => {}
+                0xf0 => {
+                    self.in_sysex = true;
+                    self.status = None;
+                }
+                0xf7 => self.in_sysex = false,
+                0x80..=0xef | 0xf1..=0xf6 => {
+                    self.in_sysex = false;
+                    self.status = Some(byte);
+                    self.len = 0;
+                }
+                _ => {
+                    let status = match self.status {
+                        Some(status) if !self.in_sysex => status,
+                        _ => continue,
+                    };
+                    self.data[self.len] = byte;
+                    self.len += 1;
+                    if self.len == data_len(status) {
+                        self.len = 0;
+                        self.message(status, &mut events);
+                        // Only channel messages have running status
+                        if status >= 0xf0 {
+                            self.status = None;
+                        }
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    fn message(&mut self, status: u8, events: &mut Vec<AudioEvent>) {
+        let channel = (status & 0x0f) as usize;
+        let [data1, data2] = self.data;
+        match status & 0xf0 {
+            0x90 if data2 > 0 => {
+                self.held[channel][data1 as usize] = true;
+                events.push(AudioEvent::PlayNote {
+                    note_id: self.note_id(channel, data1),
+                    frequency: note_frequency(data1),
+                    velocity: data2 as f32 / 127.0,
+                });
+            }
+            // Note on at velocity 0 is note off
+            0x80 | 0x90 => {
+                self.held[channel][data1 as usize] = false;
+                events.push(AudioEvent::NoteOff {
+                    note_id: self.note_id(channel, data1),
+                });
+            }
+            0xb0 => match data1 {
+                CC_SOSTENUTO => events.push(AudioEvent::SetHold {
+                    enabled: data2 >= 64,
+                }),
+                CC_ALL_SOUND_OFF | CC_ALL_NOTES_OFF => self.release_channel(channel, events),
+                _ => {}
+            },
+            0xe0 => {
+                let bend = (data1 as i32 | (data2 as i32) << 7) - 8192;
+                events.push(AudioEvent::SetPitchBend {
+                    bend: bend as f32 / 8192.0,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn release_channel(&mut self, channel: usize, events: &mut Vec<AudioEvent>) {
+        for note in 0..128u8 {
+            if std::mem::replace(&mut self.held[channel][note as usize], false) {
+                events.push(AudioEvent::NoteOff {
+                    note_id: self.note_id(channel, note),
+                });
+            }
+        }
+    }
+
+    /// Release every note the device is holding and centre the pitch wheel,
+    /// for when it's unplugged or disconnected mid-note
+    pub fn release_all(&mut self) -> Vec<AudioEvent> {
+        let mut events = Vec::new();
+        for channel in 0..16 {
+            self.release_channel(channel, &mut events);
+        }
+        events.push(AudioEvent::SetPitchBend { bend: 0.0 });
+        self.status = None;
+        self.len = 0;
+        events
+    }
+}
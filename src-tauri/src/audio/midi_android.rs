@@ -0,0 +1,297 @@
+// MIDI input devices on Android. The activity finds the devices and opens
+// them (see BleMidi.kt and UsbMidi.kt). Bluetooth devices' bytes come back
+// through the JNI entry points in jni_bridge.rs; USB devices are handed over
+// and read through AMidi. Either way they go to the shared parser. Devices are
+// known by an id from the Java side, e.g. a Bluetooth address, and shown by name
+use super::amidi::UsbMidiInput;
+use super::midi::{queue_events, MidiDeviceInfo, MidiParser, MAX_MIDI_SOURCES};
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// How long a Bluetooth scan runs. Scanning is hard on the battery, so it
+/// only happens when asked for
+const BLUETOOTH_SCAN_SECONDS: i32 = 10;
+
+struct Device {
+    id: String,
+    name: String,
+    /// The parser's source, while connected
+    source: Option<u32>,
+    parser: MidiParser,
+    /// A USB device being read through AMidi, which parses as it reads
+    usb: Option<UsbMidiInput>,
+}
+
+struct MidiInputs {
+    devices: Vec<Device>,
+    /// Connect devices as they're found
+    auto_connect: bool,
+}
+
+static INPUTS: Mutex<MidiInputs> = Mutex::new(MidiInputs {
+    devices: Vec::new(),
+    auto_connect: true,
+});
+
+/// Call a method on the activity, from any thread
+fn with_activity(
+    f: impl FnOnce(&mut JNIEnv, &JObject) -> jni::errors::Result<()>,
+) -> Result<(), String> {
+    let context = ndk_context::android_context();
+    let vm = unsafe { jni::JavaVM::from_raw(context.vm().cast()) }.map_err(|e| e.to_string())?;
+    let mut env = vm.attach_current_thread().map_err(|e| e.to_string())?;
+    let activity = unsafe { JObject::from_raw(context.context().cast()) };
+    f(&mut *env, &activity).map_err(|e| e.to_string())
+}
+
+impl MidiInputs {
+    fn devices(&self) -> Vec<MidiDeviceInfo> {
+        self.devices
+            .iter()
+            .map(|device| MidiDeviceInfo {
+                name: device.name.clone(),
+                connected: device.source.is_some(),
+            })
+            .collect()
+    }
+
+    /// Give the named device a source, returning its id for the activity to
+    /// open. None if it's already connected
+    fn claim(&mut self, name: &str) -> Result<Option<(String, u32)>, String> {
+        let source = (0..MAX_MIDI_SOURCES)
+            .find(|&source| !self.devices.iter().any(|d| d.source == Some(source)))
+            .ok_or_else(|| format!("No more than {} MIDI inputs at once", MAX_MIDI_SOURCES));
+        let device = self
+            .devices
+            .iter_mut()
+            .find(|device| device.name == name)
+            .ok_or_else(|| format!("No MIDI input named {}", name))?;
+        if device.source.is_some() {
+            return Ok(None);
+        }
+        let source = source?;
+        device.source = Some(source);
+        device.parser = MidiParser::new(source);
+        Ok(Some((device.id.clone(), source)))
+    }
+
+    /// Forget the device's source, releasing its notes. Returns whether it
+    /// was connected
+    fn release(&mut self, id: &str) -> bool {
+        match self.devices.iter_mut().find(|device| device.id == id) {
+            Some(device) if device.source.is_some() => {
+                device.source = None;
+                device.usb = None;
+                queue_events(device.parser.release_all());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn device_list_changed(inputs: &MidiInputs) {
+    super::emit_event("midi-devices-changed", inputs.devices());
+}
+
+/// Ask the activity to open a device and send its bytes as `source`
+fn open_device(id: &str, source: u32) -> Result<(), String> {
+    with_activity(|env, activity| {
+        let id = env.new_string(id)?;
+        env.call_method(
+            activity,
+            "connectMidiDevice",
+            "(Ljava/lang/String;I)V",
+            &[JValue::Object(&id), JValue::Int(source as i32)],
+        )?;
+        Ok(())
+    })
+}
+
+fn close_device(id: &str) -> Result<(), String> {
+    with_activity(|env, activity| {
+        let id = env.new_string(id)?;
+        env.call_method(
+            activity,
+            "disconnectMidiDevice",
+            "(Ljava/lang/String;)V",
+            &[JValue::Object(&id)],
+        )?;
+        Ok(())
+    })
+}
+
+fn connect(name: &str) -> Result<(), String> {
+    let claimed = INPUTS.lock().unwrap().claim(name)?;
+    if let Some((id, source)) = claimed {
+        if let Err(e) = open_device(&id, source) {
+            INPUTS.lock().unwrap().release(&id);
+            return Err(format!("Failed to connect to {}: {}", name, e));
+        }
+        info!("🎹 MIDI input connected: {}", name);
+    }
+    Ok(())
+}
+
+pub fn list_midi_inputs() -> Vec<MidiDeviceInfo> {
+    INPUTS.lock().unwrap().devices()
+}
+
+/// Play notes from the named input, alongside any already connected
+pub fn connect_midi_input(name: &str) -> Result<(), String> {
+    connect(name)?;
+    device_list_changed(&INPUTS.lock().unwrap());
+    Ok(())
+}
+
+/// Stop listening to the named input, releasing its notes
+pub fn disconnect_midi_input(name: &str) -> Result<(), String> {
+    let mut inputs = INPUTS.lock().unwrap();
+    let id = match inputs.devices.iter().find(|device| device.name == name) {
+        Some(device) if device.source.is_some() => device.id.clone(),
+        _ => return Err(format!("MIDI input {} is not connected", name)),
+    };
+    inputs.release(&id);
+    device_list_changed(&inputs);
+    drop(inputs);
+    close_device(&id)?;
+    info!("🎹 MIDI input disconnected: {}", name);
+    Ok(())
+}
+
+/// Whether devices are connected as they're found. Doesn't touch those
+/// already there
+pub fn set_midi_auto_connect(enabled: bool) {
+    INPUTS.lock().unwrap().auto_connect = enabled;
+}
+
+pub fn midi_auto_connect() -> bool {
+    INPUTS.lock().unwrap().auto_connect
+}
+
+/// Look for Bluetooth MIDI devices for a while. Those found are reported
+/// through `midi_device_added`. Asks for the Bluetooth permissions first if
+/// the app doesn't have them yet
+pub fn scan_bluetooth_midi() -> Result<(), String> {
+    with_activity(|env, activity| {
+        env.call_method(
+            activity,
+            "scanBluetoothMidi",
+            "(I)V",
+            &[JValue::Int(BLUETOOTH_SCAN_SECONDS)],
+        )?;
+        Ok(())
+    })
+}
+
+pub fn list_midi_outputs() -> Vec<String> {
+    Vec::new()
+}
+
+pub fn open_midi_output(_name: Option<&str>) -> Result<(), String> {
+    Err("MIDI output is not supported on Android".to_string())
+}
+
+pub fn close_midi_output() {}
+
+pub fn send_midi(_message: &[u8]) -> Result<(), String> {
+    Ok(())
+}
+
+/// Nothing to start: the activity reports devices as it finds them
+pub fn start_midi_input() {}
+
+/// The activity found a device
+pub fn midi_device_added(id: String, name: String) {
+    let mut inputs = INPUTS.lock().unwrap();
+    if inputs.devices.iter().any(|device| device.id == id) {
+        return;
+    }
+    info!("🎹 MIDI input found: {}", name);
+    // Two of the same controller mustn't share a name, as names pick devices
+    let name = if inputs.devices.iter().any(|device| device.name == name) {
+        format!("{} ({})", name, id)
+    } else {
+        name
+    };
+    inputs.devices.push(Device {
+        id,
+        name: name.clone(),
+        source: None,
+        parser: MidiParser::new(0),
+        usb: None,
+    });
+    let auto_connect = inputs.auto_connect;
+    drop(inputs);
+    if auto_connect {
+        if let Err(e) = connect(&name) {
+            warn!("{}", e);
+        }
+    }
+    device_list_changed(&INPUTS.lock().unwrap());
+}
+
+/// The device went away, e.g. out of range or switched off
+pub fn midi_device_removed(id: String) {
+    let mut inputs = INPUTS.lock().unwrap();
+    inputs.release(&id);
+    if let Some(index) = inputs.devices.iter().position(|device| device.id == id) {
+        let device = inputs.devices.remove(index);
+        info!("🎹 MIDI input gone: {}", device.name);
+        device_list_changed(&inputs);
+    }
+}
+
+/// The activity opened a USB device it was asked to. Start reading it
+/// through AMidi. Returns false if that failed, and the device is closed again
+pub fn open_usb_midi(env: &mut JNIEnv, id: String, source: u32, device: &JObject) -> bool {
+    let mut inputs = INPUTS.lock().unwrap();
+    let entry = match inputs
+        .devices
+        .iter_mut()
+        .find(|entry| entry.id == id && entry.source == Some(source))
+    {
+        Some(entry) => entry,
+        // Disconnected while the activity was opening it
+        None => return false,
+    };
+    match UsbMidiInput::open(env, device, source) {
+        Ok(usb) => {
+            entry.usb = Some(usb);
+            true
+        }
+        Err(e) => {
+            warn!("Failed to read MIDI input {}: {}", entry.name, e);
+            inputs.release(&id);
+            device_list_changed(&inputs);
+            false
+        }
+    }
+}
+
+/// The activity couldn't open a device it was asked to
+pub fn midi_device_failed(id: String) {
+    let mut inputs = INPUTS.lock().unwrap();
+    if inputs.release(&id) {
+        warn!("Failed to open MIDI input {}", id);
+        device_list_changed(&inputs);
+    }
+}
+
+/// Bytes from the device given `source` when it was opened
+pub fn midi_data(source: u32, bytes: &[u8]) {
+    let mut inputs = INPUTS.lock().unwrap();
+    let events = match inputs
+        .devices
+        .iter_mut()
+        .find(|device| device.source == Some(source))
+    {
+        Some(device) => device.parser.feed(bytes),
+        // Stragglers from a device just disconnected
+        None => return,
+    };
+    drop(inputs);
+    queue_events(events);
+}
@@ -0,0 +1,245 @@
+// MIDI input devices on desktop, through midir. Any number can be open at
+// once. A thread watches the port list, so devices can come and go while the
+// app runs: new ones are connected (unless auto-connect is off), unplugged
+// ones have their notes released, and the frontend hears about every change
+use super::midi::{queue_events, MidiDeviceInfo, MidiParser, MAX_MIDI_SOURCES};
+use midir::{MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const CLIENT_NAME: &str = "Harphonium";
+/// How often the port list is checked for devices coming and going
+const HOTPLUG_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Connection {
+    name: String,
+    source: u32,
+    parser: Arc<Mutex<MidiParser>>,
+    connection: MidiInputConnection<()>,
+}
+
+struct MidiInputs {
+    /// Port names as of the last check
+    ports: Vec<String>,
+    connections: Vec<Connection>,
+    /// Connect devices as they appear
+    auto_connect: bool,
+}
+
+static INPUTS: Mutex<MidiInputs> = Mutex::new(MidiInputs {
+    ports: Vec::new(),
+    connections: Vec::new(),
+    auto_connect: true,
+});
+
+impl MidiInputs {
+    fn devices(&self) -> Vec<MidiDeviceInfo> {
+        self.ports
+            .iter()
+            .map(|name| MidiDeviceInfo {
+                name: name.clone(),
+                connected: self.connections.iter().any(|c| c.name == *name),
+            })
+            .collect()
+    }
+
+    fn connect(&mut self, name: &str) -> Result<(), String> {
+        if self.connections.iter().any(|c| c.name == name) {
+            return Ok(());
+        }
+        let source = (0..MAX_MIDI_SOURCES)
+            .find(|&source| !self.connections.iter().any(|c| c.source == source))
+            .ok_or_else(|| format!("No more than {} MIDI inputs at once", MAX_MIDI_SOURCES))?;
+        // Each connection takes its own client
+        let input = MidiInput::new(CLIENT_NAME).map_err(|e| e.to_string())?;
+        let port = input
+            .ports()
+            .into_iter()
+            .find(|port| input.port_name(port).map_or(false, |n| n == name))
+            .ok_or_else(|| format!("No MIDI input named {}", name))?;
+        let parser = Arc::new(Mutex::new(MidiParser::new(source)));
+        let callback_parser = parser.clone();
+        let connection = input
+            .connect(
+                &port,
+                "harphonium-input",
+                move |_, message, _| queue_events(callback_parser.lock().unwrap().feed(message)),
+                (),
+            )
+            .map_err(|e| format!("Failed to connect to {}: {}", name, e))?;
+        info!("🎹 MIDI input connected: {}", name);
+        self.connections.push(Connection {
+            name: name.to_string(),
+            source,
+            parser,
+            connection,
+        });
+        Ok(())
+    }
+
+    fn disconnect(&mut self, name: &str) -> bool {
+        let index = match self.connections.iter().position(|c| c.name == name) {
+            Some(index) => index,
+            None => return false,
+        };
+        let connection = self.connections.remove(index);
+        connection.connection.close();
+        queue_events(connection.parser.lock().unwrap().release_all());
+        info!("🎹 MIDI input disconnected: {}", name);
+        true
+    }
+
+    /// Catch up with the ports there are now. Returns whether anything changed
+    fn update_ports(&mut self, ports: Vec<String>) -> bool {
+        if ports == self.ports {
+            return false;
+        }
+        let gone: Vec<String> = self
+            .ports
+            .iter()
+            .filter(|name| !ports.contains(name))
+            .cloned()
+            .collect();
+        let new: Vec<String> = ports
+            .iter()
+            .filter(|name| !self.ports.contains(name))
+            .cloned()
+            .collect();
+        self.ports = ports;
+        for name in gone {
+            info!("🎹 MIDI input unplugged: {}", name);
+            self.disconnect(&name);
+        }
+        for name in new {
+            info!("🎹 MIDI input found: {}", name);
+            if self.auto_connect {
+                if let Err(e) = self.connect(&name) {
+                    warn!("{}", e);
+                }
+            }
+        }
+        true
+    }
+}
+
+fn device_list_changed(inputs: &MidiInputs) {
+    super::emit_event("midi-devices-changed", inputs.devices());
+}
+
+pub fn list_midi_inputs() -> Vec<MidiDeviceInfo> {
+    INPUTS.lock().unwrap().devices()
+}
+
+/// Play notes from the named input, alongside any already connected
+pub fn connect_midi_input(name: &str) -> Result<(), String> {
+    let mut inputs = INPUTS.lock().unwrap();
+    inputs.connect(name)?;
+    device_list_changed(&inputs);
+    Ok(())
+}
+
+/// Stop listening to the named input, releasing its notes
+pub fn disconnect_midi_input(name: &str) -> Result<(), String> {
+    let mut inputs = INPUTS.lock().unwrap();
+    if !inputs.disconnect(name) {
+        return Err(format!("MIDI input {} is not connected", name));
+    }
+    device_list_changed(&inputs);
+    Ok(())
+}
+
+/// Whether devices are connected as they're plugged in. Doesn't touch
+/// those already there
+pub fn set_midi_auto_connect(enabled: bool) {
+    INPUTS.lock().unwrap().auto_connect = enabled;
+}
+
+pub fn midi_auto_connect() -> bool {
+    INPUTS.lock().unwrap().auto_connect
+}
+
+/// Bluetooth MIDI devices show up as ordinary ports once paired with the system
+pub fn scan_bluetooth_midi() -> Result<(), String> {
+    Err("Pair Bluetooth MIDI devices in the system settings".to_string())
+}
+
+/// The output controller mode sends to
+static OUTPUT: Mutex<Option<MidiOutputConnection>> = Mutex::new(None);
+
+pub fn list_midi_outputs() -> Vec<String> {
+    match MidiOutput::new(CLIENT_NAME) {
+        Ok(output) => output
+            .ports()
+            .iter()
+            .filter_map(|port| output.port_name(port).ok())
+            .collect(),
+        Err(e) => {
+            warn!("No MIDI output support: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Open the named output (or the first there is), replacing any open already
+pub fn open_midi_output(name: Option<&str>) -> Result<(), String> {
+    let output = MidiOutput::new(CLIENT_NAME).map_err(|e| e.to_string())?;
+    let port = output
+        .ports()
+        .into_iter()
+        .find(|port| match name {
+            Some(name) => output.port_name(port).map_or(false, |n| n == name),
+            None => true,
+        })
+        .ok_or_else(|| match name {
+            Some(name) => format!("No MIDI output named {}", name),
+            None => "No MIDI outputs".to_string(),
+        })?;
+    let port_name = output.port_name(&port).unwrap_or_default();
+    let connection = output
+        .connect(&port, "harphonium-output")
+        .map_err(|e| format!("Failed to connect to {}: {}", port_name, e))?;
+    info!("🎹 MIDI output connected: {}", port_name);
+    *OUTPUT.lock().unwrap() = Some(connection);
+    Ok(())
+}
+
+pub fn close_midi_output() {
+    if let Some(connection) = OUTPUT.lock().unwrap().take() {
+        connection.close();
+    }
+}
+
+pub fn send_midi(message: &[u8]) -> Result<(), String> {
+    match OUTPUT.lock().unwrap().as_mut() {
+        Some(connection) => connection.send(message).map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Start watching for MIDI devices. Those already plugged in count as new,
+/// so are connected straight away if auto-connect is on
+pub fn start_midi_input() {
+    std::thread::spawn(|| {
+        let probe = match MidiInput::new(CLIENT_NAME) {
+            Ok(probe) => probe,
+            Err(e) => {
+                warn!("No MIDI input support: {}", e);
+                return;
+            }
+        };
+        loop {
+            let ports = probe
+                .ports()
+                .iter()
+                .filter_map(|port| probe.port_name(port).ok())
+                .collect();
+            let mut inputs = INPUTS.lock().unwrap();
+            if inputs.update_ports(ports) {
+                device_list_changed(&inputs);
+            }
+            drop(inputs);
+            std::thread::sleep(HOTPLUG_INTERVAL);
+        }
+    });
+}
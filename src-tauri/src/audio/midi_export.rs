@@ -0,0 +1,101 @@
+// Standard MIDI file export of captured performances
+use super::capture::PerformanceLog;
+use super::synthesis::AudioEvent;
+use std::collections::HashMap;
+
+/// Ticks per quarter note
+const PPQ: u16 = 480;
+/// Tempo written to the file. Any tempo works as long as ticks are converted with it
+const MICROS_PER_QUARTER: u32 = 500_000;
+const TICKS_PER_SECOND: f64 = PPQ as f64 * 1_000_000.0 / MICROS_PER_QUARTER as f64;
+
+/// Nearest MIDI note number for a frequency
+fn frequency_to_note(frequency: f32) -> u8 {
+    let note = 69.0 + 12.0 * (frequency / 440.0).log2();
+    note.round().clamp(0.0, 127.0) as u8
+}
+
+/// Append a variable length quantity
+fn write_vlq(out: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+/// Convert the note events of a performance to a format 0 MIDI file. Notes
+/// are quantised to the nearest semitone; parameter changes and pitch slides
+/// aren't exported
+pub fn performance_to_midi(log: &PerformanceLog) -> Vec<u8> {
+    let to_tick = |sample: u64| (sample as f64 / log.sample_rate as f64 * TICKS_PER_SECOND) as u32;
+
+    // (tick, status, key, velocity)
+    let mut messages: Vec<(u32, u8, u8, u8)> = Vec::new();
+    let mut held: HashMap<u32, u8> = HashMap::new();
+    let mut events = log.events.clone();
+    events.sort_by_key(|e| e.sample);
+    let mut last_tick = 0;
+
+    for captured in &events {
+        let tick = to_tick(captured.sample);
+        last_tick = tick;
+        match &captured.event {
+            AudioEvent::PlayNote {
+                note_id,
+                frequency,
+                velocity,
+            } => {
+                // Retriggering a held note id ends the old note first
+                if let Some(key) = held.remove(note_id) {
+                    messages.push((tick, 0x80, key, 0));
+                }
+                let key = frequency_to_note(*frequency);
+                let velocity = (velocity.clamp(0.0, 1.0) * 127.0).round().max(1.0) as u8;
+                messages.push((tick, 0x90, key, velocity));
+                held.insert(*note_id, key);
+            }
+            AudioEvent::NoteOff { note_id } => {
+                if let Some(key) = held.remove(note_id) {
+                    messages.push((tick, 0x80, key, 0));
+                }
+            }
+            AudioEvent::NoteOffAll => {
+                for (_, key) in held.drain() {
+                    messages.push((tick, 0x80, key, 0));
+                }
+            }
+            _ => (),
+        }
+    }
+    // Close anything still held when the capture stopped
+    for (_, key) in held.drain() {
+        messages.push((last_tick, 0x80, key, 0));
+    }
+
+    let mut track = Vec::new();
+    // Tempo meta event
+    track.extend([0x00, 0xff, 0x51, 0x03]);
+    track.extend(&MICROS_PER_QUARTER.to_be_bytes()[1..]);
+    let mut previous = 0;
+    for (tick, status, key, velocity) in messages {
+        write_vlq(&mut track, tick - previous);
+        track.extend([status, key, velocity]);
+        previous = tick;
+    }
+    // End of track
+    track.extend([0x00, 0xff, 0x2f, 0x00]);
+
+    let mut file = Vec::new();
+    file.extend(b"MThd");
+    file.extend(6u32.to_be_bytes());
+    file.extend(0u16.to_be_bytes()); // Format 0, single track
+    file.extend(1u16.to_be_bytes());
+    file.extend(PPQ.to_be_bytes());
+    file.extend(b"MTrk");
+    file.extend((track.len() as u32).to_be_bytes());
+    file.extend(track);
+    file
+}
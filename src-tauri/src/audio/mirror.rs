@@ -0,0 +1,196 @@
+// Copy of the synth's parameters. The audio thread publishes a snapshot
+// after handling events, and getters read it without going near the synth
+// at all. Publishing copies into a fixed slot and only tries the lock, so
+// the audio thread never allocates or waits on a reader
+use super::effects::Effect;
+use super::follower::ModDestination;
+use super::gain::gain_to_db;
+use super::instrument::InstrumentMode;
+use super::layer::Layer;
+use super::lfo::LfoTarget;
+use super::mono::NotePriority;
+use super::patch::Patch;
+use super::schedule::StrumSettings;
+use super::smoothing::Smoothing;
+use super::split::KeySplit;
+use super::synthesis::{AudioEvent, AudioEventResult};
+use super::vocoder::EngineMode;
+use super::voice::{StealPolicy, Unison};
+use std::sync::Mutex;
+
+/// Everything a getter can ask for
+#[derive(Debug, Clone, Copy)]
+pub struct ParamSnapshot {
+    pub patch: Patch,
+    pub tempo: f32,
+    pub latch: bool,
+    pub hold: bool,
+    pub mono: bool,
+    pub note_priority: NotePriority,
+    pub instrument_mode: InstrumentMode,
+    pub steal_policy: StealPolicy,
+    pub limiter_attack: f32,
+    pub limiter_release: f32,
+    /// dB
+    pub limiter_ceiling: f32,
+    pub limiter_bypass: bool,
+    /// dB, indexed by `GainStage::index`
+    pub gain_trims: [f32; 3],
+    pub strum_settings: StrumSettings,
+    pub frequency_smoothing: Smoothing,
+    pub quantize: u32,
+    pub input_level: f32,
+    /// Indexed by `ModDestination::index`
+    pub input_envelope_amounts: [f32; 2],
+    pub engine_mode: EngineMode,
+    pub vocoder_bands: u32,
+    pub formant_shift: f32,
+    pub pitch_bend_range: f32,
+    /// dB
+    pub output_ceiling: f32,
+    pub key_split: Option<KeySplit>,
+    pub layer: Option<Layer>,
+}
+
+impl ParamSnapshot {
+    /// The answer to a getter event, as the synth would give it. None for
+    /// anything that isn't a getter
+    pub fn answer(&self, event: &AudioEvent) -> Option<AudioEventResult> {
+        let patch = &self.patch;
+        let result = match event {
+            AudioEvent::GetMasterVolume => AudioEventResult::ValueF32(patch.master_volume),
+            AudioEvent::GetWaveform => AudioEventResult::ValueWaveform(patch.waveform),
+            AudioEvent::GetAttack => AudioEventResult::ValueF32(patch.attack),
+            AudioEvent::GetDecay => AudioEventResult::ValueF32(patch.decay),
+            AudioEvent::GetSustain => AudioEventResult::ValueF32(patch.sustain),
+            AudioEvent::GetRelease => AudioEventResult::ValueF32(patch.release),
+            AudioEvent::GetDelayTime => AudioEventResult::ValueF32(patch.delay_time),
+            AudioEvent::GetDelayFeedback => AudioEventResult::ValueF32(patch.delay_feedback),
+            AudioEvent::GetDelayMix => AudioEventResult::ValueF32(patch.delay_mix),
+            AudioEvent::GetDelayMode => AudioEventResult::ValueDelayMode(patch.delay_mode),
+            AudioEvent::GetDelayLowCut => AudioEventResult::ValueF32(patch.delay_low_cut),
+            AudioEvent::GetDelayHighCut => AudioEventResult::ValueF32(patch.delay_high_cut),
+            AudioEvent::GetFilterCutoff => AudioEventResult::ValueF32(patch.filter_cutoff),
+            AudioEvent::GetFilterResonance => AudioEventResult::ValueF32(patch.filter_resonance),
+            AudioEvent::GetRingModMix => AudioEventResult::ValueF32(patch.ring_mod_mix),
+            AudioEvent::GetRingModRatio => AudioEventResult::ValueF32(patch.ring_mod_ratio),
+            AudioEvent::GetVibratoRate => AudioEventResult::ValueF32(patch.vibrato_rate),
+            AudioEvent::GetVibratoDepth => AudioEventResult::ValueF32(patch.vibrato_depth),
+            AudioEvent::GetVibratoDelay => AudioEventResult::ValueF32(patch.vibrato_delay),
+            AudioEvent::GetFilterLfoRate => AudioEventResult::ValueF32(patch.filter_lfo_rate),
+            AudioEvent::GetFilterLfoDepth => AudioEventResult::ValueF32(patch.filter_lfo_depth),
+            AudioEvent::GetFilterLfoSync => AudioEventResult::ValueF32(patch.filter_lfo_sync),
+            AudioEvent::GetTempo => AudioEventResult::ValueF32(self.tempo),
+            AudioEvent::GetLfoShape { lfo } => AudioEventResult::ValueLfoShape(match lfo {
+                LfoTarget::Vibrato => patch.vibrato_shape,
+                LfoTarget::Filter => patch.filter_lfo_shape,
+            }),
+            AudioEvent::GetEnvelopeMode => AudioEventResult::ValueEnvelopeMode(patch.envelope_mode),
+            AudioEvent::GetEnvelopeDelay => AudioEventResult::ValueF32(patch.envelope_delay),
+            AudioEvent::GetEnvelopeHold => AudioEventResult::ValueF32(patch.envelope_hold),
+            AudioEvent::GetFilterVelocityAmount => {
+                AudioEventResult::ValueF32(patch.filter_velocity_amount)
+            }
+            AudioEvent::GetVoiceSpread => AudioEventResult::ValueF32(patch.voice_spread),
+            AudioEvent::GetStereoWidth => AudioEventResult::ValueF32(patch.stereo_width),
+            AudioEvent::GetLimiterAttack => AudioEventResult::ValueF32(self.limiter_attack),
+            AudioEvent::GetLimiterRelease => AudioEventResult::ValueF32(self.limiter_release),
+            AudioEvent::GetLimiterCeiling => AudioEventResult::ValueF32(self.limiter_ceiling),
+            AudioEvent::GetSoftClipDrive => AudioEventResult::ValueF32(patch.soft_clip_drive),
+            AudioEvent::GetGateSmoothing => AudioEventResult::ValueSmoothing(patch.gate_smoothing),
+            AudioEvent::GetEnvelopeSmoothing => {
+                AudioEventResult::ValueSmoothing(patch.envelope_smoothing)
+            }
+            AudioEvent::GetPatch => AudioEventResult::ValuePatch(*patch),
+            AudioEvent::GetUnison => AudioEventResult::ValueUnison(Unison {
+                voices: patch.unison_voices,
+                detune: patch.unison_detune,
+                spread: patch.unison_spread,
+            }),
+            AudioEvent::GetLatch => AudioEventResult::ValueBool(self.latch),
+            AudioEvent::GetHold => AudioEventResult::ValueBool(self.hold),
+            AudioEvent::GetMono => AudioEventResult::ValueBool(self.mono),
+            AudioEvent::GetNotePriority => AudioEventResult::ValueNotePriority(self.note_priority),
+            AudioEvent::GetInstrumentMode => {
+                AudioEventResult::ValueInstrumentMode(self.instrument_mode)
+            }
+            AudioEvent::GetStealPolicy => AudioEventResult::ValueStealPolicy(self.steal_policy),
+            AudioEvent::GetVoicePanMode => {
+                AudioEventResult::ValueVoicePanMode(patch.voice_pan_mode)
+            }
+            AudioEvent::GetLimiterBypass => AudioEventResult::ValueBool(self.limiter_bypass),
+            AudioEvent::GetEffectOrder => AudioEventResult::ValueEffectOrder(patch.effect_order),
+            AudioEvent::GetEffectSlot { slot } => {
+                AudioEventResult::ValueEffectSlot(*patch.effect_slots.get(*slot)?)
+            }
+            AudioEvent::GetEffectMix { effect } => AudioEventResult::ValueF32(match effect {
+                Effect::Filter => patch.filter_mix,
+                Effect::Delay => patch.delay_mix,
+                Effect::Slot(slot) => patch.effect_slots.get(*slot)?.mix,
+            }),
+            AudioEvent::GetSoftClip => AudioEventResult::ValueBool(patch.soft_clip),
+            AudioEvent::GetMasterVolumeDb => {
+                AudioEventResult::ValueF32(gain_to_db(patch.master_volume))
+            }
+            AudioEvent::GetGainTrim { stage } => {
+                AudioEventResult::ValueF32(self.gain_trims[stage.index()])
+            }
+            AudioEvent::GetFrequencySmoothing => {
+                AudioEventResult::ValueSmoothing(self.frequency_smoothing)
+            }
+            AudioEvent::GetStrumSettings => {
+                AudioEventResult::ValueStrumSettings(self.strum_settings)
+            }
+            AudioEvent::GetQuantize => AudioEventResult::ValueU32(self.quantize),
+            AudioEvent::GetInputLevel => AudioEventResult::ValueF32(self.input_level),
+            AudioEvent::GetInputEnvelopeAmount { destination } => {
+                AudioEventResult::ValueF32(self.input_envelope_amounts[destination.index()])
+            }
+            AudioEvent::GetEngineMode => AudioEventResult::ValueEngineMode(self.engine_mode),
+            AudioEvent::GetVocoderBands => AudioEventResult::ValueU32(self.vocoder_bands),
+            AudioEvent::GetFormantShift => AudioEventResult::ValueF32(self.formant_shift),
+            AudioEvent::GetPitchBendRange => AudioEventResult::ValueF32(self.pitch_bend_range),
+            AudioEvent::GetOutputCeiling => AudioEventResult::ValueF32(self.output_ceiling),
+            AudioEvent::GetKeySplit => AudioEventResult::ValueKeySplit(self.key_split),
+            AudioEvent::GetLayer => AudioEventResult::ValueLayer(self.layer),
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+/// The latest snapshot. The audio thread only ever tries the lock, so a
+/// getter copying the snapshot out can't hold it up; if one is, the
+/// snapshot is published again on the next buffer
+pub struct ParamMirror {
+    snapshot: Mutex<Option<ParamSnapshot>>,
+}
+
+impl ParamMirror {
+    const fn new() -> Self {
+        ParamMirror {
+            snapshot: Mutex::new(None),
+        }
+    }
+
+    /// Replace the snapshot. Returns false, leaving the old one, if a
+    /// getter was reading it
+    pub fn publish(&self, snapshot: ParamSnapshot) -> bool {
+        match self.snapshot.try_lock() {
+            Ok(mut current) => {
+                *current = Some(snapshot);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Answer a getter from the latest snapshot. None before the first one,
+    /// or for events that aren't getters
+    pub fn answer(&self, event: &AudioEvent) -> Option<AudioEventResult> {
+        let snapshot = (*self.snapshot.lock().ok()?)?;
+        snapshot.answer(event)
+    }
+}
+
+pub static PARAM_MIRROR: ParamMirror = ParamMirror::new();
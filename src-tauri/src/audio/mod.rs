@@ -1,61 +1,280 @@
 // Cross-platform audio module for Harphonium synthesizer
+// Most of the engine API is only reached through Tauri commands, which don't exist on the web
+#![cfg_attr(target_arch = "wasm32", allow(dead_code))]
 use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{error, info};
 
+// Glitch counters for the UI
+mod stats;
+// Output tap for the oscilloscope
+#[cfg(not(target_arch = "wasm32"))]
+mod scope;
+#[cfg(not(target_arch = "wasm32"))]
+mod spectrum;
+mod wav;
+#[cfg(not(target_arch = "wasm32"))]
+pub use spectrum::SpectrumInfo;
+mod capture;
+mod comb;
+mod delay;
+mod denormal;
+mod drums;
+mod effects;
+#[cfg(not(target_arch = "wasm32"))]
+mod encode;
+#[cfg(not(target_arch = "wasm32"))]
+pub use encode::ExportFormat;
+mod envelope;
+mod error;
+// Logging queued events to JSONL, and replaying them
+#[cfg(not(target_arch = "wasm32"))]
+mod event_log;
+mod event_queue;
+mod follower;
+mod formant;
+mod gain;
+mod instrument;
+mod layer;
+// Audio input, run through the effects
+#[cfg(not(target_arch = "wasm32"))]
+mod input;
+// Pitch detection on the audio input, for the tuner and pitch tracking
+#[cfg(not(target_arch = "wasm32"))]
+mod pitch;
+// Game controller input
+#[cfg(not(target_arch = "wasm32"))]
+mod gamepad;
+#[cfg(not(target_arch = "wasm32"))]
+pub use gamepad::GamepadMapping;
+// Computer keyboard note input
+#[cfg(not(target_arch = "wasm32"))]
+mod qwerty;
+#[cfg(not(target_arch = "wasm32"))]
+pub use pitch::{PitchTrackingSettings, TunerReading};
+#[cfg(not(target_arch = "wasm32"))]
+pub use qwerty::QwertySettings;
+mod lfo;
+// MIDI input: parsing shared by every platform, devices per platform
+#[cfg(not(target_arch = "wasm32"))]
+mod midi;
+#[cfg(target_os = "android")]
+mod midi_android;
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+mod midi_desktop;
+#[cfg(not(target_arch = "wasm32"))]
+pub use midi::MidiDeviceInfo;
+// OSC output of notes and parameter changes
+#[cfg(not(target_arch = "wasm32"))]
+mod osc;
+#[cfg(not(target_arch = "wasm32"))]
+pub use osc::OscSettings;
+// Controller mode: played notes sent out over MIDI
+#[cfg(not(target_arch = "wasm32"))]
+mod controller;
+#[cfg(not(target_arch = "wasm32"))]
+pub use controller::ControllerSettings;
+// Jam sync: tempo and transport shared between devices on the network
+#[cfg(not(target_arch = "wasm32"))]
+mod jam;
+#[cfg(not(target_arch = "wasm32"))]
+pub use jam::{JamSettings, JamStatus};
+#[cfg(target_os = "android")]
+pub use midi_android::{
+    midi_data, midi_device_added, midi_device_failed, midi_device_removed, open_usb_midi,
+};
+// USB MIDI on Android, through the NDK
+#[cfg(target_os = "android")]
+mod amidi;
+mod midi_export;
+mod mirror;
+mod mono;
+mod patch;
+#[cfg(not(target_arch = "wasm32"))]
+mod query;
+// Live recording of the output to WAV
+#[cfg(not(target_arch = "wasm32"))]
+mod recording;
+#[cfg(not(target_arch = "wasm32"))]
+pub use recording::{RecordingOptions, RecordingState, RecordingStatus};
+mod resample;
+mod reverb;
+mod sampler;
+mod saturation;
+mod schedule;
+mod sequencer;
+// Saving the synth's state as it changes, and restoring it at startup
+#[cfg(not(target_arch = "wasm32"))]
+mod session;
+mod sf2;
+mod shifter;
+mod slots;
+mod smoothing;
+mod split;
+mod state;
+mod svf;
 // Shared synthesis module using FunDSP
 mod synthesis;
+mod vocoder;
+mod voice;
+mod wah;
+mod watchdog;
+mod widener;
+pub use capture::PerformanceLog;
+pub use delay::DelayMode;
+pub use drums::DrumKind;
+pub use effects::{Effect, EffectOrder};
+pub use envelope::EnvelopeMode;
+pub use error::AudioError;
+use event_queue::NOTE_QUEUE_CAPACITY;
+pub use event_queue::{EventQueueConfig, OverflowPolicy};
+pub use follower::ModDestination;
+pub use gain::GainStage;
+pub use instrument::InstrumentMode;
+pub use layer::{Layer, LayerMix};
+pub use lfo::{LfoShape, LfoTarget};
+pub use mono::NotePriority;
+pub use patch::Patch;
+pub use reverb::ReverbType;
 use rtrb::Producer;
-use synthesis::FunDSPSynth;
-pub use synthesis::{AudioEvent, AudioEventResult, Waveform};
+pub use schedule::StrumSettings;
+pub use sequencer::{Arrangement, SongEntry, Step};
+pub use slots::{SlotEffect, SlotEffectInfo, SlotPatch};
+pub use smoothing::Smoothing;
+pub use split::KeySplit;
+pub use stats::{AudioStatsInfo, DspLoadInfo};
+use stats::{DSP_LOAD, STATS};
+pub use synthesis::FunDSPSynth;
+pub use synthesis::{AudioEvent, AudioEventResult, KeyZone, Waveform, ZoneParam};
+#[cfg(not(target_arch = "wasm32"))]
+use tauri::Emitter;
+pub use vocoder::EngineMode;
+pub use voice::{StealPolicy, Unison, VoicePanMode};
 
 // Desktop audio implementation using cpal
-#[cfg(not(target_os = "android"))]
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
 mod desktop;
 
+// Real-time thread priority for desktop audio callbacks
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+mod priority;
+
+// Low latency exclusive mode output on Windows
+#[cfg(target_os = "windows")]
+mod wasapi_exclusive;
+
 // Android audio implementation using oboe
 #[cfg(target_os = "android")]
 mod android;
 
+// Web Audio implementation, driven by an AudioWorklet
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+#[cfg(target_os = "android")]
+use android as backend;
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+use desktop as backend;
+#[cfg(target_arch = "wasm32")]
+use web as backend;
+
+#[cfg(target_os = "android")]
+use midi_android as midi_ports;
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+use midi_desktop as midi_ports;
+
+/// An output device as reported to the frontend
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AudioDeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// Stream settings requested by the user. `None` means use the platform default
+#[derive(Debug, Clone, Default)]
+pub struct StreamSettings {
+    /// Audio host, e.g. "ALSA" or "JACK" on Linux
+    pub host_name: Option<String>,
+    pub device_name: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub buffer_size: Option<u32>,
+    /// Ask for exclusive access to the device for lower latency
+    pub exclusive_mode: bool,
+    /// Run the audio callback at real-time priority
+    pub realtime_priority: bool,
+}
+
+/// The stream configuration actually obtained from the device
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AudioConfigInfo {
+    pub host_name: String,
+    pub device_name: String,
+    pub sample_rate: u32,
+    /// Rate the synth renders at. Differs from `sample_rate` when the device
+    /// couldn't run at the rate asked for, and the output is resampled
+    pub engine_sample_rate: u32,
+    /// Frames per callback, if known
+    pub buffer_size: Option<u32>,
+    pub channels: u16,
+    /// Whether we got exclusive access, or fell back to sharing the device
+    pub exclusive_mode: bool,
+    /// Whether the audio callback is running at real-time priority
+    pub realtime_priority: bool,
+}
+
+/// Payload of the `audio-watchdog` event, sent when the output watchdog has
+/// had to reset the synth
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, serde::Serialize)]
+struct WatchdogEvent {
+    resets: u64,
+    fault: Option<watchdog::WatchdogFault>,
+}
+
+/// Payload of the `audio-device-changed` event, sent when a backend rebuilds
+/// its stream by itself (device unplugged, default device changed, etc.)
+#[derive(Debug, Clone, serde::Serialize)]
+struct DeviceChangedEvent {
+    device_name: String,
+    reason: &'static str,
+}
+
 // Cross-platform audio engine wrapper
 pub struct AudioEngine {
     synth: Arc<Mutex<FunDSPSynth>>,
+    stream: backend::StreamController,
 }
 
 impl AudioEngine {
     pub fn new(
         event_consumer: rtrb::Consumer<AudioEvent>,
+        note_consumer: rtrb::Consumer<AudioEvent>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         // Tentative sample rate; platform backends will align it to the device after opening streams
         let sample_rate = 48000.0f32;
-        let synth = Arc::new(Mutex::new(FunDSPSynth::new(sample_rate, event_consumer)?));
+        let mut synth = FunDSPSynth::new(sample_rate, event_consumer)?;
+        synth.set_note_consumer(note_consumer);
+        synth.set_sample_clock(&capture::SAMPLE_CLOCK);
+        synth.set_param_mirror(&mirror::PARAM_MIRROR);
+        synth.set_transport_clock(&schedule::TRANSPORT_CLOCK);
 
-        let engine = AudioEngine {
-            synth: synth.clone(),
-        };
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (tap, tap_reader) = scope::output_tap();
+            synth.set_output_tap(tap);
+            scope::start_scope_reader(tap_reader);
 
-        // Initialize the platform-specific audio streaming
-        engine.init_platform_audio(synth)?;
+            let (query_client, query_server) = query::query_ring();
+            synth.set_query_server(query_server);
+            let _ = QUERY_CLIENT.set(Mutex::new(query_client));
+        }
 
-        Ok(engine)
-    }
+        let synth = Arc::new(Mutex::new(synth));
 
-    fn init_platform_audio(
-        &self,
-        synth: Arc<Mutex<FunDSPSynth>>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
         // Platform-specific initialization that connects to our synth
-        #[cfg(not(target_os = "android"))]
-        {
-            desktop::start_audio_stream(synth)?;
-            println!("Desktop audio stream started");
-        }
+        let stream = backend::start_audio_stream(synth.clone())?;
+        info!("Audio stream started");
 
-        #[cfg(target_os = "android")]
-        {
-            android::start_audio_stream(synth)?;
-            println!("Android audio stream started");
-        }
-
-        Ok(())
+        Ok(AudioEngine { synth, stream })
     }
 
     /// Handle a result immediately, without queuing. Use this for anything
@@ -65,7 +284,7 @@ impl AudioEngine {
         if let Ok(mut synth) = self.synth.lock() {
             synth.handle_event(event)
         } else {
-            AudioEventResult::Err("Failed to acquire synth lock".to_string())
+            AudioEventResult::Err("Failed to acquire synth lock")
         }
     }
 }
@@ -73,46 +292,716 @@ impl AudioEngine {
 // Global audio engine
 static AUDIO_ENGINE: OnceLock<AudioEngine> = OnceLock::new();
 static EVENT_PRODUCER: OnceLock<Arc<Mutex<Producer<AudioEvent>>>> = OnceLock::new();
+// The queue's settings as it was created, and its consumer, for dropping the
+// oldest event when it's full
+static EVENT_QUEUE_CONFIG: OnceLock<EventQueueConfig> = OnceLock::new();
+static EVENT_CONSUMER: OnceLock<event_queue::SharedConsumer> = OnceLock::new();
+// Priority lane for note on/off, so they never wait behind parameter events
+static NOTE_PRODUCER: OnceLock<Mutex<Producer<AudioEvent>>> = OnceLock::new();
+// The sequencer's patterns and song. Edited here and sent to the synth whole,
+// so edits never wait on the audio thread
+static ARRANGEMENT: Mutex<Arrangement> = Mutex::new(Arrangement::new());
+// Getter queries for the audio thread to answer
+#[cfg(not(target_arch = "wasm32"))]
+static QUERY_CLIENT: OnceLock<Mutex<query::QueryClient>> = OnceLock::new();
+// App handle, used to push events to the frontend from the audio backends
+#[cfg(not(target_arch = "wasm32"))]
+static APP_HANDLE: OnceLock<tauri::AppHandle> = OnceLock::new();
+
+/// Register the app handle so the audio engine can emit events to the frontend
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_app_handle(app: tauri::AppHandle) {
+    let _ = APP_HANDLE.set(app);
+}
 
+/// Emit an event to the frontend. Does nothing before `set_app_handle` has been called
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn emit_event<S: serde::Serialize + Clone>(event: &str, payload: S) {
+    if let Some(app) = APP_HANDLE.get() {
+        if let Err(e) = app.emit(event, payload) {
+            error!("Failed to emit {} event: {}", event, e);
+        }
+    }
+}
+
+/// Initialize with the saved event queue settings (see `set_event_queue_config`)
 pub fn initialize_audio() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let queue = event_queue::load_config();
+    #[cfg(target_arch = "wasm32")]
+    let queue = EventQueueConfig::default();
+    initialize_audio_with(queue)
+}
+
+/// Initialize with a non-default event queue. Only the first call does anything
+pub fn initialize_audio_with(queue: EventQueueConfig) -> Result<(), Box<dyn std::error::Error>> {
     if AUDIO_ENGINE.get().is_none() {
-        let (event_producer, event_consumer) = rtrb::RingBuffer::<AudioEvent>::new(64);
+        let queue = queue.clamped();
+        let (event_producer, event_consumer) = rtrb::RingBuffer::<AudioEvent>::new(queue.capacity);
 
         EVENT_PRODUCER
             .set(Arc::new(Mutex::new(event_producer)))
             .unwrap();
+        let _ = EVENT_QUEUE_CONFIG.set(queue);
+        let (note_producer, note_consumer) =
+            rtrb::RingBuffer::<AudioEvent>::new(NOTE_QUEUE_CAPACITY);
+        let _ = NOTE_PRODUCER.set(Mutex::new(note_producer));
+        info!(
+            "Event queue: {} slots, {} when full",
+            queue.capacity,
+            queue.overflow.as_str()
+        );
 
-        match AudioEngine::new(event_consumer) {
+        match AudioEngine::new(event_consumer, note_consumer) {
             Ok(engine) => {
+                if let Ok(synth) = engine.synth.lock() {
+                    let _ = EVENT_CONSUMER.set(synth.event_consumer());
+                }
                 if AUDIO_ENGINE.set(engine).is_err() {
                     return Err("Failed to initialize audio engine".into());
                 }
             }
             Err(e) => return Err(e),
         }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            session::restore_and_autosave();
+            start_stats_reporter();
+            gamepad::start_gamepad_input();
+            midi_ports::start_midi_input();
+        }
     }
     Ok(())
 }
 
-/// Immediately handle an event, skipping the queue
-pub fn handle_audio_event(event: AudioEvent) -> AudioEventResult {
-    if let Some(engine) = AUDIO_ENGINE.get() {
-        engine.handle_event(event)
-    } else {
-        AudioEventResult::Err("Audio engine not initialized".to_string())
+/// How often glitch counters are pushed to the frontend (only when they've changed)
+#[cfg(not(target_arch = "wasm32"))]
+const STATS_REPORT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Periodically emit `audio-stats` events so the UI can show why audio is
+/// glitching, plus `dsp-load` events for the load meter
+#[cfg(not(target_arch = "wasm32"))]
+fn start_stats_reporter() {
+    std::thread::spawn(|| {
+        let mut last = AudioStatsInfo::default();
+        loop {
+            std::thread::sleep(STATS_REPORT_INTERVAL);
+            let stats = STATS.snapshot();
+            if stats != last {
+                emit_event("audio-stats", stats);
+                if stats.watchdog_resets > last.watchdog_resets {
+                    emit_event(
+                        "audio-watchdog",
+                        WatchdogEvent {
+                            resets: stats.watchdog_resets,
+                            fault: STATS.last_watchdog_fault(),
+                        },
+                    );
+                }
+                last = stats;
+            }
+            emit_event("dsp-load", DSP_LOAD.snapshot());
+        }
+    });
+}
+
+fn lock_synth() -> Result<std::sync::MutexGuard<'static, FunDSPSynth>, AudioError> {
+    AUDIO_ENGINE
+        .get()
+        .ok_or(AudioError::NotInitialized)?
+        .synth
+        .lock()
+        .map_err(|_| AudioError::Lock)
+}
+
+/// Start capturing every queued event, along with the current patch
+pub fn start_capture() -> Result<(), AudioError> {
+    let synth = lock_synth()?;
+    capture::start_capture(synth.get_sample_rate(), synth.get_patch());
+    Ok(())
+}
+
+/// Stop capturing, returning the number of events captured
+pub fn stop_capture() -> Result<usize, AudioError> {
+    capture::stop_capture().map_err(AudioError::Capture)
+}
+
+/// Save the last capture as JSON
+pub fn export_capture(path: &str) -> Result<(), AudioError> {
+    capture::export_capture(path).map_err(AudioError::Capture)
+}
+
+/// Load a capture saved with `export_capture`, returning its number of events
+pub fn import_capture(path: &str) -> Result<usize, AudioError> {
+    capture::import_capture(path).map_err(AudioError::Capture)
+}
+
+/// Start writing every queued event to a JSONL file at `path`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn start_event_log(path: &str) -> Result<(), AudioError> {
+    let sample_rate = lock_synth()?.get_sample_rate();
+    event_log::start_event_log(path, sample_rate).map_err(AudioError::Capture)
+}
+
+/// Stop logging events, returning the number logged
+#[cfg(not(target_arch = "wasm32"))]
+pub fn stop_event_log() -> Result<usize, AudioError> {
+    event_log::stop_event_log().map_err(AudioError::Capture)
+}
+
+/// Replay an event log through the synth, in the background. Returns its
+/// number of events
+#[cfg(not(target_arch = "wasm32"))]
+pub fn replay_event_log(path: &str) -> Result<usize, AudioError> {
+    let sample_rate = lock_synth()?.get_sample_rate();
+    event_log::replay_event_log(path, sample_rate).map_err(AudioError::Capture)
+}
+
+/// Write the notes of the last capture to a standard MIDI file
+pub fn export_capture_midi(path: &str) -> Result<(), AudioError> {
+    let midi =
+        midi_export::performance_to_midi(&capture::last_capture().map_err(AudioError::Capture)?);
+    std::fs::write(path, midi)
+        .map_err(|e| AudioError::Capture(format!("Failed to write {}: {}", path, e)))
+}
+
+/// Re-render the last capture offline to an audio file in `format`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_capture(
+    path: &str,
+    sample_rate: u32,
+    format: ExportFormat,
+) -> Result<(), AudioError> {
+    let log = capture::last_capture().map_err(AudioError::Capture)?;
+    let frames = capture::render_offline(&log, sample_rate as f32, 3.0)
+        .map_err(|e| AudioError::Capture(e.to_string()))?;
+    encode::write_stereo(path, sample_rate, &frames, format).map_err(AudioError::Capture)
+}
+
+/// Record the output to a new take in `options.directory`, returning its
+/// path. Clicks `count_in_beats` on the hi-hat first, and only keeps audio
+/// between the punch points
+#[cfg(not(target_arch = "wasm32"))]
+pub fn start_recording(options: RecordingOptions) -> Result<String, AudioError> {
+    let path = recording::next_take_path(std::path::Path::new(&options.directory), options.format);
+    let origin_frame = {
+        let mut synth = lock_synth()?;
+        let sample_rate = synth.get_sample_rate();
+        let plan = recording::RecordingPlan::new(
+            &options,
+            synth.get_frame_position(),
+            synth.get_tempo(),
+            sample_rate,
+        );
+        let tap =
+            recording::start_recording(path.clone(), options.format, sample_rate as u32, plan)
+                .map_err(AudioError::Recording)?;
+        synth.set_recording_tap(tap);
+        for beat in 0..options.count_in_beats {
+            synth.handle_event(AudioEvent::At {
+                frame: plan.start_frame + (beat as f64 * plan.frames_per_beat) as u64,
+                event: Box::new(AudioEvent::TriggerDrum {
+                    drum: DrumKind::Hat,
+                    velocity: if beat == 0 { 1.0 } else { 0.6 },
+                }),
+            });
+        }
+        plan.origin_frame
+    };
+    if options.play_sequencer {
+        queue_audio_event(AudioEvent::At {
+            frame: origin_frame,
+            event: Box::new(AudioEvent::SequencerPlay { pattern: 0 }),
+        })?;
+    }
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Finish the recording in progress. The final `recording-status` event
+/// follows once the take is saved
+#[cfg(not(target_arch = "wasm32"))]
+pub fn stop_recording() -> Result<(), AudioError> {
+    recording::stop_recording().map_err(AudioError::Recording)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_recording_status() -> RecordingStatus {
+    recording::recording_status()
+}
+
+/// Re-encode a WAV file (e.g. a recorded take) as `format`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn convert_audio(
+    source: &std::path::Path,
+    dest: &std::path::Path,
+    format: ExportFormat,
+) -> Result<(), AudioError> {
+    encode::transcode_wav(source, dest, format).map_err(AudioError::Export)
+}
+
+/// Render a performance offline with a fresh synth, as `render_capture` does.
+/// Used by the golden render tests
+pub fn render_performance(
+    log: &PerformanceLog,
+    sample_rate: u32,
+    tail_secs: f32,
+) -> Result<Vec<(f32, f32)>, AudioError> {
+    capture::render_offline(log, sample_rate as f32, tail_secs)
+        .map_err(|e| AudioError::Capture(e.to_string()))
+}
+
+/// Load a WAV file for the sampler waveform and switch to it. The sample
+/// plays at its original pitch at `root_frequency`; with `loop_points`
+/// (start and end frames) it loops for as long as the note is held
+pub fn load_sample(
+    path: &str,
+    root_frequency: f32,
+    loop_points: Option<(usize, usize)>,
+) -> Result<(), AudioError> {
+    // Decode before taking the lock, files can be big
+    install_sample(
+        sampler::Sample::load_wav(path, root_frequency, loop_points).map_err(AudioError::Sample)?,
+    )
+}
+
+/// Names of the presets in a SoundFont file
+pub fn list_soundfont_presets(path: &str) -> Result<Vec<String>, AudioError> {
+    Ok(sf2::SoundFont::load(path)
+        .map_err(AudioError::Sample)?
+        .preset_names())
+}
+
+/// Play a SoundFont preset with the sampler waveform. Only the preset's
+/// sample around middle C is used, pitched across the whole keyboard
+pub fn load_soundfont_preset(path: &str, preset: &str) -> Result<(), AudioError> {
+    let sample = sf2::SoundFont::load(path)
+        .and_then(|soundfont| soundfont.preset_sample(preset))
+        .map_err(AudioError::Sample)?;
+    install_sample(sample)
+}
+
+fn install_sample(sample: sampler::Sample) -> Result<(), AudioError> {
+    lock_synth()?.load_sample(sample);
+    Ok(())
+}
+
+/// Glitch counters since startup (or the last reset)
+pub fn get_audio_stats() -> AudioStatsInfo {
+    STATS.snapshot()
+}
+
+pub fn reset_audio_stats() {
+    STATS.reset();
+    DSP_LOAD.reset_peak();
+}
+
+/// Latest oscilloscope frame: recent output downsampled for display. Also
+/// pushed as `oscilloscope` events at about 30fps while audio is running
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_oscilloscope() -> Vec<f32> {
+    scope::scope_frame()
+}
+
+/// FFT of the most recent output, for the spectrum view
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_spectrum() -> Result<SpectrumInfo, AudioError> {
+    let sample_rate = lock_synth()?.get_sample_rate();
+    spectrum::analyze(&scope::recent_samples(), sample_rate)
+        .ok_or_else(|| AudioError::Engine("Not enough audio captured yet".to_string()))
+}
+
+/// How much of each buffer's time is spent rendering it
+pub fn get_dsp_load() -> DspLoadInfo {
+    DSP_LOAD.snapshot()
+}
+
+/// Frames the synth has rendered since startup, the clock timestamped
+/// events (`AudioEvent::At`) run on
+pub fn get_sample_clock() -> u64 {
+    capture::SAMPLE_CLOCK.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Seconds the synth has played for, the clock `AudioEvent::ScheduleNote` runs on
+pub fn get_transport_time() -> f64 {
+    f64::from_bits(schedule::TRANSPORT_CLOCK.load(std::sync::atomic::Ordering::Relaxed))
+}
+
+/// Handle an event and return its result, skipping the queue. Meant for
+/// getters, which are read from the parameter mirror. Anything it can't
+/// answer goes to the audio thread between buffers, and the synth is only
+/// locked if that doesn't answer either (the stream is paused or shut down).
+/// Errors from the synth come back as `AudioError::Engine`
+pub fn handle_audio_event(event: AudioEvent) -> Result<AudioEventResult, AudioError> {
+    let engine = AUDIO_ENGINE.get().ok_or(AudioError::NotInitialized)?;
+    if let Some(result) = mirror::PARAM_MIRROR.answer(&event) {
+        return Ok(result);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let answer = QUERY_CLIENT
+        .get()
+        .and_then(|client| query::ask(client, event.clone()));
+    #[cfg(target_arch = "wasm32")]
+    let answer = None;
+    match answer.unwrap_or_else(|| engine.handle_event(event)) {
+        AudioEventResult::Err(e) => Err(AudioError::Engine(e.to_string())),
+        result => Ok(result),
     }
 }
 
+fn with_stream<T>(
+    f: impl FnOnce(&backend::StreamController) -> Result<T, String>,
+) -> Result<T, AudioError> {
+    match AUDIO_ENGINE.get() {
+        Some(engine) => f(&engine.stream).map_err(AudioError::Device),
+        None => Err(AudioError::NotInitialized),
+    }
+}
+
+/// List the output devices available on the current audio host
+pub fn list_audio_devices() -> Result<Vec<AudioDeviceInfo>, AudioError> {
+    with_stream(|stream| stream.list_devices())
+}
+
+/// List the audio hosts available on this system. JACK is only offered when
+/// built with the `jack` feature
+pub fn list_audio_hosts() -> Result<Vec<String>, AudioError> {
+    with_stream(|stream| stream.list_hosts())
+}
+
+/// Rebuild the output stream on another audio host (`None` for the platform default)
+pub fn select_audio_host(name: Option<String>) -> Result<(), AudioError> {
+    with_stream(|stream| stream.select_host(name))
+}
+
+/// Move the output stream to the named device (or the default device if
+/// `None`). Synth state is kept, only the stream is rebuilt.
+pub fn select_audio_device(name: Option<String>) -> Result<(), AudioError> {
+    with_stream(|stream| stream.select_device(name))
+}
+
+/// Renegotiate the stream at a new sample rate (`None` for the device default)
+pub fn set_stream_sample_rate(sample_rate: Option<u32>) -> Result<(), AudioError> {
+    with_stream(|stream| stream.set_sample_rate(sample_rate))
+}
+
+/// Renegotiate the stream with a new buffer size in frames (`None` for the device default)
+pub fn set_stream_buffer_size(buffer_size: Option<u32>) -> Result<(), AudioError> {
+    with_stream(|stream| stream.set_buffer_size(buffer_size))
+}
+
+/// Ask for exclusive (low latency) access to the output device, falling
+/// back to shared mode where that isn't possible
+pub fn set_stream_exclusive_mode(exclusive: bool) -> Result<(), AudioError> {
+    with_stream(|stream| stream.set_exclusive_mode(exclusive))
+}
+
+/// Ask for the audio callback thread to run at real-time priority, which
+/// helps avoid underruns when the system is busy
+pub fn set_stream_realtime_priority(enabled: bool) -> Result<(), AudioError> {
+    with_stream(|stream| stream.set_realtime_priority(enabled))
+}
+
+/// List the input devices available on the current audio host
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_input_devices() -> Result<Vec<AudioDeviceInfo>, AudioError> {
+    with_stream(|stream| stream.list_input_devices())
+}
+
+/// Run an input device (the default if `None`) through the effects, mixed
+/// in with the voices. Replaces any input already running
+#[cfg(not(target_arch = "wasm32"))]
+pub fn start_audio_input(device_name: Option<String>) -> Result<(), AudioError> {
+    let (producer, consumer, analysis) = input::input_ring();
+    let sample_rate = {
+        let mut synth = lock_synth()?;
+        synth.set_audio_input(Some(consumer));
+        synth.get_sample_rate()
+    };
+    let result = with_stream(|stream| stream.start_input(device_name, producer));
+    match result {
+        Ok(()) => pitch::start_input_analyser(analysis, sample_rate),
+        Err(_) => lock_synth()?.set_audio_input(None),
+    }
+    result
+}
+
+/// Turn pitch detection on the audio input on or off
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_tuner_enabled(enabled: bool) {
+    pitch::set_tuner_enabled(enabled)
+}
+
+/// The tuner's latest reading, or None if it can't hear a pitch
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_tuner_reading() -> Option<TunerReading> {
+    pitch::tuner_reading()
+}
+
+/// Play the synth from the pitch and level of the audio input
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_pitch_tracking(settings: PitchTrackingSettings) {
+    pitch::set_pitch_tracking(settings)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_pitch_tracking() -> PitchTrackingSettings {
+    pitch::pitch_tracking()
+}
+
+/// Play a note from a computer keyboard key going down or up. Returns
+/// whether the key plays the synth
+#[cfg(not(target_arch = "wasm32"))]
+pub fn qwerty_key(code: &str, pressed: bool) -> Result<bool, AudioError> {
+    qwerty::qwerty_key(code, pressed)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn qwerty_release_all() -> Result<(), AudioError> {
+    qwerty::qwerty_release_all()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_qwerty_settings(settings: QwertySettings) {
+    qwerty::set_qwerty_settings(settings)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_qwerty_settings() -> QwertySettings {
+    qwerty::qwerty_settings()
+}
+
+/// Change what the game controller's buttons and axes do
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_gamepad_mapping(mapping: GamepadMapping) -> Result<(), AudioError> {
+    gamepad::set_gamepad_mapping(mapping)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_gamepad_mapping() -> GamepadMapping {
+    gamepad::gamepad_mapping()
+}
+
+/// The MIDI inputs plugged in now, and which of them are connected
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_midi_inputs() -> Vec<MidiDeviceInfo> {
+    midi_ports::list_midi_inputs()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn connect_midi_input(name: &str) -> Result<(), AudioError> {
+    midi_ports::connect_midi_input(name).map_err(AudioError::Device)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn disconnect_midi_input(name: &str) -> Result<(), AudioError> {
+    midi_ports::disconnect_midi_input(name).map_err(AudioError::Device)
+}
+
+/// Whether MIDI inputs are connected as they're plugged in
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_midi_auto_connect(enabled: bool) {
+    midi_ports::set_midi_auto_connect(enabled)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_midi_auto_connect() -> bool {
+    midi_ports::midi_auto_connect()
+}
+
+/// Effect types the insert slots can host, with their parameters
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_slot_effects() -> Vec<SlotEffectInfo> {
+    slots::slot_effects()
+}
+
+/// Send played notes out over MIDI, with or without the synth playing them too
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_controller_settings(settings: ControllerSettings) -> Result<(), AudioError> {
+    controller::set_controller_settings(settings)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_controller_settings() -> ControllerSettings {
+    controller::controller_settings()
+}
+
+/// Send notes and parameter changes to an OSC destination
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_osc_settings(settings: OscSettings) -> Result<(), AudioError> {
+    osc::set_osc_settings(settings)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_osc_settings() -> OscSettings {
+    osc::osc_settings()
+}
+
+/// Lead or follow a jam: the master's tempo and sequencer start/stop are
+/// followed by every device in the same session
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_jam_settings(settings: JamSettings) -> Result<(), AudioError> {
+    jam::set_jam_settings(settings)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_jam_settings() -> JamSettings {
+    jam::jam_settings()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_jam_status() -> JamStatus {
+    jam::jam_status()
+}
+
+/// The MIDI outputs controller mode can send to
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_midi_outputs() -> Vec<String> {
+    midi_ports::list_midi_outputs()
+}
+
+/// Look for Bluetooth MIDI devices (Android only; elsewhere they're paired
+/// in the system settings)
+#[cfg(not(target_arch = "wasm32"))]
+pub fn scan_bluetooth_midi() -> Result<(), AudioError> {
+    midi_ports::scan_bluetooth_midi().map_err(AudioError::Device)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn stop_audio_input() -> Result<(), AudioError> {
+    with_stream(|stream| stream.stop_input())?;
+    lock_synth()?.set_audio_input(None);
+    Ok(())
+}
+
+/// Pause the output stream, e.g. while the app is in the background
+pub fn pause_audio() -> Result<(), AudioError> {
+    with_stream(|stream| stream.pause())
+}
+
+/// Resume a paused stream, or reopen it after `shutdown_audio`
+pub fn resume_audio() -> Result<(), AudioError> {
+    with_stream(|stream| stream.resume())
+}
+
+/// Close the output stream and release the audio device. Synth state is kept,
+/// so `resume_audio` picks up where we left off
+pub fn shutdown_audio() -> Result<(), AudioError> {
+    with_stream(|stream| stream.shutdown())
+}
+
+/// Report the stream configuration the device actually gave us
+pub fn get_audio_config() -> Result<AudioConfigInfo, AudioError> {
+    with_stream(|stream| {
+        stream
+            .get_config()
+            .ok_or_else(|| "No audio stream running".to_string())
+    })
+}
+
+/// The sequencer's current patterns and song
+pub fn get_arrangement() -> Result<Arrangement, AudioError> {
+    Ok(ARRANGEMENT.lock().map_err(|_| AudioError::Lock)?.clone())
+}
+
+/// Apply `edit` to the arrangement and send the result to the synth. Nothing
+/// changes if the edit fails or can't be queued
+pub fn edit_arrangement<T>(
+    edit: impl FnOnce(&mut Arrangement) -> Result<T, String>,
+) -> Result<T, AudioError> {
+    let mut current = ARRANGEMENT.lock().map_err(|_| AudioError::Lock)?;
+    let mut arrangement = current.clone();
+    let result = edit(&mut arrangement).map_err(AudioError::Sequencer)?;
+    queue_audio_event(AudioEvent::SetArrangement {
+        arrangement: arrangement.clone(),
+    })?;
+    *current = arrangement;
+    Ok(result)
+}
+
 /// Queue an audio event for processing. NB events may be dropped if superceded
-/// by subsequent events in the same buffer
-pub fn queue_audio_event(event: AudioEvent) -> AudioEventResult {
+/// by subsequent events in the same buffer. When the queue is full, what
+/// happens depends on the `OverflowPolicy` it was initialized with
+pub fn queue_audio_event(event: AudioEvent) -> Result<(), AudioError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if controller::echo(&event) {
+        // Controller mode with the synth muted: it only goes out over MIDI
+        return Ok(());
+    }
+    if !synthesis::is_coalesced(&event) {
+        if let Some(producer) = NOTE_PRODUCER.get() {
+            record_event(&event);
+            #[cfg(not(target_arch = "wasm32"))]
+            if !is_note_event(&event) {
+                session::mark_changed();
+            }
+            return producer.lock().unwrap().push(event).map_err(|_| {
+                STATS.record_queue_overflow();
+                AudioError::QueueFull
+            });
+        }
+    }
     if let Some(producer) = EVENT_PRODUCER.get() {
+        record_event(&event);
+        #[cfg(not(target_arch = "wasm32"))]
+        session::mark_changed();
         let mut producer = producer.lock().unwrap();
-        match producer.push(event) {
-            Ok(_) => AudioEventResult::Ok,
-            Err(_) => AudioEventResult::Err("Event queue full".to_string()),
+        let event = match producer.push(event) {
+            Ok(_) => return Ok(()),
+            Err(rtrb::PushError::Full(event)) => event,
+        };
+        STATS.record_queue_overflow();
+        let policy = EVENT_QUEUE_CONFIG
+            .get()
+            .map_or(OverflowPolicy::default(), |config| config.overflow);
+        if policy == OverflowPolicy::DropOldest && discard_oldest_event() {
+            return producer.push(event).map_err(|_| AudioError::QueueFull);
         }
+        Err(AudioError::QueueFull)
     } else {
-        AudioEventResult::Err("Producer not initialized".to_string())
+        Err(AudioError::NotInitialized)
     }
 }
+
+/// Pass a queued event to the performance capture and event log
+fn record_event(event: &AudioEvent) {
+    capture::record(event);
+    #[cfg(not(target_arch = "wasm32"))]
+    event_log::record(event);
+    #[cfg(not(target_arch = "wasm32"))]
+    osc::record(event);
+    #[cfg(not(target_arch = "wasm32"))]
+    jam::record(event);
+}
+
+/// Events that don't change the session. Everything that isn't coalesced,
+/// these included, goes in the note lane so it stays in the order it was
+/// sent; the main queue only takes parameter changes, which can come in
+/// bursts of hundreds during a drag
+#[cfg(not(target_arch = "wasm32"))]
+fn is_note_event(event: &AudioEvent) -> bool {
+    matches!(
+        event,
+        AudioEvent::PlayNote { .. } | AudioEvent::NoteOff { .. } | AudioEvent::NoteOffAll
+    )
+}
+
+/// Take the oldest event off the queue. Only waits for the audio thread if
+/// it's draining the queue at that moment, which is brief
+fn discard_oldest_event() -> bool {
+    EVENT_CONSUMER
+        .get()
+        .and_then(|consumer| consumer.lock().ok())
+        .map_or(false, |mut consumer| consumer.pop().is_ok())
+}
+
+/// The event queue settings in use
+pub fn get_event_queue_config() -> EventQueueConfig {
+    EVENT_QUEUE_CONFIG.get().copied().unwrap_or_default()
+}
+
+/// Save event queue settings. They take effect at the next startup
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_event_queue_config(config: EventQueueConfig) -> Result<(), AudioError> {
+    event_queue::save_config(config).map_err(AudioError::Settings)
+}
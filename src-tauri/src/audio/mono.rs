@@ -0,0 +1,71 @@
+// Mono (legato) mode: one voice, with the keys held down tracked so that
+// releasing the sounding note falls back to another held one
+use serde::{Deserialize, Serialize};
+
+/// Which held note sounds in mono mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotePriority {
+    /// The most recently pressed
+    #[default]
+    Last,
+    Lowest,
+    Highest,
+}
+
+impl NotePriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotePriority::Last => "last",
+            NotePriority::Lowest => "lowest",
+            NotePriority::Highest => "highest",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "last" => Some(NotePriority::Last),
+            "lowest" | "low" => Some(NotePriority::Lowest),
+            "highest" | "high" => Some(NotePriority::Highest),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeldNote {
+    pub note_id: u32,
+    pub frequency: f32,
+    pub velocity: f32,
+}
+
+/// Keys held down in mono mode, in the order they were pressed
+#[derive(Default)]
+pub struct HeldNotes {
+    notes: Vec<HeldNote>,
+}
+
+impl HeldNotes {
+    pub fn press(&mut self, note: HeldNote) {
+        self.release(note.note_id);
+        self.notes.push(note);
+    }
+
+    pub fn release(&mut self, note_id: u32) {
+        self.notes.retain(|n| n.note_id != note_id);
+    }
+
+    /// The note that should be sounding
+    pub fn current(&self, priority: NotePriority) -> Option<HeldNote> {
+        let notes = self.notes.iter().copied();
+        match priority {
+            NotePriority::Last => notes.last(),
+            NotePriority::Lowest => notes.min_by(|a, b| a.frequency.total_cmp(&b.frequency)),
+            NotePriority::Highest => notes.max_by(|a, b| a.frequency.total_cmp(&b.frequency)),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.notes.clear();
+    }
+}
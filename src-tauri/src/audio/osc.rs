@@ -0,0 +1,211 @@
+// OSC output: every note and parameter change queued for the synth is also
+// sent as an OSC message over UDP, so lighting rigs and visualizers can
+// follow the performance. Notes are /harphonium/note_on (id, frequency,
+// velocity) and /harphonium/note_off (id); anything else is named after its
+// event, e.g. SetFilterCutoff is /harphonium/filter_cutoff, with the event's
+// numbers, flags and names as arguments in alphabetical order
+use super::error::AudioError;
+use super::synthesis::AudioEvent;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+const ADDRESS_PREFIX: &str = "/harphonium/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscSettings {
+    pub enabled: bool,
+    /// Where to send, a host name or IP address
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for OscSettings {
+    fn default() -> Self {
+        OscSettings {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 9000,
+        }
+    }
+}
+
+struct OscOutput {
+    socket: UdpSocket,
+    destination: SocketAddr,
+}
+
+/// The settings as set, None for the defaults
+static SETTINGS: Mutex<Option<OscSettings>> = Mutex::new(None);
+/// The open socket, while enabled
+static OUTPUT: Mutex<Option<OscOutput>> = Mutex::new(None);
+
+/// One OSC argument
+enum Arg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+    Bool(bool),
+}
+
+/// Append an OSC string: null terminated, padded to four bytes
+fn push_string(buffer: &mut Vec<u8>, s: &str) {
+    buffer.extend_from_slice(s.as_bytes());
+    buffer.push(0);
+    while buffer.len() % 4 != 0 {
+        buffer.push(0);
+    }
+}
+
+fn encode(address: &str, args: &[Arg]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    push_string(&mut buffer, address);
+    let tags: String = std::iter::once(',')
+        .chain(args.iter().map(|arg| match arg {
+            Arg::Int(_) => 'i',
+            Arg::Float(_) => 'f',
+            Arg::Str(_) => 's',
+            Arg::Bool(true) => 'T',
+            Arg::Bool(false) => 'F',
+        }))
+        .collect();
+    push_string(&mut buffer, &tags);
+    for arg in args {
+        match arg {
+            Arg::Int(i) => buffer.extend_from_slice(&i.to_be_bytes()),
+            Arg::Float(f) => buffer.extend_from_slice(&f.to_be_bytes()),
+            Arg::Str(s) => push_string(&mut buffer, s),
+            // Carried in the type tag alone
+            Arg::Bool(_) => {}
+        }
+    }
+    buffer
+}
+
+/// "SetFilterCutoff" -> "filter_cutoff"
+fn snake_case(name: &str) -> String {
+    let name = name.strip_prefix("Set").unwrap_or(name);
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn scalar_arg(value: &Value) -> Option<Arg> {
+    match value {
+        Value::Bool(b) => Some(Arg::Bool(*b)),
+        Value::Number(n) if n.is_f64() => n.as_f64().map(|f| Arg::Float(f as f32)),
+        Value::Number(n) => n.as_i64().map(|i| Arg::Int(i as i32)),
+        Value::String(s) => Some(Arg::Str(s.clone())),
+        _ => None,
+    }
+}
+
+/// The OSC message for an event, if it has anything worth sending
+fn message(event: &AudioEvent) -> Option<Vec<u8>> {
+    match event {
+        AudioEvent::PlayNote {
+            note_id,
+            frequency,
+            velocity,
+        } => {
+            return Some(encode(
+                "/harphonium/note_on",
+                &[
+                    Arg::Int(*note_id as i32),
+                    Arg::Float(*frequency),
+                    Arg::Float(*velocity),
+                ],
+            ))
+        }
+        AudioEvent::NoteOff { note_id } => {
+            return Some(encode("/harphonium/note_off", &[Arg::Int(*note_id as i32)]))
+        }
+        // Sent as it's queued, not when it's due
+        AudioEvent::At { event, .. } => return message(event),
+        _ => {}
+    }
+    let mut fields = match serde_json::to_value(event).ok()? {
+        Value::Object(fields) => fields,
+        _ => return None,
+    };
+    let name = match fields.remove("type")? {
+        Value::String(name) => name,
+        _ => return None,
+    };
+    // Sorted here, as the map's order depends on serde_json's features
+    let mut fields: Vec<(String, Value)> = fields.into_iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(&b.0));
+    let args: Vec<Arg> = fields
+        .iter()
+        .filter_map(|(_, value)| scalar_arg(value))
+        .collect();
+    // Events that only carry structures (patches, arrangements, ...). Those
+    // with no fields at all, e.g. NoteOffAll, still go
+    if args.is_empty() && !fields.is_empty() {
+        return None;
+    }
+    Some(encode(
+        &format!("{}{}", ADDRESS_PREFIX, snake_case(&name)),
+        &args,
+    ))
+}
+
+/// Send an event queued for the synth, if OSC output is on
+pub fn record(event: &AudioEvent) {
+    let output = OUTPUT.lock().unwrap();
+    let output = match output.as_ref() {
+        Some(output) => output,
+        None => return,
+    };
+    if let Some(message) = message(event) {
+        if let Err(e) = output.socket.send_to(&message, output.destination) {
+            warn!("Failed to send OSC: {}", e);
+        }
+    }
+}
+
+/// Change where OSC goes, or turn it on or off. Fails, leaving OSC off, if
+/// the host can't be resolved
+pub fn set_osc_settings(settings: OscSettings) -> Result<(), AudioError> {
+    let mut output = OUTPUT.lock().unwrap();
+    *output = None;
+    if settings.enabled {
+        let destination = (settings.host.as_str(), settings.port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| AudioError::InvalidValue {
+                kind: "OSC host",
+                value: settings.host.clone(),
+            })?;
+        let local = if destination.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local).map_err(|e| AudioError::Device(e.to_string()))?;
+        info!("📡 Sending OSC to {}", destination);
+        *output = Some(OscOutput {
+            socket,
+            destination,
+        });
+    }
+    *SETTINGS.lock().unwrap() = Some(settings);
+    Ok(())
+}
+
+pub fn osc_settings() -> OscSettings {
+    SETTINGS.lock().unwrap().clone().unwrap_or_default()
+}
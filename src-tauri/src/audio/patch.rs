@@ -0,0 +1,257 @@
+// Patch snapshots: every sound parameter of the synth in one serialisable value
+use super::delay::DelayMode;
+use super::effects::EffectOrder;
+use super::envelope::EnvelopeMode;
+use super::lfo::LfoShape;
+use super::slots::{SlotPatch, MAX_EFFECT_SLOTS};
+use super::smoothing::Smoothing;
+use super::synthesis::{AdsrParams, Waveform};
+use super::voice::{
+    Unison, VoicePanMode, DEFAULT_ENVELOPE_SMOOTHING, DEFAULT_GATE_SMOOTHING, MAX_UNISON,
+};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+/// Version 1 made the delay's mix a wet/dry crossfade like every other
+/// effect's. Before it, the echoes were added over the full dry signal
+pub const PATCH_VERSION: u32 = 1;
+
+/// A complete set of sound parameters. Missing fields fall back to the
+/// defaults, so patches saved before a parameter existed still load
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Patch {
+    /// Patches saved before this existed are version 0
+    #[serde(default)]
+    pub version: u32,
+    pub waveform: Waveform,
+    pub master_volume: f32,
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+    pub delay_time: f32,
+    pub delay_feedback: f32,
+    pub delay_mix: f32,
+    pub delay_mode: DelayMode,
+    /// Tone of the delay's feedback path, high and low pass in Hz
+    pub delay_low_cut: f32,
+    pub delay_high_cut: f32,
+    pub filter_cutoff: f32,
+    pub filter_resonance: f32,
+    /// Wet/dry mix of the filter; the delay's is `delay_mix` and the insert
+    /// slots' are in `effect_slots`
+    pub filter_mix: f32,
+    pub unison_voices: usize,
+    /// Cents
+    pub unison_detune: f32,
+    pub unison_spread: f32,
+    pub ring_mod_mix: f32,
+    pub ring_mod_ratio: f32,
+    pub vibrato_rate: f32,
+    /// Cents
+    pub vibrato_depth: f32,
+    pub vibrato_delay: f32,
+    pub filter_lfo_rate: f32,
+    /// Octaves
+    pub filter_lfo_depth: f32,
+    /// Beats per LFO cycle, 0 for free running
+    pub filter_lfo_sync: f32,
+    pub vibrato_shape: LfoShape,
+    pub filter_lfo_shape: LfoShape,
+    pub envelope_mode: EnvelopeMode,
+    pub envelope_delay: f32,
+    pub envelope_hold: f32,
+    pub filter_velocity_amount: f32,
+    pub voice_spread: f32,
+    pub voice_pan_mode: VoicePanMode,
+    pub stereo_width: f32,
+    pub soft_clip: bool,
+    pub soft_clip_drive: f32,
+    /// Retrigger click suppression, see `AudioEvent::SetGateSmoothing`
+    pub gate_smoothing: Smoothing,
+    pub envelope_smoothing: Smoothing,
+    /// The effects, first to last
+    pub effect_order: EffectOrder,
+    /// What each insert slot holds
+    pub effect_slots: [SlotPatch; MAX_EFFECT_SLOTS],
+}
+
+impl Default for Patch {
+    /// The synth's startup sound
+    fn default() -> Self {
+        Patch {
+            version: PATCH_VERSION,
+            waveform: Waveform::default(),
+            master_volume: 0.7,
+            attack: 0.02,
+            decay: 0.2,
+            sustain: 0.6,
+            release: 0.3,
+            delay_time: 0.3,
+            delay_feedback: 0.4,
+            delay_mix: 0.2,
+            delay_mode: DelayMode::Digital,
+            delay_low_cut: 20.0,
+            delay_high_cut: 20000.0,
+            filter_cutoff: 1000.0,
+            filter_resonance: 0.1,
+            filter_mix: 1.0,
+            unison_voices: 1,
+            unison_detune: 10.0,
+            unison_spread: 0.5,
+            ring_mod_mix: 0.0,
+            ring_mod_ratio: 2.0,
+            vibrato_rate: 5.5,
+            vibrato_depth: 0.0,
+            vibrato_delay: 0.3,
+            filter_lfo_rate: 1.0,
+            filter_lfo_depth: 0.0,
+            filter_lfo_sync: 0.0,
+            vibrato_shape: LfoShape::Sine,
+            filter_lfo_shape: LfoShape::Sine,
+            envelope_mode: EnvelopeMode::Adsr,
+            envelope_delay: 0.0,
+            envelope_hold: 0.0,
+            filter_velocity_amount: 0.0,
+            voice_spread: 0.0,
+            voice_pan_mode: VoicePanMode::RoundRobin,
+            stereo_width: 1.0,
+            soft_clip: false,
+            soft_clip_drive: 0.0,
+            gate_smoothing: DEFAULT_GATE_SMOOTHING,
+            envelope_smoothing: DEFAULT_ENVELOPE_SMOOTHING,
+            effect_order: EffectOrder::default(),
+            effect_slots: [SlotPatch::default(); MAX_EFFECT_SLOTS],
+        }
+    }
+}
+
+/// Waveforms to pick from when randomizing
+const WAVEFORMS: [Waveform; 4] = [
+    Waveform::Sine,
+    Waveform::Square,
+    Waveform::Sawtooth,
+    Waveform::Triangle,
+];
+
+/// Pick a value in `min..max`. Times and frequencies are picked on a log
+/// scale, so short attacks are as likely as long ones
+fn random_in(min: f32, max: f32, log: bool) -> f32 {
+    let t = fastrand::f32();
+    if log {
+        min * (max / min).powf(t)
+    } else {
+        min + (max - min) * t
+    }
+}
+
+impl Patch {
+    /// The patch's envelope, clamped as the synth's setters would
+    pub fn adsr(&self) -> AdsrParams {
+        AdsrParams {
+            mode: self.envelope_mode,
+            delay: self.envelope_delay.clamp(0.0, 5.0),
+            attack: self.attack.clamp(0.001, 5.0),
+            hold: self.envelope_hold.clamp(0.0, 5.0),
+            decay: self.decay.clamp(0.001, 5.0),
+            sustain: self.sustain.clamp(0.0, 1.0),
+            release: self.release.clamp(0.001, 10.0),
+        }
+    }
+
+    /// The patch's unison stack, clamped as for `FunDSPSynth::set_unison`
+    pub fn unison(&self) -> Unison {
+        Unison {
+            voices: self.unison_voices.clamp(1, MAX_UNISON),
+            detune: self.unison_detune.clamp(0.0, 100.0),
+            spread: self.unison_spread.clamp(0.0, 1.0),
+        }
+    }
+
+    /// This patch brought up to `PATCH_VERSION`, sounding as it did when saved
+    pub fn migrated(&self) -> Patch {
+        let mut patch = *self;
+        if patch.version < 1 {
+            // An echo level over full dry becomes the crossfade with the same
+            // wet to dry ratio, tan(mix * pi / 2)
+            patch.delay_mix = patch.delay_mix.max(0.0).atan() / FRAC_PI_2;
+        }
+        patch.version = PATCH_VERSION;
+        patch
+    }
+
+    /// A random variation on this patch. `amount` (0.0 to 1.0) is how far to
+    /// move each parameter towards a random value; 1.0 gives a completely new
+    /// patch. Parameters named in `locked` (e.g. "attack", "waveform") are
+    /// kept. Ranges are narrower than the synth allows so results stay playable
+    pub fn randomized(&self, amount: f32, locked: &[String]) -> Patch {
+        let amount = amount.clamp(0.0, 1.0);
+        let is_locked = |name: &str| locked.iter().any(|l| l == name);
+        let vary = |name: &str, value: f32, min: f32, max: f32, log: bool| {
+            if is_locked(name) {
+                value
+            } else {
+                value + amount * (random_in(min, max, log) - value)
+            }
+        };
+
+        Patch {
+            version: self.version,
+            waveform: if !is_locked("waveform") && fastrand::f32() < amount {
+                WAVEFORMS[fastrand::usize(..WAVEFORMS.len())]
+            } else {
+                self.waveform
+            },
+            // Loudness isn't part of the sound design, never randomize it
+            master_volume: self.master_volume,
+            attack: vary("attack", self.attack, 0.002, 0.5, true),
+            decay: vary("decay", self.decay, 0.05, 1.5, true),
+            sustain: vary("sustain", self.sustain, 0.2, 1.0, false),
+            release: vary("release", self.release, 0.05, 1.5, true),
+            delay_time: vary("delay_time", self.delay_time, 0.1, 0.6, false),
+            delay_feedback: vary("delay_feedback", self.delay_feedback, 0.0, 0.6, false),
+            delay_mix: vary("delay_mix", self.delay_mix, 0.0, 0.4, false),
+            delay_mode: self.delay_mode,
+            delay_low_cut: self.delay_low_cut,
+            delay_high_cut: vary("delay_high_cut", self.delay_high_cut, 2000.0, 20000.0, true),
+            filter_cutoff: vary("filter_cutoff", self.filter_cutoff, 300.0, 8000.0, true),
+            filter_resonance: vary("filter_resonance", self.filter_resonance, 0.0, 0.6, false),
+            filter_mix: self.filter_mix,
+            // Stacking changes the character too much, keep the user's choice
+            unison_voices: self.unison_voices,
+            unison_detune: vary("unison_detune", self.unison_detune, 0.0, 30.0, false),
+            unison_spread: vary("unison_spread", self.unison_spread, 0.0, 1.0, false),
+            // Ring mod gets harsh quickly, so only a little is mixed in
+            ring_mod_mix: vary("ring_mod_mix", self.ring_mod_mix, 0.0, 0.3, false),
+            ring_mod_ratio: vary("ring_mod_ratio", self.ring_mod_ratio, 0.5, 4.0, true),
+            vibrato_rate: vary("vibrato_rate", self.vibrato_rate, 3.0, 8.0, false),
+            vibrato_depth: vary("vibrato_depth", self.vibrato_depth, 0.0, 30.0, false),
+            vibrato_delay: vary("vibrato_delay", self.vibrato_delay, 0.0, 1.0, false),
+            filter_lfo_rate: vary("filter_lfo_rate", self.filter_lfo_rate, 0.1, 8.0, true),
+            filter_lfo_depth: vary("filter_lfo_depth", self.filter_lfo_depth, 0.0, 2.0, false),
+            filter_lfo_sync: self.filter_lfo_sync,
+            vibrato_shape: self.vibrato_shape,
+            filter_lfo_shape: self.filter_lfo_shape,
+            envelope_mode: self.envelope_mode,
+            envelope_delay: self.envelope_delay,
+            envelope_hold: vary("envelope_hold", self.envelope_hold, 0.0, 0.5, false),
+            filter_velocity_amount: vary(
+                "filter_velocity_amount",
+                self.filter_velocity_amount,
+                -4.0,
+                4.0,
+                false,
+            ),
+            voice_spread: vary("voice_spread", self.voice_spread, 0.0, 1.0, false),
+            voice_pan_mode: self.voice_pan_mode,
+            stereo_width: vary("stereo_width", self.stereo_width, 0.5, 1.5, false),
+            soft_clip: self.soft_clip,
+            soft_clip_drive: self.soft_clip_drive,
+            gate_smoothing: self.gate_smoothing,
+            envelope_smoothing: self.envelope_smoothing,
+            effect_order: self.effect_order,
+            effect_slots: self.effect_slots,
+        }
+    }
+}
@@ -0,0 +1,295 @@
+// Pitch detection on the audio input. A thread drains the input's analysis
+// ring and runs YIN over the latest window, for the tuner and for pitch
+// tracking, which plays the synth from whatever's sung or whistled into it
+use super::synthesis::AudioEvent;
+use rtrb::Consumer;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Samples the tuner analyses at a time. Half is the longest period we can
+/// find, so this reaches down to about 23Hz at 48kHz
+const ANALYSIS_WINDOW: usize = 4096;
+const TUNER_INTERVAL: Duration = Duration::from_millis(50);
+/// Pitch tracking needs to keep up with the voice, so looks at less, more often.
+/// Down to about 47Hz at 48kHz
+const TRACKING_WINDOW: usize = 2048;
+const TRACKING_INTERVAL: Duration = Duration::from_millis(10);
+/// Samples the input level is measured over, about the tracking interval
+const ENVELOPE_WINDOW: usize = 512;
+/// Pitches less clear than this don't move the tracked note
+const MIN_TRACKING_CLARITY: f32 = 0.8;
+/// Passes without a pitch before the tracked note is released, so a
+/// consonant or a crack in the voice doesn't retrigger it
+const TRACKING_HOLD_PASSES: u32 = 5;
+/// Tracked notes get their own id, clear of the UI's and the scheduler's
+const TRACKING_NOTE_ID: u32 = 0x7fff_ff00;
+/// Highest pitch looked for, around the top of a concert harp
+const MAX_FREQUENCY: f32 = 4200.0;
+/// YIN's aperiodicity threshold. Lower is stricter
+const YIN_THRESHOLD: f32 = 0.15;
+/// Quieter than this (RMS) counts as silence, with no pitch
+const MIN_LEVEL: f32 = 0.005;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// What the tuner hears
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TunerReading {
+    /// Hz
+    pub frequency: f32,
+    /// Nearest equal tempered note (A4 = 440Hz), e.g. "C#4", and its MIDI number
+    pub note: String,
+    pub midi_note: i32,
+    /// How far off that note, -50 to +50
+    pub cents: f32,
+    /// How periodic the input is, 0.0 to 1.0. Readings under about 0.9 are shaky
+    pub clarity: f32,
+}
+
+impl TunerReading {
+    fn new(frequency: f32, clarity: f32) -> Self {
+        let note = 69.0 + 12.0 * (frequency / 440.0).log2();
+        let midi_note = note.round() as i32;
+        TunerReading {
+            frequency,
+            note: format!(
+                "{}{}",
+                NOTE_NAMES[midi_note.rem_euclid(12) as usize],
+                midi_note.div_euclid(12) - 1
+            ),
+            midi_note,
+            cents: (note - midi_note as f32) * 100.0,
+            clarity,
+        }
+    }
+}
+
+static TUNER_ENABLED: AtomicBool = AtomicBool::new(false);
+static TUNER_READING: Mutex<Option<TunerReading>> = Mutex::new(None);
+
+/// How the audio input plays the synth
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PitchTrackingSettings {
+    pub enabled: bool,
+    /// Time to slide most of the way to a new pitch
+    pub glide_ms: f32,
+    /// Input level (RMS) that opens the gate. It closes again at half this
+    pub gate_threshold: f32,
+}
+
+impl PitchTrackingSettings {
+    const DEFAULT: PitchTrackingSettings = PitchTrackingSettings {
+        enabled: false,
+        glide_ms: 40.0,
+        gate_threshold: 0.02,
+    };
+}
+
+impl Default for PitchTrackingSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static TRACKING_SETTINGS: Mutex<PitchTrackingSettings> = Mutex::new(PitchTrackingSettings::DEFAULT);
+
+pub fn set_pitch_tracking(settings: PitchTrackingSettings) {
+    *TRACKING_SETTINGS.lock().unwrap() = PitchTrackingSettings {
+        glide_ms: settings.glide_ms.max(0.0),
+        gate_threshold: settings.gate_threshold.clamp(0.0, 1.0),
+        ..settings
+    };
+}
+
+pub fn pitch_tracking() -> PitchTrackingSettings {
+    *TRACKING_SETTINGS.lock().unwrap()
+}
+
+/// Run the tuner while the input is running. Readings are pushed as `tuner`
+/// events, and kept for `tuner_reading`
+pub fn set_tuner_enabled(enabled: bool) {
+    TUNER_ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        *TUNER_READING.lock().unwrap() = None;
+    }
+}
+
+/// The latest reading. None if the tuner is off, there's no input, or it
+/// can't hear a pitch
+pub fn tuner_reading() -> Option<TunerReading> {
+    TUNER_READING.lock().unwrap().clone()
+}
+
+/// Find the fundamental in `samples` with YIN, returning the frequency and
+/// how clear it was (0.0 to 1.0). None for silence or unpitched sound
+pub fn detect_pitch(samples: &[f32], sample_rate: f32) -> Option<(f32, f32)> {
+    let window = samples.len() / 2;
+    if window < 3 || rms(samples) < MIN_LEVEL {
+        return None;
+    }
+    let min_lag = ((sample_rate / MAX_FREQUENCY) as usize).max(2);
+    let max_lag = window - 1;
+    if min_lag >= max_lag {
+        return None;
+    }
+
+    // Cumulative mean normalised difference
+    let mut cmnd = vec![1.0; max_lag + 1];
+    let mut running = 0.0;
+    for lag in 1..=max_lag {
+        let difference: f32 = (0..window)
+            .map(|i| {
+                let d = samples[i] - samples[i + lag];
+                d * d
+            })
+            .sum();
+        running += difference;
+        cmnd[lag] = if running > 0.0 {
+            difference * lag as f32 / running
+        } else {
+            1.0
+        };
+    }
+
+    // First dip under the threshold, followed down to its minimum
+    let mut lag = (min_lag..max_lag).find(|&lag| cmnd[lag] < YIN_THRESHOLD)?;
+    while lag + 1 < max_lag && cmnd[lag + 1] < cmnd[lag] {
+        lag += 1;
+    }
+    // Parabolic interpolation between lags for a finer estimate
+    let (a, b, c) = (cmnd[lag - 1], cmnd[lag], cmnd[lag + 1]);
+    let curvature = a - 2.0 * b + c;
+    let shift = if curvature.abs() > f32::EPSILON {
+        (0.5 * (a - c) / curvature).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    Some((
+        sample_rate / (lag as f32 + shift),
+        (1.0 - b).clamp(0.0, 1.0),
+    ))
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len().max(1) as f32).sqrt()
+}
+
+/// Turns levels and pitches from the input into a note: gated on the level,
+/// gliding between pitches
+#[derive(Default)]
+struct PitchTracker {
+    /// Frequency the note is sounding at, while the gate's open
+    frequency: Option<f32>,
+    /// Passes in a row without a clear pitch
+    missed: u32,
+}
+
+impl PitchTracker {
+    /// The event to send for one analysis pass, `elapsed` after the last
+    fn update(
+        &mut self,
+        settings: &PitchTrackingSettings,
+        level: f32,
+        pitch: Option<f32>,
+        elapsed: Duration,
+    ) -> Option<AudioEvent> {
+        self.missed = if pitch.is_some() { 0 } else { self.missed + 1 };
+        match (self.frequency, pitch) {
+            (None, Some(target)) if level >= settings.gate_threshold => {
+                self.frequency = Some(target);
+                Some(AudioEvent::PlayNote {
+                    note_id: TRACKING_NOTE_ID,
+                    frequency: target,
+                    velocity: (level / settings.gate_threshold.max(0.001) * 0.25).clamp(0.1, 1.0),
+                })
+            }
+            (Some(_), _)
+                if level < 0.5 * settings.gate_threshold || self.missed > TRACKING_HOLD_PASSES =>
+            {
+                self.release()
+            }
+            (Some(current), Some(target)) => {
+                // Glide in octaves, so up and down take the same time
+                let amount = if settings.glide_ms > 0.0 {
+                    1.0 - (-elapsed.as_secs_f32() * 1000.0 / settings.glide_ms).exp()
+                } else {
+                    1.0
+                };
+                let octaves = (target / current).log2() * amount;
+                let frequency = current * octaves.exp2();
+                self.frequency = Some(frequency);
+                Some(AudioEvent::SetFrequency { frequency })
+            }
+            _ => None,
+        }
+    }
+
+    fn release(&mut self) -> Option<AudioEvent> {
+        self.frequency.take().map(|_| AudioEvent::NoteOff {
+            note_id: TRACKING_NOTE_ID,
+        })
+    }
+}
+
+fn send(event: Option<AudioEvent>) {
+    if let Some(event) = event {
+        if let Err(e) = super::queue_audio_event(event) {
+            warn!("Pitch tracking event dropped: {}", e);
+        }
+    }
+}
+
+/// Analyse the input from `consumer` until the input stream goes away
+pub fn start_input_analyser(mut consumer: Consumer<f32>, sample_rate: f32) {
+    std::thread::spawn(move || {
+        let mut history: Vec<f32> = Vec::with_capacity(2 * ANALYSIS_WINDOW);
+        let mut tracker = PitchTracker::default();
+        let mut last_pass = Instant::now();
+        let mut last_tuner = Instant::now();
+        while !consumer.is_abandoned() {
+            std::thread::sleep(TRACKING_INTERVAL);
+            let available = consumer.slots();
+            if let Ok(chunk) = consumer.read_chunk(available) {
+                history.extend(chunk);
+            }
+            let excess = history.len().saturating_sub(ANALYSIS_WINDOW);
+            history.drain(..excess);
+            let elapsed = last_pass.elapsed();
+            last_pass = Instant::now();
+
+            let settings = pitch_tracking();
+            if !settings.enabled {
+                send(tracker.release());
+            } else if history.len() >= TRACKING_WINDOW {
+                let level = rms(&history[history.len() - ENVELOPE_WINDOW..]);
+                let pitch = if level >= 0.5 * settings.gate_threshold {
+                    detect_pitch(&history[history.len() - TRACKING_WINDOW..], sample_rate)
+                        .filter(|&(_, clarity)| clarity >= MIN_TRACKING_CLARITY)
+                        .map(|(frequency, _)| frequency)
+                } else {
+                    None
+                };
+                send(tracker.update(&settings, level, pitch, elapsed));
+            }
+
+            if TUNER_ENABLED.load(Ordering::Relaxed)
+                && history.len() >= ANALYSIS_WINDOW
+                && last_tuner.elapsed() >= TUNER_INTERVAL
+            {
+                last_tuner = Instant::now();
+                let reading = detect_pitch(&history, sample_rate)
+                    .map(|(frequency, clarity)| TunerReading::new(frequency, clarity));
+                *TUNER_READING.lock().unwrap() = reading.clone();
+                super::emit_event("tuner", reading);
+            }
+        }
+        send(tracker.release());
+        *TUNER_READING.lock().unwrap() = None;
+    });
+}
@@ -0,0 +1,20 @@
+// Real-time scheduling for audio callback threads: SCHED_FIFO (via rtkit
+// where needed) on Linux, time constraint threads on macOS, MMCSS on Windows
+use audio_thread_priority::promote_current_thread_to_real_time;
+use tracing::{error, info};
+
+/// Raise the calling thread to real-time priority, sized for callbacks of
+/// `buffer_frames` at `sample_rate`. Returns false if the OS refused, in which
+/// case we carry on at normal priority. The thread stays promoted until it exits
+pub fn promote_current_thread(buffer_frames: u32, sample_rate: u32) -> bool {
+    match promote_current_thread_to_real_time(buffer_frames, sample_rate) {
+        Ok(_) => {
+            info!("🔧 Audio thread promoted to real-time priority");
+            true
+        }
+        Err(e) => {
+            error!("Could not raise audio thread priority: {}", e);
+            false
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// Getter queries answered by the audio thread. The UI pushes a query onto one
+// ring and the audio thread pushes the answer back on another between
+// buffers, so reading a parameter never waits on the synth lock
+use super::synthesis::{AudioEvent, AudioEventResult};
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const QUERY_CAPACITY: usize = 32;
+/// A few buffers' worth. No answer by then and the stream probably isn't running
+const QUERY_TIMEOUT: Duration = Duration::from_millis(50);
+const POLL_INTERVAL: Duration = Duration::from_micros(500);
+
+/// The audio thread's end
+pub struct QueryServer {
+    requests: Consumer<(u64, AudioEvent)>,
+    responses: Producer<(u64, AudioEventResult)>,
+}
+
+impl QueryServer {
+    /// Answer every waiting query with `answer`
+    pub fn serve(&mut self, mut answer: impl FnMut(AudioEvent) -> AudioEventResult) {
+        while let Ok((id, event)) = self.requests.pop() {
+            // Only full if nobody is reading answers, so nobody's waiting either
+            let _ = self.responses.push((id, answer(event)));
+        }
+    }
+}
+
+/// The UI's end. Shared between commands, so answers can arrive for
+/// someone else's query; they're kept until that caller comes for them
+pub struct QueryClient {
+    requests: Producer<(u64, AudioEvent)>,
+    responses: Consumer<(u64, AudioEventResult)>,
+    next_id: u64,
+    waiting: HashSet<u64>,
+    answered: HashMap<u64, AudioEventResult>,
+}
+
+pub fn query_ring() -> (QueryClient, QueryServer) {
+    let (request_producer, request_consumer) = RingBuffer::new(QUERY_CAPACITY);
+    let (response_producer, response_consumer) = RingBuffer::new(QUERY_CAPACITY);
+    (
+        QueryClient {
+            requests: request_producer,
+            responses: response_consumer,
+            next_id: 0,
+            waiting: HashSet::new(),
+            answered: HashMap::new(),
+        },
+        QueryServer {
+            requests: request_consumer,
+            responses: response_producer,
+        },
+    )
+}
+
+impl QueryClient {
+    fn send(&mut self, event: AudioEvent) -> Option<u64> {
+        let id = self.next_id;
+        self.requests.push((id, event)).ok()?;
+        self.next_id += 1;
+        self.waiting.insert(id);
+        Some(id)
+    }
+
+    fn receive(&mut self, id: u64) -> Option<AudioEventResult> {
+        while let Ok((answered_id, result)) = self.responses.pop() {
+            // Answers to queries that timed out are dropped
+            if self.waiting.contains(&answered_id) {
+                self.answered.insert(answered_id, result);
+            }
+        }
+        let result = self.answered.remove(&id)?;
+        self.waiting.remove(&id);
+        Some(result)
+    }
+
+    fn give_up(&mut self, id: u64) {
+        self.waiting.remove(&id);
+        self.answered.remove(&id);
+    }
+}
+
+/// Ask the audio thread to handle `event`. None if the query ring is full or
+/// there was no answer in time, e.g. because the stream is paused
+pub fn ask(client: &Mutex<QueryClient>, event: AudioEvent) -> Option<AudioEventResult> {
+    let id = client.lock().ok()?.send(event)?;
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    loop {
+        {
+            let mut client = client.lock().ok()?;
+            if let Some(result) = client.receive(id) {
+                return Some(result);
+            }
+            if Instant::now() >= deadline {
+                client.give_up(id);
+                return None;
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
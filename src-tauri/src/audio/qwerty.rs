@@ -0,0 +1,140 @@
+// Computer keyboard note input, for playing without a MIDI keyboard. The
+// window forwards its key events (by physical key, as in KeyboardEvent.code)
+// and they're mapped to notes here, laid out like a piano: the home row is
+// the white keys from A, the row above the black keys
+use super::error::AudioError;
+use super::synthesis::AudioEvent;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// Note keys and their semitones above the octave's C
+const NOTE_KEYS: [(&str, i32); 17] = [
+    ("KeyA", 0),
+    ("KeyW", 1),
+    ("KeyS", 2),
+    ("KeyE", 3),
+    ("KeyD", 4),
+    ("KeyF", 5),
+    ("KeyT", 6),
+    ("KeyG", 7),
+    ("KeyY", 8),
+    ("KeyH", 9),
+    ("KeyU", 10),
+    ("KeyJ", 11),
+    ("KeyK", 12),
+    ("KeyO", 13),
+    ("KeyL", 14),
+    ("KeyP", 15),
+    ("Semicolon", 16),
+];
+const OCTAVE_DOWN_KEY: &str = "KeyZ";
+const OCTAVE_UP_KEY: &str = "KeyX";
+const VELOCITY_DOWN_KEY: &str = "KeyC";
+const VELOCITY_UP_KEY: &str = "KeyV";
+const VELOCITY_STEP: f32 = 0.1;
+const MIN_OCTAVE: i32 = 0;
+const MAX_OCTAVE: i32 = 8;
+/// Each key plays its own note id, clear of the UI's, pitch tracking's and
+/// the scheduler's
+const QWERTY_NOTE_ID_BASE: u32 = 0x7ffe_0000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QwertySettings {
+    /// Octave of the A key's C, in scientific pitch notation (4 = middle C)
+    pub octave: i32,
+    /// Velocity of every note, 0.0 to 1.0
+    pub velocity: f32,
+}
+
+impl QwertySettings {
+    const DEFAULT: QwertySettings = QwertySettings {
+        octave: 4,
+        velocity: 0.8,
+    };
+}
+
+impl Default for QwertySettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+struct QwertyState {
+    settings: QwertySettings,
+    /// Which note keys are down, indexed like `NOTE_KEYS`
+    held: [bool; NOTE_KEYS.len()],
+}
+
+static STATE: Mutex<QwertyState> = Mutex::new(QwertyState {
+    settings: QwertySettings::DEFAULT,
+    held: [false; NOTE_KEYS.len()],
+});
+
+pub fn set_qwerty_settings(settings: QwertySettings) {
+    STATE.lock().unwrap().settings = QwertySettings {
+        octave: settings.octave.clamp(MIN_OCTAVE, MAX_OCTAVE),
+        velocity: settings.velocity.clamp(0.0, 1.0),
+    };
+}
+
+pub fn qwerty_settings() -> QwertySettings {
+    STATE.lock().unwrap().settings
+}
+
+fn note_frequency(octave: i32, semitone: i32) -> f32 {
+    let note = 12 * (octave + 1) + semitone;
+    440.0 * 2.0f32.powf((note - 69) as f32 / 12.0)
+}
+
+/// Act on a key going down or up. `code` is the physical key, e.g. "KeyA".
+/// Returns whether the key is one of ours, so the window knows whether to
+/// let it through. Key repeat is ignored; a note keeps its pitch until its
+/// key comes up, even if the octave changes meanwhile
+pub fn qwerty_key(code: &str, pressed: bool) -> Result<bool, AudioError> {
+    let mut state = STATE.lock().unwrap();
+    if let Some(index) = NOTE_KEYS.iter().position(|(key, _)| *key == code) {
+        if state.held[index] == pressed {
+            return Ok(true);
+        }
+        state.held[index] = pressed;
+        let note_id = QWERTY_NOTE_ID_BASE + index as u32;
+        let event = if pressed {
+            AudioEvent::PlayNote {
+                note_id,
+                frequency: note_frequency(state.settings.octave, NOTE_KEYS[index].1),
+                velocity: state.settings.velocity,
+            }
+        } else {
+            AudioEvent::NoteOff { note_id }
+        };
+        drop(state);
+        super::queue_audio_event(event)?;
+        return Ok(true);
+    }
+
+    let settings = &mut state.settings;
+    match code {
+        OCTAVE_DOWN_KEY | OCTAVE_UP_KEY | VELOCITY_DOWN_KEY | VELOCITY_UP_KEY if !pressed => {}
+        OCTAVE_DOWN_KEY => settings.octave = (settings.octave - 1).max(MIN_OCTAVE),
+        OCTAVE_UP_KEY => settings.octave = (settings.octave + 1).min(MAX_OCTAVE),
+        VELOCITY_DOWN_KEY => settings.velocity = (settings.velocity - VELOCITY_STEP).max(0.0),
+        VELOCITY_UP_KEY => settings.velocity = (settings.velocity + VELOCITY_STEP).min(1.0),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Release every note held from the keyboard. For when the window loses
+/// focus, as the key up events then go elsewhere
+pub fn qwerty_release_all() -> Result<(), AudioError> {
+    let mut state = STATE.lock().unwrap();
+    let held = std::mem::replace(&mut state.held, [false; NOTE_KEYS.len()]);
+    drop(state);
+    for (index, _) in held.iter().enumerate().filter(|(_, &down)| down) {
+        super::queue_audio_event(AudioEvent::NoteOff {
+            note_id: QWERTY_NOTE_ID_BASE + index as u32,
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+// Live recording of the master output to WAV. The audio thread pushes every
+// output frame into a lock-free ring while a recording is running; a writer
+// thread drains it and keeps the frames between the punch-in and punch-out
+// points, counted in beats from the end of the count-in. Compressed takes
+// are recorded to a temporary WAV and encoded when they finish
+use super::encode::{transcode_wav, ExportFormat};
+use super::wav::{StereoWavWriter, WavFormat};
+use rtrb::{Consumer, Producer, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// About a second and a half at 48kHz, plenty for the writer to keep up
+const TAP_CAPACITY: usize = 65536;
+const WRITER_INTERVAL: Duration = Duration::from_millis(20);
+/// How often `recording-status` events are sent while nothing else changes
+const STATUS_INTERVAL: Duration = Duration::from_millis(250);
+/// Takes are named "<prefix> <n>.wav", with n the first number not yet used
+const TAKE_PREFIX: &str = "Harphonium take";
+
+/// The audio thread's end of the recording ring
+pub struct RecordingTap {
+    producer: Producer<(f32, f32)>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl RecordingTap {
+    /// Never blocks; drops the frame if the writer has fallen behind
+    pub fn push(&mut self, left: f32, right: f32) {
+        if self.producer.push((left, right)).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// True once the writer has finished, so the tap can be dropped
+    pub fn is_finished(&self) -> bool {
+        self.producer.is_abandoned()
+    }
+}
+
+/// How to record. Punch points are in beats from the end of the count-in,
+/// at the tempo when recording starts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingOptions {
+    /// Where takes are saved
+    pub directory: String,
+    /// Start keeping audio here, or straight after the count-in if None
+    pub punch_in: Option<f64>,
+    /// Stop here, or when `stop_recording` is called if None
+    pub punch_out: Option<f64>,
+    /// Beats of metronome clicks before the transport starts
+    pub count_in_beats: u32,
+    /// Start the sequencer when the count-in ends
+    pub play_sequencer: bool,
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingState {
+    Idle,
+    CountIn,
+    /// Waiting for the punch-in point
+    Armed,
+    Recording,
+    /// The take has been written to `path`
+    Finished,
+    /// The take couldn't be written
+    Failed,
+}
+
+/// Payload of `recording-status` events
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingStatus {
+    pub state: RecordingState,
+    /// The take's file
+    pub path: Option<String>,
+    /// Beats since the end of the count-in. Negative during the count-in
+    pub beat: f64,
+    /// Length of the take so far
+    pub seconds: f64,
+    /// Frames lost because the writer fell behind
+    pub dropped_frames: u64,
+    pub error: Option<String>,
+}
+
+impl RecordingStatus {
+    const IDLE: RecordingStatus = RecordingStatus {
+        state: RecordingState::Idle,
+        path: None,
+        beat: 0.0,
+        seconds: 0.0,
+        dropped_frames: 0,
+        error: None,
+    };
+}
+
+static STATUS: Mutex<RecordingStatus> = Mutex::new(RecordingStatus::IDLE);
+/// Tells the writer of the recording in progress to stop
+static STOP: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+pub fn recording_status() -> RecordingStatus {
+    STATUS.lock().unwrap().clone()
+}
+
+/// Ask the recording in progress to finish. The writer saves what it has
+/// and sends a final status
+pub fn stop_recording() -> Result<(), String> {
+    let stop = STOP
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| "Not recording".to_string())?;
+    stop.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// The first "<prefix> <n>.<extension>" in `directory` that doesn't exist yet
+pub fn next_take_path(directory: &Path, format: ExportFormat) -> PathBuf {
+    (1..)
+        .map(|n| directory.join(format!("{} {}.{}", TAKE_PREFIX, n, format.extension())))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Where a take goes, and how it's encoded when it's finished
+struct TakeFile {
+    path: PathBuf,
+    format: ExportFormat,
+}
+
+impl TakeFile {
+    /// Where the frames are written as they arrive
+    fn wav_path(&self) -> PathBuf {
+        match self.format.wav_format() {
+            Some(_) => self.path.clone(),
+            None => self.path.with_extension("tmp.wav"),
+        }
+    }
+
+    fn finish(&self, writer: StereoWavWriter) -> Result<(), String> {
+        writer.finalize()?;
+        if self.format.wav_format().is_none() {
+            let wav_path = self.wav_path();
+            let result = transcode_wav(&wav_path, &self.path, self.format);
+            let _ = std::fs::remove_file(&wav_path);
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/// Where one recording's transport positions fall on the synth's frame clock
+#[derive(Debug, Clone, Copy)]
+pub struct RecordingPlan {
+    /// Frame the tap's first frame was rendered at
+    pub start_frame: u64,
+    /// Frame the count-in ends and the transport starts, beat 0
+    pub origin_frame: u64,
+    pub frames_per_beat: f64,
+    pub punch_in_frame: u64,
+    pub punch_out_frame: Option<u64>,
+}
+
+impl RecordingPlan {
+    pub fn new(options: &RecordingOptions, start_frame: u64, tempo: f32, sample_rate: f32) -> Self {
+        let frames_per_beat = 60.0 / tempo as f64 * sample_rate as f64;
+        let origin_frame = start_frame + (options.count_in_beats as f64 * frames_per_beat) as u64;
+        let at_beat = |beat: f64| origin_frame + (beat.max(0.0) * frames_per_beat) as u64;
+        RecordingPlan {
+            start_frame,
+            origin_frame,
+            frames_per_beat,
+            punch_in_frame: at_beat(options.punch_in.unwrap_or(0.0)),
+            punch_out_frame: options.punch_out.map(at_beat),
+        }
+    }
+
+    fn beat(&self, frame: u64) -> f64 {
+        (frame as f64 - self.origin_frame as f64) / self.frames_per_beat
+    }
+
+    fn state(&self, frame: u64) -> RecordingState {
+        if frame < self.origin_frame {
+            RecordingState::CountIn
+        } else if frame < self.punch_in_frame {
+            RecordingState::Armed
+        } else {
+            RecordingState::Recording
+        }
+    }
+}
+
+fn publish(status: RecordingStatus) {
+    *STATUS.lock().unwrap() = status.clone();
+    super::emit_event("recording-status", status);
+}
+
+/// Create a take file at `path` and start the writer thread. Returns the
+/// audio thread's end of the ring, to hand to the synth
+pub fn start_recording(
+    path: PathBuf,
+    format: ExportFormat,
+    sample_rate: u32,
+    plan: RecordingPlan,
+) -> Result<RecordingTap, String> {
+    let mut stop = STOP.lock().unwrap();
+    if stop.is_some() {
+        return Err("Already recording".to_string());
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let take = TakeFile { path, format };
+    let writer = StereoWavWriter::create(
+        &take.wav_path().to_string_lossy(),
+        sample_rate,
+        format.wav_format().unwrap_or(WavFormat::Float32),
+    )?;
+    let (producer, consumer) = RingBuffer::new(TAP_CAPACITY);
+    let dropped = Arc::new(AtomicU64::new(0));
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    *stop = Some(stop_flag.clone());
+    drop(stop);
+
+    info!("⏺️ Recording to {}", take.path.display());
+    let writer_dropped = dropped.clone();
+    std::thread::spawn(move || {
+        let status = run_writer(
+            consumer,
+            writer,
+            &take,
+            sample_rate,
+            plan,
+            &stop_flag,
+            &writer_dropped,
+        );
+        // Punched out by itself, so nobody called stop_recording
+        let mut stop = STOP.lock().unwrap();
+        if stop.as_ref().map_or(false, |s| Arc::ptr_eq(s, &stop_flag)) {
+            *stop = None;
+        }
+        drop(stop);
+        publish(status);
+    });
+    Ok(RecordingTap { producer, dropped })
+}
+
+fn run_writer(
+    mut consumer: Consumer<(f32, f32)>,
+    mut writer: StereoWavWriter,
+    take: &TakeFile,
+    sample_rate: u32,
+    plan: RecordingPlan,
+    stop: &AtomicBool,
+    dropped: &AtomicU64,
+) -> RecordingStatus {
+    let path = take.path.to_string_lossy();
+    let mut status = RecordingStatus {
+        state: RecordingState::CountIn,
+        path: Some(path.to_string()),
+        ..RecordingStatus::IDLE
+    };
+    let mut frame = plan.start_frame;
+    let mut written: u64 = 0;
+    let mut last_status = Instant::now();
+    let mut result = Ok(());
+    'writing: while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(WRITER_INTERVAL);
+        let available = consumer.slots();
+        if let Ok(chunk) = consumer.read_chunk(available) {
+            for (left, right) in chunk {
+                if plan.punch_out_frame.map_or(false, |out| frame >= out) {
+                    break 'writing;
+                }
+                if frame >= plan.punch_in_frame {
+                    result = writer.write_frame(left, right);
+                    if result.is_err() {
+                        break 'writing;
+                    }
+                    written += 1;
+                }
+                frame += 1;
+            }
+        }
+
+        status.dropped_frames = dropped.load(Ordering::Relaxed);
+        let state = plan.state(frame);
+        if state != status.state || last_status.elapsed() >= STATUS_INTERVAL {
+            status.state = state;
+            status.beat = plan.beat(frame);
+            status.seconds = written as f64 / sample_rate as f64;
+            publish(status.clone());
+            last_status = Instant::now();
+        }
+    }
+    // Dropping the consumer tells the synth to let go of the tap
+    drop(consumer);
+
+    status.beat = plan.beat(frame);
+    status.seconds = written as f64 / sample_rate as f64;
+    status.dropped_frames = dropped.load(Ordering::Relaxed);
+    match result.and_then(|_| take.finish(writer)) {
+        Ok(()) => {
+            info!("⏹️ Recorded {:.1}s to {}", status.seconds, path);
+            status.state = RecordingState::Finished;
+        }
+        Err(e) => {
+            error!("❌ Recording to {} failed: {}", path, e);
+            status.state = RecordingState::Failed;
+            status.error = Some(e);
+        }
+    }
+    status
+}
@@ -0,0 +1,51 @@
+// Sample rate conversion between the synth and the output device, for when
+// the device can't run at the rate the synth is set to. The synth carries on
+// at its own rate (so its state, delay times and tuning are untouched) and
+// its output is interpolated to the device's rate
+
+/// Cubic (Hermite) interpolating resampler for stereo frames. Cheap enough
+/// for the audio thread, and clean enough for small ratios like 44.1k/48k
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    /// Input frames per output frame
+    step: f64,
+    /// Position between `history[1]` and `history[2]`, 0.0 to 1.0
+    position: f64,
+    /// The last four input frames
+    history: [(f32, f32); 4],
+}
+
+impl Resampler {
+    pub fn new(input_rate: f32, output_rate: f32) -> Self {
+        Resampler {
+            step: input_rate as f64 / output_rate as f64,
+            position: 0.0,
+            history: [(0.0, 0.0); 4],
+        }
+    }
+
+    /// The next output frame, calling `pull` for as many input frames as it takes
+    pub fn next(&mut self, mut pull: impl FnMut() -> (f32, f32)) -> (f32, f32) {
+        self.position += self.step;
+        while self.position >= 1.0 {
+            self.position -= 1.0;
+            self.history.rotate_left(1);
+            self.history[3] = pull();
+        }
+        let t = self.position as f32;
+        let [a, b, c, d] = self.history;
+        (
+            hermite(a.0, b.0, c.0, d.0, t),
+            hermite(a.1, b.1, c.1, d.1, t),
+        )
+    }
+}
+
+/// Catmull-Rom interpolation between `b` and `c` at `t`
+fn hermite(a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+    let c0 = b;
+    let c1 = 0.5 * (c - a);
+    let c2 = a - 2.5 * b + 2.0 * c - 0.5 * d;
+    let c3 = 0.5 * (d - a) + 1.5 * (b - c);
+    ((c3 * t + c2) * t + c1) * t + c0
+}
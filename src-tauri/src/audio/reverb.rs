@@ -0,0 +1,137 @@
+// Reverb types for the slot reverb. Each is a voicing of FunDSP's stereo
+// reverb with a size, time and damping to suit, so picking "hall" gets a
+// hall rather than leaving the numbers to the player
+use super::shifter::PitchShifter;
+use fundsp::hacker::{
+    delay, feedback2, highpass_hz, reverb_stereo, An, AudioNode, AudioUnit, Frame, U2,
+};
+
+/// Hall pre-delay, the gap before the first reflections, in seconds
+const HALL_PREDELAY: f32 = 0.025;
+/// Plates don't hold much low end
+const PLATE_HIGHPASS: f32 = 200.0;
+/// How much of the octave-up signal goes back into the shimmer reverb
+const SHIMMER_FEEDBACK: f32 = 0.35;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReverbType {
+    /// The plain reverb: small and close by default
+    #[default]
+    Room,
+    /// Large and long, with a pre-delay
+    Hall,
+    /// Bright and dense, light on the bass
+    Plate,
+    /// A hall feeding an octave up back into itself, so tails rise and glitter
+    Shimmer,
+}
+
+impl ReverbType {
+    pub const ALL: [ReverbType; 4] = [
+        ReverbType::Room,
+        ReverbType::Hall,
+        ReverbType::Plate,
+        ReverbType::Shimmer,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReverbType::Room => "room",
+            ReverbType::Hall => "hall",
+            ReverbType::Plate => "plate",
+            ReverbType::Shimmer => "shimmer",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "room" => Some(ReverbType::Room),
+            "hall" => Some(ReverbType::Hall),
+            "plate" => Some(ReverbType::Plate),
+            "shimmer" => Some(ReverbType::Shimmer),
+            _ => None,
+        }
+    }
+
+    /// As stored in the reverb's "type" parameter
+    pub fn value(&self) -> f32 {
+        ReverbType::ALL.iter().position(|t| t == self).unwrap_or(0) as f32
+    }
+
+    pub fn from_value(value: f32) -> Self {
+        ReverbType::ALL
+            .get(value.round().max(0.0) as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Size (metres), time (seconds) and damping the type starts with
+    pub fn settings(&self) -> (f32, f32, f32) {
+        match self {
+            ReverbType::Room => (8.0, 0.8, 0.6),
+            ReverbType::Hall => (25.0, 3.5, 0.5),
+            ReverbType::Plate => (12.0, 2.0, 0.15),
+            ReverbType::Shimmer => (25.0, 6.0, 0.4),
+        }
+    }
+}
+
+/// A stereo reverb of type `reverb_type`, all wet
+pub fn reverb_of_type(
+    reverb_type: ReverbType,
+    size: f32,
+    time: f32,
+    damping: f32,
+) -> Box<dyn AudioUnit + Send> {
+    let reverb = || reverb_stereo(size as f64, time as f64, damping as f64);
+    match reverb_type {
+        ReverbType::Room => Box::new(reverb()),
+        ReverbType::Hall => Box::new((delay(HALL_PREDELAY) | delay(HALL_PREDELAY)) >> reverb()),
+        ReverbType::Plate => Box::new(
+            (highpass_hz(PLATE_HIGHPASS, 0.7) | highpass_hz(PLATE_HIGHPASS, 0.7)) >> reverb(),
+        ),
+        ReverbType::Shimmer => Box::new(feedback2(reverb(), An(ShimmerNode::default()))),
+    }
+}
+
+/// The shimmer's feedback path: an octave up, softly limited so the loop
+/// can't run away
+#[derive(Clone)]
+pub struct ShimmerNode {
+    shifters: [PitchShifter; 2],
+}
+
+impl Default for ShimmerNode {
+    fn default() -> Self {
+        ShimmerNode {
+            shifters: [PitchShifter::new(48000.0), PitchShifter::new(48000.0)],
+        }
+    }
+}
+
+impl AudioNode for ShimmerNode {
+    const ID: u64 = 0x4861_7270_5368_696d;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.shifters.iter_mut().for_each(PitchShifter::reset);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.shifters = [
+            PitchShifter::new(sample_rate as f32),
+            PitchShifter::new(sample_rate as f32),
+        ];
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let mut output = [0.0; 2];
+        for channel in 0..2 {
+            let shifted = self.shifters[channel].tick(input[channel], 2.0);
+            output[channel] = shifted.tanh() * SHIMMER_FEEDBACK;
+        }
+        output.into()
+    }
+}
@@ -0,0 +1,133 @@
+// Sample playback: a WAV file pitched across the keyboard, used in place of
+// a voice's oscillator so it goes through the same envelope and effects
+use fundsp::hacker::{shared, AudioNode, Frame, U1};
+use std::sync::Arc;
+
+/// A loaded sample, mixed down to mono
+pub struct Sample {
+    pub data: Vec<f32>,
+    pub sample_rate: f32,
+    /// Pitch the sample was recorded at; played back unchanged at this frequency
+    pub root_frequency: f32,
+    /// Loop region in frames (start, end). Without one the sample plays once
+    pub loop_points: Option<(usize, usize)>,
+}
+
+impl Sample {
+    /// Load a WAV file
+    pub fn load_wav(
+        path: &str,
+        root_frequency: f32,
+        loop_points: Option<(usize, usize)>,
+    ) -> Result<Self, String> {
+        let mut reader =
+            hound::WavReader::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        let spec = reader.spec();
+        let samples: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader
+                .samples::<f32>()
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 * scale))
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| e.to_string())?
+            }
+        };
+
+        let channels = spec.channels.max(1) as usize;
+        let data: Vec<f32> = samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        if data.is_empty() {
+            return Err(format!("{} contains no audio", path));
+        }
+
+        if let Some((start, end)) = loop_points {
+            if start >= end || end > data.len() {
+                return Err(format!(
+                    "Loop points {}..{} don't fit in a {} frame sample",
+                    start,
+                    end,
+                    data.len()
+                ));
+            }
+        }
+
+        Ok(Sample {
+            data,
+            sample_rate: spec.sample_rate as f32,
+            root_frequency: root_frequency.max(1.0),
+            loop_points,
+        })
+    }
+}
+
+/// Plays a sample at the pitch given on its input, with linear interpolation.
+/// Restarts from the beginning whenever the voice bumps its trigger
+#[derive(Clone)]
+pub struct SamplerNode {
+    sample: Arc<Sample>,
+    trigger: shared::Shared,
+    last_trigger: f32,
+    position: f64,
+    sample_rate: f64,
+}
+
+impl SamplerNode {
+    pub fn new(sample: Arc<Sample>, trigger: shared::Shared) -> Self {
+        let last_trigger = trigger.value();
+        SamplerNode {
+            sample,
+            trigger,
+            last_trigger,
+            // Start past the end so a voice stays silent until it's played
+            position: f64::MAX,
+            sample_rate: 48000.0,
+        }
+    }
+}
+
+impl AudioNode for SamplerNode {
+    const ID: u64 = 0x4861_7270_5361_6d70;
+    type Inputs = U1;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.position = f64::MAX;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let trigger = self.trigger.value();
+        if trigger != self.last_trigger {
+            self.last_trigger = trigger;
+            self.position = 0.0;
+        }
+
+        let data = &self.sample.data;
+        if let Some((start, end)) = self.sample.loop_points {
+            if self.position >= end as f64 && self.position != f64::MAX {
+                self.position = start as f64 + (self.position - end as f64) % (end - start) as f64;
+            }
+        }
+        let index = self.position as usize;
+        if index >= data.len() - 1 {
+            return [0.0].into();
+        }
+        let fraction = (self.position - index as f64) as f32;
+        let output = data[index] + (data[index + 1] - data[index]) * fraction;
+
+        let rate = (input[0] / self.sample.root_frequency) as f64 * self.sample.sample_rate as f64
+            / self.sample_rate;
+        self.position += rate.max(0.0);
+        [output].into()
+    }
+}
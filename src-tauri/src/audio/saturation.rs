@@ -0,0 +1,31 @@
+// Soft clipping ahead of the limiter, so hot patches saturate smoothly
+// rather than hitting the hard clamp at the end of the chain
+use fundsp::hacker::{shared, AudioNode, Frame, U2};
+
+/// Stereo tanh saturation. `drive` is a linear gain into the curve;
+/// `enabled` below 0.5 passes the signal through untouched
+#[derive(Clone)]
+pub struct SoftClipNode {
+    drive: shared::Shared,
+    enabled: shared::Shared,
+}
+
+impl SoftClipNode {
+    pub fn new(drive: shared::Shared, enabled: shared::Shared) -> Self {
+        SoftClipNode { drive, enabled }
+    }
+}
+
+impl AudioNode for SoftClipNode {
+    const ID: u64 = 0x4861_7270_436c_6970;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        if self.enabled.value() < 0.5 {
+            return [input[0], input[1]].into();
+        }
+        let drive = self.drive.value();
+        [(input[0] * drive).tanh(), (input[1] * drive).tanh()].into()
+    }
+}
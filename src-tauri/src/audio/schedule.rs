@@ -0,0 +1,136 @@
+// Notes scheduled ahead on the synth's own frame clock, so timing doesn't
+// depend on when IPC calls happen to arrive. Used for strums / glissandos
+// and timestamped events
+use super::synthesis::AudioEvent;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::AtomicU64;
+
+/// Strum and scheduled notes get ids from here up, clear of the ids the UI hands out
+const STRUM_NOTE_ID_BASE: u32 = 0x8000_0000;
+
+/// Seconds the live synth has played for, as f64 bits. The transport clock
+/// `schedule_note` start times are on
+pub static TRANSPORT_CLOCK: AtomicU64 = AtomicU64::new(0);
+
+/// How a strum is played
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StrumSettings {
+    /// Time between successive notes
+    pub interval_ms: f32,
+    /// Velocity of the first and last notes, interpolated in between
+    pub velocity_start: f32,
+    pub velocity_end: f32,
+    /// How long each note is held before it's released
+    pub hold_ms: f32,
+}
+
+impl Default for StrumSettings {
+    fn default() -> Self {
+        StrumSettings {
+            interval_ms: 30.0,
+            velocity_start: 0.8,
+            velocity_end: 0.8,
+            hold_ms: 400.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ScheduledAction {
+    NoteOn {
+        note_id: u32,
+        frequency: f32,
+        velocity: f32,
+    },
+    NoteOff {
+        note_id: u32,
+    },
+    /// Start a note on a voice that has finished fading out after being stolen
+    StolenNoteOn {
+        voice: usize,
+        note_id: u32,
+        frequency: f32,
+        velocity: f32,
+    },
+    /// An event sent with a timestamp, see `AudioEvent::At`
+    Event(Box<AudioEvent>),
+    /// Play the sequencer's next step, unless it's been stopped or
+    /// restarted (changing its generation) since this was scheduled
+    SequencerStep {
+        generation: u32,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Scheduled {
+    frame: u64,
+    action: ScheduledAction,
+}
+
+/// Pending actions, kept sorted by frame
+#[derive(Default)]
+pub struct Scheduler {
+    pending: Vec<Scheduled>,
+    next_strum_id: u32,
+}
+
+impl Scheduler {
+    pub fn schedule(&mut self, frame: u64, action: ScheduledAction) {
+        let index = self.pending.partition_point(|s| s.frame <= frame);
+        self.pending.insert(index, Scheduled { frame, action });
+    }
+
+    /// Queue the notes of a strum starting at `now`, in the order given
+    pub fn strum(
+        &mut self,
+        now: u64,
+        sample_rate: f32,
+        frequencies: &[f32],
+        settings: &StrumSettings,
+    ) {
+        let interval = (settings.interval_ms.max(0.0) / 1000.0 * sample_rate) as u64;
+        let hold = (settings.hold_ms.max(0.0) / 1000.0 * sample_rate) as u64;
+        let last = frequencies.len().saturating_sub(1).max(1) as f32;
+        for (i, &frequency) in frequencies.iter().enumerate() {
+            let t = i as f32 / last;
+            let velocity =
+                settings.velocity_start + (settings.velocity_end - settings.velocity_start) * t;
+            self.note(now + interval * i as u64, hold, frequency, velocity);
+        }
+    }
+
+    /// Queue a note starting at frame `start`, released `length` frames later
+    pub fn note(&mut self, start: u64, length: u64, frequency: f32, velocity: f32) {
+        let note_id = STRUM_NOTE_ID_BASE + self.next_strum_id;
+        self.next_strum_id = (self.next_strum_id + 1) % STRUM_NOTE_ID_BASE;
+        self.schedule(
+            start,
+            ScheduledAction::NoteOn {
+                note_id,
+                frequency,
+                velocity,
+            },
+        );
+        self.schedule(start + length, ScheduledAction::NoteOff { note_id });
+    }
+
+    /// Frame of the next pending action
+    pub fn next_frame(&self) -> Option<u64> {
+        self.pending.first().map(|s| s.frame)
+    }
+
+    /// Take the next action due at or before `now`
+    pub fn pop_due(&mut self, now: u64) -> Option<ScheduledAction> {
+        if self.next_frame()? <= now {
+            Some(self.pending.remove(0).action)
+        } else {
+            None
+        }
+    }
+
+    /// Drop everything pending
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
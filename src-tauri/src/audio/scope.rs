@@ -0,0 +1,86 @@
+// Oscilloscope tap on the master output. The audio thread pushes samples
+// into a lock-free ring; a UI thread drains it into a short history and
+// sends the frontend a downsampled snapshot at display rate.
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Room for a couple of display frames of samples at 48kHz. If the reader
+/// falls behind, new samples are dropped rather than blocking the audio thread
+const TAP_CAPACITY: usize = 8192;
+/// Recent output kept for display and analysis
+pub const HISTORY_SIZE: usize = 4096;
+/// Samples shown in one oscilloscope frame
+const WINDOW_SIZE: usize = 1024;
+/// Points sent to the frontend per frame
+const SCOPE_POINTS: usize = 256;
+/// About 30 frames per second
+const SCOPE_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Most recent output, oldest sample first. Written by the scope thread only
+static HISTORY: Mutex<Vec<f32>> = Mutex::new(Vec::new());
+
+/// The audio thread's end of the tap
+pub struct OutputTap {
+    producer: Producer<f32>,
+}
+
+impl OutputTap {
+    /// Never blocks; drops the sample if the reader has fallen behind
+    pub fn push(&mut self, sample: f32) {
+        let _ = self.producer.push(sample);
+    }
+}
+
+/// Create a tap, returning the audio thread's end and the reader's end
+pub fn output_tap() -> (OutputTap, Consumer<f32>) {
+    let (producer, consumer) = RingBuffer::new(TAP_CAPACITY);
+    (OutputTap { producer }, consumer)
+}
+
+/// Copy of the most recent output, oldest sample first
+pub fn recent_samples() -> Vec<f32> {
+    HISTORY.lock().unwrap().clone()
+}
+
+/// Move whatever the audio thread has produced into the history. Returns false if there was nothing new
+fn drain_into_history(consumer: &mut Consumer<f32>) -> bool {
+    let available = consumer.slots();
+    if available == 0 {
+        return false;
+    }
+    let mut history = HISTORY.lock().unwrap();
+    if let Ok(chunk) = consumer.read_chunk(available) {
+        history.extend(chunk);
+    }
+    let excess = history.len().saturating_sub(HISTORY_SIZE);
+    history.drain(..excess);
+    true
+}
+
+/// Downsample the latest window for display, starting at a rising zero
+/// crossing where there is one so periodic waveforms hold still
+pub fn scope_frame() -> Vec<f32> {
+    let history = HISTORY.lock().unwrap();
+    if history.len() < WINDOW_SIZE {
+        return Vec::new();
+    }
+    let latest_start = history.len() - WINDOW_SIZE;
+    let start = (1..=latest_start)
+        .rev()
+        .find(|&i| history[i - 1] < 0.0 && history[i] >= 0.0)
+        .unwrap_or(latest_start);
+    let window = &history[start..start + WINDOW_SIZE];
+    let step = WINDOW_SIZE / SCOPE_POINTS;
+    window.iter().step_by(step).copied().collect()
+}
+
+/// Drain the tap and emit `oscilloscope` events while new audio is arriving
+pub fn start_scope_reader(mut consumer: Consumer<f32>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SCOPE_INTERVAL);
+        if drain_into_history(&mut consumer) {
+            super::emit_event("oscilloscope", scope_frame());
+        }
+    });
+}
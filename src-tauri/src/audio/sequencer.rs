@@ -0,0 +1,260 @@
+// Step sequencer: patterns of steps played at the synth's tempo, chained
+// one after another or arranged into a song. The arrangement is edited on
+// the UI side and sent to the synth whole
+use serde::{Deserialize, Serialize};
+
+/// Most steps a pattern can have
+pub const MAX_PATTERN_STEPS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Step {
+    /// Note to play, or None for a rest
+    pub frequency: Option<f32>,
+    pub velocity: f32,
+    /// How much of the step the note is held for (0.0 to 1.0)
+    pub gate: f32,
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Step {
+            frequency: None,
+            velocity: 0.8,
+            gate: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pattern {
+    pub name: String,
+    pub steps: Vec<Step>,
+    pub steps_per_beat: u32,
+    /// Pattern to chain to when this one ends, or None to loop it.
+    /// Ignored in song mode
+    #[serde(default)]
+    pub next: Option<usize>,
+}
+
+/// One line of a song: play `pattern` `repeats` times
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SongEntry {
+    pub pattern: usize,
+    pub repeats: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Arrangement {
+    pub patterns: Vec<Pattern>,
+    pub song: Vec<SongEntry>,
+    /// Play the song from the top and stop at its end, rather than
+    /// following pattern chains
+    pub song_mode: bool,
+}
+
+impl Arrangement {
+    pub const fn new() -> Self {
+        Arrangement {
+            patterns: Vec::new(),
+            song: Vec::new(),
+            song_mode: false,
+        }
+    }
+
+    fn check_pattern(&self, pattern: usize) -> Result<(), String> {
+        if pattern < self.patterns.len() {
+            Ok(())
+        } else {
+            Err(format!("No pattern {}", pattern))
+        }
+    }
+
+    /// Add an empty pattern of `length` steps, returning its index
+    pub fn add_pattern(
+        &mut self,
+        name: String,
+        length: usize,
+        steps_per_beat: u32,
+    ) -> Result<usize, String> {
+        if length == 0 || length > MAX_PATTERN_STEPS {
+            return Err(format!(
+                "Patterns have 1 to {} steps, not {}",
+                MAX_PATTERN_STEPS, length
+            ));
+        }
+        self.patterns.push(Pattern {
+            name,
+            steps: vec![Step::default(); length],
+            steps_per_beat: steps_per_beat.max(1),
+            next: None,
+        });
+        Ok(self.patterns.len() - 1)
+    }
+
+    /// Remove a pattern, along with song entries and chains that point at it
+    pub fn remove_pattern(&mut self, pattern: usize) -> Result<(), String> {
+        self.check_pattern(pattern)?;
+        self.patterns.remove(pattern);
+        // Indices past the removed pattern shift down one
+        let renumber = |index: usize| {
+            if index > pattern {
+                Some(index - 1)
+            } else if index == pattern {
+                None
+            } else {
+                Some(index)
+            }
+        };
+        for p in self.patterns.iter_mut() {
+            p.next = p.next.and_then(renumber);
+        }
+        self.song.retain(|entry| entry.pattern != pattern);
+        for entry in self.song.iter_mut() {
+            entry.pattern = renumber(entry.pattern).unwrap_or(0);
+        }
+        Ok(())
+    }
+
+    /// Change a pattern's length, adding rests or dropping steps at the end
+    pub fn set_pattern_length(&mut self, pattern: usize, length: usize) -> Result<(), String> {
+        self.check_pattern(pattern)?;
+        if length == 0 || length > MAX_PATTERN_STEPS {
+            return Err(format!(
+                "Patterns have 1 to {} steps, not {}",
+                MAX_PATTERN_STEPS, length
+            ));
+        }
+        self.patterns[pattern].steps.resize(length, Step::default());
+        Ok(())
+    }
+
+    pub fn set_step(&mut self, pattern: usize, step: usize, value: Step) -> Result<(), String> {
+        self.check_pattern(pattern)?;
+        let steps = &mut self.patterns[pattern].steps;
+        let slot = steps
+            .get_mut(step)
+            .ok_or_else(|| format!("Pattern {} has no step {}", pattern, step))?;
+        *slot = Step {
+            frequency: value.frequency,
+            velocity: value.velocity.clamp(0.0, 1.0),
+            gate: value.gate.clamp(0.0, 1.0),
+        };
+        Ok(())
+    }
+
+    /// Chain `pattern` to `next` (None loops it)
+    pub fn set_next(&mut self, pattern: usize, next: Option<usize>) -> Result<(), String> {
+        self.check_pattern(pattern)?;
+        if let Some(next) = next {
+            self.check_pattern(next)?;
+        }
+        self.patterns[pattern].next = next;
+        Ok(())
+    }
+
+    /// Replace everything, e.g. with a loaded project, if `arrangement` is
+    /// one the edits above could have made
+    pub fn replace(&mut self, arrangement: Arrangement) -> Result<(), String> {
+        for (i, pattern) in arrangement.patterns.iter().enumerate() {
+            if pattern.steps.is_empty() || pattern.steps.len() > MAX_PATTERN_STEPS {
+                return Err(format!(
+                    "Patterns have 1 to {} steps, pattern {} has {}",
+                    MAX_PATTERN_STEPS,
+                    i,
+                    pattern.steps.len()
+                ));
+            }
+            if pattern.steps_per_beat == 0 {
+                return Err(format!("Pattern {} has no steps per beat", i));
+            }
+            if let Some(next) = pattern.next {
+                arrangement.check_pattern(next)?;
+            }
+        }
+        for entry in &arrangement.song {
+            arrangement.check_pattern(entry.pattern)?;
+        }
+        *self = arrangement;
+        Ok(())
+    }
+
+    pub fn set_song(&mut self, song: Vec<SongEntry>) -> Result<(), String> {
+        for entry in &song {
+            self.check_pattern(entry.pattern)?;
+        }
+        self.song = song;
+        Ok(())
+    }
+}
+
+/// Playback position in an arrangement. Timing is left to the synth, which
+/// asks for one step at a time
+#[derive(Default)]
+pub struct Sequencer {
+    arrangement: Arrangement,
+    /// Bumped on every start and stop, so steps scheduled by an earlier run are ignored
+    generation: u32,
+    pattern: usize,
+    step: usize,
+    /// Song mode: the entry playing, and how many times its pattern has finished
+    song_entry: usize,
+    repeat: u32,
+}
+
+impl Sequencer {
+    pub fn set_arrangement(&mut self, arrangement: Arrangement) {
+        self.arrangement = arrangement;
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Start from the top of the song in song mode, otherwise from `pattern`
+    pub fn start(&mut self, pattern: usize) {
+        self.generation = self.generation.wrapping_add(1);
+        self.step = 0;
+        self.song_entry = 0;
+        self.repeat = 0;
+        self.pattern = if self.arrangement.song_mode {
+            self.arrangement
+                .song
+                .first()
+                .map_or(0, |entry| entry.pattern)
+        } else {
+            pattern
+        };
+    }
+
+    pub fn stop(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// The step to play now, with its pattern's steps per beat, moving on to
+    /// the next. None at the end of the song, or if there's nothing to play
+    pub fn next_step(&mut self) -> Option<(Step, u32)> {
+        // Bounded, in case every pattern on the way is empty
+        for _ in 0..=self.arrangement.patterns.len() + self.arrangement.song.len() {
+            let pattern = self.arrangement.patterns.get(self.pattern)?;
+            if let Some(&step) = pattern.steps.get(self.step) {
+                self.step += 1;
+                return Some((step, pattern.steps_per_beat));
+            }
+            self.step = 0;
+            self.pattern = if self.arrangement.song_mode {
+                self.repeat += 1;
+                let entry = self.arrangement.song.get(self.song_entry)?;
+                if self.repeat >= entry.repeats {
+                    self.song_entry += 1;
+                    self.repeat = 0;
+                }
+                self.arrangement.song.get(self.song_entry)?.pattern
+            } else {
+                pattern.next.unwrap_or(self.pattern)
+            };
+        }
+        None
+    }
+}
@@ -0,0 +1,119 @@
+// Session auto-save: the synth's whole state, written to the app data
+// directory a moment after it stops changing and restored at startup, so the
+// app reopens where it was left. Unlike the project, nobody saves it by hand
+use super::sequencer::Arrangement;
+use super::state::SynthState;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::Manager;
+use tracing::{error, info, warn};
+
+/// Version of the session format. Bump this and add a migration in
+/// `load_session` when the layout changes
+const SESSION_VERSION: u32 = 1;
+const SESSION_FILE: &str = "session.json";
+/// How often changes are checked for. The session is saved once there have
+/// been none for a whole interval, so a slider drag is written once, when it stops
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bumped for every event that might change the session. Saving only looks
+/// at the synth (and so takes its lock) after this has moved
+static CHANGES: AtomicU64 = AtomicU64::new(0);
+
+/// Note that the session may have changed
+pub fn mark_changed() {
+    CHANGES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The saved session: the synth's state and the sequencer's arrangement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Session {
+    version: u32,
+    arrangement: Arrangement,
+    #[serde(flatten)]
+    state: SynthState,
+}
+
+fn session_path() -> Result<PathBuf, String> {
+    let app = super::APP_HANDLE
+        .get()
+        .ok_or_else(|| "No app handle".to_string())?;
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(SESSION_FILE))
+}
+
+/// The saved session, or None if nothing has been saved yet
+fn load_session() -> Result<Option<Session>, String> {
+    let path = session_path()?;
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let session: Session = serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    if session.version > SESSION_VERSION {
+        return Err(format!(
+            "Session version {} is newer than this app supports ({})",
+            session.version, SESSION_VERSION
+        ));
+    }
+    Ok(Some(session))
+}
+
+/// Write out the current session. Goes via a temporary file so a crash
+/// can't leave it half written
+fn save_session() -> Result<(), String> {
+    let arrangement = super::get_arrangement().map_err(|e| e.to_string())?;
+    let session = Session {
+        version: SESSION_VERSION,
+        arrangement,
+        state: SynthState::capture(&*super::lock_synth().map_err(|e| e.to_string())?),
+    };
+    let json = serde_json::to_string_pretty(&session).map_err(|e| e.to_string())?;
+    let path = session_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
+
+/// Restore the saved session, if there is one, then save the session
+/// whenever it changes. Call once the engine is running
+pub fn restore_and_autosave() {
+    match load_session() {
+        Ok(Some(session)) => match super::lock_synth() {
+            Ok(mut synth) => {
+                session.state.apply(&mut synth);
+                synth.set_arrangement(session.arrangement.clone());
+                *super::ARRANGEMENT.lock().unwrap() = session.arrangement;
+                info!("💾 Restored the last session");
+            }
+            Err(e) => error!("Failed to restore the last session: {}", e),
+        },
+        Ok(None) => {}
+        // Carry on with the defaults. The bad file is replaced at the next save
+        Err(e) => warn!("Not restoring the last session: {}", e),
+    }
+
+    std::thread::spawn(|| {
+        let mut saved = CHANGES.load(Ordering::Relaxed);
+        let mut previous = saved;
+        loop {
+            std::thread::sleep(AUTOSAVE_INTERVAL);
+            let changes = CHANGES.load(Ordering::Relaxed);
+            // Settled, and not saved yet
+            if changes == previous && changes != saved {
+                match save_session() {
+                    Ok(()) => saved = changes,
+                    Err(e) => error!("Failed to save the session: {}", e),
+                }
+            }
+            previous = changes;
+        }
+    });
+}
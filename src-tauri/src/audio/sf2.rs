@@ -0,0 +1,238 @@
+// SoundFont 2 loading. Just enough of the format to pull one sample out of
+// a preset for the sampler: the zone that covers middle C, with its root key
+// and loop points. Modulators and most generators are ignored
+use super::sampler::Sample;
+
+/// Key the playable zone is chosen around
+const CENTER_KEY: u8 = 60;
+
+// Generator operators we read
+const GEN_INSTRUMENT: u16 = 41;
+const GEN_KEY_RANGE: u16 = 43;
+const GEN_SAMPLE_ID: u16 = 53;
+const GEN_SAMPLE_MODES: u16 = 54;
+const GEN_OVERRIDING_ROOT_KEY: u16 = 58;
+
+/// Record sizes in the pdta chunk
+const PHDR_SIZE: usize = 38;
+const INST_SIZE: usize = 22;
+const BAG_SIZE: usize = 4;
+const GEN_SIZE: usize = 4;
+const SHDR_SIZE: usize = 46;
+
+/// A parsed SoundFont: the raw sample pool and the hydra tables
+pub struct SoundFont {
+    samples: Vec<i16>,
+    phdr: Vec<u8>,
+    pbag: Vec<u8>,
+    pgen: Vec<u8>,
+    inst: Vec<u8>,
+    ibag: Vec<u8>,
+    igen: Vec<u8>,
+    shdr: Vec<u8>,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn name_at(data: &[u8], offset: usize) -> String {
+    let name = &data[offset..offset + 20];
+    let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..end]).trim().to_string()
+}
+
+/// Walk the sub-chunks of a RIFF list, calling `f` with each id and body
+fn for_each_chunk(mut data: &[u8], mut f: impl FnMut(&[u8], &[u8])) -> Result<(), String> {
+    while data.len() >= 8 {
+        let id = &data[0..4];
+        let size = u32_at(data, 4) as usize;
+        let body = data
+            .get(8..8 + size)
+            .ok_or_else(|| "Truncated SoundFont chunk".to_string())?;
+        f(id, body);
+        // Chunks are padded to an even length
+        let next = (8 + size + 1) & !1;
+        data = data.get(next..).unwrap_or(&[]);
+    }
+    Ok(())
+}
+
+/// Generators of one zone (a bag), as (operator, amount) pairs
+fn zone_generators(bags: &[u8], gens: &[u8], bag: usize) -> Vec<(u16, u16)> {
+    let first = u16_at(bags, bag * BAG_SIZE) as usize;
+    let last = u16_at(bags, (bag + 1) * BAG_SIZE) as usize;
+    (first..last)
+        .filter(|g| (g + 1) * GEN_SIZE <= gens.len())
+        .map(|g| (u16_at(gens, g * GEN_SIZE), u16_at(gens, g * GEN_SIZE + 2)))
+        .collect()
+}
+
+/// Whether a zone's key range (if it has one) includes `key`
+fn covers(generators: &[(u16, u16)], key: u8) -> bool {
+    generators
+        .iter()
+        .find(|(op, _)| *op == GEN_KEY_RANGE)
+        .map(|(_, amount)| {
+            let [low, high] = amount.to_le_bytes();
+            (low..=high).contains(&key)
+        })
+        .unwrap_or(true)
+}
+
+fn generator(generators: &[(u16, u16)], op: u16) -> Option<u16> {
+    generators.iter().find(|(o, _)| *o == op).map(|(_, a)| *a)
+}
+
+/// Pick the zone in `first..last` that has `op` and covers the center key,
+/// falling back to the first zone with `op`
+fn pick_zone(
+    bags: &[u8],
+    gens: &[u8],
+    first: usize,
+    last: usize,
+    op: u16,
+) -> Option<Vec<(u16, u16)>> {
+    let zones: Vec<_> = (first..last)
+        .filter(|b| (b + 2) * BAG_SIZE <= bags.len())
+        .map(|b| zone_generators(bags, gens, b))
+        .filter(|z| generator(z, op).is_some())
+        .collect();
+    let index = zones
+        .iter()
+        .position(|z| covers(z, CENTER_KEY))
+        .unwrap_or(0);
+    zones.into_iter().nth(index)
+}
+
+impl SoundFont {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"sfbk" {
+            return Err(format!("{} is not a SoundFont file", path));
+        }
+
+        let mut font = SoundFont {
+            samples: Vec::new(),
+            phdr: Vec::new(),
+            pbag: Vec::new(),
+            pgen: Vec::new(),
+            inst: Vec::new(),
+            ibag: Vec::new(),
+            igen: Vec::new(),
+            shdr: Vec::new(),
+        };
+        let mut lists = Vec::new();
+        for_each_chunk(&data[12..], |id, body| {
+            if id == b"LIST" && body.len() >= 4 {
+                lists.push(body);
+            }
+        })?;
+        for list in lists {
+            for_each_chunk(&list[4..], |id, body| match id {
+                b"smpl" => {
+                    font.samples = body
+                        .chunks_exact(2)
+                        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                        .collect()
+                }
+                b"phdr" => font.phdr = body.to_vec(),
+                b"pbag" => font.pbag = body.to_vec(),
+                b"pgen" => font.pgen = body.to_vec(),
+                b"inst" => font.inst = body.to_vec(),
+                b"ibag" => font.ibag = body.to_vec(),
+                b"igen" => font.igen = body.to_vec(),
+                b"shdr" => font.shdr = body.to_vec(),
+                _ => (),
+            })?;
+        }
+        if font.samples.is_empty() || font.phdr.len() < 2 * PHDR_SIZE {
+            return Err(format!("{} has no presets", path));
+        }
+        Ok(font)
+    }
+
+    /// Preset names, in file order. The last header is a terminator, not a preset
+    pub fn preset_names(&self) -> Vec<String> {
+        let count = self.phdr.len() / PHDR_SIZE - 1;
+        (0..count)
+            .map(|i| name_at(&self.phdr, i * PHDR_SIZE))
+            .collect()
+    }
+
+    /// The sample a preset plays around middle C
+    pub fn preset_sample(&self, preset_name: &str) -> Result<Sample, String> {
+        let preset = self
+            .preset_names()
+            .iter()
+            .position(|name| name == preset_name)
+            .ok_or_else(|| format!("No preset named {}", preset_name))?;
+
+        let first_bag = u16_at(&self.phdr, preset * PHDR_SIZE + 24) as usize;
+        let last_bag = u16_at(&self.phdr, (preset + 1) * PHDR_SIZE + 24) as usize;
+        let preset_zone = pick_zone(&self.pbag, &self.pgen, first_bag, last_bag, GEN_INSTRUMENT)
+            .ok_or_else(|| format!("Preset {} has no instrument", preset_name))?;
+        let instrument = generator(&preset_zone, GEN_INSTRUMENT).unwrap_or(0) as usize;
+
+        if (instrument + 2) * INST_SIZE > self.inst.len() {
+            return Err(format!(
+                "Preset {} refers to a missing instrument",
+                preset_name
+            ));
+        }
+        let first_bag = u16_at(&self.inst, instrument * INST_SIZE + 20) as usize;
+        let last_bag = u16_at(&self.inst, (instrument + 1) * INST_SIZE + 20) as usize;
+        let zone = pick_zone(&self.ibag, &self.igen, first_bag, last_bag, GEN_SAMPLE_ID)
+            .ok_or_else(|| format!("Preset {} has no samples", preset_name))?;
+
+        let header = generator(&zone, GEN_SAMPLE_ID).unwrap_or(0) as usize * SHDR_SIZE;
+        if header + SHDR_SIZE > self.shdr.len() {
+            return Err(format!("Preset {} refers to a missing sample", preset_name));
+        }
+        let start = u32_at(&self.shdr, header + 20) as usize;
+        let end = (u32_at(&self.shdr, header + 24) as usize).min(self.samples.len());
+        let loop_start = u32_at(&self.shdr, header + 28) as usize;
+        let loop_end = u32_at(&self.shdr, header + 32) as usize;
+        let sample_rate = u32_at(&self.shdr, header + 36) as f32;
+        let original_key = self.shdr[header + 40];
+        let correction_cents = self.shdr[header + 41] as i8;
+        if start >= end {
+            return Err(format!("Preset {} has an empty sample", preset_name));
+        }
+
+        let root_key = match generator(&zone, GEN_OVERRIDING_ROOT_KEY) {
+            Some(key) if key < 128 => key as f32,
+            _ => original_key.min(127) as f32,
+        };
+        let root_frequency =
+            440.0 * 2.0f32.powf((root_key - 69.0 - correction_cents as f32 / 100.0) / 12.0);
+
+        // Sample modes 1 and 3 loop, 0 and 2 play once
+        let looped = generator(&zone, GEN_SAMPLE_MODES).unwrap_or(0) & 1 == 1;
+        let loop_points =
+            if looped && start <= loop_start && loop_start < loop_end && loop_end <= end {
+                Some((loop_start - start, loop_end - start))
+            } else {
+                None
+            };
+
+        Ok(Sample {
+            data: self.samples[start..end]
+                .iter()
+                .map(|&s| s as f32 / 32768.0)
+                .collect(),
+            sample_rate,
+            root_frequency,
+            loop_points,
+        })
+    }
+}
@@ -0,0 +1,107 @@
+// Delay line pitch shifting. Two taps sweep through a short window at the
+// rate the pitch needs, each faded out as it jumps back, so there's no
+// click where it wraps. Cheap and a little grainy, which suits effects
+use fundsp::hacker::{shared, AudioNode, Frame, U2};
+use std::f32::consts::PI;
+
+/// Length of the sweep window. Longer is smoother but smears attacks
+const WINDOW_SECONDS: f32 = 0.05;
+
+/// Shifts one channel
+#[derive(Clone)]
+pub struct PitchShifter {
+    buffer: Vec<f32>,
+    write: usize,
+    /// Where the first tap is in the window, 0.0 to 1.0
+    phase: f32,
+    /// Window length in samples
+    window: f32,
+}
+
+impl PitchShifter {
+    pub fn new(sample_rate: f32) -> Self {
+        let window = WINDOW_SECONDS * sample_rate;
+        PitchShifter {
+            buffer: vec![0.0; window.ceil() as usize + 2],
+            write: 0,
+            phase: 0.0,
+            window,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.iter_mut().for_each(|sample| *sample = 0.0);
+        self.phase = 0.0;
+    }
+
+    /// `delay` samples ago, interpolated
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let position = self.write as f32 + len as f32 - delay;
+        let index = position.floor();
+        let fraction = position - index;
+        let a = self.buffer[index as usize % len];
+        let b = self.buffer[(index as usize + 1) % len];
+        a + (b - a) * fraction
+    }
+
+    /// Take in a sample and give one back shifted by `ratio`, 2.0 being an
+    /// octave up
+    pub fn tick(&mut self, input: f32, ratio: f32) -> f32 {
+        let len = self.buffer.len();
+        self.buffer[self.write] = input;
+        // Rising pitch reads faster than it's written, so the delay shrinks
+        self.phase = (self.phase + (1.0 - ratio) / self.window).rem_euclid(1.0);
+        let other = (self.phase + 0.5) % 1.0;
+        // sin² windows half a cycle apart sum to one
+        let gain = (PI * self.phase).sin().powi(2);
+        let output = self.read(self.phase * self.window) * gain
+            + self.read(other * self.window) * (1.0 - gain);
+        self.write = (self.write + 1) % len;
+        output
+    }
+}
+
+/// Stereo pitch shifter, `semitones` (-12.0 to 12.0) up or down. All wet:
+/// the slot's mix blends it with the original, e.g. an octave down under
+/// a lead line
+#[derive(Clone)]
+pub struct PitchShiftNode {
+    semitones: shared::Shared,
+    shifters: [PitchShifter; 2],
+}
+
+impl PitchShiftNode {
+    pub fn new(semitones: shared::Shared) -> Self {
+        PitchShiftNode {
+            semitones,
+            shifters: [PitchShifter::new(48000.0), PitchShifter::new(48000.0)],
+        }
+    }
+}
+
+impl AudioNode for PitchShiftNode {
+    const ID: u64 = 0x4861_7270_5368_6674;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.shifters.iter_mut().for_each(PitchShifter::reset);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.shifters = [
+            PitchShifter::new(sample_rate as f32),
+            PitchShifter::new(sample_rate as f32),
+        ];
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let ratio = (self.semitones.value().clamp(-12.0, 12.0) / 12.0).exp2();
+        [
+            self.shifters[0].tick(input[0], ratio),
+            self.shifters[1].tick(input[1], ratio),
+        ]
+        .into()
+    }
+}
@@ -0,0 +1,678 @@
+// Insert effect slots. Each slot in the effect chain hosts one of the effect
+// types registered in `SLOT_EFFECTS`, with its own parameters, saved with
+// the patch. A new effect is an entry there and a function building its
+// node; the synth doesn't need to know about it. Effects are built all wet:
+// each slot has its own wet/dry mix
+use super::comb::CombNode;
+use super::effects::push_dry_wet;
+use super::formant::FormantNode;
+use super::gain::db_to_gain;
+use super::reverb::{reverb_of_type, ReverbType};
+use super::shifter::PitchShiftNode;
+use super::wah::WahNode;
+use fundsp::hacker::{pass, shared, var, An, AudioNode, AudioUnit, Frame, Net, NodeId, U2};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::f32::consts::TAU;
+
+/// Insert slots in the chain
+pub const MAX_EFFECT_SLOTS: usize = 4;
+/// Most parameters an effect type can have
+pub const MAX_SLOT_PARAMS: usize = 6;
+
+/// One of an effect type's parameters
+pub struct SlotParam {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+    /// Baked into the effect's node, so changing it builds a new one
+    pub rebuild: bool,
+}
+
+/// A registered effect type
+pub struct SlotEffectDef {
+    pub name: &'static str,
+    pub params: &'static [SlotParam],
+    /// Build the effect's stereo node. `vars` hold the parameters, in the
+    /// order of `params`, for those read as it runs; `values` are the same
+    /// parameters now, for those baked in. `note` is the frequency (Hz) of
+    /// the note played last, for effects that follow the keyboard
+    pub build: fn(
+        vars: &[shared::Shared],
+        values: &[f32],
+        note: &shared::Shared,
+    ) -> Box<dyn AudioUnit + Send>,
+}
+
+/// Every effect type a slot can host. The first is the empty slot
+static SLOT_EFFECTS: &[SlotEffectDef] = &[
+    SlotEffectDef {
+        name: "none",
+        params: &[],
+        build: build_none,
+    },
+    SlotEffectDef {
+        name: "distortion",
+        params: &[
+            SlotParam {
+                name: "drive",
+                min: 0.0,
+                max: 36.0,
+                default: 12.0,
+                rebuild: false,
+            },
+            SlotParam {
+                name: "tone",
+                min: 500.0,
+                max: 12000.0,
+                default: 4000.0,
+                rebuild: false,
+            },
+            SlotParam {
+                name: "level",
+                min: -24.0,
+                max: 0.0,
+                default: -6.0,
+                rebuild: false,
+            },
+        ],
+        build: build_distortion,
+    },
+    SlotEffectDef {
+        name: "reverb",
+        params: &[
+            SlotParam {
+                name: "size",
+                min: 5.0,
+                max: 30.0,
+                default: 15.0,
+                rebuild: true,
+            },
+            SlotParam {
+                name: "time",
+                min: 0.3,
+                max: 10.0,
+                default: 2.0,
+                rebuild: true,
+            },
+            SlotParam {
+                name: "damping",
+                min: 0.0,
+                max: 1.0,
+                default: 0.5,
+                rebuild: true,
+            },
+            SlotParam {
+                name: "type",
+                min: 0.0,
+                max: 3.0,
+                default: 0.0,
+                rebuild: true,
+            },
+        ],
+        build: build_reverb,
+    },
+    SlotEffectDef {
+        name: "formant",
+        params: &[
+            SlotParam {
+                name: "vowel",
+                min: 0.0,
+                max: 4.0,
+                default: 0.0,
+                rebuild: false,
+            },
+            SlotParam {
+                name: "resonance",
+                min: 0.0,
+                max: 1.0,
+                default: 0.5,
+                rebuild: false,
+            },
+        ],
+        build: build_formant,
+    },
+    SlotEffectDef {
+        name: "resonator",
+        params: &[
+            SlotParam {
+                name: "key_track",
+                min: 0.0,
+                max: 1.0,
+                default: 1.0,
+                rebuild: false,
+            },
+            SlotParam {
+                name: "frequency",
+                min: 20.0,
+                max: 2000.0,
+                default: 220.0,
+                rebuild: false,
+            },
+            SlotParam {
+                name: "transpose",
+                min: -24.0,
+                max: 24.0,
+                default: 0.0,
+                rebuild: false,
+            },
+            SlotParam {
+                name: "decay",
+                min: 0.05,
+                max: 5.0,
+                default: 1.0,
+                rebuild: false,
+            },
+            SlotParam {
+                name: "damping",
+                min: 0.0,
+                max: 1.0,
+                default: 0.3,
+                rebuild: false,
+            },
+        ],
+        build: build_resonator,
+    },
+    SlotEffectDef {
+        name: "auto_wah",
+        params: &[
+            SlotParam {
+                name: "sensitivity",
+                min: 0.0,
+                max: 1.0,
+                default: 0.5,
+                rebuild: false,
+            },
+            SlotParam {
+                name: "range",
+                min: 0.5,
+                max: 5.0,
+                default: 3.0,
+                rebuild: false,
+            },
+            SlotParam {
+                name: "resonance",
+                min: 0.0,
+                max: 1.0,
+                default: 0.5,
+                rebuild: false,
+            },
+        ],
+        build: build_auto_wah,
+    },
+    SlotEffectDef {
+        name: "pitch_shift",
+        params: &[SlotParam {
+            name: "semitones",
+            min: -12.0,
+            max: 12.0,
+            default: -12.0,
+            rebuild: false,
+        }],
+        build: build_pitch_shift,
+    },
+];
+
+fn build_none(
+    _vars: &[shared::Shared],
+    _values: &[f32],
+    _note: &shared::Shared,
+) -> Box<dyn AudioUnit + Send> {
+    Box::new(pass() | pass())
+}
+
+/// Parameters: drive (dB), tone (Hz), level (dB)
+fn build_distortion(
+    vars: &[shared::Shared],
+    _values: &[f32],
+    _note: &shared::Shared,
+) -> Box<dyn AudioUnit + Send> {
+    Box::new(An(DistortionNode::new(
+        vars[0].clone(),
+        vars[1].clone(),
+        vars[2].clone(),
+    )))
+}
+
+/// Parameters: size (room size in metres), time (seconds), damping, type
+/// (see `ReverbType::value`). All are baked into the reverb, so changing
+/// them cuts off the tail
+fn build_reverb(
+    _vars: &[shared::Shared],
+    values: &[f32],
+    _note: &shared::Shared,
+) -> Box<dyn AudioUnit + Send> {
+    reverb_of_type(
+        ReverbType::from_value(values[3]),
+        values[0],
+        values[1],
+        values[2],
+    )
+}
+
+/// Parameters: vowel (0 A, 1 E, 2 I, 3 O, 4 U, morphing between), resonance
+fn build_formant(
+    vars: &[shared::Shared],
+    _values: &[f32],
+    _note: &shared::Shared,
+) -> Box<dyn AudioUnit + Send> {
+    Box::new(An(FormantNode::new(vars[0].clone(), vars[1].clone())))
+}
+
+/// Parameters: key_track (1 to follow the keyboard, 0 for a fixed pitch),
+/// frequency (Hz, when not key tracking), transpose (semitones, when key
+/// tracking), decay (seconds), damping
+fn build_resonator(
+    vars: &[shared::Shared],
+    _values: &[f32],
+    note: &shared::Shared,
+) -> Box<dyn AudioUnit + Send> {
+    Box::new(An(CombNode::new(
+        vars[0].clone(),
+        vars[1].clone(),
+        vars[2].clone(),
+        vars[3].clone(),
+        vars[4].clone(),
+        note.clone(),
+    )))
+}
+
+/// Parameters: sensitivity, range (octaves), resonance
+fn build_auto_wah(
+    vars: &[shared::Shared],
+    _values: &[f32],
+    _note: &shared::Shared,
+) -> Box<dyn AudioUnit + Send> {
+    Box::new(An(WahNode::new(
+        vars[0].clone(),
+        vars[1].clone(),
+        vars[2].clone(),
+    )))
+}
+
+/// Parameters: semitones. Starts an octave down; the slot's mix sets how
+/// much of it is heard alongside the original
+fn build_pitch_shift(
+    vars: &[shared::Shared],
+    _values: &[f32],
+    _note: &shared::Shared,
+) -> Box<dyn AudioUnit + Send> {
+    Box::new(An(PitchShiftNode::new(vars[0].clone())))
+}
+
+/// A registered effect type, saved by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotEffect(usize);
+
+impl SlotEffect {
+    pub const NONE: SlotEffect = SlotEffect(0);
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        SLOT_EFFECTS
+            .iter()
+            .position(|def| def.name.eq_ignore_ascii_case(name))
+            .map(SlotEffect)
+    }
+
+    pub fn def(&self) -> &'static SlotEffectDef {
+        &SLOT_EFFECTS[self.0]
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.def().name
+    }
+
+    /// Index of the parameter called `name`
+    pub fn param_index(&self, name: &str) -> Option<usize> {
+        self.def()
+            .params
+            .iter()
+            .position(|param| param.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The parameters at their defaults
+    fn default_params(&self) -> [f32; MAX_SLOT_PARAMS] {
+        let mut params = [0.0; MAX_SLOT_PARAMS];
+        for (value, param) in params.iter_mut().zip(self.def().params) {
+            *value = param.default;
+        }
+        params
+    }
+}
+
+impl Serialize for SlotEffect {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for SlotEffect {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        SlotEffect::from_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown effect: {}", name)))
+    }
+}
+
+/// A registered effect type and its parameters, for the frontend to build
+/// controls from
+#[derive(Debug, Clone, Serialize)]
+pub struct SlotEffectInfo {
+    pub name: &'static str,
+    pub params: Vec<SlotParamInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlotParamInfo {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+}
+
+pub fn slot_effects() -> Vec<SlotEffectInfo> {
+    SLOT_EFFECTS
+        .iter()
+        .map(|def| SlotEffectInfo {
+            name: def.name,
+            params: def
+                .params
+                .iter()
+                .map(|param| SlotParamInfo {
+                    name: param.name,
+                    min: param.min,
+                    max: param.max,
+                    default: param.default,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// What a slot holds, as saved in the patch. Serialized as the effect's
+/// name, its wet/dry mix and its parameters by name, so patches survive
+/// effects gaining or losing parameters. An effect this version doesn't
+/// know loads as an empty slot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlotPatch {
+    pub effect: SlotEffect,
+    /// 0.0 for all dry to 1.0 for all wet
+    pub mix: f32,
+    pub params: [f32; MAX_SLOT_PARAMS],
+}
+
+impl SlotPatch {
+    /// The slot as a reverb of type `reverb_type`, with the type's size,
+    /// time and damping
+    pub fn reverb(reverb_type: ReverbType, mix: f32) -> Self {
+        let effect = SlotEffect::from_name("reverb").expect("reverb is registered");
+        let mut params = effect.default_params();
+        let (size, time, damping) = reverb_type.settings();
+        for (name, value) in [
+            ("size", size),
+            ("time", time),
+            ("damping", damping),
+            ("type", reverb_type.value()),
+        ] {
+            if let Some(index) = effect.param_index(name) {
+                params[index] = value;
+            }
+        }
+        SlotPatch {
+            effect,
+            mix,
+            params,
+        }
+    }
+
+    /// The reverb type, if the slot holds a reverb
+    pub fn reverb_type(&self) -> Option<ReverbType> {
+        if self.effect.name() != "reverb" {
+            return None;
+        }
+        let index = self.effect.param_index("type")?;
+        Some(ReverbType::from_value(self.params[index]))
+    }
+}
+
+impl Default for SlotPatch {
+    fn default() -> Self {
+        SlotPatch {
+            effect: SlotEffect::NONE,
+            mix: 1.0,
+            params: SlotEffect::NONE.default_params(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SlotPatchFields {
+    effect: String,
+    #[serde(default = "default_mix")]
+    mix: f32,
+    #[serde(default)]
+    params: BTreeMap<String, f32>,
+}
+
+fn default_mix() -> f32 {
+    1.0
+}
+
+impl Serialize for SlotPatch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SlotPatchFields {
+            effect: self.effect.name().to_string(),
+            mix: self.mix,
+            params: self
+                .effect
+                .def()
+                .params
+                .iter()
+                .zip(self.params)
+                .map(|(param, value)| (param.name.to_string(), value))
+                .collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SlotPatch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = SlotPatchFields::deserialize(deserializer)?;
+        let effect = SlotEffect::from_name(&fields.effect).unwrap_or(SlotEffect::NONE);
+        let mut params = effect.default_params();
+        for (value, param) in params.iter_mut().zip(effect.def().params) {
+            if let Some(saved) = fields.params.get(param.name) {
+                *value = saved.clamp(param.min, param.max);
+            }
+        }
+        Ok(SlotPatch {
+            effect,
+            mix: fields.mix.clamp(0.0, 1.0),
+            params,
+        })
+    }
+}
+
+/// A slot in the synth's graph
+pub struct EffectSlot {
+    effect: SlotEffect,
+    params: [f32; MAX_SLOT_PARAMS],
+    vars: [shared::Shared; MAX_SLOT_PARAMS],
+    mix_var: shared::Shared,
+    /// The synth's latest note, see `SlotEffectDef::build`
+    note: shared::Shared,
+    nodeid: NodeId,
+    mix_nodeid: NodeId,
+}
+
+impl EffectSlot {
+    /// An empty slot, added to `net` unconnected
+    pub fn new(net: &mut Net, note: &shared::Shared) -> Self {
+        let vars = [(); MAX_SLOT_PARAMS].map(|_| shared(0.0));
+        let mix_var = shared(1.0);
+        let effect = SlotEffect::NONE;
+        let params = effect.default_params();
+        let nodeid = net.push((effect.def().build)(&vars, &params, note));
+        let mix_nodeid = push_dry_wet(net, Box::new(var(&mix_var)));
+        EffectSlot {
+            effect,
+            params,
+            vars,
+            mix_var,
+            note: note.clone(),
+            nodeid,
+            mix_nodeid,
+        }
+    }
+
+    /// The effect's node, all wet
+    pub fn nodeid(&self) -> NodeId {
+        self.nodeid
+    }
+
+    /// The slot's wet/dry crossfade
+    pub fn mix_nodeid(&self) -> NodeId {
+        self.mix_nodeid
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix_var.set_value(mix.clamp(0.0, 1.0));
+    }
+
+    pub fn mix(&self) -> f32 {
+        self.mix_var.value()
+    }
+
+    fn rebuild(&mut self, net: &mut Net) {
+        for (var, value) in self.vars.iter().zip(self.params) {
+            var.set_value(value);
+        }
+        net.replace(
+            self.nodeid,
+            (self.effect.def().build)(&self.vars, &self.params, &self.note),
+        );
+    }
+
+    /// Host a different effect, with its parameters at their defaults.
+    /// Returns whether the graph changed and needs committing
+    pub fn set_effect(&mut self, net: &mut Net, effect: SlotEffect) -> bool {
+        if effect == self.effect {
+            return false;
+        }
+        self.effect = effect;
+        self.params = effect.default_params();
+        self.rebuild(net);
+        true
+    }
+
+    /// Set parameter `index` of the effect, clamped to its range. Returns
+    /// whether the graph changed and needs committing
+    pub fn set_param(&mut self, net: &mut Net, index: usize, value: f32) -> bool {
+        let param = match self.effect.def().params.get(index) {
+            Some(param) => param,
+            None => return false,
+        };
+        let value = value.clamp(param.min, param.max);
+        if value == self.params[index] {
+            return false;
+        }
+        self.params[index] = value;
+        if param.rebuild {
+            self.rebuild(net);
+            true
+        } else {
+            self.vars[index].set_value(value);
+            false
+        }
+    }
+
+    pub fn patch(&self) -> SlotPatch {
+        SlotPatch {
+            effect: self.effect,
+            mix: self.mix(),
+            params: self.params,
+        }
+    }
+
+    /// Load a saved slot. Returns whether the graph changed and needs committing
+    pub fn apply(&mut self, net: &mut Net, patch: &SlotPatch) -> bool {
+        self.set_mix(patch.mix);
+        if patch.effect != self.effect {
+            self.effect = patch.effect;
+            self.params = patch.params;
+            self.rebuild(net);
+            return true;
+        }
+        // Built once, however many baked in parameters change
+        let mut rebuild = false;
+        for (index, param) in self.effect.def().params.iter().enumerate() {
+            let value = patch.params[index].clamp(param.min, param.max);
+            if value == self.params[index] {
+                continue;
+            }
+            self.params[index] = value;
+            if param.rebuild {
+                rebuild = true;
+            } else {
+                self.vars[index].set_value(value);
+            }
+        }
+        if rebuild {
+            self.rebuild(net);
+        }
+        rebuild
+    }
+}
+
+/// Drive into tanh, then a one pole low pass to take the fizz off, then
+/// back down to level. Takes drive (dB), tone (Hz) and level (dB)
+#[derive(Clone)]
+pub struct DistortionNode {
+    drive: shared::Shared,
+    tone: shared::Shared,
+    level: shared::Shared,
+    low: [f32; 2],
+    sample_rate: f32,
+}
+
+impl DistortionNode {
+    pub fn new(drive: shared::Shared, tone: shared::Shared, level: shared::Shared) -> Self {
+        DistortionNode {
+            drive,
+            tone,
+            level,
+            low: [0.0; 2],
+            sample_rate: 48000.0,
+        }
+    }
+}
+
+impl AudioNode for DistortionNode {
+    const ID: u64 = 0x4861_7270_4469_7374;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.low = [0.0; 2];
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let drive = db_to_gain(self.drive.value());
+        let level = db_to_gain(self.level.value());
+        let coefficient = 1.0 - (-TAU * self.tone.value() / self.sample_rate).exp();
+        let mut output = [0.0; 2];
+        for channel in 0..2 {
+            let shaped = (input[channel] * drive).tanh();
+            self.low[channel] += coefficient * (shaped - self.low[channel]);
+            output[channel] = self.low[channel] * level;
+        }
+        output.into()
+    }
+}
@@ -0,0 +1,129 @@
+// Control signal smoothing with separate rise and fall times, adjustable
+// while playing. Like FunDSP's afollow, but with the times in shared vars
+use fundsp::hacker::{shared, AudioNode, Frame, U1};
+use serde::{Deserialize, Serialize};
+
+/// Shortest smoothing time. Anything shorter is a step anyway
+pub const MIN_SMOOTHING: f32 = 0.0001;
+
+/// Rise and fall times of a smoother, in seconds. Each is the time to get
+/// halfway to a new value
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Smoothing {
+    pub up: f32,
+    pub down: f32,
+}
+
+impl Smoothing {
+    pub const fn new(up: f32, down: f32) -> Self {
+        Smoothing { up, down }
+    }
+
+    /// Both times clamped to `MIN_SMOOTHING..=max`
+    pub fn clamped(&self, max: f32) -> Self {
+        Smoothing {
+            up: self.up.clamp(MIN_SMOOTHING, max),
+            down: self.down.clamp(MIN_SMOOTHING, max),
+        }
+    }
+}
+
+/// The shared vars a `SmootherNode` reads its times from
+#[derive(Clone)]
+pub struct SmoothingVars {
+    pub up: shared::Shared,
+    pub down: shared::Shared,
+}
+
+impl SmoothingVars {
+    pub fn new(smoothing: Smoothing) -> Self {
+        SmoothingVars {
+            up: shared(smoothing.up),
+            down: shared(smoothing.down),
+        }
+    }
+
+    pub fn set(&self, smoothing: Smoothing) {
+        self.up.set_value(smoothing.up);
+        self.down.set_value(smoothing.down);
+    }
+
+    pub fn get(&self) -> Smoothing {
+        Smoothing::new(self.up.value(), self.down.value())
+    }
+}
+
+/// One pole smoother: rising inputs are followed with the up time and
+/// falling ones with the down time
+#[derive(Clone)]
+pub struct SmootherNode {
+    times: SmoothingVars,
+    value: f32,
+    sample_rate: f32,
+    /// The times the coefficients were worked out for
+    up: f32,
+    down: f32,
+    up_coefficient: f32,
+    down_coefficient: f32,
+}
+
+impl SmootherNode {
+    pub fn new(times: SmoothingVars) -> Self {
+        let mut node = SmootherNode {
+            times,
+            value: 0.0,
+            sample_rate: 48000.0,
+            up: 0.0,
+            down: 0.0,
+            up_coefficient: 0.0,
+            down_coefficient: 0.0,
+        };
+        node.set_sample_rate(48000.0);
+        node
+    }
+
+    /// Per-sample step towards the target for a halfway time of `seconds`
+    fn coefficient(&self, seconds: f32) -> f32 {
+        1.0 - 0.5f32.powf(1.0 / (seconds.max(MIN_SMOOTHING) * self.sample_rate))
+    }
+
+    fn update_coefficients(&mut self) {
+        let (up, down) = (self.times.up.value(), self.times.down.value());
+        if up != self.up {
+            self.up = up;
+            self.up_coefficient = self.coefficient(up);
+        }
+        if down != self.down {
+            self.down = down;
+            self.down_coefficient = self.coefficient(down);
+        }
+    }
+}
+
+impl AudioNode for SmootherNode {
+    const ID: u64 = 0x4861_7270_536d_6f6f;
+    type Inputs = U1;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.value = 0.0;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+        self.up_coefficient = self.coefficient(self.up);
+        self.down_coefficient = self.coefficient(self.down);
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        self.update_coefficients();
+        let target = input[0];
+        let coefficient = if target > self.value {
+            self.up_coefficient
+        } else {
+            self.down_coefficient
+        };
+        self.value += coefficient * (target - self.value);
+        [self.value].into()
+    }
+}
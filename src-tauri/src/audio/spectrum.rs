@@ -0,0 +1,53 @@
+// Spectrum analysis of the output, computed from the oscilloscope history
+// on the calling thread (never the audio thread)
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+/// FFT length. At 48kHz this gives ~23Hz bins over the last ~43ms of output
+const FFT_SIZE: usize = 2048;
+/// Floor for reported magnitudes, so silence doesn't come out as -inf
+const MIN_DB: f32 = -120.0;
+
+/// Magnitude spectrum as reported to the frontend
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SpectrumInfo {
+    /// Width of each bin in Hz; bin `i` is centred on `i * bin_hz`
+    pub bin_hz: f32,
+    /// Magnitude of each bin from DC up to Nyquist, in dB relative to a full scale sine
+    pub magnitudes: Vec<f32>,
+}
+
+/// Analyse the most recent `FFT_SIZE` samples. Returns `None` until enough
+/// output has been captured
+pub fn analyze(samples: &[f32], sample_rate: f32) -> Option<SpectrumInfo> {
+    if samples.len() < FFT_SIZE {
+        return None;
+    }
+    let samples = &samples[samples.len() - FFT_SIZE..];
+
+    // Hann window to keep leakage from smearing everything together
+    let mut buffer: Vec<Complex<f32>> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let w = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos();
+            Complex::new(s * w, 0.0)
+        })
+        .collect();
+
+    FftPlanner::new()
+        .plan_fft_forward(FFT_SIZE)
+        .process(&mut buffer);
+
+    // A full scale sine comes out at FFT_SIZE / 4 after the Hann window
+    let scale = 4.0 / FFT_SIZE as f32;
+    let magnitudes = buffer[..=FFT_SIZE / 2]
+        .iter()
+        .map(|c| (20.0 * (c.norm() * scale).log10()).max(MIN_DB))
+        .collect();
+
+    Some(SpectrumInfo {
+        bin_hz: sample_rate / FFT_SIZE as f32,
+        magnitudes,
+    })
+}
@@ -0,0 +1,47 @@
+// Keyboard split: notes below a split point play a second sound on a group
+// of voices of their own, e.g. a bass drone under the left hand and a lead
+// under the right
+use super::patch::Patch;
+use super::voice::MAX_VOICES;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KeySplit {
+    /// Notes below this frequency play the lower sound, the rest the patch
+    pub frequency: f32,
+    /// Voices kept for the lower sound, from the start of the pool
+    pub lower_voices: usize,
+    /// The lower sound. Only its waveform, envelope and unison are used:
+    /// the filter, LFOs and effects are shared with the upper sound
+    pub lower: Patch,
+}
+
+impl KeySplit {
+    /// Split point clamped to the audible range, and the voices shared so
+    /// that each side keeps at least one
+    pub fn clamped(&self) -> Self {
+        KeySplit {
+            frequency: self.frequency.clamp(20.0, 20000.0),
+            lower_voices: self.lower_voices.clamp(1, MAX_VOICES - 1),
+            lower: self.lower,
+        }
+    }
+
+    pub fn is_lower(&self, frequency: f32) -> bool {
+        frequency < self.frequency
+    }
+
+    /// Indices of the voices that may play a note at `frequency`
+    pub fn group(&self, frequency: f32) -> Range<usize> {
+        if self.is_lower(frequency) {
+            0..self.lower_voices
+        } else {
+            self.lower_voices..MAX_VOICES
+        }
+    }
+
+    pub fn is_lower_voice(&self, index: usize) -> bool {
+        index < self.lower_voices
+    }
+}
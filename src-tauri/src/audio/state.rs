@@ -0,0 +1,125 @@
+// The synth's state as the user set it, apart from the sequencer: what the
+// session saves, and what survives the synth being rebuilt
+use super::follower::ModDestination;
+use super::gain::GainStage;
+use super::instrument::InstrumentMode;
+use super::layer::Layer;
+use super::mono::NotePriority;
+use super::patch::Patch;
+use super::schedule::StrumSettings;
+use super::smoothing::Smoothing;
+use super::split::KeySplit;
+use super::synthesis::{FunDSPSynth, KeyZone};
+use super::vocoder::EngineMode;
+use super::voice::StealPolicy;
+use serde::{Deserialize, Serialize};
+
+/// Everything the user can change that outlives a note, bar the sequencer:
+/// the sound, and the performance and output settings around it. The
+/// session saves it, and the synth carries it over when rebuilt after a panic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SynthState {
+    patch: Patch,
+    tempo: f32,
+    key_zones: Vec<KeyZone>,
+    key_split: Option<KeySplit>,
+    layer: Option<Layer>,
+    instrument_mode: InstrumentMode,
+    mono: bool,
+    note_priority: NotePriority,
+    steal_policy: StealPolicy,
+    strum_settings: StrumSettings,
+    frequency_smoothing: Smoothing,
+    quantize: u32,
+    gain_trims: Vec<(GainStage, f32)>,
+    limiter_attack: f32,
+    limiter_release: f32,
+    limiter_ceiling: f32,
+    limiter_bypass: bool,
+    input_level: f32,
+    input_envelope_amounts: Vec<(ModDestination, f32)>,
+    engine_mode: EngineMode,
+    vocoder_bands: u32,
+    formant_shift: f32,
+    pitch_bend_range: f32,
+    output_ceiling: f32,
+}
+
+impl SynthState {
+    pub fn capture(synth: &FunDSPSynth) -> Self {
+        SynthState {
+            patch: synth.get_patch(),
+            tempo: synth.get_tempo(),
+            key_zones: synth.get_key_zones(),
+            key_split: synth.get_key_split(),
+            layer: synth.get_layer(),
+            instrument_mode: synth.get_instrument_mode(),
+            mono: synth.get_mono(),
+            note_priority: synth.get_note_priority(),
+            steal_policy: synth.get_steal_policy(),
+            strum_settings: synth.get_strum_settings(),
+            frequency_smoothing: synth.get_frequency_smoothing(),
+            quantize: synth.get_quantize(),
+            gain_trims: GainStage::ALL
+                .iter()
+                .map(|&stage| (stage, synth.get_gain_trim(stage)))
+                .collect(),
+            limiter_attack: synth.get_limiter_attack(),
+            limiter_release: synth.get_limiter_release(),
+            limiter_ceiling: synth.get_limiter_ceiling(),
+            limiter_bypass: synth.get_limiter_bypass(),
+            input_level: synth.get_input_level(),
+            input_envelope_amounts: ModDestination::ALL
+                .iter()
+                .map(|&destination| (destination, synth.get_input_envelope_amount(destination)))
+                .collect(),
+            engine_mode: synth.get_engine_mode(),
+            vocoder_bands: synth.get_vocoder_bands(),
+            formant_shift: synth.get_formant_shift(),
+            pitch_bend_range: synth.get_pitch_bend_range(),
+            output_ceiling: synth.get_output_ceiling(),
+        }
+    }
+
+    pub fn apply(&self, synth: &mut FunDSPSynth) {
+        synth.apply_patch(&self.patch);
+        synth.set_tempo(self.tempo);
+        synth.clear_key_zones();
+        for zone in &self.key_zones {
+            synth.set_key_zone(*zone);
+        }
+        match self.key_split {
+            Some(split) => synth.set_key_split(split),
+            None => synth.clear_key_split(),
+        }
+        match self.layer {
+            Some(layer) => synth.set_layer(layer),
+            None => synth.clear_layer(),
+        }
+        // The mode's profile first, so the saved settings win over it
+        synth.set_instrument_mode(self.instrument_mode);
+        synth.set_mono(self.mono);
+        synth.set_note_priority(self.note_priority);
+        synth.set_steal_policy(self.steal_policy);
+        synth.set_strum_settings(self.strum_settings);
+        synth.set_frequency_smoothing(self.frequency_smoothing);
+        synth.set_quantize(self.quantize);
+        for &(stage, db) in &self.gain_trims {
+            synth.set_gain_trim(stage, db);
+        }
+        synth.set_limiter_attack(self.limiter_attack);
+        synth.set_limiter_release(self.limiter_release);
+        synth.set_limiter_ceiling(self.limiter_ceiling);
+        synth.set_limiter_bypass(self.limiter_bypass);
+        synth.set_input_level(self.input_level);
+        for &(destination, amount) in &self.input_envelope_amounts {
+            synth.set_input_envelope_amount(destination, amount);
+        }
+        synth.set_vocoder_bands(self.vocoder_bands);
+        synth.set_formant_shift(self.formant_shift);
+        synth.set_pitch_bend_range(self.pitch_bend_range);
+        synth.set_output_ceiling(self.output_ceiling);
+        synth.set_engine_mode(self.engine_mode);
+        synth.publish_params();
+    }
+}
@@ -0,0 +1,149 @@
+// Glitch counters, bumped from the audio callbacks and read by the UI
+use super::watchdog::WatchdogFault;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// Counters for the things that make audio glitch. Only atomics, so safe to
+/// update from the audio thread
+pub struct AudioStats {
+    underruns: AtomicU64,
+    lock_contention: AtomicU64,
+    queue_overflows: AtomicU64,
+    watchdog_resets: AtomicU64,
+    dsp_panics: AtomicU64,
+    /// `WatchdogFault` code of the latest watchdog reset
+    watchdog_fault: AtomicU32,
+}
+
+/// A snapshot of `AudioStats`, as reported to the frontend
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct AudioStatsInfo {
+    /// Callbacks that arrived too late for the device, as reported by the
+    /// backend or inferred from callback timing
+    pub underruns: u64,
+    /// Callbacks that output silence because the synth was locked elsewhere
+    pub lock_contention: u64,
+    /// Times the event queue was full. Either the new event was refused or,
+    /// with `OverflowPolicy::DropOldest`, the oldest queued one was dropped
+    pub queue_overflows: u64,
+    /// Times the output watchdog found bad audio and reset the synth
+    pub watchdog_resets: u64,
+    /// Times rendering panicked and the synth was rebuilt
+    pub dsp_panics: u64,
+}
+
+impl AudioStats {
+    const fn new() -> Self {
+        AudioStats {
+            underruns: AtomicU64::new(0),
+            lock_contention: AtomicU64::new(0),
+            queue_overflows: AtomicU64::new(0),
+            watchdog_resets: AtomicU64::new(0),
+            dsp_panics: AtomicU64::new(0),
+            watchdog_fault: AtomicU32::new(0),
+        }
+    }
+
+    pub fn record_underruns(&self, count: u64) {
+        self.underruns.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_lock_contention(&self) {
+        self.lock_contention.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_queue_overflow(&self) {
+        self.queue_overflows.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_watchdog_reset(&self, fault: WatchdogFault) {
+        self.watchdog_fault
+            .store(fault.to_code(), Ordering::Relaxed);
+        self.watchdog_resets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dsp_panic(&self) {
+        self.dsp_panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// What the watchdog found the last time it reset the synth
+    pub fn last_watchdog_fault(&self) -> Option<WatchdogFault> {
+        WatchdogFault::from_code(self.watchdog_fault.load(Ordering::Relaxed))
+    }
+
+    pub fn snapshot(&self) -> AudioStatsInfo {
+        AudioStatsInfo {
+            underruns: self.underruns.load(Ordering::Relaxed),
+            lock_contention: self.lock_contention.load(Ordering::Relaxed),
+            queue_overflows: self.queue_overflows.load(Ordering::Relaxed),
+            watchdog_resets: self.watchdog_resets.load(Ordering::Relaxed),
+            dsp_panics: self.dsp_panics.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.underruns.store(0, Ordering::Relaxed);
+        self.lock_contention.store(0, Ordering::Relaxed);
+        self.queue_overflows.store(0, Ordering::Relaxed);
+        self.watchdog_resets.store(0, Ordering::Relaxed);
+        self.dsp_panics.store(0, Ordering::Relaxed);
+        self.watchdog_fault.store(0, Ordering::Relaxed);
+    }
+}
+
+pub static STATS: AudioStats = AudioStats::new();
+
+/// How quickly the reported load follows the per-buffer measurements
+const LOAD_SMOOTHING: f32 = 0.1;
+
+/// Time spent rendering as a fraction of the time available. Values are f32
+/// bits in atomics, written by the audio thread only
+pub struct DspLoad {
+    average: AtomicU32,
+    peak: AtomicU32,
+}
+
+/// A snapshot of `DspLoad`, as reported to the frontend. 1.0 means rendering
+/// takes as long as the buffer lasts, i.e. dropouts
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct DspLoadInfo {
+    /// Smoothed load over recent buffers
+    pub load: f32,
+    /// Highest single-buffer load since startup (or the last reset)
+    pub peak: f32,
+}
+
+impl DspLoad {
+    const fn new() -> Self {
+        DspLoad {
+            average: AtomicU32::new(0),
+            peak: AtomicU32::new(0),
+        }
+    }
+
+    /// Record the time spent rendering a buffer that plays for `buffer_secs`
+    pub fn record(&self, render_secs: f32, buffer_secs: f32) {
+        if buffer_secs <= 0.0 {
+            return;
+        }
+        let load = render_secs / buffer_secs;
+        let average = f32::from_bits(self.average.load(Ordering::Relaxed));
+        let average = average + LOAD_SMOOTHING * (load - average);
+        self.average.store(average.to_bits(), Ordering::Relaxed);
+        if load > f32::from_bits(self.peak.load(Ordering::Relaxed)) {
+            self.peak.store(load.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> DspLoadInfo {
+        DspLoadInfo {
+            load: f32::from_bits(self.average.load(Ordering::Relaxed)),
+            peak: f32::from_bits(self.peak.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub fn reset_peak(&self) {
+        self.peak.store(0, Ordering::Relaxed);
+    }
+}
+
+pub static DSP_LOAD: DspLoad = DspLoad::new();
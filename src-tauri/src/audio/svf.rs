@@ -0,0 +1,49 @@
+// State variable filter for effects whose frequency moves every sample
+// (formants, wah). The topology preserving form stays stable however fast
+// it's swept, which the textbook one doesn't
+use std::f32::consts::PI;
+
+/// One channel of filter state
+#[derive(Clone, Copy, Default)]
+pub struct Svf {
+    s1: f32,
+    s2: f32,
+}
+
+/// Coefficients for a frequency (Hz) and Q, worked out once a sample and
+/// shared between channels and filters
+#[derive(Clone, Copy)]
+pub struct SvfCoefficients {
+    g: f32,
+    k: f32,
+    h: f32,
+}
+
+impl SvfCoefficients {
+    pub fn new(frequency: f32, q: f32, sample_rate: f32) -> Self {
+        let frequency = frequency.clamp(10.0, sample_rate * 0.45);
+        let g = (PI * frequency / sample_rate).tan();
+        let k = 1.0 / q.max(0.1);
+        SvfCoefficients {
+            g,
+            k,
+            h: 1.0 / (1.0 + g * (g + k)),
+        }
+    }
+}
+
+impl Svf {
+    pub fn reset(&mut self) {
+        *self = Svf::default();
+    }
+
+    /// Band pass, unity gain at the centre frequency
+    pub fn bandpass(&mut self, input: f32, c: &SvfCoefficients) -> f32 {
+        let high = (input - (c.g + c.k) * self.s1 - self.s2) * c.h;
+        let band = c.g * high + self.s1;
+        self.s1 = c.g * high + band;
+        let low = c.g * band + self.s2;
+        self.s2 = c.g * band + low;
+        band * c.k
+    }
+}
@@ -1,79 +1,543 @@
+use super::delay::{
+    DelayMode, FeedbackToneNode, TapeDelayNode, MAX_DELAY_CUT, MAX_DELAY_TIME, MIN_DELAY_CUT,
+};
+use super::denormal::DenormalGuard;
+use super::drums::{drum, DrumKind, DrumTrigger};
+use super::effects::{push_dry_wet, Effect, EffectOrder};
+use super::envelope::EnvelopeMode;
+use super::event_queue::SharedConsumer;
+use super::follower::{FollowerNode, ModDestination};
+use super::gain::{db_to_gain, gain_to_db, GainStage, MAX_TRIM_DB, MIN_TRIM_DB};
+#[cfg(not(target_arch = "wasm32"))]
+use super::input::InputConsumer;
+use super::instrument::InstrumentMode;
+use super::layer::{apply_layer_controls, Layer, LayerMix};
+use super::lfo::{CutoffLfoNode, LfoShape, LfoTarget};
+use super::mirror::{ParamMirror, ParamSnapshot};
+use super::mono::{HeldNote, HeldNotes, NotePriority};
+use super::patch::{Patch, PATCH_VERSION};
+#[cfg(not(target_arch = "wasm32"))]
+use super::query::QueryServer;
+#[cfg(not(target_arch = "wasm32"))]
+use super::recording::RecordingTap;
+use super::resample::Resampler;
+use super::reverb::ReverbType;
+use super::sampler::Sample;
+use super::saturation::SoftClipNode;
+use super::schedule::{ScheduledAction, Scheduler, StrumSettings};
+#[cfg(not(target_arch = "wasm32"))]
+use super::scope::OutputTap;
+use super::sequencer::{Arrangement, Sequencer};
+use super::slots::{EffectSlot, SlotEffect, SlotPatch, MAX_EFFECT_SLOTS};
+use super::smoothing::Smoothing;
+use super::split::KeySplit;
+use super::state::SynthState;
+use super::stats::{DSP_LOAD, STATS};
+use super::vocoder::{
+    EngineMode, VocoderNode, DEFAULT_VOCODER_BANDS, MAX_FORMANT_SHIFT, MAX_VOCODER_BANDS,
+    MIN_VOCODER_BANDS,
+};
+use super::voice::{
+    allocate_voice, find_latched, voice_pan, StealPolicy, Unison, Voice, VoiceControls,
+    VoicePanMode, DEFAULT_PITCH_BEND_RANGE, MAX_ENVELOPE_SMOOTHING, MAX_FREQUENCY_SMOOTHING,
+    MAX_GATE_SMOOTHING, MAX_PITCH_BEND, MAX_UNISON, MAX_VOICES, STEAL_FADE_SECS,
+};
+use super::watchdog::{Watchdog, WatchdogFault};
+use super::widener::WidenerNode;
 /// Audio synthesis module using FunDSP
-use fundsp::buffer::{BufferArray, BufferRef};
+use fundsp::buffer::BufferArray;
 use fundsp::hacker::{
-    adsr_live, afollow, dcblock, delay, limiter, lowpass, pass, saw, shared, sine, split, square,
-    triangle, var, AudioUnit, Net, NodeId, MAX_BUFFER_SIZE, U1,
+    clip_to, dcblock, delay, limiter_stereo, lowpass, pass, saw, shared, sine, square, triangle,
+    var, An, AudioUnit, Net, NodeId, MAX_BUFFER_SIZE, U2,
 };
 use rtrb::Consumer;
-use std::collections::HashMap;
-
-pub fn drain_and_coalesce_events(consumer: &mut Consumer<AudioEvent>) -> Vec<AudioEvent> {
-    let mut last_events: HashMap<&'static str, AudioEvent> = HashMap::new();
-    let mut passthrough_events = Vec::new();
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// Parameters whose events coalesce, so that only the latest of each per
+/// buffer is applied
+#[derive(Debug, Clone, Copy)]
+enum CoalescedParam {
+    Frequency,
+    PitchBend,
+    MasterVolume,
+    Waveform,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    DelayTime,
+    DelayFeedback,
+    DelayMix,
+    DelayLowCut,
+    DelayHighCut,
+    FilterCutoff,
+    FilterResonance,
+    UnisonVoices,
+    UnisonDetune,
+    UnisonSpread,
+    RingModMix,
+    RingModRatio,
+    VibratoRate,
+    VibratoDepth,
+    VibratoDelay,
+    FilterLfoRate,
+    FilterLfoDepth,
+    FilterLfoSync,
+    Tempo,
+    EnvelopeMode,
+    EnvelopeDelay,
+    EnvelopeHold,
+    FilterVelocityAmount,
+    VoiceSpread,
+    StereoWidth,
+    LimiterAttack,
+    LimiterRelease,
+    LimiterCeiling,
+    SoftClipDrive,
+    GateSmoothing,
+    EnvelopeSmoothing,
+    StrumSettings,
+    FrequencySmoothing,
+    Quantize,
+    InputLevel,
+    EngineMode,
+    VocoderBands,
+    LayerMix,
+    PitchBendRange,
+    OutputCeiling,
+    FormantShift,
+}
 
-    while let Ok(event) = consumer.pop() {
-        match &event {
-            AudioEvent::SetFrequency { .. } => {
-                last_events.insert("SetFrequency", event);
-            }
-            AudioEvent::SetMasterVolume { .. } => {
-                last_events.insert("SetMasterVolume", event);
-            }
-            AudioEvent::SetWaveform { .. } => {
-                last_events.insert("SetWaveform", event);
-            }
-            AudioEvent::SetAttack { .. } => {
-                last_events.insert("SetAttack", event);
-            }
-            AudioEvent::SetDecay { .. } => {
-                last_events.insert("SetDecay", event);
-            }
-            AudioEvent::SetSustain { .. } => {
-                last_events.insert("SetSustain", event);
-            }
-            AudioEvent::SetRelease { .. } => {
-                last_events.insert("SetRelease", event);
-            }
-            AudioEvent::SetDelayTime { .. } => {
-                last_events.insert("SetDelayTime", event);
-            }
-            AudioEvent::SetDelayFeedback { .. } => {
-                last_events.insert("SetDelayFeedback", event);
-            }
-            AudioEvent::SetDelayMix { .. } => {
-                last_events.insert("SetDelayMix", event);
-            }
-            AudioEvent::SetFilterCutoff { .. } => {
-                last_events.insert("SetFilterCutoff", event);
-            }
-            AudioEvent::SetFilterResonance { .. } => {
-                last_events.insert("SetFilterResonance", event);
+impl CoalescedParam {
+    const COUNT: usize = CoalescedParam::FormantShift as usize + 1;
+
+    /// The parameter `event` sets, or None if it can't be coalesced
+    fn of(event: &AudioEvent) -> Option<Self> {
+        Some(match event {
+            AudioEvent::SetFrequency { .. } => CoalescedParam::Frequency,
+            AudioEvent::SetPitchBend { .. } => CoalescedParam::PitchBend,
+            // Both set the same volume, so only the last of either counts
+            AudioEvent::SetMasterVolume { .. } | AudioEvent::SetMasterVolumeDb { .. } => {
+                CoalescedParam::MasterVolume
             }
-            // Non-coalescable events (e.g., PlayNote, NoteOff, queries) go straight through
-            _ => passthrough_events.push(event),
+            AudioEvent::SetWaveform { .. } => CoalescedParam::Waveform,
+            AudioEvent::SetAttack { .. } => CoalescedParam::Attack,
+            AudioEvent::SetDecay { .. } => CoalescedParam::Decay,
+            AudioEvent::SetSustain { .. } => CoalescedParam::Sustain,
+            AudioEvent::SetRelease { .. } => CoalescedParam::Release,
+            AudioEvent::SetDelayTime { .. } => CoalescedParam::DelayTime,
+            AudioEvent::SetDelayFeedback { .. } => CoalescedParam::DelayFeedback,
+            AudioEvent::SetDelayMix { .. } => CoalescedParam::DelayMix,
+            AudioEvent::SetDelayLowCut { .. } => CoalescedParam::DelayLowCut,
+            AudioEvent::SetDelayHighCut { .. } => CoalescedParam::DelayHighCut,
+            AudioEvent::SetFilterCutoff { .. } => CoalescedParam::FilterCutoff,
+            AudioEvent::SetFilterResonance { .. } => CoalescedParam::FilterResonance,
+            AudioEvent::SetUnisonVoices { .. } => CoalescedParam::UnisonVoices,
+            AudioEvent::SetUnisonDetune { .. } => CoalescedParam::UnisonDetune,
+            AudioEvent::SetUnisonSpread { .. } => CoalescedParam::UnisonSpread,
+            AudioEvent::SetRingModMix { .. } => CoalescedParam::RingModMix,
+            AudioEvent::SetRingModRatio { .. } => CoalescedParam::RingModRatio,
+            AudioEvent::SetVibratoRate { .. } => CoalescedParam::VibratoRate,
+            AudioEvent::SetVibratoDepth { .. } => CoalescedParam::VibratoDepth,
+            AudioEvent::SetVibratoDelay { .. } => CoalescedParam::VibratoDelay,
+            AudioEvent::SetFilterLfoRate { .. } => CoalescedParam::FilterLfoRate,
+            AudioEvent::SetFilterLfoDepth { .. } => CoalescedParam::FilterLfoDepth,
+            AudioEvent::SetFilterLfoSync { .. } => CoalescedParam::FilterLfoSync,
+            AudioEvent::SetTempo { .. } => CoalescedParam::Tempo,
+            AudioEvent::SetEnvelopeMode { .. } => CoalescedParam::EnvelopeMode,
+            AudioEvent::SetEnvelopeDelay { .. } => CoalescedParam::EnvelopeDelay,
+            AudioEvent::SetEnvelopeHold { .. } => CoalescedParam::EnvelopeHold,
+            AudioEvent::SetFilterVelocityAmount { .. } => CoalescedParam::FilterVelocityAmount,
+            AudioEvent::SetVoiceSpread { .. } => CoalescedParam::VoiceSpread,
+            AudioEvent::SetStereoWidth { .. } => CoalescedParam::StereoWidth,
+            AudioEvent::SetLimiterAttack { .. } => CoalescedParam::LimiterAttack,
+            AudioEvent::SetLimiterRelease { .. } => CoalescedParam::LimiterRelease,
+            AudioEvent::SetLimiterCeiling { .. } => CoalescedParam::LimiterCeiling,
+            AudioEvent::SetSoftClipDrive { .. } => CoalescedParam::SoftClipDrive,
+            AudioEvent::SetGateSmoothing { .. } => CoalescedParam::GateSmoothing,
+            AudioEvent::SetEnvelopeSmoothing { .. } => CoalescedParam::EnvelopeSmoothing,
+            AudioEvent::SetStrumSettings { .. } => CoalescedParam::StrumSettings,
+            AudioEvent::SetFrequencySmoothing { .. } => CoalescedParam::FrequencySmoothing,
+            AudioEvent::SetQuantize { .. } => CoalescedParam::Quantize,
+            AudioEvent::SetInputLevel { .. } => CoalescedParam::InputLevel,
+            AudioEvent::SetEngineMode { .. } => CoalescedParam::EngineMode,
+            AudioEvent::SetVocoderBands { .. } => CoalescedParam::VocoderBands,
+            AudioEvent::SetLayerMix { .. } => CoalescedParam::LayerMix,
+            AudioEvent::SetPitchBendRange { .. } => CoalescedParam::PitchBendRange,
+            AudioEvent::SetOutputCeiling { .. } => CoalescedParam::OutputCeiling,
+            AudioEvent::SetFormantShift { .. } => CoalescedParam::FormantShift,
+            _ => return None,
+        })
+    }
+}
+
+/// The latest event for each coalesced parameter, indexed by `CoalescedParam`
+type LatestEvents = [Option<AudioEvent>; CoalescedParam::COUNT];
+const NO_EVENT: Option<AudioEvent> = None;
+
+/// Take everything queued since the last buffer into `events`, which should
+/// be empty. Events that can't be coalesced (notes, queries, ...) come first,
+/// in the order they were sent, and none are dropped. Of each kind of
+/// parameter event only the latest is kept, after them in `CoalescedParam`
+/// order. `latest` is scratch space; with `events` reserved to the queue's
+/// capacity (see `event_buffer`) this doesn't allocate
+pub fn drain_and_coalesce_events(
+    consumer: &mut Consumer<AudioEvent>,
+    latest: &mut LatestEvents,
+    events: &mut Vec<AudioEvent>,
+) {
+    // Only what's there now, so a producer that keeps up can't keep us here
+    // or outgrow `events`
+    for _ in 0..consumer.slots() {
+        let event = match consumer.pop() {
+            Ok(event) => event,
+            Err(_) => break,
+        };
+        match CoalescedParam::of(&event) {
+            Some(param) => latest[param as usize] = Some(event),
+            None => events.push(event),
         }
     }
-    passthrough_events.extend(last_events.into_values());
-    passthrough_events
+    events.extend(latest.iter_mut().filter_map(Option::take));
 }
 
-/// Enum representing all possible audio commands/events
-#[derive(Debug)]
+/// Whether `event` is a parameter change that coalesces, keeping only the
+/// latest per buffer
+pub fn is_coalesced(event: &AudioEvent) -> bool {
+    CoalescedParam::of(event).is_some()
+}
+
+/// Room for everything one drain of `consumer` can return
+fn event_buffer(consumer: &Consumer<AudioEvent>) -> Vec<AudioEvent> {
+    Vec::with_capacity(consumer.buffer().capacity() + CoalescedParam::COUNT)
+}
+
+/// Take everything queued in the note lane into `events`, in order. Like
+/// `drain_and_coalesce_events`, only what's there now
+fn drain_note_events(consumer: &mut Consumer<AudioEvent>, events: &mut Vec<AudioEvent>) {
+    for _ in 0..consumer.slots() {
+        match consumer.pop() {
+            Ok(event) => events.push(event),
+            Err(_) => break,
+        }
+    }
+}
+
+/// Enum representing all possible audio commands/events. All are handled on
+/// the audio thread; notes, queries and parameter changes without allocating,
+/// graph changes (see `render`) by building their new nodes there
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
 pub enum AudioEvent {
-    PlayNote { frequency: f32 },
-    SetFrequency { frequency: f32 },
-    NoteOff,
-    SetMasterVolume { volume: f32 },
-    SetWaveform { waveform: Waveform },
-    SetAttack { attack: f32 },
-    SetDecay { decay: f32 },
-    SetSustain { sustain: f32 },
-    SetRelease { release: f32 },
-    SetDelayTime { delay_time: f32 },
-    SetDelayFeedback { delay_feedback: f32 },
-    SetDelayMix { delay_mix: f32 },
-    SetFilterCutoff { cutoff: f32 },
-    SetFilterResonance { resonance: f32 },
+    PlayNote {
+        note_id: u32,
+        frequency: f32,
+        velocity: f32,
+    },
+    SetFrequency {
+        frequency: f32,
+    },
+    /// Bend every voice's pitch, from -1.0 (down by the pitch bend range)
+    /// to 1.0 (up by it)
+    SetPitchBend {
+        bend: f32,
+    },
+    NoteOff {
+        note_id: u32,
+    },
+    NoteOffAll,
+    SetMasterVolume {
+        volume: f32,
+    },
+    SetMasterVolumeDb {
+        db: f32,
+    },
+    SetWaveform {
+        waveform: Waveform,
+    },
+    SetAttack {
+        attack: f32,
+    },
+    SetDecay {
+        decay: f32,
+    },
+    SetSustain {
+        sustain: f32,
+    },
+    SetRelease {
+        release: f32,
+    },
+    SetDelayTime {
+        delay_time: f32,
+    },
+    SetDelayFeedback {
+        delay_feedback: f32,
+    },
+    SetDelayMix {
+        delay_mix: f32,
+    },
+    /// Clean digital repeats or a tape echo
+    SetDelayMode {
+        mode: DelayMode,
+    },
+    /// High pass in the delay's feedback path, in Hz. Each repeat loses a
+    /// little more low end. 20 is off
+    SetDelayLowCut {
+        low_cut: f32,
+    },
+    /// Low pass in the delay's feedback path, in Hz. Each repeat comes back
+    /// a little darker. 20000 is off
+    SetDelayHighCut {
+        high_cut: f32,
+    },
+    SetFilterCutoff {
+        cutoff: f32,
+    },
+    SetFilterResonance {
+        resonance: f32,
+    },
+    SetUnisonVoices {
+        voices: usize,
+    },
+    SetUnisonDetune {
+        detune: f32,
+    },
+    SetUnisonSpread {
+        spread: f32,
+    },
+    SetRingModMix {
+        mix: f32,
+    },
+    SetRingModRatio {
+        ratio: f32,
+    },
+    SetVibratoRate {
+        rate: f32,
+    },
+    SetVibratoDepth {
+        depth: f32,
+    },
+    SetVibratoDelay {
+        delay: f32,
+    },
+    SetFilterLfoRate {
+        rate: f32,
+    },
+    SetFilterLfoDepth {
+        depth: f32,
+    },
+    SetFilterLfoSync {
+        beats: f32,
+    },
+    SetTempo {
+        bpm: f32,
+    },
+    SetLfoShape {
+        lfo: LfoTarget,
+        shape: LfoShape,
+    },
+    SetGainTrim {
+        stage: GainStage,
+        db: f32,
+    },
+    SetEnvelopeMode {
+        mode: EnvelopeMode,
+    },
+    SetEnvelopeDelay {
+        delay: f32,
+    },
+    SetEnvelopeHold {
+        hold: f32,
+    },
+    SetFilterVelocityAmount {
+        amount: f32,
+    },
+    SetVoiceSpread {
+        spread: f32,
+    },
+    SetStereoWidth {
+        width: f32,
+    },
+    SetLimiterAttack {
+        attack: f32,
+    },
+    SetLimiterRelease {
+        release: f32,
+    },
+    SetLimiterCeiling {
+        ceiling: f32,
+    },
+    SetSoftClipDrive {
+        drive: f32,
+    },
+    /// Smoothing of the gate into the envelope. Longer suppresses clicks
+    /// when a voice is retriggered, shorter keeps attacks snappy
+    SetGateSmoothing {
+        smoothing: Smoothing,
+    },
+    /// Smoothing of the envelope's output, taking the corners off its stages
+    SetEnvelopeSmoothing {
+        smoothing: Smoothing,
+    },
+    SetKeyZone {
+        zone: KeyZone,
+    },
+    ClearKeyZones,
+    /// Play notes below the split point with a second sound, on voices of their own
+    SetKeySplit {
+        split: KeySplit,
+    },
+    ClearKeySplit,
+    /// Play a second patch on every note, layered over the main one
+    SetLayer {
+        layer: Layer,
+    },
+    /// Level and pan of the layer, and of the main patch under it
+    SetLayerMix {
+        mix: LayerMix,
+        main_mix: LayerMix,
+    },
+    ClearLayer,
+    /// Apply `event` at `frame` on the synth's frame clock (see
+    /// `get_sample_clock`), rather than at the start of the next buffer.
+    /// Frames already past apply straight away
+    At {
+        frame: u64,
+        event: Box<AudioEvent>,
+    },
+    /// Play a note at `start_time` seconds on the transport clock, for
+    /// `duration` seconds
+    ScheduleNote {
+        frequency: f32,
+        velocity: f32,
+        start_time: f64,
+        duration: f32,
+    },
+    /// Replace the step sequencer's patterns and song
+    SetArrangement {
+        arrangement: Arrangement,
+    },
+    /// Start the sequencer at `pattern` (ignored in song mode)
+    SequencerPlay {
+        pattern: usize,
+    },
+    SequencerStop,
+    /// Play the notes one after another, timed by the synth
+    Strum {
+        frequencies: Vec<f32>,
+    },
+    SetStrumSettings {
+        settings: StrumSettings,
+    },
+    /// How quickly voices follow pitch changes, rising and falling. Longer
+    /// hides finger jitter on the fretless keyboard
+    SetFrequencySmoothing {
+        smoothing: Smoothing,
+    },
+    /// Hold live notes back to the next 1/`subdivision` of a beat. 0 turns it off
+    SetQuantize {
+        subdivision: u32,
+    },
+    SetInputLevel {
+        level: f32,
+    },
+    SetEngineMode {
+        mode: EngineMode,
+    },
+    SetInputEnvelopeAmount {
+        destination: ModDestination,
+        amount: f32,
+    },
+    SetVocoderBands {
+        bands: u32,
+    },
+    SetFormantShift {
+        semitones: f32,
+    },
+    /// How far a full pitch bend reaches, in semitones (1 to 24)
+    SetPitchBendRange {
+        semitones: f32,
+    },
+    /// Hearing protection: the loudest the output can get, in dB (-40 to 0),
+    /// whatever the volume, patch and limiter settings
+    SetOutputCeiling {
+        ceiling: f32,
+    },
+    /// In latch mode notes keep sounding after note off, until played again
+    SetLatch {
+        enabled: bool,
+    },
+    /// Freeze the notes sounding now until hold is turned off
+    SetHold {
+        enabled: bool,
+    },
+    /// In mono mode every note plays on one voice, legato
+    SetMono {
+        enabled: bool,
+    },
+    /// Which held note sounds in mono mode
+    SetNotePriority {
+        priority: NotePriority,
+    },
+    /// Switch play mode, applying its profile of playing settings
+    SetInstrumentMode {
+        mode: InstrumentMode,
+    },
+    /// Saturate smoothly ahead of the limiter
+    SetSoftClip {
+        enabled: bool,
+    },
+    /// Take the master limiter out of the signal path
+    SetLimiterBypass {
+        bypass: bool,
+    },
+    /// Rewire the effects in a new order, first to last
+    SetEffectOrder {
+        order: EffectOrder,
+    },
+    /// Put an effect in an insert slot, with its parameters at their defaults
+    SetSlotEffect {
+        slot: usize,
+        effect: SlotEffect,
+    },
+    /// Set one of the parameters of the effect in a slot, by its index in
+    /// the effect's parameter list
+    SetSlotParam {
+        slot: usize,
+        param: usize,
+        value: f32,
+    },
+    /// Make an insert slot a reverb of the given type, with the type's size,
+    /// time and damping
+    SetReverbType {
+        slot: usize,
+        reverb_type: ReverbType,
+    },
+    /// Set an effect's wet/dry mix, 0.0 for all dry to 1.0 for all wet
+    SetEffectMix {
+        effect: Effect,
+        mix: f32,
+    },
+    /// How voice spread places notes: round robin or by pitch
+    SetVoicePanMode {
+        mode: VoicePanMode,
+    },
+    /// How voices are stolen when they're all in use
+    SetStealPolicy {
+        policy: StealPolicy,
+    },
+    /// Play a drum hit. Drums skip the filter and delay
+    TriggerDrum {
+        drum: DrumKind,
+        velocity: f32,
+    },
+    /// Set every sound parameter at once, e.g. when loading a preset
+    LoadPatch {
+        patch: Patch,
+    },
     // Query events:
     GetMasterVolume,
     GetWaveform,
@@ -84,8 +548,71 @@ pub enum AudioEvent {
     GetDelayTime,
     GetDelayFeedback,
     GetDelayMix,
+    GetDelayMode,
+    GetDelayLowCut,
+    GetDelayHighCut,
     GetFilterCutoff,
     GetFilterResonance,
+    GetRingModMix,
+    GetRingModRatio,
+    GetVibratoRate,
+    GetVibratoDepth,
+    GetVibratoDelay,
+    GetFilterLfoRate,
+    GetFilterLfoDepth,
+    GetFilterLfoSync,
+    GetTempo,
+    GetLfoShape {
+        lfo: LfoTarget,
+    },
+    GetEnvelopeMode,
+    GetEnvelopeDelay,
+    GetEnvelopeHold,
+    GetFilterVelocityAmount,
+    GetVoiceSpread,
+    GetStereoWidth,
+    GetLimiterAttack,
+    GetLimiterRelease,
+    GetLimiterCeiling,
+    GetSoftClipDrive,
+    GetGateSmoothing,
+    GetEnvelopeSmoothing,
+    GetPatch,
+    GetUnison,
+    GetStrumSettings,
+    GetFrequencySmoothing,
+    GetQuantize,
+    GetInputLevel,
+    GetEngineMode,
+    GetInputEnvelopeAmount {
+        destination: ModDestination,
+    },
+    GetVocoderBands,
+    GetFormantShift,
+    GetPitchBendRange,
+    GetOutputCeiling,
+    GetLatch,
+    GetHold,
+    GetMono,
+    GetNotePriority,
+    GetInstrumentMode,
+    GetStealPolicy,
+    GetVoicePanMode,
+    GetLimiterBypass,
+    GetEffectOrder,
+    GetEffectSlot {
+        slot: usize,
+    },
+    GetEffectMix {
+        effect: Effect,
+    },
+    GetSoftClip,
+    GetMasterVolumeDb,
+    GetGainTrim {
+        stage: GainStage,
+    },
+    GetKeySplit,
+    GetLayer,
 }
 
 #[derive(Debug)]
@@ -94,16 +621,38 @@ pub enum AudioEventResult {
     ValueF32(f32),
     // ValueString(String),
     ValueWaveform(Waveform),
-    Err(String),
+    ValueBool(bool),
+    ValuePatch(Patch),
+    ValueEnvelopeMode(EnvelopeMode),
+    ValueLfoShape(LfoShape),
+    ValueNotePriority(NotePriority),
+    ValueStealPolicy(StealPolicy),
+    ValueVoicePanMode(VoicePanMode),
+    ValueDelayMode(DelayMode),
+    ValueUnison(Unison),
+    ValueStrumSettings(StrumSettings),
+    ValueSmoothing(Smoothing),
+    ValueU32(u32),
+    ValueEngineMode(EngineMode),
+    ValueInstrumentMode(InstrumentMode),
+    ValueKeySplit(Option<KeySplit>),
+    ValueLayer(Option<Layer>),
+    ValueEffectOrder(EffectOrder),
+    ValueEffectSlot(SlotPatch),
+    /// Static, so answering a query on the audio thread doesn't allocate
+    Err(&'static str),
 }
 
 /// Waveform types available in the synthesizer
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Waveform {
     Sine,
     Square,
     Sawtooth,
     Triangle,
+    /// Plays the loaded sample, see `FunDSPSynth::load_sample`
+    Sampler,
 }
 
 impl Default for Waveform {
@@ -113,12 +662,21 @@ impl Default for Waveform {
 }
 
 impl Waveform {
+    pub const ALL: [Waveform; 5] = [
+        Waveform::Sine,
+        Waveform::Square,
+        Waveform::Sawtooth,
+        Waveform::Triangle,
+        Waveform::Sampler,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Waveform::Sine => "sine",
             Waveform::Square => "square",
             Waveform::Sawtooth => "sawtooth",
             Waveform::Triangle => "triangle",
+            Waveform::Sampler => "sampler",
         }
     }
 
@@ -128,19 +686,216 @@ impl Waveform {
             "square" => Some(Waveform::Square),
             "sawtooth" => Some(Waveform::Sawtooth),
             "triangle" => Some(Waveform::Triangle),
+            "sampler" => Some(Waveform::Sampler),
             _ => None,
         }
     }
 
     /// Create the appropriate oscillator for this waveform
-    fn create_oscillator(&self) -> Box<dyn AudioUnit + Send> {
+    pub(super) fn create_oscillator(&self) -> Box<dyn AudioUnit + Send> {
         match self {
             Waveform::Sine => Box::new(sine()),
             Waveform::Square => Box::new(square()),
             Waveform::Sawtooth => Box::new(saw()),
             Waveform::Triangle => Box::new(triangle()),
+            // The sampler needs a sample, so voices build it themselves
+            // (see Voice::set_sample). This is just a silent stand-in
+            Waveform::Sampler => Box::new(pass() * 0.0),
+        }
+    }
+}
+
+/// Parameters that a key zone can offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneParam {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    FilterCutoff,
+    DelayMix,
+}
+
+impl ZoneParam {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ZoneParam::Attack => "attack",
+            ZoneParam::Decay => "decay",
+            ZoneParam::Sustain => "sustain",
+            ZoneParam::Release => "release",
+            ZoneParam::FilterCutoff => "filter_cutoff",
+            ZoneParam::DelayMix => "delay_mix",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "attack" => Some(ZoneParam::Attack),
+            "decay" => Some(ZoneParam::Decay),
+            "sustain" => Some(ZoneParam::Sustain),
+            "release" => Some(ZoneParam::Release),
+            "filter_cutoff" => Some(ZoneParam::FilterCutoff),
+            "delay_mix" => Some(ZoneParam::DelayMix),
+            _ => None,
+        }
+    }
+}
+
+/// A pitch range in which a parameter offset is applied at note-on
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct KeyZone {
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    pub param: ZoneParam,
+    /// Added to the patch value, in the parameter's own units
+    pub offset: f32,
+}
+
+impl KeyZone {
+    fn contains(&self, frequency: f32) -> bool {
+        frequency >= self.min_frequency && frequency < self.max_frequency
+    }
+}
+
+/// A delay line per channel
+fn stereo_delay(delay_time: f32) -> Box<dyn AudioUnit + Send> {
+    Box::new(delay(delay_time) | delay(delay_time))
+}
+
+/// The delay line for `mode`. The digital one has its time baked in; the
+/// tape one follows `delay_time` as it changes
+fn delay_line(mode: DelayMode, delay_time: &shared::Shared) -> Box<dyn AudioUnit + Send> {
+    match mode {
+        DelayMode::Digital => stereo_delay(delay_time.value()),
+        DelayMode::Tape => Box::new(An(TapeDelayNode::new(delay_time.clone()))),
+    }
+}
+
+/// Master limiter defaults, in seconds
+const DEFAULT_LIMITER_ATTACK: f32 = 0.003;
+const DEFAULT_LIMITER_RELEASE: f32 = 0.050;
+/// Quietest output ceiling, in dB
+const MIN_OUTPUT_CEILING: f32 = -40.0;
+/// Finest quantize grid, in subdivisions of a beat
+const MAX_QUANTIZE: u32 = 16;
+/// A block of silence, for when there's nothing (good) to output
+const SILENCE: [f32; MAX_BUFFER_SIZE] = [0.0; MAX_BUFFER_SIZE];
+
+/// One rendered block, handed out across however many device callbacks it
+/// takes
+struct BlockFifo {
+    left: [f32; MAX_BUFFER_SIZE],
+    right: [f32; MAX_BUFFER_SIZE],
+    /// Frames already handed out
+    read: usize,
+}
+
+impl BlockFifo {
+    fn new() -> Self {
+        BlockFifo {
+            left: [0.0; MAX_BUFFER_SIZE],
+            right: [0.0; MAX_BUFFER_SIZE],
+            read: MAX_BUFFER_SIZE,
+        }
+    }
+
+    fn available(&self) -> usize {
+        MAX_BUFFER_SIZE - self.read
+    }
+}
+
+/// The master limiter, or a plain pass through when bypassed
+fn master_limiter(attack: f32, release: f32, bypass: bool) -> Box<dyn AudioUnit + Send> {
+    if bypass {
+        Box::new(pass() | pass())
+    } else {
+        Box::new(limiter_stereo(attack, release))
+    }
+}
+
+/// A node summing two stereo signals: inputs are (left, right, left, right)
+pub(super) fn stereo_mixer() -> Box<dyn AudioUnit + Send> {
+    Box::new((pass() | pass()) + (pass() | pass()))
+}
+
+/// Sum the voices' outputs, returning the node with the mix
+fn mix_voices(net: &mut Net, voices: &[Voice]) -> NodeId {
+    let mut mix_nodeid = voices[0].output_nodeid;
+    for voice in &voices[1..] {
+        let adder_nodeid = net.push(stereo_mixer());
+        connect_stereo(net, mix_nodeid, adder_nodeid, 0);
+        connect_stereo(net, voice.output_nodeid, adder_nodeid, 2);
+        mix_nodeid = adder_nodeid;
+    }
+    mix_nodeid
+}
+
+/// Give a voice the oscillator for `waveform`. Without a sample loaded the
+/// sampler falls back to `fallback`
+fn set_oscillator(
+    net: &mut Net,
+    voice: &mut Voice,
+    waveform: Waveform,
+    fallback: Waveform,
+    sample: &Option<Arc<Sample>>,
+    unison: &Unison,
+) {
+    match (waveform, sample) {
+        (Waveform::Sampler, Some(sample)) => voice.set_sample(net, sample.clone(), unison),
+        (Waveform::Sampler, None) => voice.set_waveform(net, fallback, unison),
+        (waveform, _) => voice.set_waveform(net, waveform, unison),
+    }
+}
+
+/// Connect both outputs of a stereo node to inputs `port` and `port + 1` of `target`
+pub(super) fn connect_stereo(net: &mut Net, source: NodeId, target: NodeId, port: usize) {
+    net.connect(source, 0, target, port);
+    net.connect(source, 1, target, port + 1);
+}
+
+/// Where an effect sits in the graph: the stereo inputs its signal goes
+/// into, as (node, first port), the node it comes out of all wet, and its
+/// wet/dry crossfade (see `push_dry_wet`)
+struct EffectNodes {
+    inputs: Vec<(NodeId, usize)>,
+    output: NodeId,
+    mix: NodeId,
+}
+
+/// Chain the effects from `input` to `output` in `order`. Each input only
+/// has one source, so connecting over the old wiring is all it takes
+fn wire_effects(
+    net: &mut Net,
+    input: NodeId,
+    output: NodeId,
+    effects: &[EffectNodes],
+    order: &EffectOrder,
+) {
+    let mut source = input;
+    for effect in order.effects() {
+        let nodes = &effects[effect.index()];
+        for &(node, port) in &nodes.inputs {
+            connect_stereo(net, source, node, port);
         }
+        connect_stereo(net, source, nodes.mix, 0);
+        connect_stereo(net, nodes.output, nodes.mix, 2);
+        source = nodes.mix;
     }
+    net.pipe_all(source, output);
+}
+
+/// Envelope times (seconds) and sustain level for a voice. Delay and hold
+/// only apply to the DAHDSR modes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AdsrParams {
+    pub mode: EnvelopeMode,
+    pub delay: f32,
+    pub attack: f32,
+    pub hold: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
 }
 
 /// FunDSP-based synthesizer that can be shared across platforms
@@ -151,38 +906,204 @@ pub struct FunDSPSynth {
     backend: Box<dyn AudioUnit + Send>,
 
     /// Fundsp node ids
-    oscillator_nodeid: NodeId,
-    adsr_nodeid: NodeId,
     delay_nodeid: NodeId,
+    delay_mode: DelayMode,
+    /// The effect chain: each effect's nodes, indexed by `Effect::index`,
+    /// the order they're wired in, and the nodes either end of it
+    effect_nodes: Vec<EffectNodes>,
+    effect_order: EffectOrder,
+    effects_output_nodeid: NodeId,
+    /// Insert slots, hosting any of the registered effects
+    effect_slots: [EffectSlot; MAX_EFFECT_SLOTS],
+
+    /// Polyphonic voices, mixed before the effects
+    voices: Vec<Voice>,
+    /// Incremented on every note on/off, used to order voices by age
+    note_counter: u64,
+    /// Latch (drone) mode
+    latch: bool,
+    /// Hold (freeze) is on
+    hold: bool,
+    /// Which voice to take when they're all busy
+    steal_policy: StealPolicy,
+    /// Voice spread: how wide (0.0 to 1.0) and how notes are placed
+    voice_spread: f32,
+    voice_pan_mode: VoicePanMode,
+    /// Notes panned so far, for round robin spread
+    voice_pan_count: usize,
+    /// Mono (legato) mode: every note plays on the first voice
+    mono: bool,
+    note_priority: NotePriority,
+    /// The play mode whose profile was applied last
+    instrument_mode: InstrumentMode,
+    /// Keys held down in mono mode
+    mono_notes: HeldNotes,
 
     /// Current waveform selection
     current_waveform: Waveform,
-    /// Frequency control for the oscillator
-    frequency_var: shared::Shared,
-    /// Key down state control (0.0 = key up/silent, 1.0 = key down/playing) - used as ADSR gate
-    key_down_var: shared::Shared,
+    /// Unison stacking for every voice's oscillator
+    unison: Unison,
+    /// Parameters shared by every voice (ring mod, vibrato)
+    voice_controls: VoiceControls,
+    /// The second layer's voices, each playing whatever the voice at the
+    /// same index in `voices` plays. Silent while there's no layer
+    layer_voices: Vec<Voice>,
+    layer_controls: VoiceControls,
+    layer: Option<Layer>,
+    /// Left and right gains of the main voices' mix and the layer's
+    main_mix_vars: (shared::Shared, shared::Shared),
+    layer_mix_vars: (shared::Shared, shared::Shared),
     /// Master volume control (0.0 = silent, 1.0 = full volume)
     master_volume_var: shared::Shared,
+    /// Master bus stereo width (0.0 = mono, 1.0 = unchanged, 2.0 = extra wide)
+    stereo_width_var: shared::Shared,
+    /// Trims for each `GainStage`, in dB, and the linear gains they set
+    gain_trims: [f32; 3],
+    gain_trim_vars: [shared::Shared; 3],
+    /// Level of the audio input, mixed in ahead of the delay
+    input_level_var: shared::Shared,
+    /// How much the audio input's envelope modulates each `ModDestination`
+    input_envelope_amount_vars: [shared::Shared; 2],
+    /// Where the voices and audio input meet: mixed in synth mode, or the
+    /// vocoder. Bands are baked into the vocoder node, so changing them replaces it
+    engine_nodeid: NodeId,
+    engine_mode: EngineMode,
+    vocoder_bands: u32,
+    /// Semitones
+    formant_shift_var: shared::Shared,
+
+    /// Soft clipper ahead of the limiter: on / off (1.0 / 0.0), and drive in
+    /// dB along with its linear gain
+    soft_clip_var: shared::Shared,
+    soft_clip_drive: f32,
+    soft_clip_drive_var: shared::Shared,
+
+    /// Master limiter. Attack and release are baked into the node, so
+    /// changing them replaces it
+    limiter_nodeid: NodeId,
+    limiter_attack: f32,
+    limiter_release: f32,
+    limiter_bypass: bool,
+    /// Ceiling in dB, and the linear gains applied either side of the limiter
+    limiter_ceiling: f32,
+    limiter_ceiling_var: shared::Shared,
+    limiter_ceiling_inv_var: shared::Shared,
+    /// Output ceiling in dB, and the linear gains either side of the final clip
+    output_ceiling: f32,
+    output_ceiling_var: shared::Shared,
+    output_ceiling_inv_var: shared::Shared,
     /// ADSR envelope parameters
     attack_var: shared::Shared,
     decay_var: shared::Shared,
     sustain_var: shared::Shared,
     release_var: shared::Shared,
+    envelope_mode: EnvelopeMode,
+    envelope_delay_var: shared::Shared,
+    envelope_hold_var: shared::Shared,
 
     delay_time_var: shared::Shared,
     delay_feedback_var: shared::Shared,
     delay_mix_var: shared::Shared,
+    /// Tone of the feedback path (Hz)
+    delay_low_cut_var: shared::Shared,
+    delay_high_cut_var: shared::Shared,
 
     /// Filter parameters
     filter_cutoff_var: shared::Shared,
     filter_resonance_var: shared::Shared,
+    filter_mix_var: shared::Shared,
+    /// Filter LFO: rate (Hz), depth (octaves) and tempo sync (beats per cycle, 0 = free)
+    filter_lfo_rate_var: shared::Shared,
+    filter_lfo_depth_var: shared::Shared,
+    filter_lfo_sync_var: shared::Shared,
+    /// Filter LFO `LfoShape`, as f32
+    filter_lfo_shape_var: shared::Shared,
+    /// Tempo in BPM, for anything synced to it
+    tempo_var: shared::Shared,
+
+    /// Key zones. Envelope offsets apply per voice, cutoff / delay mix
+    /// offsets follow the most recently played note
+    key_zones: Vec<KeyZone>,
+    filter_cutoff_offset_var: shared::Shared,
+    delay_mix_offset_var: shared::Shared,
+    /// Keyboard split, if any. The lower sound's voices come first in `voices`
+    key_split: Option<KeySplit>,
+    /// Velocity to cutoff: octaves the cutoff moves between velocity 1.0 and 0.0
+    filter_velocity_amount: f32,
+    /// Cutoff multiplier for the most recently played note's velocity
+    filter_velocity_scale_var: shared::Shared,
+    /// Frequency (Hz) of the most recently played note, for effects that
+    /// follow the keyboard
+    note_frequency_var: shared::Shared,
 
     /// Sample rate for proper delay calculation
     sample_rate: f32,
-    /// Whether FunDSP is enabled (can be disabled if panics occur)
+    /// Whether FunDSP is enabled. Turned off only if rebuilding after a panic fails
     enabled: bool,
+    /// Watches the output for NaNs, stuck DC and unexpected silence
+    watchdog: Watchdog,
+    /// Render whole blocks and hand them out through `fifo`, whatever size
+    /// the device asks for. See `set_fixed_blocks`
+    fixed_blocks: bool,
+    fifo: BlockFifo,
+    /// Converts the output to the device's rate, when it can't run at ours.
+    /// See `set_output_sample_rate`
+    resampler: Option<Resampler>,
     // pub queue: AudioEventQueue,
-    event_consumer: rtrb::Consumer<AudioEvent>,
+    /// Shared so a full queue can drop its oldest event without the synth
+    /// lock (see `OverflowPolicy::DropOldest`)
+    event_consumer: SharedConsumer,
+    /// Reused by `drain_and_coalesce_events` each buffer, so it doesn't allocate
+    latest_events: LatestEvents,
+    pending_events: Vec<AudioEvent>,
+    /// Notes and everything else that has to stay in order with them, kept
+    /// apart from the coalesced parameter events so a burst of those can't
+    /// hold a note up. See `set_note_consumer`
+    note_consumer: Option<rtrb::Consumer<AudioEvent>>,
+    /// Sample played by the sampler waveform
+    sample: Option<Arc<Sample>>,
+    /// Strum and scheduled notes waiting to start or stop, and timed events
+    scheduler: Scheduler,
+    strum_settings: StrumSettings,
+    /// Step sequencer, played through the scheduler
+    sequencer: Sequencer,
+    /// Live notes start on the next 1/`quantize` of a beat, or straight away if 0
+    quantize: u32,
+    /// Frame the beat grid is counted from, where the sequencer last started
+    beat_origin: u64,
+    /// Live notes held back by quantizing that haven't been released yet, with
+    /// how many frames they were held back. Their note offs are held back the
+    /// same, so they keep the length they were played with
+    quantize_delays: Vec<(u32, u64)>,
+    /// Pitch bend, -1.0 to 1.0, and the semitones that reaches
+    pitch_bend: f32,
+    pitch_bend_range: f32,
+    /// Frames rendered so far, the clock scheduled notes run on
+    frame_position: u64,
+    /// Seconds rendered so far. Unlike frames, this survives sample rate changes
+    transport_time: f64,
+    /// Where `transport_time` is published for the UI
+    transport_clock: Option<&'static AtomicU64>,
+    /// Triggers for the drum nodes
+    drums: Vec<(DrumKind, DrumTrigger)>,
+    /// Copies the output to the oscilloscope, if one is attached
+    #[cfg(not(target_arch = "wasm32"))]
+    output_tap: Option<OutputTap>,
+    /// Copies the output to a WAV recording, while one is running
+    #[cfg(not(target_arch = "wasm32"))]
+    recording_tap: Option<RecordingTap>,
+    /// Frames from the audio input stream, while it's running
+    #[cfg(not(target_arch = "wasm32"))]
+    audio_input: Option<InputConsumer>,
+    /// Getter queries from the UI, answered between buffers
+    #[cfg(not(target_arch = "wasm32"))]
+    query_server: Option<QueryServer>,
+    /// Advanced by the frames rendered, for timestamping captured events
+    sample_clock: Option<&'static AtomicU64>,
+    /// Where parameters are published for getters to read
+    param_mirror: Option<&'static ParamMirror>,
+    /// The mirror was busy at the last publish, so try again next buffer
+    params_unpublished: bool,
 }
 
 impl FunDSPSynth {
@@ -193,9 +1114,18 @@ impl FunDSPSynth {
     ) -> Result<Self, Box<dyn std::error::Error>> {
         // let queue = AudioEventQueue::new(64);
 
-        let frequency_var = shared(440.0);
-        let key_down_var = shared(0.0); // 0.0 = key up/silent, 1.0 = key down/playing
         let master_volume_var = shared(0.7); // Default to 70% volume
+        let stereo_width_var = shared(1.0);
+        let gain_trim_vars = GainStage::ALL.map(|_| shared(1.0));
+        let input_level_var = shared(1.0);
+        let input_envelope_amount_vars = ModDestination::ALL.map(|_| shared(0.0));
+        let formant_shift_var = shared(0.0);
+        let soft_clip_var = shared(0.0);
+        let soft_clip_drive_var = shared(1.0);
+        let limiter_ceiling_var = shared(1.0);
+        let limiter_ceiling_inv_var = shared(1.0);
+        let output_ceiling_var = shared(1.0);
+        let output_ceiling_inv_var = shared(1.0);
 
         // ADSR envelope parameters with reasonable defaults
         let attack_var = shared(0.02); // 50ms attack
@@ -203,109 +1133,281 @@ impl FunDSPSynth {
         let sustain_var = shared(0.6); // 60% sustain level
         let release_var = shared(0.3); // 300ms release
 
+        // Extra stages for the DAHDSR envelope modes
+        let envelope_delay_var = shared(0.0);
+        let envelope_hold_var = shared(0.0);
+
         let delay_time_var = shared(0.3);
         let delay_feedback_var = shared(0.4);
         let delay_mix_var = shared(0.2);
+        let delay_low_cut_var = shared(MIN_DELAY_CUT);
+        let delay_high_cut_var = shared(MAX_DELAY_CUT);
 
         let filter_cutoff_var = shared(1000.0);
         let filter_resonance_var = shared(0.1);
-
-        let mut net = Net::new(0, 1);
-
-        // Create the synthesis chain dynamically
-        let freq_dc_id = net.push(Box::new(var(&frequency_var)));
-        let freq_smooth_id = net.push(Box::new(afollow(0.001, 0.001)));
-        net.connect(freq_dc_id, 0, freq_smooth_id, 0);
-
+        let filter_mix_var = shared(1.0);
+        let filter_lfo_rate_var = shared(1.0);
+        let filter_lfo_depth_var = shared(0.0);
+        let filter_lfo_sync_var = shared(0.0);
+        let filter_lfo_shape_var = shared(LfoShape::Sine.to_f32());
+        let tempo_var = shared(120.0);
+
+        // Key zone offsets for parameters that are wired directly into the graph
+        let filter_cutoff_offset_var = shared(0.0);
+        let delay_mix_offset_var = shared(0.0);
+        let filter_velocity_scale_var = shared(1.0);
+        let note_frequency_var = shared(220.0);
+
+        // Stereo from the voices onwards, so unison copies can be spread.
+        // The inputs are the audio input, see `set_audio_input`
+        let mut net = Net::new(2, 2);
+
+        // Create the voices and sum them
         let current_waveform = Waveform::default();
-        let oscillator_nodeid = net.push(current_waveform.create_oscillator());
-        net.pipe_all(freq_smooth_id, oscillator_nodeid);
-
-        // Try to avoid clipping
-        let pad_volume_nodeid = net.push(Box::new(pass() * 0.5));
-        net.connect(oscillator_nodeid, 0, pad_volume_nodeid, 0);
-
-        // ADSR stuff
-        let key_down_nodeid = net.push(Box::new(var(&key_down_var)));
-
-        // Smoothing to try to mitigate audible clicks when retriggering the adsr
-        let gate_smoother_id = net.push(Box::new(afollow(0.001, 0.001)));
-        net.connect(key_down_nodeid, 0, gate_smoother_id, 0);
-
-        let adsr_envelope = adsr_live(
-            attack_var.value(),
-            decay_var.value(),
-            sustain_var.value(),
-            release_var.value(),
-        );
-        let adsr_nodeid = net.push(Box::new(adsr_envelope));
-        net.pipe_all(gate_smoother_id, adsr_nodeid);
-
-        // More ADSR smoothing:
-        let env_micro_id = net.push(Box::new(afollow(0.0005, 0.0005)));
-        net.connect(adsr_nodeid, 0, env_micro_id, 0);
-        let vca_nodeid = net.push(Box::new(pass() * pass()));
-        net.connect(pad_volume_nodeid, 0, vca_nodeid, 0);
-        net.connect(env_micro_id, 0, vca_nodeid, 1);
-
-        // Delay stuff
+        let adsr = AdsrParams {
+            mode: EnvelopeMode::default(),
+            delay: envelope_delay_var.value(),
+            attack: attack_var.value(),
+            hold: envelope_hold_var.value(),
+            decay: decay_var.value(),
+            sustain: sustain_var.value(),
+            release: release_var.value(),
+        };
+        let unison = Unison::default();
+        let voice_controls = VoiceControls::default();
+        let voices: Vec<Voice> = (0..MAX_VOICES)
+            .map(|_| Voice::new(&mut net, current_waveform, adsr, &unison, &voice_controls))
+            .collect();
+        let voice_mix_nodeid = mix_voices(&mut net, &voices);
+
+        // The second layer shadows the voices, silent until a layer is set.
+        // Pitch smoothing and bend are playing settings, so they're shared
+        let layer_controls = VoiceControls {
+            frequency_smoothing: voice_controls.frequency_smoothing.clone(),
+            pitch_bend: voice_controls.pitch_bend.clone(),
+            ..VoiceControls::default()
+        };
+        let layer_voices: Vec<Voice> = voices
+            .iter()
+            .map(|voice| {
+                let mut layer_voice =
+                    voice.layer(&mut net, current_waveform, adsr, &unison, &layer_controls);
+                layer_voice.set_silent(&mut net);
+                layer_voice
+            })
+            .collect();
+        let layer_voice_mix_nodeid = mix_voices(&mut net, &layer_voices);
+
+        let stereo_gain = |gain: &shared::Shared| {
+            Box::new((pass() * var(gain)) | (pass() * var(gain))) as Box<dyn AudioUnit + Send>
+        };
+        let balance = |(left, right): &(shared::Shared, shared::Shared)| {
+            Box::new((pass() * var(left)) | (pass() * var(right))) as Box<dyn AudioUnit + Send>
+        };
+        let main_mix_vars = (shared(1.0), shared(1.0));
+        let layer_mix_vars = (shared(0.0), shared(0.0));
+        let main_mix_nodeid = net.push(balance(&main_mix_vars));
+        net.pipe_all(voice_mix_nodeid, main_mix_nodeid);
+        let layer_mix_nodeid = net.push(balance(&layer_mix_vars));
+        net.pipe_all(layer_voice_mix_nodeid, layer_mix_nodeid);
+        let layers_nodeid = net.push(stereo_mixer());
+        connect_stereo(&mut net, main_mix_nodeid, layers_nodeid, 0);
+        connect_stereo(&mut net, layer_mix_nodeid, layers_nodeid, 2);
+
+        let osc_trim_nodeid = net.push(stereo_gain(&gain_trim_vars[GainStage::Oscillator.index()]));
+        net.pipe_all(layers_nodeid, osc_trim_nodeid);
+
+        // Audio input joins here, so it goes through the same effects as the voices
+        let input_level_nodeid = net.push(stereo_gain(&input_level_var));
+        net.connect_input(0, input_level_nodeid, 0);
+        net.connect_input(1, input_level_nodeid, 1);
+        // Its envelope modulates the voices' amplitude and the filter cutoff
+        let follower_nodeid = net.push(Box::new(An(FollowerNode::new(
+            input_envelope_amount_vars.clone(),
+        ))));
+        net.pipe_all(input_level_nodeid, follower_nodeid);
+        // Inputs are (left, right, gain)
+        let voice_vca_nodeid = net.push(Box::new((pass() | pass()) * (pass() ^ pass())));
+        connect_stereo(&mut net, osc_trim_nodeid, voice_vca_nodeid, 0);
+        net.connect(follower_nodeid, 1, voice_vca_nodeid, 2);
+        let engine_nodeid = net.push(stereo_mixer());
+        connect_stereo(&mut net, voice_vca_nodeid, engine_nodeid, 0);
+        connect_stereo(&mut net, input_level_nodeid, engine_nodeid, 2);
+
+        // Delay stuff. Each stage is a pair of nodes, one per channel
 
         // Create mixer to feed delayed signal back to the delay node, mixed with the dry input signal
-        let delay_feedback_gain_nodeid = net.push(Box::new(pass() * var(&delay_feedback_var)));
-        let delay_feedback_mixer_nodeid = net.push(Box::new(pass() + pass()));
-        net.connect(
+        // Repeats go through the tone stage on their way back, so they get
+        // darker and thinner as they fade
+        let delay_feedback_gain_nodeid = net.push(Box::new(
+            An(FeedbackToneNode::new(
+                delay_low_cut_var.clone(),
+                delay_high_cut_var.clone(),
+            )) >> ((pass() * var(&delay_feedback_var)) | (pass() * var(&delay_feedback_var))),
+        ));
+        let delay_feedback_mixer_nodeid = net.push(stereo_mixer());
+        connect_stereo(
+            &mut net,
             delay_feedback_gain_nodeid,
-            0,
             delay_feedback_mixer_nodeid,
-            1,
+            2,
         );
 
         // Create delay node
-        let delay_nodeid = net.push(Box::new(delay(delay_time_var.value())));
+        let delay_nodeid = net.push(delay_line(DelayMode::default(), &delay_time_var));
         // Connect the delay feedback mixer to the delay node
-        net.connect(delay_feedback_mixer_nodeid, 0, delay_nodeid, 0);
-        // Create delay gain node
-        let delay_gain_nodeid = net.push(Box::new(pass() * var(&delay_mix_var)));
-        // Create output mixer node
-        // Mixes direct input, delay output
-        let delay_output_mixer_nodeid = net.push(Box::new(pass() + pass()));
-        // Wire direct input into output mixer node:
-        net.connect(vca_nodeid, 0, delay_output_mixer_nodeid, 0);
-        // Wire input into delay feedback mixer
-        net.connect(vca_nodeid, 0, delay_feedback_mixer_nodeid, 0);
-        // Wire delay output into delay mix node
-        net.connect(delay_nodeid, 0, delay_gain_nodeid, 0);
-        // Wire "gained" delay output into delay outputmixer node
-        net.connect(delay_gain_nodeid, 0, delay_output_mixer_nodeid, 1);
+        net.pipe_all(delay_feedback_mixer_nodeid, delay_nodeid);
+        // Crossfade between the delay's input (dry) and its output, the mix
+        // moved by key zones. The input's wired up with the rest of the chain below
+        let delay_mix_nodeid = push_dry_wet(
+            &mut net,
+            Box::new((var(&delay_mix_var) + var(&delay_mix_offset_var)) >> clip_to(0.0, 1.0)),
+        );
 
         // Wire delay output into delay feedback mixer
-        net.connect(delay_nodeid, 0, delay_feedback_gain_nodeid, 0);
-        // net.connect(delay_feedback_mixer_nodeid, 0, delay_mixer_nodeid, 2);
-
-        // Filter
-        let filter_nodeid = net.push(Box::new(lowpass()));
-        net.connect(delay_output_mixer_nodeid, 0, filter_nodeid, 0);
-        let filter_cutoff_nodeid = net.push(Box::new(var(&filter_cutoff_var)));
+        net.pipe_all(delay_nodeid, delay_feedback_gain_nodeid);
+
+        // Filter. Inputs are (left, cutoff, resonance, right, cutoff, resonance)
+        let pre_filter_trim_nodeid =
+            net.push(stereo_gain(&gain_trim_vars[GainStage::PreFilter.index()]));
+        let filter_nodeid = net.push(Box::new(lowpass() | lowpass()));
+        net.connect(pre_filter_trim_nodeid, 0, filter_nodeid, 0);
+        net.connect(pre_filter_trim_nodeid, 1, filter_nodeid, 3);
+        let filter_lfo = CutoffLfoNode::new(
+            filter_lfo_rate_var.clone(),
+            filter_lfo_depth_var.clone(),
+            filter_lfo_sync_var.clone(),
+            tempo_var.clone(),
+            filter_lfo_shape_var.clone(),
+        );
+        let filter_base_cutoff_nodeid = net.push(Box::new(
+            (var(&filter_cutoff_var) + var(&filter_cutoff_offset_var))
+                * var(&filter_velocity_scale_var),
+        ));
+        // Inputs are (cutoff, input envelope multiplier)
+        let filter_cutoff_nodeid = net.push(Box::new(
+            (pass() * pass()) >> An(filter_lfo) >> clip_to(20.0, 20000.0),
+        ));
+        net.connect(filter_base_cutoff_nodeid, 0, filter_cutoff_nodeid, 0);
+        net.connect(follower_nodeid, 0, filter_cutoff_nodeid, 1);
         net.connect(filter_cutoff_nodeid, 0, filter_nodeid, 1);
+        net.connect(filter_cutoff_nodeid, 0, filter_nodeid, 4);
         let filter_resonance_nodeid = net.push(Box::new(var(&filter_resonance_var)));
         net.connect(filter_resonance_nodeid, 0, filter_nodeid, 2);
+        net.connect(filter_resonance_nodeid, 0, filter_nodeid, 5);
+        let filter_mix_nodeid = push_dry_wet(&mut net, Box::new(var(&filter_mix_var)));
+
+        // Drums join after the filter, so the beat doesn't follow the synth's tone.
+        // They're mono, so they go to the centre
+        let mut drums = Vec::with_capacity(DrumKind::ALL.len());
+        let mut drum_mix_nodeid = None;
+        for kind in DrumKind::ALL {
+            let (node, trigger) = drum(kind);
+            let drum_nodeid = net.push(Box::new(An(node)));
+            drum_mix_nodeid = Some(match drum_mix_nodeid {
+                None => drum_nodeid,
+                Some(mix_nodeid) => {
+                    let adder_nodeid = net.push(Box::new(pass() + pass()));
+                    net.connect(mix_nodeid, 0, adder_nodeid, 0);
+                    net.connect(drum_nodeid, 0, adder_nodeid, 1);
+                    adder_nodeid
+                }
+            });
+            drums.push((kind, trigger));
+        }
+        let drum_mix_nodeid = drum_mix_nodeid.unwrap();
+        let post_fx_trim_nodeid = net.push(stereo_gain(&gain_trim_vars[GainStage::PostFx.index()]));
+
+        // Chain the effects, from the engine to the post-effects trim. The
+        // insert slots start empty
+        let effect_slots =
+            [(); MAX_EFFECT_SLOTS].map(|_| EffectSlot::new(&mut net, &note_frequency_var));
+        let mut effect_nodes = vec![
+            EffectNodes {
+                inputs: vec![(pre_filter_trim_nodeid, 0)],
+                output: filter_nodeid,
+                mix: filter_mix_nodeid,
+            },
+            EffectNodes {
+                inputs: vec![(delay_feedback_mixer_nodeid, 0)],
+                output: delay_nodeid,
+                mix: delay_mix_nodeid,
+            },
+        ];
+        effect_nodes.extend(effect_slots.iter().map(|slot| EffectNodes {
+            inputs: vec![(slot.nodeid(), 0)],
+            output: slot.nodeid(),
+            mix: slot.mix_nodeid(),
+        }));
+        let effect_order = EffectOrder::default();
+        wire_effects(
+            &mut net,
+            engine_nodeid,
+            post_fx_trim_nodeid,
+            &effect_nodes,
+            &effect_order,
+        );
+        let drum_bus_nodeid = net.push(stereo_mixer());
+        connect_stereo(&mut net, post_fx_trim_nodeid, drum_bus_nodeid, 0);
+        net.connect(drum_mix_nodeid, 0, drum_bus_nodeid, 2);
+        net.connect(drum_mix_nodeid, 0, drum_bus_nodeid, 3);
 
-        let master_vol_nodeid = net.push(Box::new(split() >> (pass() * var(&master_volume_var))));
-        net.pipe_all(filter_nodeid, master_vol_nodeid);
+        let widener_nodeid = net.push(Box::new(An(WidenerNode::new(stereo_width_var.clone()))));
+        net.pipe_all(drum_bus_nodeid, widener_nodeid);
 
-        let dcblock_id = net.push(Box::new(dcblock()));
-        net.pipe_all(master_vol_nodeid, dcblock_id);
+        let master_vol_nodeid = net.push(Box::new(
+            (pass() * var(&master_volume_var)) | (pass() * var(&master_volume_var)),
+        ));
+        net.pipe_all(widener_nodeid, master_vol_nodeid);
 
-        let limiter_id = net.push(Box::new(limiter(0.003, 0.050)));
-        net.pipe_all(dcblock_id, limiter_id);
+        let dcblock_id = net.push(Box::new(dcblock() | dcblock()));
+        net.pipe_all(master_vol_nodeid, dcblock_id);
 
-        net.pipe_output(limiter_id);
+        let soft_clip_id = net.push(Box::new(An(SoftClipNode::new(
+            soft_clip_drive_var.clone(),
+            soft_clip_var.clone(),
+        ))));
+        net.pipe_all(dcblock_id, soft_clip_id);
+
+        // The limiter holds its input under 1.0, so the ceiling is set by
+        // scaling up going in and back down coming out
+        let ceiling_in_id = net.push(Box::new(
+            (pass() * var(&limiter_ceiling_inv_var)) | (pass() * var(&limiter_ceiling_inv_var)),
+        ));
+        net.pipe_all(soft_clip_id, ceiling_in_id);
+
+        let limiter_id = net.push(master_limiter(
+            DEFAULT_LIMITER_ATTACK,
+            DEFAULT_LIMITER_RELEASE,
+            false,
+        ));
+        net.pipe_all(ceiling_in_id, limiter_id);
+
+        let ceiling_out_id = net.push(Box::new(
+            (pass() * var(&limiter_ceiling_var)) | (pass() * var(&limiter_ceiling_var)),
+        ));
+        net.pipe_all(limiter_id, ceiling_out_id);
+
+        // Last line of defence, even with the limiter bypassed. Done here
+        // rather than per sample on the way out, so it runs a block at a time.
+        // Clips at the output ceiling, scaled like the limiter's
+        let clip_id = net.push(Box::new(
+            (pass() * var(&output_ceiling_inv_var)
+                >> clip_to(-1.0, 1.0)
+                >> pass() * var(&output_ceiling_var))
+                | (pass() * var(&output_ceiling_inv_var)
+                    >> clip_to(-1.0, 1.0)
+                    >> pass() * var(&output_ceiling_var)),
+        ));
+        net.pipe_all(ceiling_out_id, clip_id);
+
+        net.pipe_output(clip_id);
 
         let mut backend = net.backend();
         backend.set_sample_rate(sample_rate as f64);
         backend.reset();
 
-        println!(
+        info!(
             "🎵 FunDSP initialized at {} Hz sample rate with {} waveform",
             sample_rate,
             current_waveform.as_str()
@@ -314,229 +1416,1878 @@ impl FunDSPSynth {
         Ok(FunDSPSynth {
             net,
             backend: Box::new(backend),
-            oscillator_nodeid,
-            adsr_nodeid,
             delay_nodeid,
+            delay_mode: DelayMode::default(),
+            effect_nodes,
+            effect_order,
+            effects_output_nodeid: post_fx_trim_nodeid,
+            effect_slots,
+
+            voices,
+            note_counter: 0,
+            latch: false,
+            hold: false,
+            steal_policy: StealPolicy::default(),
+            voice_spread: 0.0,
+            voice_pan_mode: VoicePanMode::default(),
+            voice_pan_count: 0,
+            mono: false,
+            note_priority: NotePriority::default(),
+            instrument_mode: InstrumentMode::default(),
+            mono_notes: HeldNotes::default(),
 
             current_waveform,
-            frequency_var,
-            key_down_var,
+            unison,
+            voice_controls,
+            layer_voices,
+            layer_controls,
+            layer: None,
+            main_mix_vars,
+            layer_mix_vars,
             master_volume_var,
+            stereo_width_var,
+            gain_trims: [0.0; 3],
+            gain_trim_vars,
+            input_level_var,
+            input_envelope_amount_vars,
+            engine_nodeid,
+            engine_mode: EngineMode::default(),
+            vocoder_bands: DEFAULT_VOCODER_BANDS,
+            formant_shift_var,
+
+            soft_clip_var,
+            soft_clip_drive: 0.0,
+            soft_clip_drive_var,
+
+            limiter_nodeid: limiter_id,
+            limiter_attack: DEFAULT_LIMITER_ATTACK,
+            limiter_release: DEFAULT_LIMITER_RELEASE,
+            limiter_bypass: false,
+            limiter_ceiling: 0.0,
+            limiter_ceiling_var,
+            limiter_ceiling_inv_var,
+            output_ceiling: 0.0,
+            output_ceiling_var,
+            output_ceiling_inv_var,
 
             attack_var,
             decay_var,
             sustain_var,
             release_var,
+            envelope_mode: EnvelopeMode::default(),
+            envelope_delay_var,
+            envelope_hold_var,
 
             delay_time_var,
             delay_feedback_var,
             delay_mix_var,
+            delay_low_cut_var,
+            delay_high_cut_var,
 
             filter_cutoff_var,
             filter_resonance_var,
+            filter_mix_var,
+            filter_lfo_rate_var,
+            filter_lfo_depth_var,
+            filter_lfo_sync_var,
+            filter_lfo_shape_var,
+            tempo_var,
+
+            key_zones: Vec::new(),
+            filter_cutoff_offset_var,
+            delay_mix_offset_var,
+            key_split: None,
+            filter_velocity_amount: 0.0,
+            filter_velocity_scale_var,
+            note_frequency_var,
 
             sample_rate,
             enabled: true,
-            event_consumer,
+            watchdog: Watchdog::new(sample_rate),
+            fixed_blocks: true,
+            fifo: BlockFifo::new(),
+            resampler: None,
+            latest_events: [NO_EVENT; CoalescedParam::COUNT],
+            pending_events: event_buffer(&event_consumer),
+            note_consumer: None,
+            event_consumer: Arc::new(Mutex::new(event_consumer)),
+            sample: None,
+            scheduler: Scheduler::default(),
+            strum_settings: StrumSettings::default(),
+            sequencer: Sequencer::default(),
+            quantize: 0,
+            beat_origin: 0,
+            quantize_delays: Vec::with_capacity(MAX_VOICES),
+            pitch_bend: 0.0,
+            pitch_bend_range: DEFAULT_PITCH_BEND_RANGE,
+            frame_position: 0,
+            transport_time: 0.0,
+            transport_clock: None,
+            drums,
+            #[cfg(not(target_arch = "wasm32"))]
+            output_tap: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            recording_tap: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            audio_input: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            query_server: None,
+            sample_clock: None,
+            param_mirror: None,
+            params_unpublished: false,
         })
     }
 
+    /// Fill a mono buffer
     #[allow(dead_code)]
     pub fn fill_buffer(&mut self, output: &mut [f32]) {
-        if !self.enabled {
-            output.fill(0.0);
+        self.render_output(output.len(), |offset, left, right| {
+            let output = &mut output[offset..offset + left.len()];
+            for ((out, left), right) in output.iter_mut().zip(left).zip(right) {
+                *out = 0.5 * (left + right);
+            }
+        });
+    }
+
+    /// Fill an interleaved buffer. Left and right go to the first two
+    /// channels; any further channels are left silent
+    #[allow(dead_code)]
+    pub fn fill_interleaved(&mut self, output: &mut [f32], channels: usize) {
+        if channels < 2 {
+            self.fill_buffer(output);
             return;
         }
-        let events = drain_and_coalesce_events(&mut self.event_consumer);
-        for event in events {
-            self.handle_event(event);
-        }
-
-        let mut i = 0;
-        let mut block = BufferArray::<U1>::new();
-        let input = BufferRef::empty();
-        while i < output.len() {
-            // Work in chunks up to MAX_BUFFER_SIZE (usually 64 samples)
-            let n = std::cmp::min(output.len() - i, MAX_BUFFER_SIZE);
-            self.backend.process(n, &input, &mut block.buffer_mut());
+        let frames = output.len() / channels;
+        self.render_output(frames, |offset, left, right| {
+            let output = &mut output[offset * channels..(offset + left.len()) * channels];
+            for ((frame, &left), &right) in output.chunks_exact_mut(channels).zip(left).zip(right) {
+                frame[0] = left;
+                frame[1] = right;
+                frame[2..].fill(0.0);
+            }
+        });
+    }
 
-            // Copy from the block into the output buffer, clamping each sample
-            let ch = block.buffer_ref().channel_f32(0);
-            for (dst, &src) in output[i..i + n].iter_mut().zip(&ch[..n]) {
-                *dst = src.clamp(-1.0, 1.0);
+    /// Fill a buffer of (left, right) frames
+    #[allow(dead_code)]
+    pub fn fill_stereo(&mut self, output: &mut [(f32, f32)]) {
+        self.render_output(output.len(), |offset, left, right| {
+            let output = &mut output[offset..offset + left.len()];
+            for ((out, &left), &right) in output.iter_mut().zip(left).zip(right) {
+                *out = (left, right);
             }
+        });
+    }
+
+    /// Render whole MAX_BUFFER_SIZE blocks only (on by default). Odd device
+    /// buffer sizes (e.g. 441 frames) are then served from the last block
+    /// rendered, rather than making fundsp render short ragged blocks, and
+    /// events are picked up on a steady grid. Costs up to a block of latency.
+    /// Offline rendering turns it off, to apply events exactly where asked
+    pub fn set_fixed_blocks(&mut self, enabled: bool) {
+        self.fixed_blocks = enabled;
+        self.fifo = BlockFifo::new();
+    }
 
+    /// `render`, through the block FIFO if `fixed_blocks` is on
+    fn render_output(&mut self, frames: usize, mut write: impl FnMut(usize, &[f32], &[f32])) {
+        if let Some(mut resampler) = self.resampler.take() {
+            // Resampled frames go out a block at a time, like rendered ones
+            let mut left = [0.0; MAX_BUFFER_SIZE];
+            let mut right = [0.0; MAX_BUFFER_SIZE];
+            let mut i = 0;
+            while i < frames {
+                let n = (frames - i).min(MAX_BUFFER_SIZE);
+                for j in 0..n {
+                    (left[j], right[j]) = resampler.next(|| self.next_fifo_frame());
+                }
+                write(i, &left[..n], &right[..n]);
+                i += n;
+            }
+            self.resampler = Some(resampler);
+            return;
+        }
+        if !self.fixed_blocks {
+            self.render(frames, write);
+            return;
+        }
+        let mut i = 0;
+        while i < frames {
+            if self.fifo.available() == 0 {
+                self.refill_fifo();
+            }
+            let n = (frames - i).min(self.fifo.available());
+            let from = self.fifo.read;
+            write(
+                i,
+                &self.fifo.left[from..from + n],
+                &self.fifo.right[from..from + n],
+            );
+            self.fifo.read += n;
             i += n;
         }
     }
 
-    /// Update the backend sample rate and reset safely.
-    #[allow(dead_code)]
-    pub fn set_sample_rate(&mut self, sample_rate: f32) {
-        if sample_rate > 0.0 {
-            self.sample_rate = sample_rate;
-            self.backend.set_sample_rate(sample_rate as f64);
-            self.backend.reset();
+    /// Render the next block into the FIFO
+    fn refill_fifo(&mut self) {
+        let mut left = [0.0; MAX_BUFFER_SIZE];
+        let mut right = [0.0; MAX_BUFFER_SIZE];
+        self.render(MAX_BUFFER_SIZE, |offset, l, r| {
+            left[offset..offset + l.len()].copy_from_slice(l);
+            right[offset..offset + r.len()].copy_from_slice(r);
+        });
+        self.fifo = BlockFifo {
+            left,
+            right,
+            read: 0,
+        };
+    }
+
+    /// One frame from the FIFO, rendering another block if it's empty
+    fn next_fifo_frame(&mut self) -> (f32, f32) {
+        if self.fifo.available() == 0 {
+            self.refill_fifo();
         }
+        let frame = (
+            self.fifo.left[self.fifo.read],
+            self.fifo.right[self.fifo.read],
+        );
+        self.fifo.read += 1;
+        frame
     }
 
-    /// Switch to a new waveform using dynamic Net replacement
-    pub fn set_waveform(&mut self, new_waveform: Waveform) {
-        if new_waveform == self.current_waveform || !self.enabled {
-            return; // No change needed
+    /// Hand `write` silence for frames `from` to `to`, a block at a time
+    fn write_silence(from: usize, to: usize, write: &mut impl FnMut(usize, &[f32], &[f32])) {
+        let mut i = from;
+        while i < to {
+            let n = (to - i).min(MAX_BUFFER_SIZE);
+            write(i, &SILENCE[..n], &SILENCE[..n]);
+            i += n;
         }
+    }
 
-        // Replace the oscillator node with the new waveform
-        self.net
-            .replace(self.oscillator_nodeid, new_waveform.create_oscillator());
+    /// The event queue's consumer, for dropping events when it's full
+    pub fn event_consumer(&self) -> SharedConsumer {
+        self.event_consumer.clone()
+    }
 
-        // Commit the changes to the backend
-        self.net.commit();
+    /// Process pending events, then render `frames` frames. `write` is handed
+    /// each block as (first frame index, left, right); samples are already
+    /// within -1.0 to 1.0
+    fn render(&mut self, frames: usize, mut write: impl FnMut(usize, &[f32], &[f32])) {
+        if !self.enabled {
+            Self::write_silence(0, frames, &mut write);
+            return;
+        }
+        // No clock in the browser (Instant panics there), so no load meter either
+        #[cfg(not(target_arch = "wasm32"))]
+        let started = std::time::Instant::now();
+        let _denormals = DenormalGuard::new();
+        // Draining, coalescing, parameter changes and the mirror don't
+        // allocate. Events that change the graph (waveform, unison, slot
+        // effects, delay mode, key split, layer, samples) still build new
+        // nodes here, and a DSP panic rebuilds the whole synth
+        let mut events = std::mem::take(&mut self.pending_events);
+        // The note lane first, in send order, then the coalesced parameter
+        // changes, which were applied after other events even in one queue
+        if let Some(notes) = &mut self.note_consumer {
+            drain_note_events(notes, &mut events);
+        }
+        // Only held elsewhere while an overflowing queue drops an event, so
+        // if it's busy the events wait for the next buffer
+        if let Ok(mut consumer) = self.event_consumer.try_lock() {
+            drain_and_coalesce_events(&mut consumer, &mut self.latest_events, &mut events);
+        }
+        let changed = !events.is_empty();
+        for event in events.drain(..) {
+            self.handle_live_event(event);
+        }
+        self.pending_events = events;
+        if changed || self.params_unpublished {
+            self.publish_params();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(mut queries) = self.query_server.take() {
+            queries.serve(|event| self.handle_event(event));
+            self.query_server = Some(queries);
+        }
 
-        self.current_waveform = new_waveform;
+        let mut i = 0;
+        let mut block = BufferArray::<U2>::new();
+        // Audio input frames, silent without one
+        #[cfg_attr(target_arch = "wasm32", allow(unused_mut))]
+        let mut input = BufferArray::<U2>::new();
+        while i < frames {
+            self.run_scheduled();
+            // Work in chunks up to MAX_BUFFER_SIZE (usually 64 samples),
+            // stopping early so the next scheduled note starts on time
+            let mut n = std::cmp::min(frames - i, MAX_BUFFER_SIZE);
+            if let Some(next) = self.scheduler.next_frame() {
+                n = n.min((next - self.frame_position) as usize).max(1);
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(audio_input) = &mut self.audio_input {
+                let mut left = [0.0; MAX_BUFFER_SIZE];
+                let mut right = [0.0; MAX_BUFFER_SIZE];
+                audio_input.read(&mut left[..n], &mut right[..n]);
+                let mut buffer = input.buffer_mut();
+                buffer.channel_f32_mut(0)[..n].copy_from_slice(&left[..n]);
+                buffer.channel_f32_mut(1)[..n].copy_from_slice(&right[..n]);
+            }
+            let processed = catch_unwind(AssertUnwindSafe(|| {
+                self.backend
+                    .process(n, &input.buffer_ref(), &mut block.buffer_mut());
+            }));
+            if processed.is_err() {
+                // The graph's state can't be trusted after a panic: finish the
+                // buffer with silence and start again from a fresh one
+                error!("💥 DSP panic, rebuilding the synth");
+                STATS.record_dsp_panic();
+                Self::write_silence(i, frames, &mut write);
+                self.advance((frames - i) as u64);
+                self.recover();
+                break;
+            }
+
+            let buffer = block.buffer_ref();
+            let (left, right) = (buffer.channel_f32(0), buffer.channel_f32(1));
+            let fault = self
+                .watchdog
+                .check(&left[..n], &right[..n], self.expects_sound());
+            if let Some(fault) = fault {
+                // Clears every node's state: filters, delay lines, envelopes
+                self.backend.reset();
+                STATS.record_watchdog_reset(fault);
+            }
+            // Hand the block over whole. The graph ends in a clipper, so it's
+            // already in range; anything non-finite is replaced with silence
+            let (left, right) = if fault == Some(WatchdogFault::NonFinite) {
+                (&SILENCE[..n], &SILENCE[..n])
+            } else {
+                (&left[..n], &right[..n])
+            };
+            write(i, left, right);
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(tap) = &mut self.output_tap {
+                for (left, right) in left.iter().zip(right) {
+                    tap.push(0.5 * (left + right));
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(tap) = &mut self.recording_tap {
+                for (&left, &right) in left.iter().zip(right) {
+                    tap.push(left, right);
+                }
+            }
+
+            i += n;
+            self.advance(n as u64);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self
+            .recording_tap
+            .as_ref()
+            .map_or(false, |tap| tap.is_finished())
+        {
+            self.recording_tap = None;
+        }
+
+        if let Some(clock) = self.sample_clock {
+            clock.fetch_add(frames as u64, Ordering::Relaxed);
+        }
+        if let Some(clock) = self.transport_clock {
+            clock.store(self.transport_time.to_bits(), Ordering::Relaxed);
+        }
 
-        println!(
-            "🔄 Switched to {} waveform using Net.replace()",
-            new_waveform.as_str()
+        #[cfg(not(target_arch = "wasm32"))]
+        DSP_LOAD.record(
+            started.elapsed().as_secs_f32(),
+            frames as f32 / self.sample_rate,
         );
     }
 
-    /// Get the current waveform
-    pub fn get_waveform(&self) -> Waveform {
-        self.current_waveform
+    /// Replace this synth with a freshly built one after a panic in the
+    /// graph. Everything the session saves carries over (see `SynthState`),
+    /// along with the sample, latch and pitch bend; held notes start afresh
+    fn recover(&mut self) {
+        let (_, placeholder) = rtrb::RingBuffer::new(1);
+        let mut synth = match FunDSPSynth::new(self.sample_rate, placeholder) {
+            Ok(synth) => synth,
+            Err(e) => {
+                error!("❌ Failed to rebuild the synth, audio disabled: {}", e);
+                self.enabled = false;
+                return;
+            }
+        };
+
+        synth.event_consumer = self.event_consumer.clone();
+        synth.pending_events = std::mem::take(&mut self.pending_events);
+        // The sample first, so a sampler patch has something to play
+        synth.sample = self.sample.take();
+        SynthState::capture(self).apply(&mut synth);
+        synth.set_latch(self.latch);
+        synth.set_pitch_bend(self.pitch_bend);
+        synth.fixed_blocks = self.fixed_blocks;
+        if let Some(notes) = self.note_consumer.take() {
+            synth.set_note_consumer(notes);
+        }
+        synth.resampler = self.resampler.take();
+        // Its next step was in the old scheduler, so it stops
+        synth.sequencer = std::mem::take(&mut self.sequencer);
+        synth.sequencer.stop();
+        synth.beat_origin = self.beat_origin;
+
+        synth.sample_clock = self.sample_clock;
+        synth.param_mirror = self.param_mirror;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            synth.output_tap = self.output_tap.take();
+            synth.recording_tap = self.recording_tap.take();
+            synth.audio_input = self.audio_input.take();
+            synth.query_server = self.query_server.take();
+        }
+        synth.frame_position = self.frame_position;
+        synth.transport_time = self.transport_time;
+        synth.transport_clock = self.transport_clock;
+        synth.publish_params();
+        *self = synth;
+        info!("🔄 Synth rebuilt after a DSP panic");
     }
 
-    /// Play a note at the specified frequency
-    pub fn play_note(&mut self, frequency: f32) {
-        if self.enabled {
-            self.frequency_var.set_value(frequency);
-            self.key_down_var.set_value(1.0); // Gate on - triggers ADSR attack
+    /// Whether the output should be audible: a note is held, and nothing
+    /// that can legitimately be silent (volume, a sample that may have ended,
+    /// an envelope delay stage) is in play
+    fn expects_sound(&self) -> bool {
+        self.master_volume_var.value() > 0.0
+            && self.current_waveform != Waveform::Sampler
+            && self.envelope_mode == EnvelopeMode::Adsr
+            // The vocoder is silent while the input is
+            && self.engine_mode == EngineMode::Synth
+            && self.voices.iter().any(|v| v.is_held())
+    }
+
+    /// Start or stop any scheduled notes that are due
+    fn run_scheduled(&mut self) {
+        let mut changed = false;
+        while let Some(action) = self.scheduler.pop_due(self.frame_position) {
+            match action {
+                ScheduledAction::NoteOn {
+                    note_id,
+                    frequency,
+                    velocity,
+                } => self.play_note(note_id, frequency, velocity),
+                ScheduledAction::NoteOff { note_id } => self.note_off(note_id),
+                ScheduledAction::StolenNoteOn {
+                    voice,
+                    note_id,
+                    frequency,
+                    velocity,
+                } => {
+                    // Skip it if the note was released during the fade
+                    if self.voices[voice].note_id == Some(note_id) {
+                        self.start_voice(voice, note_id, frequency, velocity);
+                    }
+                }
+                ScheduledAction::Event(event) => {
+                    self.handle_event(*event);
+                    changed = true;
+                }
+                ScheduledAction::SequencerStep { generation } => {
+                    if generation == self.sequencer.generation() {
+                        self.sequencer_step();
+                    }
+                }
+            }
         }
+        if changed {
+            self.publish_params();
+        }
+    }
 
-        // println!("Playing frequency: {} Hz", frequency);
+    /// Move the clocks on by `frames` rendered frames
+    fn advance(&mut self, frames: u64) {
+        self.frame_position += frames;
+        self.transport_time += frames as f64 / self.sample_rate as f64;
     }
 
-    /// Set note frequency (for violin / fretless mode)
-    pub fn set_frequency(&mut self, frequency: f32) {
+    /// Queue a note to start at `start_time` on the transport clock (seconds
+    /// played, see `set_transport_clock`) and play for `duration` seconds.
+    /// Start times already past play straight away
+    pub fn schedule_note(&mut self, frequency: f32, velocity: f32, start_time: f64, duration: f32) {
         if self.enabled {
-            self.frequency_var.set_value(frequency);
+            let delay =
+                ((start_time - self.transport_time).max(0.0) * self.sample_rate as f64) as u64;
+            let length = (duration.max(0.0) * self.sample_rate) as u64;
+            self.scheduler.note(
+                self.frame_position + delay,
+                length,
+                frequency,
+                velocity.clamp(0.0, 1.0),
+            );
         }
     }
 
-    /// Stop the current note
-    pub fn note_off(&mut self) {
+    /// Replace the sequencer's patterns and song. Playback carries on from the
+    /// same position, if it still exists
+    pub fn set_arrangement(&mut self, arrangement: Arrangement) {
+        self.sequencer.set_arrangement(arrangement);
+    }
+
+    /// Start the sequencer: from the top of the song in song mode, otherwise
+    /// from `pattern`
+    pub fn sequencer_play(&mut self, pattern: usize) {
         if self.enabled {
-            self.key_down_var.set_value(0.0); // Gate off - triggers ADSR release
+            self.sequencer.start(pattern);
+            self.beat_origin = self.frame_position;
+            self.sequencer_step();
         }
     }
 
-    /// Set master volume (0.0 = silent, 1.0 = full volume)
-    pub fn set_master_volume(&mut self, volume: f32) {
-        // Clamp volume to valid range
-        let clamped_volume = volume.clamp(0.0, 1.0);
+    /// Stop the sequencer. Notes already playing are released as usual
+    pub fn sequencer_stop(&mut self) {
+        self.sequencer.stop();
+    }
 
-        if self.enabled {
-            self.master_volume_var.set_value(clamped_volume);
+    /// Play the sequencer's next step now and schedule the one after, one
+    /// step length (at the current tempo) later
+    fn sequencer_step(&mut self) {
+        let (step, steps_per_beat) = match self.sequencer.next_step() {
+            Some(next) => next,
+            None => {
+                self.sequencer.stop();
+                return;
+            }
+        };
+        let step_frames =
+            ((60.0 / self.get_tempo() / steps_per_beat as f32 * self.sample_rate) as u64).max(1);
+        if let Some(frequency) = step.frequency {
+            let length = ((step_frames as f32 * step.gate) as u64).max(1);
+            self.scheduler
+                .note(self.frame_position, length, frequency, step.velocity);
         }
+        self.scheduler.schedule(
+            self.frame_position + step_frames,
+            ScheduledAction::SequencerStep {
+                generation: self.sequencer.generation(),
+            },
+        );
     }
 
-    /// Get current master volume
-    pub fn get_master_volume(&self) -> f32 {
-        self.master_volume_var.value()
+    /// Queue a strum of `frequencies`, played in order with the current strum settings
+    pub fn strum(&mut self, frequencies: &[f32]) {
+        if self.enabled {
+            self.scheduler.strum(
+                self.frame_position,
+                self.sample_rate,
+                frequencies,
+                &self.strum_settings,
+            );
+        }
     }
 
-    pub fn set_adsr(&mut self) {
-        if !self.enabled {
-            return; // No change needed
-        }
+    pub fn set_strum_settings(&mut self, settings: StrumSettings) {
+        self.strum_settings = settings;
+    }
 
-        let attack = self.attack_var.value();
-        let decay = self.decay_var.value();
-        let sustain = self.sustain_var.value();
-        let release = self.release_var.value();
+    pub fn get_strum_settings(&self) -> StrumSettings {
+        self.strum_settings
+    }
 
-        let new_adsr = Box::new(adsr_live(attack, decay, sustain, release));
-        self.net.replace(self.adsr_nodeid, new_adsr);
+    pub fn set_quantize(&mut self, subdivision: u32) {
+        self.quantize = subdivision.min(MAX_QUANTIZE);
+    }
 
-        self.net.commit();
+    pub fn get_quantize(&self) -> u32 {
+        self.quantize
     }
 
-    pub fn set_attack(&mut self, attack: f32) {
-        println!("Setting attack to {}", attack);
-        let clamped_attack = attack.clamp(0.001, 5.0); // 1ms to 5s
-        self.attack_var.set_value(clamped_attack);
-        self.set_adsr();
+    /// The first frame from now on the quantize grid, counted from where the
+    /// sequencer last started at the current tempo
+    fn next_grid_frame(&self) -> u64 {
+        let grid = 60.0 / self.get_tempo() as f64 / self.quantize as f64 * self.sample_rate as f64;
+        let since_origin = self.frame_position.saturating_sub(self.beat_origin) as f64;
+        self.beat_origin + ((since_origin / grid).ceil() * grid) as u64
     }
 
-    /// Get ADSR attack time
-    pub fn get_attack(&self) -> f32 {
-        self.attack_var.value()
+    /// Handle an event from the queue. Live notes are quantized here, rather
+    /// than in `handle_event`, so timestamped and scheduled ones aren't moved
+    fn handle_live_event(&mut self, event: AudioEvent) {
+        match event {
+            AudioEvent::PlayNote {
+                note_id,
+                frequency,
+                velocity,
+            } if self.quantize > 0 && self.enabled => {
+                self.quantize_delays.retain(|&(id, _)| id != note_id);
+                let start = self.next_grid_frame().max(self.frame_position);
+                if start > self.frame_position {
+                    self.quantize_delays
+                        .push((note_id, start - self.frame_position));
+                    self.scheduler.schedule(
+                        start,
+                        ScheduledAction::NoteOn {
+                            note_id,
+                            frequency,
+                            velocity,
+                        },
+                    );
+                } else {
+                    self.play_note(note_id, frequency, velocity);
+                }
+            }
+            AudioEvent::NoteOff { note_id } => {
+                match self
+                    .quantize_delays
+                    .iter()
+                    .position(|&(id, _)| id == note_id)
+                {
+                    Some(i) => {
+                        let (_, delay) = self.quantize_delays.swap_remove(i);
+                        self.scheduler.schedule(
+                            self.frame_position + delay,
+                            ScheduledAction::NoteOff { note_id },
+                        );
+                    }
+                    None => self.note_off(note_id),
+                }
+            }
+            event => {
+                self.handle_event(event);
+            }
+        }
     }
 
-    /// Set ADSR decay time (in seconds)
-    pub fn set_decay(&mut self, decay: f32) {
-        let clamped_decay = decay.clamp(0.001, 5.0); // 1ms to 5s
-        self.decay_var.set_value(clamped_decay);
-        self.set_adsr();
+    /// Send a copy of the output to the oscilloscope
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_output_tap(&mut self, tap: OutputTap) {
+        self.output_tap = Some(tap);
     }
 
-    /// Get ADSR decay time
-    pub fn get_decay(&self) -> f32 {
-        self.decay_var.value()
+    /// Copy the output to a recording, starting with the next frame rendered
+    /// (`get_frame_position`). The tap is let go once the recording finishes
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_recording_tap(&mut self, tap: RecordingTap) {
+        self.recording_tap = Some(tap);
     }
 
-    /// Set ADSR sustain level (0.0 to 1.0)
-    pub fn set_sustain(&mut self, sustain: f32) {
-        let clamped_sustain = sustain.clamp(0.0, 1.0);
-        self.sustain_var.set_value(clamped_sustain);
-        self.set_adsr();
+    /// Frames rendered so far, the clock `AudioEvent::At` runs on
+    pub fn get_frame_position(&self) -> u64 {
+        self.frame_position
     }
 
-    /// Get ADSR sustain level
-    pub fn get_sustain(&self) -> f32 {
-        self.sustain_var.value()
+    /// Mix frames from `input` in ahead of the effects, or stop if None.
+    /// The input stream has to run at the synth's sample rate
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_audio_input(&mut self, input: Option<InputConsumer>) {
+        self.audio_input = input;
     }
 
-    /// Set ADSR release time (in seconds)
-    pub fn set_release(&mut self, release: f32) {
-        let clamped_release = release.clamp(0.001, 10.0); // 1ms to 10s
-        self.release_var.set_value(clamped_release);
-        self.set_adsr();
+    /// Answer getter queries from `server` at the start of each buffer
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_query_server(&mut self, server: QueryServer) {
+        self.query_server = Some(server);
     }
 
-    /// Get ADSR release time
-    pub fn get_release(&self) -> f32 {
-        self.release_var.value()
+    /// Take notes, and the events that must stay in order with them, from
+    /// `consumer` as well as the main queue. They're handled before the
+    /// coalesced parameter events each buffer
+    pub fn set_note_consumer(&mut self, consumer: rtrb::Consumer<AudioEvent>) {
+        self.pending_events
+            .reserve(self.pending_events.capacity() + consumer.buffer().capacity());
+        self.note_consumer = Some(consumer);
     }
 
-    /// Set delay time (in seconds)
-    pub fn set_delay_time(&mut self, delay_time: f32) {
-        if !self.enabled {
-            return; // No change needed
-        }
-        self.delay_time_var.set_value(delay_time.clamp(0.0, 5.0)); // Clamp to 0-5 seconds
+    /// Count rendered frames on `clock`
+    pub fn set_sample_clock(&mut self, clock: &'static AtomicU64) {
+        self.sample_clock = Some(clock);
+    }
 
-        let new_delay = Box::new(delay(self.delay_time_var.value()));
-        self.net.replace(self.delay_nodeid, new_delay);
-        self.net.commit();
+    /// Publish seconds played (as f64 bits) on `clock`
+    pub fn set_transport_clock(&mut self, clock: &'static AtomicU64) {
+        self.transport_clock = Some(clock);
     }
 
-    /// Get delay time (in seconds)
-    pub fn get_delay_time(&self) -> f32 {
-        self.delay_time_var.value()
+    /// Publish parameters to `mirror` whenever they change
+    pub fn set_param_mirror(&mut self, mirror: &'static ParamMirror) {
+        self.param_mirror = Some(mirror);
+        self.publish_params();
     }
 
-    /// Set delay feedback (0.0 to 1.0)
-    pub fn set_delay_feedback(&mut self, feedback: f32) {
-        if !self.enabled {
-            return; // No change needed
+    pub fn publish_params(&mut self) {
+        if let Some(mirror) = self.param_mirror {
+            self.params_unpublished = !mirror.publish(self.param_snapshot());
         }
-        self.delay_feedback_var.set_value(feedback.clamp(0.0, 1.0));
     }
 
-    /// Get delay feedback
-    pub fn get_delay_feedback(&self) -> f32 {
-        self.delay_feedback_var.value()
+    /// Every parameter a getter can ask for
+    pub fn param_snapshot(&self) -> ParamSnapshot {
+        ParamSnapshot {
+            patch: self.get_patch(),
+            tempo: self.get_tempo(),
+            latch: self.latch,
+            hold: self.hold,
+            mono: self.mono,
+            note_priority: self.note_priority,
+            instrument_mode: self.instrument_mode,
+            steal_policy: self.steal_policy,
+            limiter_attack: self.limiter_attack,
+            limiter_release: self.limiter_release,
+            limiter_ceiling: self.limiter_ceiling,
+            limiter_bypass: self.limiter_bypass,
+            gain_trims: self.gain_trims,
+            strum_settings: self.strum_settings,
+            frequency_smoothing: self.get_frequency_smoothing(),
+            quantize: self.quantize,
+            input_level: self.get_input_level(),
+            input_envelope_amounts: ModDestination::ALL
+                .map(|destination| self.get_input_envelope_amount(destination)),
+            engine_mode: self.engine_mode,
+            vocoder_bands: self.vocoder_bands,
+            formant_shift: self.get_formant_shift(),
+            pitch_bend_range: self.pitch_bend_range,
+            output_ceiling: self.output_ceiling,
+            key_split: self.key_split,
+            layer: self.layer,
+        }
+    }
+
+    /// Render at `sample_rate`, with the output going out at the same rate.
+    /// Resets the graph, unless the rate hasn't changed
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.resampler = None;
+        if sample_rate > 0.0 && sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.watchdog.set_sample_rate(sample_rate);
+            self.backend.set_sample_rate(sample_rate as f64);
+            self.backend.reset();
+        }
+    }
+
+    /// Resample the output to `sample_rate`, for a device that can't run at
+    /// the synth's own rate. The synth carries on as it was
+    pub fn set_output_sample_rate(&mut self, sample_rate: f32) {
+        self.resampler = if sample_rate > 0.0 && sample_rate != self.sample_rate {
+            info!(
+                "🔁 Resampling output from {} Hz to {} Hz",
+                self.sample_rate, sample_rate
+            );
+            Some(Resampler::new(self.sample_rate, sample_rate))
+        } else {
+            None
+        };
+    }
+
+    pub fn get_sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Snapshot of the current sound parameters
+    pub fn get_patch(&self) -> Patch {
+        Patch {
+            version: PATCH_VERSION,
+            waveform: self.get_waveform(),
+            master_volume: self.get_master_volume(),
+            attack: self.get_attack(),
+            decay: self.get_decay(),
+            sustain: self.get_sustain(),
+            release: self.get_release(),
+            delay_time: self.get_delay_time(),
+            delay_feedback: self.get_delay_feedback(),
+            delay_mix: self.get_delay_mix(),
+            delay_mode: self.get_delay_mode(),
+            delay_low_cut: self.get_delay_low_cut(),
+            delay_high_cut: self.get_delay_high_cut(),
+            filter_cutoff: self.get_filter_cutoff(),
+            filter_resonance: self.get_filter_resonance(),
+            filter_mix: self.get_filter_mix(),
+            unison_voices: self.unison.voices,
+            unison_detune: self.unison.detune,
+            unison_spread: self.unison.spread,
+            ring_mod_mix: self.get_ring_mod_mix(),
+            ring_mod_ratio: self.get_ring_mod_ratio(),
+            vibrato_rate: self.get_vibrato_rate(),
+            vibrato_depth: self.get_vibrato_depth(),
+            vibrato_delay: self.get_vibrato_delay(),
+            filter_lfo_rate: self.get_filter_lfo_rate(),
+            filter_lfo_depth: self.get_filter_lfo_depth(),
+            filter_lfo_sync: self.get_filter_lfo_sync(),
+            vibrato_shape: self.get_lfo_shape(LfoTarget::Vibrato),
+            filter_lfo_shape: self.get_lfo_shape(LfoTarget::Filter),
+            envelope_mode: self.get_envelope_mode(),
+            envelope_delay: self.get_envelope_delay(),
+            envelope_hold: self.get_envelope_hold(),
+            filter_velocity_amount: self.get_filter_velocity_amount(),
+            voice_spread: self.get_voice_spread(),
+            voice_pan_mode: self.get_voice_pan_mode(),
+            stereo_width: self.get_stereo_width(),
+            soft_clip: self.get_soft_clip(),
+            soft_clip_drive: self.get_soft_clip_drive(),
+            gate_smoothing: self.get_gate_smoothing(),
+            envelope_smoothing: self.get_envelope_smoothing(),
+            effect_order: self.effect_order,
+            effect_slots: self.effect_slot_patches(),
+        }
+    }
+
+    /// Set every sound parameter from a patch, migrated from older versions.
+    /// Values are clamped as for the individual setters
+    pub fn apply_patch(&mut self, patch: &Patch) {
+        let patch = &patch.migrated();
+        self.set_waveform(patch.waveform);
+        self.set_master_volume(patch.master_volume);
+        self.set_attack(patch.attack);
+        self.set_decay(patch.decay);
+        self.set_sustain(patch.sustain);
+        self.set_release(patch.release);
+        self.set_delay_time(patch.delay_time);
+        self.set_delay_feedback(patch.delay_feedback);
+        self.set_delay_mix(patch.delay_mix);
+        self.set_delay_mode(patch.delay_mode);
+        self.set_delay_low_cut(patch.delay_low_cut);
+        self.set_delay_high_cut(patch.delay_high_cut);
+        self.set_filter_cutoff(patch.filter_cutoff);
+        self.set_filter_resonance(patch.filter_resonance);
+        self.set_filter_mix(patch.filter_mix);
+        self.set_unison(Unison {
+            voices: patch.unison_voices,
+            detune: patch.unison_detune,
+            spread: patch.unison_spread,
+        });
+        self.set_ring_mod_mix(patch.ring_mod_mix);
+        self.set_ring_mod_ratio(patch.ring_mod_ratio);
+        self.set_vibrato_rate(patch.vibrato_rate);
+        self.set_vibrato_depth(patch.vibrato_depth);
+        self.set_vibrato_delay(patch.vibrato_delay);
+        self.set_filter_lfo_rate(patch.filter_lfo_rate);
+        self.set_filter_lfo_depth(patch.filter_lfo_depth);
+        self.set_filter_lfo_sync(patch.filter_lfo_sync);
+        self.set_lfo_shape(LfoTarget::Vibrato, patch.vibrato_shape);
+        self.set_lfo_shape(LfoTarget::Filter, patch.filter_lfo_shape);
+        self.set_envelope_mode(patch.envelope_mode);
+        self.set_envelope_delay(patch.envelope_delay);
+        self.set_envelope_hold(patch.envelope_hold);
+        self.set_filter_velocity_amount(patch.filter_velocity_amount);
+        self.set_voice_spread(patch.voice_spread);
+        self.set_voice_pan_mode(patch.voice_pan_mode);
+        self.set_stereo_width(patch.stereo_width);
+        self.set_soft_clip(patch.soft_clip);
+        self.set_soft_clip_drive(patch.soft_clip_drive);
+        self.set_gate_smoothing(patch.gate_smoothing);
+        self.set_envelope_smoothing(patch.envelope_smoothing);
+        self.set_effect_order(patch.effect_order);
+        let mut changed = false;
+        for (slot, slot_patch) in self.effect_slots.iter_mut().zip(&patch.effect_slots) {
+            changed |= slot.apply(&mut self.net, slot_patch);
+        }
+        if changed {
+            self.net.commit();
+        }
+    }
+
+    /// Switch to a new waveform using dynamic Net replacement. The sampler
+    /// is ignored until a sample's been loaded
+    pub fn set_waveform(&mut self, new_waveform: Waveform) {
+        if new_waveform == self.current_waveform || !self.enabled {
+            return; // No change needed
+        }
+
+        if new_waveform == Waveform::Sampler && self.sample.is_none() {
+            return;
+        }
+
+        self.current_waveform = new_waveform;
+        self.rebuild_oscillators();
+    }
+
+    /// Get the current waveform
+    pub fn get_waveform(&self) -> Waveform {
+        self.current_waveform
+    }
+
+    /// Load a sample for the sampler waveform and switch to it
+    pub fn load_sample(&mut self, sample: Sample) {
+        self.sample = Some(Arc::new(sample));
+        self.current_waveform = Waveform::Sampler;
+        self.rebuild_oscillators();
+        if self.layer.is_some() {
+            self.rebuild_layer();
+        }
+        self.publish_params();
+    }
+
+    /// Replace the oscillator node on every voice, for the current waveform
+    /// (or sample) and unison settings, and commit the changes to the backend.
+    /// With a keyboard split the lower voices get the lower sound's instead
+    fn rebuild_oscillators(&mut self) {
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            let (waveform, unison) = match &self.key_split {
+                Some(split) if split.is_lower_voice(index) => {
+                    (split.lower.waveform, split.lower.unison())
+                }
+                _ => (self.current_waveform, self.unison),
+            };
+            set_oscillator(
+                &mut self.net,
+                voice,
+                waveform,
+                self.current_waveform,
+                &self.sample,
+                &unison,
+            );
+        }
+        self.net.commit();
+    }
+
+    /// Replace the oscillator and envelope on every layer voice for the
+    /// layer's patch, or silence them if there's no layer
+    fn rebuild_layer(&mut self) {
+        for voice in self.layer_voices.iter_mut() {
+            match &self.layer {
+                Some(layer) => {
+                    set_oscillator(
+                        &mut self.net,
+                        voice,
+                        layer.patch.waveform,
+                        self.current_waveform,
+                        &self.sample,
+                        &layer.patch.unison(),
+                    );
+                    voice.set_adsr(&mut self.net, layer.patch.adsr());
+                }
+                None => voice.set_silent(&mut self.net),
+            }
+        }
+        self.net.commit();
+    }
+
+    /// Set the gains of the main and layer mixes. Without a layer the main
+    /// voices play as they are
+    fn apply_layer_mix(&self) {
+        let (main, layer) = match &self.layer {
+            Some(layer) => (layer.main_mix.gains(), layer.mix.gains()),
+            None => (LayerMix::default().gains(), (0.0, 0.0)),
+        };
+        self.main_mix_vars.0.set_value(main.0);
+        self.main_mix_vars.1.set_value(main.1);
+        self.layer_mix_vars.0.set_value(layer.0);
+        self.layer_mix_vars.1.set_value(layer.1);
+    }
+
+    /// Layer a second patch over the main one on every note, replacing any
+    /// layer already set. Costs a second set of voices while it's on
+    pub fn set_layer(&mut self, layer: Layer) {
+        if !self.enabled {
+            return;
+        }
+        let layer = Layer {
+            patch: layer.patch,
+            mix: layer.mix.clamped(),
+            main_mix: layer.main_mix.clamped(),
+        };
+        apply_layer_controls(&self.layer_controls, &layer.patch);
+        self.layer = Some(layer);
+        self.rebuild_layer();
+        self.apply_layer_mix();
+    }
+
+    /// Level and pan of the layer and of the main patch. Does nothing without a layer
+    pub fn set_layer_mix(&mut self, mix: LayerMix, main_mix: LayerMix) {
+        if let Some(layer) = self.layer.as_mut() {
+            layer.mix = mix.clamped();
+            layer.main_mix = main_mix.clamped();
+            self.apply_layer_mix();
+        }
+    }
+
+    /// Back to the main patch alone
+    pub fn clear_layer(&mut self) {
+        if self.layer.take().is_some() && self.enabled {
+            self.rebuild_layer();
+            self.apply_layer_mix();
+        }
+    }
+
+    pub fn get_layer(&self) -> Option<Layer> {
+        self.layer
+    }
+
+    /// Set the unison stack. Voices are clamped to 1 to 8, detune to 0 to
+    /// 100 cents and spread to 0.0 to 1.0
+    pub fn set_unison(&mut self, unison: Unison) {
+        let unison = Unison {
+            voices: unison.voices.clamp(1, MAX_UNISON),
+            detune: unison.detune.clamp(0.0, 100.0),
+            spread: unison.spread.clamp(0.0, 1.0),
+        };
+        if unison == self.unison || !self.enabled {
+            return; // No change needed
+        }
+        self.unison = unison;
+        self.rebuild_oscillators();
+    }
+
+    pub fn get_unison(&self) -> Unison {
+        self.unison
+    }
+
+    /// Set the ring modulation amount (0.0 = off, 1.0 = fully ring modulated)
+    pub fn set_ring_mod_mix(&mut self, mix: f32) {
+        if self.enabled {
+            self.voice_controls.ring_mix.set_value(mix.clamp(0.0, 1.0));
+        }
+    }
+
+    pub fn get_ring_mod_mix(&self) -> f32 {
+        self.voice_controls.ring_mix.value()
+    }
+
+    /// Set the ring modulator frequency as a multiple of the note (0.1 to 16.0).
+    /// Non-integer ratios give the clangorous, bell-like tones
+    pub fn set_ring_mod_ratio(&mut self, ratio: f32) {
+        if self.enabled {
+            self.voice_controls
+                .ring_ratio
+                .set_value(ratio.clamp(0.1, 16.0));
+        }
+    }
+
+    pub fn get_ring_mod_ratio(&self) -> f32 {
+        self.voice_controls.ring_ratio.value()
+    }
+
+    /// Set the vibrato speed in Hz (0.1 to 20.0)
+    pub fn set_vibrato_rate(&mut self, rate: f32) {
+        if self.enabled {
+            self.voice_controls
+                .vibrato_rate
+                .set_value(rate.clamp(0.1, 20.0));
+        }
+    }
+
+    /// Rise and fall times of the voices' pitch smoothing, in seconds
+    pub fn set_frequency_smoothing(&mut self, smoothing: Smoothing) {
+        self.voice_controls
+            .frequency_smoothing
+            .set(smoothing.clamped(MAX_FREQUENCY_SMOOTHING));
+    }
+
+    pub fn get_frequency_smoothing(&self) -> Smoothing {
+        self.voice_controls.frequency_smoothing.get()
+    }
+
+    /// Bend every voice's pitch, from -1.0 (down by the pitch bend range) to 1.0
+    pub fn set_pitch_bend(&mut self, bend: f32) {
+        self.pitch_bend = bend.clamp(-1.0, 1.0);
+        self.apply_pitch_bend();
+    }
+
+    /// How far a full pitch bend reaches, in semitones (1 to 24). A bend in
+    /// progress follows the new range
+    pub fn set_pitch_bend_range(&mut self, semitones: f32) {
+        self.pitch_bend_range = semitones.clamp(1.0, MAX_PITCH_BEND);
+        self.apply_pitch_bend();
+    }
+
+    pub fn get_pitch_bend_range(&self) -> f32 {
+        self.pitch_bend_range
+    }
+
+    fn apply_pitch_bend(&self) {
+        let semitones = self.pitch_bend * self.pitch_bend_range;
+        self.voice_controls
+            .pitch_bend
+            .set_value(2.0f32.powf(semitones / 12.0));
+    }
+
+    pub fn get_vibrato_rate(&self) -> f32 {
+        self.voice_controls.vibrato_rate.value()
+    }
+
+    /// Set the vibrato depth in cents either side of the note (0 = off, up to 200)
+    pub fn set_vibrato_depth(&mut self, depth: f32) {
+        if self.enabled {
+            self.voice_controls
+                .vibrato_depth
+                .set_value(depth.clamp(0.0, 200.0));
+        }
+    }
+
+    pub fn get_vibrato_depth(&self) -> f32 {
+        self.voice_controls.vibrato_depth.value()
+    }
+
+    /// Set how long after note on the vibrato starts, in seconds (0.0 to 5.0)
+    pub fn set_vibrato_delay(&mut self, delay: f32) {
+        if self.enabled {
+            self.voice_controls
+                .vibrato_delay
+                .set_value(delay.clamp(0.0, 5.0));
+        }
+    }
+
+    pub fn get_vibrato_delay(&self) -> f32 {
+        self.voice_controls.vibrato_delay.value()
+    }
+
+    /// Play a note at the specified frequency and velocity (0.0 to 1.0).
+    /// Playing a note id that is already held retriggers it on the same voice.
+    pub fn play_note(&mut self, note_id: u32, frequency: f32, velocity: f32) {
+        if self.enabled {
+            if self.mono {
+                self.play_mono_note(note_id, frequency, velocity);
+                return;
+            }
+            if self.latch {
+                if let Some(index) = find_latched(&self.voices, frequency) {
+                    self.note_counter += 1;
+                    self.voices[index].note_off(self.note_counter);
+                    return;
+                }
+            }
+            let group = match &self.key_split {
+                Some(split) => split.group(frequency),
+                None => 0..self.voices.len(),
+            };
+            let index = group.start
+                + allocate_voice(&self.voices[group], note_id, frequency, self.steal_policy);
+            let voice = &self.voices[index];
+            if voice.is_held() && voice.note_id != Some(note_id) {
+                // Stealing: let the old note fade out before the new one starts
+                self.note_counter += 1;
+                self.voices[index].steal(note_id, self.note_counter);
+                let fade_frames = (STEAL_FADE_SECS * self.sample_rate) as u64;
+                self.scheduler.schedule(
+                    self.frame_position + fade_frames,
+                    ScheduledAction::StolenNoteOn {
+                        voice: index,
+                        note_id,
+                        frequency,
+                        velocity,
+                    },
+                );
+                return;
+            }
+            self.start_voice(index, note_id, frequency, velocity);
+        }
+
+        // println!("Playing frequency: {} Hz", frequency);
+    }
+
+    fn start_voice(&mut self, index: usize, note_id: u32, frequency: f32, velocity: f32) {
+        self.apply_key_zones(index, frequency);
+        self.apply_voice_pan(index, frequency);
+        self.apply_filter_velocity(velocity);
+        self.note_frequency_var.set_value(frequency);
+        self.note_counter += 1;
+        self.voices[index].note_on(note_id, frequency, velocity, self.note_counter);
+        self.voices[index].latched = self.latch;
+    }
+
+    /// Place a voice that's starting a note in the stereo field
+    fn apply_voice_pan(&mut self, index: usize, frequency: f32) {
+        let pan = voice_pan(
+            self.voice_pan_mode,
+            self.voice_spread,
+            frequency,
+            self.voice_pan_count,
+        );
+        self.voice_pan_count = self.voice_pan_count.wrapping_add(1);
+        self.voices[index].set_pan(pan);
+    }
+
+    /// Set how widely notes are spread across the stereo field (0.0 to 1.0)
+    pub fn set_voice_spread(&mut self, spread: f32) {
+        self.voice_spread = spread.clamp(0.0, 1.0);
+    }
+
+    pub fn get_voice_spread(&self) -> f32 {
+        self.voice_spread
+    }
+
+    pub fn set_voice_pan_mode(&mut self, mode: VoicePanMode) {
+        self.voice_pan_mode = mode;
+    }
+
+    pub fn get_voice_pan_mode(&self) -> VoicePanMode {
+        self.voice_pan_mode
+    }
+
+    /// Choose which voice is taken when every voice is busy
+    pub fn set_steal_policy(&mut self, policy: StealPolicy) {
+        self.steal_policy = policy;
+    }
+
+    pub fn get_steal_policy(&self) -> StealPolicy {
+        self.steal_policy
+    }
+
+    /// Set note frequency (for violin / fretless mode). Applies to the most
+    /// recently played note that is still held.
+    pub fn set_frequency(&mut self, frequency: f32) {
+        if self.enabled {
+            if let Some(voice) = self
+                .voices
+                .iter_mut()
+                .filter(|v| v.is_held())
+                .max_by_key(|v| v.started_at)
+            {
+                voice.set_frequency(frequency);
+            }
+        }
+    }
+
+    /// Mono mode note on. The voice only retriggers if nothing is sounding,
+    /// otherwise it moves to whichever held note has priority
+    fn play_mono_note(&mut self, note_id: u32, frequency: f32, velocity: f32) {
+        self.mono_notes.press(HeldNote {
+            note_id,
+            frequency,
+            velocity,
+        });
+        let note = match self.mono_notes.current(self.note_priority) {
+            Some(note) => note,
+            None => return,
+        };
+        self.note_counter += 1;
+        self.note_frequency_var.set_value(note.frequency);
+        if self.voices[0].is_held() {
+            self.voices[0].glide_to(note.note_id, note.frequency);
+        } else {
+            self.apply_key_zones(0, note.frequency);
+            self.apply_voice_pan(0, note.frequency);
+            self.apply_filter_velocity(note.velocity);
+            self.voices[0].note_on(
+                note.note_id,
+                note.frequency,
+                note.velocity,
+                self.note_counter,
+            );
+        }
+    }
+
+    /// Mono mode note off. Releasing the sounding note returns to the held
+    /// note with priority, if there is one
+    fn mono_note_off(&mut self, note_id: u32) {
+        self.mono_notes.release(note_id);
+        if self.voices[0].note_id != Some(note_id) {
+            return;
+        }
+        self.note_counter += 1;
+        let voice = &mut self.voices[0];
+        match self.mono_notes.current(self.note_priority) {
+            Some(note) => {
+                self.note_frequency_var.set_value(note.frequency);
+                voice.glide_to(note.note_id, note.frequency)
+            }
+            None if voice.frozen => voice.key_released = true,
+            None => voice.note_off(self.note_counter),
+        }
+    }
+
+    /// Turn mono (legato) mode on or off. Latch doesn't apply in mono mode.
+    /// Either way, whatever is sounding is released
+    pub fn set_mono(&mut self, enabled: bool) {
+        self.mono = enabled;
+        self.mono_notes.clear();
+        self.note_counter += 1;
+        let now = self.note_counter;
+        for voice in self.voices.iter_mut() {
+            voice.note_off(now);
+        }
+    }
+
+    pub fn get_mono(&self) -> bool {
+        self.mono
+    }
+
+    /// Choose which held note sounds in mono mode
+    pub fn set_note_priority(&mut self, priority: NotePriority) {
+        self.note_priority = priority;
+        if self.mono && self.voices[0].is_held() {
+            if let Some(note) = self.mono_notes.current(priority) {
+                self.voices[0].glide_to(note.note_id, note.frequency);
+            }
+        }
+    }
+
+    pub fn get_note_priority(&self) -> NotePriority {
+        self.note_priority
+    }
+
+    /// Switch play mode, setting mono, note priority, voice stealing,
+    /// quantize and strum to suit it
+    pub fn set_instrument_mode(&mut self, mode: InstrumentMode) {
+        let profile = mode.profile();
+        self.instrument_mode = mode;
+        if profile.mono != self.mono {
+            self.set_mono(profile.mono);
+        }
+        self.set_note_priority(profile.note_priority);
+        self.set_steal_policy(profile.steal_policy);
+        self.set_quantize(profile.quantize);
+        self.set_strum_settings(profile.strum_settings);
+        self.set_frequency_smoothing(profile.frequency_smoothing);
+    }
+
+    pub fn get_instrument_mode(&self) -> InstrumentMode {
+        self.instrument_mode
+    }
+
+    /// Release the voice playing the given note id
+    pub fn note_off(&mut self, note_id: u32) {
+        if self.mono {
+            self.mono_note_off(note_id);
+            return;
+        }
+        if self.enabled {
+            self.note_counter += 1;
+            let now = self.note_counter;
+            for voice in self
+                .voices
+                .iter_mut()
+                .filter(|v| v.note_id == Some(note_id) && !v.latched)
+            {
+                if voice.frozen {
+                    voice.key_released = true;
+                } else {
+                    voice.note_off(now);
+                }
+            }
+        }
+    }
+
+    /// Turn latch mode on or off. Turning it off releases the latched notes
+    pub fn set_latch(&mut self, enabled: bool) {
+        self.latch = enabled;
+        if !enabled {
+            self.note_counter += 1;
+            let now = self.note_counter;
+            for voice in self.voices.iter_mut().filter(|v| v.latched) {
+                voice.note_off(now);
+            }
+        }
+    }
+
+    pub fn get_latch(&self) -> bool {
+        self.latch
+    }
+
+    /// Turn hold on or off. On freezes every held voice, bypassing its
+    /// release; off releases the frozen voices whose notes have ended
+    pub fn set_hold(&mut self, enabled: bool) {
+        self.hold = enabled;
+        self.note_counter += 1;
+        let now = self.note_counter;
+        for voice in self.voices.iter_mut().filter(|v| v.is_held()) {
+            if enabled {
+                voice.frozen = true;
+            } else if voice.frozen {
+                voice.frozen = false;
+                if voice.key_released {
+                    voice.note_off(now);
+                }
+            }
+        }
+    }
+
+    pub fn get_hold(&self) -> bool {
+        self.hold
+    }
+
+    /// Release every held voice (latched ones too), and cancel any strum in progress
+    pub fn note_off_all(&mut self) {
+        if self.enabled {
+            self.scheduler.clear();
+            self.sequencer.stop();
+            self.quantize_delays.clear();
+            self.mono_notes.clear();
+            self.note_counter += 1;
+            let now = self.note_counter;
+            for voice in self.voices.iter_mut() {
+                voice.note_off(now);
+            }
+        }
+    }
+
+    /// Play a drum hit (velocity 0.0 to 1.0)
+    pub fn trigger_drum(&mut self, kind: DrumKind, velocity: f32) {
+        if self.enabled {
+            if let Some((_, trigger)) = self.drums.iter().find(|(k, _)| *k == kind) {
+                trigger.hit(velocity);
+            }
+        }
+    }
+
+    /// Set master volume (0.0 = silent, 1.0 = full volume)
+    pub fn set_master_volume(&mut self, volume: f32) {
+        // Clamp volume to valid range
+        let clamped_volume = volume.clamp(0.0, 1.0);
+
+        if self.enabled {
+            self.master_volume_var.set_value(clamped_volume);
+        }
+    }
+
+    /// Get current master volume
+    pub fn get_master_volume(&self) -> f32 {
+        self.master_volume_var.value()
+    }
+
+    /// Set master volume in dB (-60.0 or below is silent, 0.0 is full volume)
+    pub fn set_master_volume_db(&mut self, db: f32) {
+        self.set_master_volume(db_to_gain(db));
+    }
+
+    pub fn get_master_volume_db(&self) -> f32 {
+        gain_to_db(self.get_master_volume())
+    }
+
+    /// Set a gain trim along the signal path, in dB (-24.0 to 12.0)
+    pub fn set_gain_trim(&mut self, stage: GainStage, db: f32) {
+        let db = db.clamp(MIN_TRIM_DB, MAX_TRIM_DB);
+        self.gain_trims[stage.index()] = db;
+        self.gain_trim_vars[stage.index()].set_value(db_to_gain(db));
+    }
+
+    pub fn get_gain_trim(&self, stage: GainStage) -> f32 {
+        self.gain_trims[stage.index()]
+    }
+
+    /// Turn the soft clipper ahead of the limiter on or off
+    pub fn set_soft_clip(&mut self, enabled: bool) {
+        self.soft_clip_var
+            .set_value(if enabled { 1.0 } else { 0.0 });
+    }
+
+    pub fn get_soft_clip(&self) -> bool {
+        self.soft_clip_var.value() > 0.5
+    }
+
+    /// Set how hard the soft clipper is driven, in dB (0.0 to 24.0)
+    pub fn set_soft_clip_drive(&mut self, drive: f32) {
+        self.soft_clip_drive = drive.clamp(0.0, 24.0);
+        self.soft_clip_drive_var
+            .set_value(10f32.powf(self.soft_clip_drive / 20.0));
+    }
+
+    pub fn get_soft_clip_drive(&self) -> f32 {
+        self.soft_clip_drive
+    }
+
+    /// Rise and fall times of the gate smoothing ahead of the envelope, in
+    /// seconds, up to 20ms
+    pub fn set_gate_smoothing(&mut self, smoothing: Smoothing) {
+        self.voice_controls
+            .gate_smoothing
+            .set(smoothing.clamped(MAX_GATE_SMOOTHING));
+    }
+
+    pub fn get_gate_smoothing(&self) -> Smoothing {
+        self.voice_controls.gate_smoothing.get()
+    }
+
+    /// Rise and fall times of the smoothing after the envelope, in seconds,
+    /// up to 10ms
+    pub fn set_envelope_smoothing(&mut self, smoothing: Smoothing) {
+        self.voice_controls
+            .envelope_smoothing
+            .set(smoothing.clamped(MAX_ENVELOPE_SMOOTHING));
+    }
+
+    pub fn get_envelope_smoothing(&self) -> Smoothing {
+        self.voice_controls.envelope_smoothing.get()
+    }
+
+    /// Level of the audio input (0.0 = muted, 1.0 = unchanged, 2.0 = doubled)
+    pub fn set_input_level(&mut self, level: f32) {
+        self.input_level_var.set_value(level.clamp(0.0, 2.0));
+    }
+
+    pub fn get_input_level(&self) -> f32 {
+        self.input_level_var.value()
+    }
+
+    /// How much the audio input's level modulates `destination`, see `ModDestination`
+    pub fn set_input_envelope_amount(&mut self, destination: ModDestination, amount: f32) {
+        let (min, max) = destination.range();
+        self.input_envelope_amount_vars[destination.index()].set_value(amount.clamp(min, max));
+    }
+
+    pub fn get_input_envelope_amount(&self, destination: ModDestination) -> f32 {
+        self.input_envelope_amount_vars[destination.index()].value()
+    }
+
+    fn rebuild_engine(&mut self) {
+        let node: Box<dyn AudioUnit + Send> = match self.engine_mode {
+            EngineMode::Synth => stereo_mixer(),
+            EngineMode::Vocoder => Box::new(An(VocoderNode::new(
+                self.vocoder_bands,
+                self.formant_shift_var.clone(),
+            ))),
+        };
+        self.net.replace(self.engine_nodeid, node);
+        self.net.commit();
+    }
+
+    /// Play the voices as they are, or through the vocoder with the audio
+    /// input as the modulator
+    pub fn set_engine_mode(&mut self, mode: EngineMode) {
+        self.engine_mode = mode;
+        self.rebuild_engine();
+    }
+
+    pub fn get_engine_mode(&self) -> EngineMode {
+        self.engine_mode
+    }
+
+    /// Number of vocoder bands (4 to 32). More is clearer, fewer more robotic
+    pub fn set_vocoder_bands(&mut self, bands: u32) {
+        self.vocoder_bands = bands.clamp(MIN_VOCODER_BANDS, MAX_VOCODER_BANDS);
+        if self.engine_mode == EngineMode::Vocoder {
+            self.rebuild_engine();
+        }
+    }
+
+    pub fn get_vocoder_bands(&self) -> u32 {
+        self.vocoder_bands
+    }
+
+    /// Move the vocoder's formants by up to an octave either way, in semitones
+    pub fn set_formant_shift(&mut self, semitones: f32) {
+        self.formant_shift_var
+            .set_value(semitones.clamp(-MAX_FORMANT_SHIFT, MAX_FORMANT_SHIFT));
+    }
+
+    pub fn get_formant_shift(&self) -> f32 {
+        self.formant_shift_var.value()
+    }
+
+    fn rebuild_limiter(&mut self) {
+        self.net.replace(
+            self.limiter_nodeid,
+            master_limiter(
+                self.limiter_attack,
+                self.limiter_release,
+                self.limiter_bypass,
+            ),
+        );
+        self.net.commit();
+    }
+
+    /// Set the limiter attack time in seconds (0.0005 to 0.1)
+    pub fn set_limiter_attack(&mut self, attack: f32) {
+        self.limiter_attack = attack.clamp(0.0005, 0.1);
+        self.rebuild_limiter();
+    }
+
+    pub fn get_limiter_attack(&self) -> f32 {
+        self.limiter_attack
+    }
+
+    /// Set the limiter release time in seconds (0.01 to 2.0). Longer
+    /// releases pump less on bass notes
+    pub fn set_limiter_release(&mut self, release: f32) {
+        self.limiter_release = release.clamp(0.01, 2.0);
+        self.rebuild_limiter();
+    }
+
+    pub fn get_limiter_release(&self) -> f32 {
+        self.limiter_release
+    }
+
+    /// Set the level the limiter holds the output under, in dB (-24.0 to 0.0)
+    pub fn set_limiter_ceiling(&mut self, ceiling: f32) {
+        self.limiter_ceiling = ceiling.clamp(-24.0, 0.0);
+        self.apply_ceilings();
+    }
+
+    /// The limiter works to the lower of its own ceiling and the output
+    /// ceiling, so the output ceiling is reached smoothly where it can be.
+    /// The final clip holds it regardless, e.g. with the limiter bypassed
+    fn apply_ceilings(&self) {
+        let gain = 10f32.powf(self.limiter_ceiling.min(self.output_ceiling) / 20.0);
+        self.limiter_ceiling_var.set_value(gain);
+        self.limiter_ceiling_inv_var.set_value(1.0 / gain);
+        let gain = 10f32.powf(self.output_ceiling / 20.0);
+        self.output_ceiling_var.set_value(gain);
+        self.output_ceiling_inv_var.set_value(1.0 / gain);
+    }
+
+    pub fn get_limiter_ceiling(&self) -> f32 {
+        self.limiter_ceiling
+    }
+
+    /// Set the loudest the output can ever get, in dB (-40.0 to 0.0). Unlike
+    /// master volume it isn't part of the patch, so presets can't raise it
+    pub fn set_output_ceiling(&mut self, ceiling: f32) {
+        self.output_ceiling = ceiling.clamp(MIN_OUTPUT_CEILING, 0.0);
+        self.apply_ceilings();
+    }
+
+    pub fn get_output_ceiling(&self) -> f32 {
+        self.output_ceiling
+    }
+
+    /// Take the limiter out of the signal path. The ceiling still applies as a gain
+    pub fn set_limiter_bypass(&mut self, bypass: bool) {
+        if bypass != self.limiter_bypass {
+            self.limiter_bypass = bypass;
+            self.rebuild_limiter();
+        }
+    }
+
+    pub fn get_limiter_bypass(&self) -> bool {
+        self.limiter_bypass
+    }
+
+    /// Rewire the effects in a new order. Delay tails and filter state
+    /// carry on, so a change mid-note doesn't click more than it has to
+    pub fn set_effect_order(&mut self, order: EffectOrder) {
+        if order == self.effect_order {
+            return;
+        }
+        self.effect_order = order;
+        wire_effects(
+            &mut self.net,
+            self.engine_nodeid,
+            self.effects_output_nodeid,
+            &self.effect_nodes,
+            &self.effect_order,
+        );
+        self.net.commit();
+    }
+
+    pub fn get_effect_order(&self) -> EffectOrder {
+        self.effect_order
+    }
+
+    /// Put `effect` in an insert slot, replacing whatever was there
+    pub fn set_slot_effect(&mut self, slot: usize, effect: SlotEffect) {
+        if let Some(effect_slot) = self.effect_slots.get_mut(slot) {
+            if effect_slot.set_effect(&mut self.net, effect) {
+                self.net.commit();
+            }
+        }
+    }
+
+    /// Set a parameter of the effect in a slot, clamped to its range
+    pub fn set_slot_param(&mut self, slot: usize, param: usize, value: f32) {
+        if let Some(effect_slot) = self.effect_slots.get_mut(slot) {
+            if effect_slot.set_param(&mut self.net, param, value) {
+                self.net.commit();
+            }
+        }
+    }
+
+    /// Make insert slot `slot` a reverb of type `reverb_type`, replacing
+    /// whatever was there. Its size, time and damping start at the type's,
+    /// its wet/dry mix stays as it was
+    pub fn set_reverb_type(&mut self, slot: usize, reverb_type: ReverbType) {
+        if let Some(effect_slot) = self.effect_slots.get_mut(slot) {
+            let patch = SlotPatch::reverb(reverb_type, effect_slot.mix());
+            if effect_slot.apply(&mut self.net, &patch) {
+                self.net.commit();
+            }
+        }
+    }
+
+    /// Set an effect's wet/dry mix, 0.0 for all dry to 1.0 for all wet. The
+    /// crossfade is equal power, so halfway is as loud as either end
+    pub fn set_effect_mix(&mut self, effect: Effect, mix: f32) {
+        match effect {
+            Effect::Filter => self.set_filter_mix(mix),
+            Effect::Delay => self.set_delay_mix(mix),
+            Effect::Slot(slot) => {
+                if let Some(effect_slot) = self.effect_slots.get_mut(slot) {
+                    effect_slot.set_mix(mix);
+                }
+            }
+        }
+    }
+
+    pub fn get_effect_mix(&self, effect: Effect) -> Option<f32> {
+        match effect {
+            Effect::Filter => Some(self.get_filter_mix()),
+            Effect::Delay => Some(self.get_delay_mix()),
+            Effect::Slot(slot) => self.effect_slots.get(slot).map(EffectSlot::mix),
+        }
+    }
+
+    pub fn get_effect_slot(&self, slot: usize) -> Option<SlotPatch> {
+        self.effect_slots.get(slot).map(EffectSlot::patch)
+    }
+
+    fn effect_slot_patches(&self) -> [SlotPatch; MAX_EFFECT_SLOTS] {
+        let mut patches = [SlotPatch::default(); MAX_EFFECT_SLOTS];
+        for (patch, slot) in patches.iter_mut().zip(&self.effect_slots) {
+            *patch = slot.patch();
+        }
+        patches
+    }
+
+    /// Set the master bus stereo width (0.0 = mono, 1.0 = unchanged, 2.0 = extra wide)
+    pub fn set_stereo_width(&mut self, width: f32) {
+        self.stereo_width_var.set_value(width.clamp(0.0, 2.0));
+    }
+
+    pub fn get_stereo_width(&self) -> f32 {
+        self.stereo_width_var.value()
+    }
+
+    /// Patch ADSR values, before any key zone offsets
+    fn base_adsr(&self) -> AdsrParams {
+        AdsrParams {
+            mode: self.envelope_mode,
+            delay: self.envelope_delay_var.value(),
+            attack: self.attack_var.value(),
+            hold: self.envelope_hold_var.value(),
+            decay: self.decay_var.value(),
+            sustain: self.sustain_var.value(),
+            release: self.release_var.value(),
+        }
+    }
+
+    /// Rebuild every voice's envelope from the patch values plus its key zone offsets
+    pub fn set_adsr(&mut self) {
+        if !self.enabled {
+            return; // No change needed
+        }
+
+        for index in 0..self.voices.len() {
+            let frequency = self.voices[index].frequency;
+            let adsr = self.zoned_adsr(index, frequency);
+            self.voices[index].set_adsr(&mut self.net, adsr);
+        }
+
+        self.net.commit();
+    }
+
+    pub fn set_attack(&mut self, attack: f32) {
+        let clamped_attack = attack.clamp(0.001, 5.0); // 1ms to 5s
+        self.attack_var.set_value(clamped_attack);
+        self.set_adsr();
+    }
+
+    /// Switch between the ADSR and DAHDSR (optionally looping) envelopes
+    pub fn set_envelope_mode(&mut self, mode: EnvelopeMode) {
+        self.envelope_mode = mode;
+        self.set_adsr();
+    }
+
+    pub fn get_envelope_mode(&self) -> EnvelopeMode {
+        self.envelope_mode
+    }
+
+    /// Set the time before the attack starts, in seconds (0 to 5). DAHDSR modes only
+    pub fn set_envelope_delay(&mut self, delay: f32) {
+        self.envelope_delay_var.set_value(delay.clamp(0.0, 5.0));
+        self.set_adsr();
+    }
+
+    pub fn get_envelope_delay(&self) -> f32 {
+        self.envelope_delay_var.value()
+    }
+
+    /// Set how long the envelope stays at full level after the attack, in
+    /// seconds (0 to 5). DAHDSR modes only
+    pub fn set_envelope_hold(&mut self, hold: f32) {
+        self.envelope_hold_var.set_value(hold.clamp(0.0, 5.0));
+        self.set_adsr();
+    }
+
+    pub fn get_envelope_hold(&self) -> f32 {
+        self.envelope_hold_var.value()
+    }
+
+    /// Get ADSR attack time
+    pub fn get_attack(&self) -> f32 {
+        self.attack_var.value()
+    }
+
+    /// Set ADSR decay time (in seconds)
+    pub fn set_decay(&mut self, decay: f32) {
+        let clamped_decay = decay.clamp(0.001, 5.0); // 1ms to 5s
+        self.decay_var.set_value(clamped_decay);
+        self.set_adsr();
+    }
+
+    /// Get ADSR decay time
+    pub fn get_decay(&self) -> f32 {
+        self.decay_var.value()
+    }
+
+    /// Set ADSR sustain level (0.0 to 1.0)
+    pub fn set_sustain(&mut self, sustain: f32) {
+        let clamped_sustain = sustain.clamp(0.0, 1.0);
+        self.sustain_var.set_value(clamped_sustain);
+        self.set_adsr();
+    }
+
+    /// Get ADSR sustain level
+    pub fn get_sustain(&self) -> f32 {
+        self.sustain_var.value()
+    }
+
+    /// Set ADSR release time (in seconds)
+    pub fn set_release(&mut self, release: f32) {
+        let clamped_release = release.clamp(0.001, 10.0); // 1ms to 10s
+        self.release_var.set_value(clamped_release);
+        self.set_adsr();
+    }
+
+    /// Get ADSR release time
+    pub fn get_release(&self) -> f32 {
+        self.release_var.value()
+    }
+
+    /// Set delay time (in seconds)
+    pub fn set_delay_time(&mut self, delay_time: f32) {
+        if !self.enabled {
+            return; // No change needed
+        }
+        self.delay_time_var
+            .set_value(delay_time.clamp(0.0, MAX_DELAY_TIME)); // Clamp to 0-5 seconds
+
+        // The tape follows the time itself
+        if self.delay_mode == DelayMode::Digital {
+            let new_delay = stereo_delay(self.delay_time_var.value());
+            self.net.replace(self.delay_nodeid, new_delay);
+            self.net.commit();
+        }
+    }
+
+    /// Switch between the clean digital delay and the tape echo. The echoes
+    /// already going are cut off
+    pub fn set_delay_mode(&mut self, mode: DelayMode) {
+        if mode == self.delay_mode {
+            return;
+        }
+        self.delay_mode = mode;
+        let new_delay = delay_line(mode, &self.delay_time_var);
+        self.net.replace(self.delay_nodeid, new_delay);
+        self.net.commit();
+    }
+
+    pub fn get_delay_mode(&self) -> DelayMode {
+        self.delay_mode
+    }
+
+    /// Get delay time (in seconds)
+    pub fn get_delay_time(&self) -> f32 {
+        self.delay_time_var.value()
+    }
+
+    /// Set delay feedback (0.0 to 1.0)
+    pub fn set_delay_feedback(&mut self, feedback: f32) {
+        if !self.enabled {
+            return; // No change needed
+        }
+        self.delay_feedback_var.set_value(feedback.clamp(0.0, 1.0));
+    }
+
+    /// Get delay feedback
+    pub fn get_delay_feedback(&self) -> f32 {
+        self.delay_feedback_var.value()
     }
 
     pub fn set_delay_mix(&mut self, delay_mix: f32) {
@@ -549,6 +3300,26 @@ impl FunDSPSynth {
         self.delay_mix_var.value()
     }
 
+    /// Set the high pass in the delay's feedback path (20 Hz, off, to 2 kHz)
+    pub fn set_delay_low_cut(&mut self, low_cut: f32) {
+        self.delay_low_cut_var
+            .set_value(low_cut.clamp(MIN_DELAY_CUT, 2000.0));
+    }
+
+    pub fn get_delay_low_cut(&self) -> f32 {
+        self.delay_low_cut_var.value()
+    }
+
+    /// Set the low pass in the delay's feedback path (500 Hz to 20 kHz, off)
+    pub fn set_delay_high_cut(&mut self, high_cut: f32) {
+        self.delay_high_cut_var
+            .set_value(high_cut.clamp(500.0, MAX_DELAY_CUT));
+    }
+
+    pub fn get_delay_high_cut(&self) -> f32 {
+        self.delay_high_cut_var.value()
+    }
+
     pub fn set_filter_cutoff(&mut self, cutoff: f32) {
         if !self.enabled {
             return; // No change needed
@@ -576,25 +3347,350 @@ impl FunDSPSynth {
         self.filter_resonance_var.value()
     }
 
+    /// Set how much of the signal goes through the filter (0.0 to 1.0)
+    pub fn set_filter_mix(&mut self, mix: f32) {
+        self.filter_mix_var.set_value(mix.clamp(0.0, 1.0));
+    }
+
+    pub fn get_filter_mix(&self) -> f32 {
+        self.filter_mix_var.value()
+    }
+
+    /// Set how much note velocity moves the filter cutoff, in octaves (-4.0 to
+    /// 4.0). Positive closes the filter on softer notes, negative opens it.
+    /// Follows the most recently played note, as the filter is shared
+    pub fn set_filter_velocity_amount(&mut self, amount: f32) {
+        self.filter_velocity_amount = amount.clamp(-4.0, 4.0);
+    }
+
+    pub fn get_filter_velocity_amount(&self) -> f32 {
+        self.filter_velocity_amount
+    }
+
+    /// Scale the cutoff for a new note. Full velocity leaves it where it is
+    fn apply_filter_velocity(&mut self, velocity: f32) {
+        let octaves = self.filter_velocity_amount * (velocity.clamp(0.0, 1.0) - 1.0);
+        self.filter_velocity_scale_var.set_value(octaves.exp2());
+    }
+
+    /// Set the filter LFO rate in Hz (0.01 to 20.0). Ignored while tempo synced
+    pub fn set_filter_lfo_rate(&mut self, rate: f32) {
+        if self.enabled {
+            self.filter_lfo_rate_var.set_value(rate.clamp(0.01, 20.0));
+        }
+    }
+
+    pub fn get_filter_lfo_rate(&self) -> f32 {
+        self.filter_lfo_rate_var.value()
+    }
+
+    /// Set how far the filter LFO sweeps the cutoff, in octaves either way (0.0 to 4.0)
+    pub fn set_filter_lfo_depth(&mut self, depth: f32) {
+        if self.enabled {
+            self.filter_lfo_depth_var.set_value(depth.clamp(0.0, 4.0));
+        }
+    }
+
+    pub fn get_filter_lfo_depth(&self) -> f32 {
+        self.filter_lfo_depth_var.value()
+    }
+
+    /// Sync the filter LFO to the tempo, one cycle every `beats` beats (up to
+    /// 16). 0 runs it free at its own rate
+    pub fn set_filter_lfo_sync(&mut self, beats: f32) {
+        if self.enabled {
+            self.filter_lfo_sync_var.set_value(beats.clamp(0.0, 16.0));
+        }
+    }
+
+    pub fn get_filter_lfo_sync(&self) -> f32 {
+        self.filter_lfo_sync_var.value()
+    }
+
+    pub fn set_lfo_shape(&mut self, lfo: LfoTarget, shape: LfoShape) {
+        if self.enabled {
+            match lfo {
+                LfoTarget::Vibrato => self.voice_controls.vibrato_shape.set_value(shape.to_f32()),
+                LfoTarget::Filter => self.filter_lfo_shape_var.set_value(shape.to_f32()),
+            }
+        }
+    }
+
+    pub fn get_lfo_shape(&self, lfo: LfoTarget) -> LfoShape {
+        let value = match lfo {
+            LfoTarget::Vibrato => self.voice_controls.vibrato_shape.value(),
+            LfoTarget::Filter => self.filter_lfo_shape_var.value(),
+        };
+        LfoShape::from_f32(value)
+    }
+
+    /// Set the tempo in BPM (20 to 300)
+    pub fn set_tempo(&mut self, bpm: f32) {
+        if self.enabled {
+            self.tempo_var.set_value(bpm.clamp(20.0, 300.0));
+        }
+    }
+
+    pub fn get_tempo(&self) -> f32 {
+        self.tempo_var.value()
+    }
+
+    /// Add a key zone, replacing any existing zone with the same range and parameter
+    pub fn set_key_zone(&mut self, zone: KeyZone) {
+        self.key_zones.retain(|z| {
+            !(z.param == zone.param
+                && z.min_frequency == zone.min_frequency
+                && z.max_frequency == zone.max_frequency)
+        });
+        self.key_zones.push(zone);
+    }
+
+    pub fn get_key_zones(&self) -> Vec<KeyZone> {
+        self.key_zones.clone()
+    }
+
+    /// Remove all key zones. Offsets on the sounding note are cleared at the next note-on
+    pub fn clear_key_zones(&mut self) {
+        self.key_zones.clear();
+    }
+
+    /// Split the keyboard, replacing any split already set. Mono mode plays
+    /// every note on the first voice, so with a split it takes the lower sound
+    pub fn set_key_split(&mut self, split: KeySplit) {
+        if !self.enabled {
+            return;
+        }
+        let split = split.clamped();
+        self.key_split = Some(split);
+        self.note_off_all();
+        self.rebuild_oscillators();
+        self.set_adsr();
+    }
+
+    /// Play every note with the patch again
+    pub fn clear_key_split(&mut self) {
+        if self.key_split.take().is_some() && self.enabled {
+            self.rebuild_oscillators();
+            self.set_adsr();
+        }
+    }
+
+    pub fn get_key_split(&self) -> Option<KeySplit> {
+        self.key_split
+    }
+
+    /// Sum the offsets of every key zone containing `frequency` for one parameter
+    fn zone_offset(&self, frequency: f32, param: ZoneParam) -> f32 {
+        self.key_zones
+            .iter()
+            .filter(|z| z.param == param && z.contains(frequency))
+            .map(|z| z.offset)
+            .sum()
+    }
+
+    /// ADSR values for a voice playing `frequency`: the patch's, or the
+    /// lower sound's on a split's lower voices, with the key zone offsets applied
+    fn zoned_adsr(&self, voice_index: usize, frequency: f32) -> AdsrParams {
+        let base = match &self.key_split {
+            Some(split) if split.is_lower_voice(voice_index) => split.lower.adsr(),
+            _ => self.base_adsr(),
+        };
+        AdsrParams {
+            attack: (base.attack + self.zone_offset(frequency, ZoneParam::Attack))
+                .clamp(0.001, 5.0),
+            decay: (base.decay + self.zone_offset(frequency, ZoneParam::Decay)).clamp(0.001, 5.0),
+            sustain: (base.sustain + self.zone_offset(frequency, ZoneParam::Sustain))
+                .clamp(0.0, 1.0),
+            release: (base.release + self.zone_offset(frequency, ZoneParam::Release))
+                .clamp(0.001, 10.0),
+            ..base
+        }
+    }
+
+    /// Apply key zone offsets for a new note on the given voice. The voice's
+    /// ADSR is only rebuilt when its values actually change, to avoid
+    /// needless node replacement on every note.
+    fn apply_key_zones(&mut self, voice_index: usize, frequency: f32) {
+        self.filter_cutoff_offset_var
+            .set_value(self.zone_offset(frequency, ZoneParam::FilterCutoff));
+        self.delay_mix_offset_var
+            .set_value(self.zone_offset(frequency, ZoneParam::DelayMix));
+
+        let adsr = self.zoned_adsr(voice_index, frequency);
+        if adsr != self.voices[voice_index].adsr {
+            self.voices[voice_index].set_adsr(&mut self.net, adsr);
+            self.net.commit();
+        }
+    }
+
     /// Route UI events to the appropriate methods
     pub fn handle_event(&mut self, event: AudioEvent) -> AudioEventResult {
         match event {
-            AudioEvent::PlayNote { frequency } => {
-                self.play_note(frequency);
+            AudioEvent::PlayNote {
+                note_id,
+                frequency,
+                velocity,
+            } => {
+                self.play_note(note_id, frequency, velocity);
                 AudioEventResult::Ok
             }
             AudioEvent::SetFrequency { frequency } => {
                 self.set_frequency(frequency);
                 AudioEventResult::Ok
             }
-            AudioEvent::NoteOff => {
-                self.note_off();
+            AudioEvent::SetPitchBend { bend } => {
+                self.set_pitch_bend(bend);
+                AudioEventResult::Ok
+            }
+            AudioEvent::NoteOff { note_id } => {
+                self.note_off(note_id);
+                AudioEventResult::Ok
+            }
+            AudioEvent::NoteOffAll => {
+                self.note_off_all();
+                AudioEventResult::Ok
+            }
+            AudioEvent::At { frame, event } => {
+                if frame <= self.frame_position || !self.enabled {
+                    self.handle_event(*event)
+                } else {
+                    self.scheduler
+                        .schedule(frame, ScheduledAction::Event(event));
+                    AudioEventResult::Ok
+                }
+            }
+            AudioEvent::ScheduleNote {
+                frequency,
+                velocity,
+                start_time,
+                duration,
+            } => {
+                self.schedule_note(frequency, velocity, start_time, duration);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetArrangement { arrangement } => {
+                self.set_arrangement(arrangement);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SequencerPlay { pattern } => {
+                self.sequencer_play(pattern);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SequencerStop => {
+                self.sequencer_stop();
+                AudioEventResult::Ok
+            }
+            AudioEvent::Strum { frequencies } => {
+                self.strum(&frequencies);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetFrequencySmoothing { smoothing } => {
+                self.set_frequency_smoothing(smoothing);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetStrumSettings { settings } => {
+                self.set_strum_settings(settings);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetQuantize { subdivision } => {
+                self.set_quantize(subdivision);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetInputLevel { level } => {
+                self.set_input_level(level);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetEngineMode { mode } => {
+                self.set_engine_mode(mode);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetInputEnvelopeAmount {
+                destination,
+                amount,
+            } => {
+                self.set_input_envelope_amount(destination, amount);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetVocoderBands { bands } => {
+                self.set_vocoder_bands(bands);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetFormantShift { semitones } => {
+                self.set_formant_shift(semitones);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetPitchBendRange { semitones } => {
+                self.set_pitch_bend_range(semitones);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetLatch { enabled } => {
+                self.set_latch(enabled);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetHold { enabled } => {
+                self.set_hold(enabled);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetMono { enabled } => {
+                self.set_mono(enabled);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetNotePriority { priority } => {
+                self.set_note_priority(priority);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetInstrumentMode { mode } => {
+                self.set_instrument_mode(mode);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetSoftClip { enabled } => {
+                self.set_soft_clip(enabled);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetLimiterBypass { bypass } => {
+                self.set_limiter_bypass(bypass);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetEffectOrder { order } => {
+                self.set_effect_order(order);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetSlotEffect { slot, effect } => {
+                self.set_slot_effect(slot, effect);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetSlotParam { slot, param, value } => {
+                self.set_slot_param(slot, param, value);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetReverbType { slot, reverb_type } => {
+                self.set_reverb_type(slot, reverb_type);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetEffectMix { effect, mix } => {
+                self.set_effect_mix(effect, mix);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetVoicePanMode { mode } => {
+                self.set_voice_pan_mode(mode);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetStealPolicy { policy } => {
+                self.set_steal_policy(policy);
+                AudioEventResult::Ok
+            }
+            AudioEvent::TriggerDrum { drum, velocity } => {
+                self.trigger_drum(drum, velocity);
                 AudioEventResult::Ok
             }
             AudioEvent::SetMasterVolume { volume } => {
                 self.set_master_volume(volume);
                 AudioEventResult::Ok
             }
+            AudioEvent::SetMasterVolumeDb { db } => {
+                self.set_master_volume_db(db);
+                AudioEventResult::Ok
+            }
             AudioEvent::SetWaveform { waveform } => {
                 self.set_waveform(waveform);
                 AudioEventResult::Ok
@@ -627,6 +3723,18 @@ impl FunDSPSynth {
                 self.set_delay_mix(delay_mix);
                 AudioEventResult::Ok
             }
+            AudioEvent::SetDelayMode { mode } => {
+                self.set_delay_mode(mode);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetDelayLowCut { low_cut } => {
+                self.set_delay_low_cut(low_cut);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetDelayHighCut { high_cut } => {
+                self.set_delay_high_cut(high_cut);
+                AudioEventResult::Ok
+            }
             AudioEvent::SetFilterCutoff { cutoff } => {
                 self.set_filter_cutoff(cutoff);
                 AudioEventResult::Ok
@@ -635,6 +3743,155 @@ impl FunDSPSynth {
                 self.set_filter_resonance(resonance);
                 AudioEventResult::Ok
             }
+            AudioEvent::SetUnisonVoices { voices } => {
+                self.set_unison(Unison {
+                    voices,
+                    ..self.unison
+                });
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetUnisonDetune { detune } => {
+                self.set_unison(Unison {
+                    detune,
+                    ..self.unison
+                });
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetUnisonSpread { spread } => {
+                self.set_unison(Unison {
+                    spread,
+                    ..self.unison
+                });
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetRingModMix { mix } => {
+                self.set_ring_mod_mix(mix);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetRingModRatio { ratio } => {
+                self.set_ring_mod_ratio(ratio);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetVibratoRate { rate } => {
+                self.set_vibrato_rate(rate);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetVibratoDepth { depth } => {
+                self.set_vibrato_depth(depth);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetVibratoDelay { delay } => {
+                self.set_vibrato_delay(delay);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetFilterLfoRate { rate } => {
+                self.set_filter_lfo_rate(rate);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetFilterLfoDepth { depth } => {
+                self.set_filter_lfo_depth(depth);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetFilterLfoSync { beats } => {
+                self.set_filter_lfo_sync(beats);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetTempo { bpm } => {
+                self.set_tempo(bpm);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetLfoShape { lfo, shape } => {
+                self.set_lfo_shape(lfo, shape);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetGainTrim { stage, db } => {
+                self.set_gain_trim(stage, db);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetEnvelopeMode { mode } => {
+                self.set_envelope_mode(mode);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetEnvelopeDelay { delay } => {
+                self.set_envelope_delay(delay);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetEnvelopeHold { hold } => {
+                self.set_envelope_hold(hold);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetFilterVelocityAmount { amount } => {
+                self.set_filter_velocity_amount(amount);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetVoiceSpread { spread } => {
+                self.set_voice_spread(spread);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetStereoWidth { width } => {
+                self.set_stereo_width(width);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetLimiterAttack { attack } => {
+                self.set_limiter_attack(attack);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetLimiterRelease { release } => {
+                self.set_limiter_release(release);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetLimiterCeiling { ceiling } => {
+                self.set_limiter_ceiling(ceiling);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetOutputCeiling { ceiling } => {
+                self.set_output_ceiling(ceiling);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetSoftClipDrive { drive } => {
+                self.set_soft_clip_drive(drive);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetGateSmoothing { smoothing } => {
+                self.set_gate_smoothing(smoothing);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetEnvelopeSmoothing { smoothing } => {
+                self.set_envelope_smoothing(smoothing);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetKeyZone { zone } => {
+                self.set_key_zone(zone);
+                AudioEventResult::Ok
+            }
+            AudioEvent::ClearKeyZones => {
+                self.clear_key_zones();
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetKeySplit { split } => {
+                self.set_key_split(split);
+                AudioEventResult::Ok
+            }
+            AudioEvent::ClearKeySplit => {
+                self.clear_key_split();
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetLayer { layer } => {
+                self.set_layer(layer);
+                AudioEventResult::Ok
+            }
+            AudioEvent::SetLayerMix { mix, main_mix } => {
+                self.set_layer_mix(mix, main_mix);
+                AudioEventResult::Ok
+            }
+            AudioEvent::ClearLayer => {
+                self.clear_layer();
+                AudioEventResult::Ok
+            }
+            AudioEvent::LoadPatch { patch } => {
+                self.apply_patch(&patch);
+                AudioEventResult::Ok
+            }
             AudioEvent::GetMasterVolume => AudioEventResult::ValueF32(self.get_master_volume()),
             AudioEvent::GetWaveform => AudioEventResult::ValueWaveform(self.get_waveform()),
             AudioEvent::GetAttack => AudioEventResult::ValueF32(self.get_attack()),
@@ -644,10 +3901,194 @@ impl FunDSPSynth {
             AudioEvent::GetDelayTime => AudioEventResult::ValueF32(self.get_delay_time()),
             AudioEvent::GetDelayFeedback => AudioEventResult::ValueF32(self.get_delay_feedback()),
             AudioEvent::GetDelayMix => AudioEventResult::ValueF32(self.get_delay_mix()),
+            AudioEvent::GetDelayMode => AudioEventResult::ValueDelayMode(self.get_delay_mode()),
+            AudioEvent::GetDelayLowCut => AudioEventResult::ValueF32(self.get_delay_low_cut()),
+            AudioEvent::GetDelayHighCut => AudioEventResult::ValueF32(self.get_delay_high_cut()),
             AudioEvent::GetFilterCutoff => AudioEventResult::ValueF32(self.get_filter_cutoff()),
             AudioEvent::GetFilterResonance => {
                 AudioEventResult::ValueF32(self.get_filter_resonance())
             }
+            AudioEvent::GetRingModMix => AudioEventResult::ValueF32(self.get_ring_mod_mix()),
+            AudioEvent::GetRingModRatio => AudioEventResult::ValueF32(self.get_ring_mod_ratio()),
+            AudioEvent::GetVibratoRate => AudioEventResult::ValueF32(self.get_vibrato_rate()),
+            AudioEvent::GetVibratoDepth => AudioEventResult::ValueF32(self.get_vibrato_depth()),
+            AudioEvent::GetVibratoDelay => AudioEventResult::ValueF32(self.get_vibrato_delay()),
+            AudioEvent::GetFilterLfoRate => AudioEventResult::ValueF32(self.get_filter_lfo_rate()),
+            AudioEvent::GetFilterLfoDepth => {
+                AudioEventResult::ValueF32(self.get_filter_lfo_depth())
+            }
+            AudioEvent::GetFilterLfoSync => AudioEventResult::ValueF32(self.get_filter_lfo_sync()),
+            AudioEvent::GetTempo => AudioEventResult::ValueF32(self.get_tempo()),
+            AudioEvent::GetLfoShape { lfo } => {
+                AudioEventResult::ValueLfoShape(self.get_lfo_shape(lfo))
+            }
+            AudioEvent::GetEnvelopeMode => {
+                AudioEventResult::ValueEnvelopeMode(self.get_envelope_mode())
+            }
+            AudioEvent::GetEnvelopeDelay => AudioEventResult::ValueF32(self.get_envelope_delay()),
+            AudioEvent::GetEnvelopeHold => AudioEventResult::ValueF32(self.get_envelope_hold()),
+            AudioEvent::GetFilterVelocityAmount => {
+                AudioEventResult::ValueF32(self.get_filter_velocity_amount())
+            }
+            AudioEvent::GetVoiceSpread => AudioEventResult::ValueF32(self.get_voice_spread()),
+            AudioEvent::GetStereoWidth => AudioEventResult::ValueF32(self.get_stereo_width()),
+            AudioEvent::GetLimiterAttack => AudioEventResult::ValueF32(self.get_limiter_attack()),
+            AudioEvent::GetLimiterRelease => AudioEventResult::ValueF32(self.get_limiter_release()),
+            AudioEvent::GetLimiterCeiling => AudioEventResult::ValueF32(self.get_limiter_ceiling()),
+            AudioEvent::GetOutputCeiling => AudioEventResult::ValueF32(self.get_output_ceiling()),
+            AudioEvent::GetSoftClipDrive => AudioEventResult::ValueF32(self.get_soft_clip_drive()),
+            AudioEvent::GetGateSmoothing => {
+                AudioEventResult::ValueSmoothing(self.get_gate_smoothing())
+            }
+            AudioEvent::GetEnvelopeSmoothing => {
+                AudioEventResult::ValueSmoothing(self.get_envelope_smoothing())
+            }
+            AudioEvent::GetPatch => AudioEventResult::ValuePatch(self.get_patch()),
+            AudioEvent::GetUnison => AudioEventResult::ValueUnison(self.get_unison()),
+            AudioEvent::GetLatch => AudioEventResult::ValueBool(self.get_latch()),
+            AudioEvent::GetHold => AudioEventResult::ValueBool(self.get_hold()),
+            AudioEvent::GetMono => AudioEventResult::ValueBool(self.get_mono()),
+            AudioEvent::GetNotePriority => {
+                AudioEventResult::ValueNotePriority(self.get_note_priority())
+            }
+            AudioEvent::GetInstrumentMode => {
+                AudioEventResult::ValueInstrumentMode(self.get_instrument_mode())
+            }
+            AudioEvent::GetKeySplit => AudioEventResult::ValueKeySplit(self.get_key_split()),
+            AudioEvent::GetLayer => AudioEventResult::ValueLayer(self.get_layer()),
+            AudioEvent::GetStealPolicy => {
+                AudioEventResult::ValueStealPolicy(self.get_steal_policy())
+            }
+            AudioEvent::GetVoicePanMode => {
+                AudioEventResult::ValueVoicePanMode(self.get_voice_pan_mode())
+            }
+            AudioEvent::GetLimiterBypass => AudioEventResult::ValueBool(self.get_limiter_bypass()),
+            AudioEvent::GetEffectOrder => {
+                AudioEventResult::ValueEffectOrder(self.get_effect_order())
+            }
+            AudioEvent::GetEffectSlot { slot } => match self.get_effect_slot(slot) {
+                Some(slot) => AudioEventResult::ValueEffectSlot(slot),
+                None => AudioEventResult::Err("No such effect slot"),
+            },
+            AudioEvent::GetEffectMix { effect } => match self.get_effect_mix(effect) {
+                Some(mix) => AudioEventResult::ValueF32(mix),
+                None => AudioEventResult::Err("No such effect"),
+            },
+            AudioEvent::GetSoftClip => AudioEventResult::ValueBool(self.get_soft_clip()),
+            AudioEvent::GetMasterVolumeDb => {
+                AudioEventResult::ValueF32(self.get_master_volume_db())
+            }
+            AudioEvent::GetGainTrim { stage } => {
+                AudioEventResult::ValueF32(self.get_gain_trim(stage))
+            }
+            AudioEvent::GetFrequencySmoothing => {
+                AudioEventResult::ValueSmoothing(self.get_frequency_smoothing())
+            }
+            AudioEvent::GetStrumSettings => {
+                AudioEventResult::ValueStrumSettings(self.get_strum_settings())
+            }
+            AudioEvent::GetQuantize => AudioEventResult::ValueU32(self.get_quantize()),
+            AudioEvent::GetInputLevel => AudioEventResult::ValueF32(self.get_input_level()),
+            AudioEvent::GetEngineMode => AudioEventResult::ValueEngineMode(self.get_engine_mode()),
+            AudioEvent::GetInputEnvelopeAmount { destination } => {
+                AudioEventResult::ValueF32(self.get_input_envelope_amount(destination))
+            }
+            AudioEvent::GetVocoderBands => AudioEventResult::ValueU32(self.get_vocoder_bands()),
+            AudioEvent::GetFormantShift => AudioEventResult::ValueF32(self.get_formant_shift()),
+            AudioEvent::GetPitchBendRange => {
+                AudioEventResult::ValueF32(self.get_pitch_bend_range())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+
+    fn event() -> impl Strategy<Value = AudioEvent> {
+        prop_oneof![
+            (0u32..8, 20.0f32..2000.0, 0.0f32..1.0).prop_map(|(note_id, frequency, velocity)| {
+                AudioEvent::PlayNote {
+                    note_id,
+                    frequency,
+                    velocity,
+                }
+            }),
+            (0u32..8).prop_map(|note_id| AudioEvent::NoteOff { note_id }),
+            Just(AudioEvent::NoteOffAll),
+            (20.0f32..2000.0).prop_map(|frequency| AudioEvent::SetFrequency { frequency }),
+            (0.0f32..1.0).prop_map(|volume| AudioEvent::SetMasterVolume { volume }),
+            (-60.0f32..0.0).prop_map(|db| AudioEvent::SetMasterVolumeDb { db }),
+            (0.0f32..2.0).prop_map(|attack| AudioEvent::SetAttack { attack }),
+            (20.0f32..20000.0).prop_map(|cutoff| AudioEvent::SetFilterCutoff { cutoff }),
+        ]
+    }
+
+    /// Which parameter a coalesced event sets, or None for note events
+    fn parameter(event: &AudioEvent) -> Option<&'static str> {
+        match event {
+            AudioEvent::SetFrequency { .. } => Some("frequency"),
+            AudioEvent::SetMasterVolume { .. } | AudioEvent::SetMasterVolumeDb { .. } => {
+                Some("volume")
+            }
+            AudioEvent::SetAttack { .. } => Some("attack"),
+            AudioEvent::SetFilterCutoff { .. } => Some("cutoff"),
+            _ => None,
+        }
+    }
+
+    fn drain(events: &[AudioEvent]) -> Vec<AudioEvent> {
+        let (mut producer, mut consumer) = rtrb::RingBuffer::new(events.len().max(1));
+        for event in events {
+            producer.push(event.clone()).unwrap();
+        }
+        let mut latest = [NO_EVENT; CoalescedParam::COUNT];
+        let mut drained = event_buffer(&consumer);
+        drain_and_coalesce_events(&mut consumer, &mut latest, &mut drained);
+        drained
+    }
+
+    fn describe(events: &[&AudioEvent]) -> Vec<String> {
+        events.iter().map(|e| format!("{:?}", e)).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn note_events_keep_their_order(events in prop::collection::vec(event(), 0..64)) {
+            let drained = drain(&events);
+            let sent: Vec<_> = events.iter().filter(|e| parameter(e).is_none()).collect();
+            let received: Vec<_> = drained.iter().filter(|e| parameter(e).is_none()).collect();
+            prop_assert_eq!(describe(&sent), describe(&received));
+        }
+
+        #[test]
+        fn note_events_come_before_parameters(events in prop::collection::vec(event(), 0..64)) {
+            let drained = drain(&events);
+            let first_parameter = drained
+                .iter()
+                .position(|e| parameter(e).is_some())
+                .unwrap_or(drained.len());
+            prop_assert!(drained[first_parameter..].iter().all(|e| parameter(e).is_some()));
+        }
+
+        #[test]
+        fn only_the_latest_parameter_is_kept(events in prop::collection::vec(event(), 0..64)) {
+            let drained = drain(&events);
+            let mut latest: HashMap<&'static str, &AudioEvent> = HashMap::new();
+            for event in &events {
+                if let Some(name) = parameter(event) {
+                    latest.insert(name, event);
+                }
+            }
+            let kept: Vec<_> = drained.iter().filter(|e| parameter(e).is_some()).collect();
+            prop_assert_eq!(kept.len(), latest.len());
+            for event in kept {
+                let expected = latest[parameter(event).unwrap()];
+                prop_assert_eq!(format!("{:?}", event), format!("{:?}", expected));
+            }
         }
     }
 }
@@ -0,0 +1,198 @@
+// Channel vocoder: the audio input (modulator) is split into bands, and each
+// band's level sets the level of the same band of the synth (carrier)
+use fundsp::hacker::{shared, AudioNode, Frame, U2, U4};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+pub const MIN_VOCODER_BANDS: u32 = 4;
+pub const MAX_VOCODER_BANDS: u32 = 32;
+pub const DEFAULT_VOCODER_BANDS: u32 = 16;
+/// Formant shift range in semitones
+pub const MAX_FORMANT_SHIFT: f32 = 12.0;
+
+/// Band centres are spread evenly in pitch between these
+const LOWEST_BAND_HZ: f32 = 100.0;
+const HIGHEST_BAND_HZ: f32 = 8000.0;
+/// Envelope follower times, fast enough to keep consonants
+const ENVELOPE_ATTACK: f32 = 0.005;
+const ENVELOPE_RELEASE: f32 = 0.030;
+/// Band envelopes are well under 1.0 for speech at normal mic levels, so the
+/// output is brought back up to about the level of the dry synth
+const OUTPUT_GAIN: f32 = 8.0;
+
+/// What the voices go through before the effects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineMode {
+    /// The voices, with the audio input mixed in
+    #[default]
+    Synth,
+    /// The voices vocoded by the audio input
+    Vocoder,
+}
+
+impl EngineMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngineMode::Synth => "synth",
+            EngineMode::Vocoder => "vocoder",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "synth" => Some(EngineMode::Synth),
+            "vocoder" => Some(EngineMode::Vocoder),
+            _ => None,
+        }
+    }
+}
+
+/// Constant peak gain band pass biquad
+#[derive(Clone, Default)]
+struct BandPass {
+    b0: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl BandPass {
+    fn set(&mut self, centre: f32, q: f32, sample_rate: f32) {
+        let centre = centre.min(0.45 * sample_rate);
+        let w = TAU * centre / sample_rate;
+        let alpha = w.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        self.b0 = alpha / a0;
+        self.b2 = -alpha / a0;
+        self.a1 = -2.0 * w.cos() / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+
+    fn tick(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+#[derive(Clone, Default)]
+struct Band {
+    modulator: BandPass,
+    carrier: [BandPass; 2],
+    envelope: f32,
+}
+
+/// Inputs are the carrier (left, right) then the modulator (left, right).
+/// The band count is fixed per node, so changing it means a new node;
+/// `formant_shift` (semitones) moves the carrier bands relative to the
+/// modulator's, making the voice sound bigger or smaller
+#[derive(Clone)]
+pub struct VocoderNode {
+    bands: Vec<Band>,
+    formant_shift: shared::Shared,
+    /// Shift the carrier filters were last tuned for
+    tuned_shift: f32,
+    sample_rate: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl VocoderNode {
+    pub fn new(bands: u32, formant_shift: shared::Shared) -> Self {
+        let bands = bands.clamp(MIN_VOCODER_BANDS, MAX_VOCODER_BANDS) as usize;
+        let mut node = VocoderNode {
+            bands: vec![Band::default(); bands],
+            formant_shift,
+            tuned_shift: 0.0,
+            sample_rate: 48000.0,
+            attack: 0.0,
+            release: 0.0,
+        };
+        node.set_sample_rate(48000.0);
+        node
+    }
+
+    /// Ratio between neighbouring band centres
+    fn spacing(&self) -> f32 {
+        (HIGHEST_BAND_HZ / LOWEST_BAND_HZ).powf(1.0 / (self.bands.len() - 1) as f32)
+    }
+
+    fn tune(&mut self) {
+        let spacing = self.spacing();
+        // Neighbouring bands cross at about -3dB
+        let q = spacing.sqrt() / (spacing - 1.0);
+        let shift = (self.tuned_shift / 12.0).exp2();
+        let sample_rate = self.sample_rate;
+        let mut centre = LOWEST_BAND_HZ;
+        for band in self.bands.iter_mut() {
+            band.modulator.set(centre, q, sample_rate);
+            for carrier in band.carrier.iter_mut() {
+                carrier.set(centre * shift, q, sample_rate);
+            }
+            centre *= spacing;
+        }
+    }
+}
+
+impl AudioNode for VocoderNode {
+    const ID: u64 = 0x4861_7270_566f_6363;
+    type Inputs = U4;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        for band in self.bands.iter_mut() {
+            band.modulator.reset();
+            for carrier in band.carrier.iter_mut() {
+                carrier.reset();
+            }
+            band.envelope = 0.0;
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+        self.attack = 1.0 - (-1.0 / (ENVELOPE_ATTACK * self.sample_rate)).exp();
+        self.release = 1.0 - (-1.0 / (ENVELOPE_RELEASE * self.sample_rate)).exp();
+        self.tune();
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let shift = self
+            .formant_shift
+            .value()
+            .clamp(-MAX_FORMANT_SHIFT, MAX_FORMANT_SHIFT);
+        if shift != self.tuned_shift {
+            self.tuned_shift = shift;
+            self.tune();
+        }
+        let modulator = 0.5 * (input[2] + input[3]);
+        let (mut left, mut right) = (0.0, 0.0);
+        for band in self.bands.iter_mut() {
+            let level = band.modulator.tick(modulator).abs();
+            let rate = if level > band.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+            band.envelope += rate * (level - band.envelope);
+            left += band.carrier[0].tick(input[0]) * band.envelope;
+            right += band.carrier[1].tick(input[1]) * band.envelope;
+        }
+        [left * OUTPUT_GAIN, right * OUTPUT_GAIN].into()
+    }
+}
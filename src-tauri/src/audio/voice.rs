@@ -0,0 +1,591 @@
+// Polyphonic voices and the allocator that assigns notes to them
+use super::envelope::{DahdsrNode, EnvelopeMode};
+use super::lfo::{LfoShape, VibratoNode};
+use super::sampler::{Sample, SamplerNode};
+use super::smoothing::{SmootherNode, Smoothing, SmoothingVars};
+use super::synthesis::{connect_stereo, stereo_mixer, AdsrParams, Waveform};
+use fundsp::hacker::{
+    adsr_live, afollow, dc, pan, pass, shared, sine, sink, var, zero, An, AudioUnit, Net, NodeId,
+};
+use std::sync::Arc;
+
+/// Number of simultaneously sounding voices
+pub const MAX_VOICES: usize = 8;
+/// How long a stolen voice takes to fade out before its new note starts
+pub const STEAL_FADE_SECS: f32 = 0.005;
+/// Most oscillator copies a unison voice can stack
+pub const MAX_UNISON: usize = 8;
+/// Pitch smoothing: just enough to take the zipper noise off pitch changes
+pub const DEFAULT_FREQUENCY_SMOOTHING: Smoothing = Smoothing::new(0.001, 0.001);
+/// Longer than this and notes audibly slide
+pub const MAX_FREQUENCY_SMOOTHING: f32 = 0.5;
+/// Widest pitch bend range, in semitones either way
+pub const MAX_PITCH_BEND: f32 = 24.0;
+/// Pitch bend range until set otherwise: the usual MIDI wheel's
+pub const DEFAULT_PITCH_BEND_RANGE: f32 = 2.0;
+/// Gate and envelope smoothing, to take the clicks off retriggered notes
+pub const DEFAULT_GATE_SMOOTHING: Smoothing = Smoothing::new(0.001, 0.001);
+pub const DEFAULT_ENVELOPE_SMOOTHING: Smoothing = Smoothing::new(0.0005, 0.0005);
+/// Longer than these and attacks go soft and short notes lose their shape
+pub const MAX_GATE_SMOOTHING: f32 = 0.02;
+pub const MAX_ENVELOPE_SMOOTHING: f32 = 0.01;
+
+/// Which held voice to take when a new note arrives and none are free
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StealPolicy {
+    /// The voice that started longest ago
+    #[default]
+    Oldest,
+    /// The voice played most softly
+    Quietest,
+    /// A voice already playing the new note's pitch, else the oldest
+    SamePitch,
+}
+
+impl StealPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StealPolicy::Oldest => "oldest",
+            StealPolicy::Quietest => "quietest",
+            StealPolicy::SamePitch => "same_pitch",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "oldest" => Some(StealPolicy::Oldest),
+            "quietest" => Some(StealPolicy::Quietest),
+            "same_pitch" | "same-pitch" => Some(StealPolicy::SamePitch),
+            _ => None,
+        }
+    }
+}
+
+/// How voice spread places each new note in the stereo field
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoicePanMode {
+    /// Successive notes alternate left and right
+    #[default]
+    RoundRobin,
+    /// Low notes to the left, high notes to the right, like a piano
+    Pitch,
+}
+
+impl VoicePanMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VoicePanMode::RoundRobin => "round_robin",
+            VoicePanMode::Pitch => "pitch",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "round_robin" | "round-robin" => Some(VoicePanMode::RoundRobin),
+            "pitch" => Some(VoicePanMode::Pitch),
+            _ => None,
+        }
+    }
+}
+
+/// Pan positions round robin voice spread steps through
+const ROUND_ROBIN_PANS: [f32; 8] = [-1.0, 1.0, -0.5, 0.5, -0.75, 0.75, -0.25, 0.25];
+
+/// Pan position (-1.0 to 1.0) for a new note. `count` is the number of
+/// notes played so far, for round robin
+pub fn voice_pan(mode: VoicePanMode, spread: f32, frequency: f32, count: usize) -> f32 {
+    let position = match mode {
+        VoicePanMode::RoundRobin => ROUND_ROBIN_PANS[count % ROUND_ROBIN_PANS.len()],
+        // Two octaves either side of middle C reach the edges
+        VoicePanMode::Pitch => ((frequency / 261.63).log2() / 2.0).clamp(-1.0, 1.0),
+    };
+    position * spread
+}
+
+/// Stacked, detuned copies of the oscillator, spread across the stereo field
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Unison {
+    /// Number of copies; 1 is a plain single oscillator
+    pub voices: usize,
+    /// Detune between the outermost copies and the note, in cents
+    pub detune: f32,
+    /// Stereo width of the copies (0.0 = all centred, 1.0 = hard left to hard right)
+    pub spread: f32,
+}
+
+impl Default for Unison {
+    fn default() -> Self {
+        Unison {
+            voices: 1,
+            detune: 10.0,
+            spread: 0.5,
+        }
+    }
+}
+
+/// Synth-wide parameters that every voice reads from inside the graph
+#[derive(Clone)]
+pub struct VoiceControls {
+    /// Ring modulation amount (0.0 = dry, 1.0 = fully ring modulated)
+    pub ring_mix: shared::Shared,
+    /// Ring modulator frequency as a multiple of the note frequency
+    pub ring_ratio: shared::Shared,
+    /// Vibrato rate (Hz), depth (cents) and onset delay (seconds)
+    pub vibrato_rate: shared::Shared,
+    pub vibrato_depth: shared::Shared,
+    pub vibrato_delay: shared::Shared,
+    /// Vibrato `LfoShape`, as f32
+    pub vibrato_shape: shared::Shared,
+    /// Rise and fall times of the note frequency
+    pub frequency_smoothing: SmoothingVars,
+    /// Pitch bend, as a multiple of the note frequency
+    pub pitch_bend: shared::Shared,
+    /// Rise and fall times of the gate into the envelope, and of its output
+    pub gate_smoothing: SmoothingVars,
+    pub envelope_smoothing: SmoothingVars,
+}
+
+impl Default for VoiceControls {
+    fn default() -> Self {
+        VoiceControls {
+            ring_mix: shared(0.0),
+            ring_ratio: shared(2.0),
+            vibrato_rate: shared(5.5),
+            vibrato_depth: shared(0.0),
+            vibrato_delay: shared(0.3),
+            vibrato_shape: shared(LfoShape::Sine.to_f32()),
+            frequency_smoothing: SmoothingVars::new(DEFAULT_FREQUENCY_SMOOTHING),
+            pitch_bend: shared(1.0),
+            gate_smoothing: SmoothingVars::new(DEFAULT_GATE_SMOOTHING),
+            envelope_smoothing: SmoothingVars::new(DEFAULT_ENVELOPE_SMOOTHING),
+        }
+    }
+}
+
+/// Build the envelope node for `adsr`: gate in, level out
+fn create_envelope(adsr: &AdsrParams) -> Box<dyn AudioUnit + Send> {
+    match adsr.mode {
+        EnvelopeMode::Adsr => Box::new(adsr_live(
+            adsr.attack,
+            adsr.decay,
+            adsr.sustain,
+            adsr.release,
+        )),
+        EnvelopeMode::Dahdsr | EnvelopeMode::Looping => Box::new(An(DahdsrNode::new(*adsr))),
+    }
+}
+
+/// Build a unison oscillator: frequency in, stereo out. `make` creates one
+/// copy of the underlying mono oscillator
+fn unison_oscillator(
+    unison: &Unison,
+    mut make: impl FnMut() -> Box<dyn AudioUnit + Send>,
+) -> Box<dyn AudioUnit + Send> {
+    let count = unison.voices.clamp(1, MAX_UNISON);
+    // Keep the overall level steady as copies are added, with headroom to avoid clipping
+    let gain = 0.5 / (count as f32).sqrt();
+    let mut net = Net::new(1, 2);
+    let mut mix_nodeid = None;
+    for i in 0..count {
+        // Copies are spaced evenly from -1 (flat, left) to 1 (sharp, right)
+        let offset = if count > 1 {
+            2.0 * i as f32 / (count - 1) as f32 - 1.0
+        } else {
+            0.0
+        };
+        let ratio = 2.0f32.powf(unison.detune * offset / 1200.0);
+        let ratio_nodeid = net.push(Box::new(pass() * ratio));
+        net.connect_input(0, ratio_nodeid, 0);
+        let oscillator_nodeid = net.push(make());
+        net.pipe_all(ratio_nodeid, oscillator_nodeid);
+        let pan_nodeid = net.push(Box::new(pass() * gain >> pan(offset * unison.spread)));
+        net.pipe_all(oscillator_nodeid, pan_nodeid);
+        mix_nodeid = Some(match mix_nodeid {
+            None => pan_nodeid,
+            Some(mix_nodeid) => {
+                let adder_nodeid = net.push(stereo_mixer());
+                connect_stereo(&mut net, mix_nodeid, adder_nodeid, 0);
+                connect_stereo(&mut net, pan_nodeid, adder_nodeid, 2);
+                adder_nodeid
+            }
+        });
+    }
+    net.pipe_output(mix_nodeid.unwrap());
+    Box::new(net)
+}
+
+/// The vars a voice's notes are played through. A layer voice shares its
+/// main voice's, so it plays whatever that voice plays
+#[derive(Clone)]
+struct NoteVars {
+    frequency: shared::Shared,
+    gate: shared::Shared,
+    velocity: shared::Shared,
+    trigger: shared::Shared,
+    fade: shared::Shared,
+    pan_left: shared::Shared,
+    pan_right: shared::Shared,
+}
+
+impl NoteVars {
+    fn new() -> Self {
+        NoteVars {
+            frequency: shared(440.0),
+            gate: shared(0.0),
+            velocity: shared(1.0),
+            trigger: shared(0.0),
+            fade: shared(1.0),
+            pan_left: shared(1.0),
+            pan_right: shared(1.0),
+        }
+    }
+}
+
+/// A single (unison) oscillator -> VCA voice with its own ADSR envelope, in stereo
+pub struct Voice {
+    /// Note currently held on this voice (None once released)
+    pub note_id: Option<u32>,
+    /// Frequency the voice was last triggered or slid to
+    pub frequency: f32,
+    /// Allocation counter value when the voice was triggered / released,
+    /// used to find the oldest voice to steal and the longest-idle voice to reuse
+    pub started_at: u64,
+    pub released_at: u64,
+
+    /// Held by latch mode: ignores note off until the note is played again
+    pub latched: bool,
+    /// Sustained by hold. `key_released` records a note off that arrived
+    /// while frozen, to be acted on when hold is turned off
+    pub frozen: bool,
+    pub key_released: bool,
+
+    /// ADSR parameters currently baked into this voice's envelope
+    pub adsr: AdsrParams,
+
+    frequency_var: shared::Shared,
+    /// Key down state control (0.0 = key up/silent, 1.0 = key down/playing) - used as ADSR gate
+    gate_var: shared::Shared,
+    /// Note velocity (0.0 to 1.0), scales the voice amplitude
+    velocity_var: shared::Shared,
+    /// Bumped on every note on, so a sampler oscillator knows to restart
+    trigger_var: shared::Shared,
+    /// Dropped to 0.0 while the voice is being stolen, fading the old note out
+    fade_var: shared::Shared,
+    /// Left and right gains placing the voice in the stereo field
+    pan_left_var: shared::Shared,
+    pan_right_var: shared::Shared,
+
+    oscillator_nodeid: NodeId,
+    adsr_nodeid: NodeId,
+    /// Final node of the voice, to be mixed with the other voices
+    pub output_nodeid: NodeId,
+}
+
+impl Voice {
+    /// Build the voice's nodes inside `net`. The caller is responsible for
+    /// wiring `output_nodeid` into the mix.
+    pub fn new(
+        net: &mut Net,
+        waveform: Waveform,
+        adsr: AdsrParams,
+        unison: &Unison,
+        controls: &VoiceControls,
+    ) -> Self {
+        Self::build(net, waveform, adsr, unison, controls, NoteVars::new())
+    }
+
+    /// Build a voice that plays every note this one does, with its own
+    /// oscillator, envelope and controls. Only this voice is played: the
+    /// layer voice's own note state is never used
+    pub fn layer(
+        &self,
+        net: &mut Net,
+        waveform: Waveform,
+        adsr: AdsrParams,
+        unison: &Unison,
+        controls: &VoiceControls,
+    ) -> Self {
+        let vars = NoteVars {
+            frequency: self.frequency_var.clone(),
+            gate: self.gate_var.clone(),
+            velocity: self.velocity_var.clone(),
+            trigger: self.trigger_var.clone(),
+            fade: self.fade_var.clone(),
+            pan_left: self.pan_left_var.clone(),
+            pan_right: self.pan_right_var.clone(),
+        };
+        Self::build(net, waveform, adsr, unison, controls, vars)
+    }
+
+    fn build(
+        net: &mut Net,
+        waveform: Waveform,
+        adsr: AdsrParams,
+        unison: &Unison,
+        controls: &VoiceControls,
+        vars: NoteVars,
+    ) -> Self {
+        let NoteVars {
+            frequency: frequency_var,
+            gate: gate_var,
+            velocity: velocity_var,
+            trigger: trigger_var,
+            fade: fade_var,
+            pan_left: pan_left_var,
+            pan_right: pan_right_var,
+        } = vars;
+
+        let freq_dc_id = net.push(Box::new(var(&frequency_var) * var(&controls.pitch_bend)));
+        let freq_smooth_id = net.push(Box::new(An(SmootherNode::new(
+            controls.frequency_smoothing.clone(),
+        ))));
+        net.connect(freq_dc_id, 0, freq_smooth_id, 0);
+
+        let gate_nodeid = net.push(Box::new(var(&gate_var)));
+
+        // Vibrato needs the gate to know when each note starts
+        let vibrato_id = net.push(Box::new(An(VibratoNode::new(
+            controls.vibrato_rate.clone(),
+            controls.vibrato_depth.clone(),
+            controls.vibrato_delay.clone(),
+            controls.vibrato_shape.clone(),
+        ))));
+        net.connect(freq_smooth_id, 0, vibrato_id, 0);
+        net.connect(gate_nodeid, 0, vibrato_id, 1);
+
+        let oscillator_nodeid =
+            net.push(unison_oscillator(unison, || waveform.create_oscillator()));
+        net.pipe_all(vibrato_id, oscillator_nodeid);
+
+        // Ring modulator: a sine at a multiple of the note frequency, blended
+        // from unity (dry) to the sine itself by the ring mix
+        let ring_osc_id = net.push(Box::new((pass() * var(&controls.ring_ratio)) >> sine()));
+        net.pipe_all(vibrato_id, ring_osc_id);
+        let ring_gain_id = net.push(Box::new(
+            (pass() * var(&controls.ring_mix)) + (dc(1.0) - var(&controls.ring_mix)),
+        ));
+        net.pipe_all(ring_osc_id, ring_gain_id);
+        // Inputs are (left, gain, right, gain)
+        let ring_id = net.push(Box::new((pass() * pass()) | (pass() * pass())));
+        net.connect(oscillator_nodeid, 0, ring_id, 0);
+        net.connect(ring_gain_id, 0, ring_id, 1);
+        net.connect(oscillator_nodeid, 1, ring_id, 2);
+        net.connect(ring_gain_id, 0, ring_id, 3);
+
+        // ADSR stuff
+        // Smoothing to try to mitigate audible clicks when retriggering the adsr
+        let gate_smoother_id = net.push(Box::new(An(SmootherNode::new(
+            controls.gate_smoothing.clone(),
+        ))));
+        net.connect(gate_nodeid, 0, gate_smoother_id, 0);
+
+        let adsr_nodeid = net.push(create_envelope(&adsr));
+        net.pipe_all(gate_smoother_id, adsr_nodeid);
+
+        // More ADSR smoothing:
+        let env_micro_id = net.push(Box::new(An(SmootherNode::new(
+            controls.envelope_smoothing.clone(),
+        ))));
+        net.connect(adsr_nodeid, 0, env_micro_id, 0);
+        // Fade for voice stealing, smoothed so it ramps rather than steps
+        let fade_id = net.push(Box::new(
+            pass() * (var(&fade_var) >> afollow(STEAL_FADE_SECS * 0.5, STEAL_FADE_SECS * 0.5)),
+        ));
+        net.connect(env_micro_id, 0, fade_id, 0);
+        // One VCA per channel: inputs are (left, envelope, right, envelope)
+        let vca_nodeid = net.push(Box::new(
+            (pass() * pass() * var(&velocity_var)) | (pass() * pass() * var(&velocity_var)),
+        ));
+        net.connect(ring_id, 0, vca_nodeid, 0);
+        net.connect(fade_id, 0, vca_nodeid, 1);
+        net.connect(ring_id, 1, vca_nodeid, 2);
+        net.connect(fade_id, 0, vca_nodeid, 3);
+
+        // Voice spread. Smoothed, as a reused voice may still be releasing
+        let pan_nodeid = net.push(Box::new(
+            (pass() * (var(&pan_left_var) >> afollow(0.005, 0.005)))
+                | (pass() * (var(&pan_right_var) >> afollow(0.005, 0.005))),
+        ));
+        net.pipe_all(vca_nodeid, pan_nodeid);
+
+        Voice {
+            note_id: None,
+            frequency: 440.0,
+            started_at: 0,
+            released_at: 0,
+            latched: false,
+            frozen: false,
+            key_released: false,
+            adsr,
+            frequency_var,
+            gate_var,
+            velocity_var,
+            trigger_var,
+            fade_var,
+            pan_left_var,
+            pan_right_var,
+            oscillator_nodeid,
+            adsr_nodeid,
+            output_nodeid: pan_nodeid,
+        }
+    }
+
+    /// Whether the voice's key is currently held
+    pub fn is_held(&self) -> bool {
+        self.note_id.is_some()
+    }
+
+    pub fn note_on(&mut self, note_id: u32, frequency: f32, velocity: f32, now: u64) {
+        self.note_id = Some(note_id);
+        self.latched = false;
+        self.frozen = false;
+        self.key_released = false;
+        self.started_at = now;
+        self.set_frequency(frequency);
+        self.velocity_var.set_value(velocity.clamp(0.0, 1.0));
+        self.trigger_var.set_value(self.trigger_var.value() + 1.0);
+        self.fade_var.set_value(1.0);
+        self.gate_var.set_value(1.0); // Gate on - triggers ADSR attack
+    }
+
+    /// Take the voice for a new note: fade the current one out, leaving the
+    /// caller to `note_on` once the fade has finished
+    pub fn steal(&mut self, note_id: u32, now: u64) {
+        self.note_id = Some(note_id);
+        self.latched = false;
+        self.frozen = false;
+        self.key_released = false;
+        self.started_at = now;
+        self.fade_var.set_value(0.0);
+        // Close the gate too, so the new note's envelope starts from its attack
+        self.gate_var.set_value(0.0);
+    }
+
+    /// Place the voice in the stereo field (-1.0 = left, 1.0 = right).
+    /// Equal power, scaled so the centre is unity gain
+    pub fn set_pan(&mut self, pan: f32) {
+        let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+        self.pan_left_var
+            .set_value(angle.cos() * std::f32::consts::SQRT_2);
+        self.pan_right_var
+            .set_value(angle.sin() * std::f32::consts::SQRT_2);
+    }
+
+    pub fn velocity(&self) -> f32 {
+        self.velocity_var.value()
+    }
+
+    /// Move a held voice to a new note without retriggering its envelope
+    pub fn glide_to(&mut self, note_id: u32, frequency: f32) {
+        self.note_id = Some(note_id);
+        self.set_frequency(frequency);
+    }
+
+    pub fn note_off(&mut self, now: u64) {
+        self.latched = false;
+        self.frozen = false;
+        if self.note_id.take().is_some() {
+            self.released_at = now;
+        }
+        self.gate_var.set_value(0.0); // Gate off - triggers ADSR release
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency;
+        self.frequency_var.set_value(frequency);
+    }
+
+    /// Replace the oscillator node. Does not commit the net.
+    pub fn set_waveform(&mut self, net: &mut Net, waveform: Waveform, unison: &Unison) {
+        net.replace(
+            self.oscillator_nodeid,
+            unison_oscillator(unison, || waveform.create_oscillator()),
+        );
+    }
+
+    /// Replace the oscillator with sample playback. Does not commit the net.
+    pub fn set_sample(&mut self, net: &mut Net, sample: Arc<Sample>, unison: &Unison) {
+        let oscillator = unison_oscillator(unison, || {
+            Box::new(An(SamplerNode::new(
+                sample.clone(),
+                self.trigger_var.clone(),
+            )))
+        });
+        net.replace(self.oscillator_nodeid, oscillator);
+    }
+
+    /// Replace the oscillator with silence, for a voice that isn't in use.
+    /// Does not commit the net.
+    pub fn set_silent(&mut self, net: &mut Net) {
+        net.replace(self.oscillator_nodeid, Box::new(sink() | zero() | zero()));
+    }
+
+    /// Rebuild the envelope with new parameters. Does not commit the net.
+    pub fn set_adsr(&mut self, net: &mut Net, adsr: AdsrParams) {
+        self.adsr = adsr;
+        net.replace(self.adsr_nodeid, create_envelope(&adsr));
+    }
+}
+
+/// Pick the voice to use for a new note: the voice already playing this
+/// note id, else the voice that has been idle longest, else steal a held
+/// voice according to `policy`. Latched and frozen voices are only stolen as a last resort, and
+/// never just because a new note reuses their id.
+pub fn allocate_voice(
+    voices: &[Voice],
+    note_id: u32,
+    frequency: f32,
+    policy: StealPolicy,
+) -> usize {
+    if let Some(i) = voices
+        .iter()
+        .position(|v| v.note_id == Some(note_id) && !v.latched)
+    {
+        return i;
+    }
+    if let Some((i, _)) = voices
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| !v.is_held())
+        .min_by_key(|(_, v)| v.released_at)
+    {
+        return i;
+    }
+    let stealable = voices
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| !v.latched && !v.frozen);
+    let stolen = match policy {
+        StealPolicy::Oldest => None,
+        StealPolicy::Quietest => stealable
+            .min_by(|(_, a), (_, b)| {
+                (a.velocity(), a.started_at)
+                    .partial_cmp(&(b.velocity(), b.started_at))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(i, _)| i),
+        StealPolicy::SamePitch => stealable
+            .filter(|(_, v)| (v.frequency / frequency - 1.0).abs() < 0.015)
+            .min_by_key(|(_, v)| v.started_at)
+            .map(|(i, _)| i),
+    };
+    stolen.unwrap_or_else(|| {
+        voices
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, v)| (v.latched || v.frozen, v.started_at))
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    })
+}
+
+/// The latched voice sounding at `frequency`, if any. Playing a latched
+/// pitch again releases it
+pub fn find_latched(voices: &[Voice], frequency: f32) -> Option<usize> {
+    // Within about a quarter of a semitone counts as the same pitch
+    voices
+        .iter()
+        .position(|v| v.latched && (v.frequency / frequency - 1.0).abs() < 0.015)
+}
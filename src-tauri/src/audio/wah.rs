@@ -0,0 +1,77 @@
+// Auto-wah: a band pass swept up by the signal's own level, so each
+// attack opens it and it closes again as the note fades
+use super::svf::{Svf, SvfCoefficients};
+use fundsp::hacker::{shared, AudioNode, Frame, U2};
+
+/// Where the band pass sits with no signal, Hz
+const WAH_BASE: f32 = 250.0;
+/// Envelope times, seconds. Quick to open on an attack, slower to close
+const WAH_ATTACK: f32 = 0.004;
+const WAH_RELEASE: f32 = 0.15;
+
+/// Stereo auto-wah. `sensitivity` (0.0 to 1.0) is how little level it
+/// takes to open fully, `range` how far it opens in octaves above 250Hz,
+/// `resonance` (0.0 to 1.0) how sharp the peak is. Both channels follow
+/// the louder one, so the sweep stays centred
+#[derive(Clone)]
+pub struct WahNode {
+    sensitivity: shared::Shared,
+    range: shared::Shared,
+    resonance: shared::Shared,
+    envelope: f32,
+    filters: [Svf; 2],
+    sample_rate: f32,
+}
+
+impl WahNode {
+    pub fn new(
+        sensitivity: shared::Shared,
+        range: shared::Shared,
+        resonance: shared::Shared,
+    ) -> Self {
+        WahNode {
+            sensitivity,
+            range,
+            resonance,
+            envelope: 0.0,
+            filters: [Svf::default(); 2],
+            sample_rate: 48000.0,
+        }
+    }
+}
+
+impl AudioNode for WahNode {
+    const ID: u64 = 0x4861_7270_5761_6821;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.envelope = 0.0;
+        self.filters.iter_mut().for_each(Svf::reset);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let level = input[0].abs().max(input[1].abs());
+        let time = if level > self.envelope {
+            WAH_ATTACK
+        } else {
+            WAH_RELEASE
+        };
+        self.envelope += (level - self.envelope) / (time * self.sample_rate);
+        // Full sensitivity opens fully at -40dB, none needs full scale
+        let gain = 100f32.powf(self.sensitivity.value().clamp(0.0, 1.0));
+        let sweep = (self.envelope * gain).min(1.0);
+        let frequency = WAH_BASE * (sweep * self.range.value()).exp2();
+        let q = 1.0 + self.resonance.value().clamp(0.0, 1.0) * 9.0;
+        let coefficients = SvfCoefficients::new(frequency, q, self.sample_rate);
+        [
+            self.filters[0].bandpass(input[0], &coefficients),
+            self.filters[1].bandpass(input[1], &coefficients),
+        ]
+        .into()
+    }
+}
@@ -0,0 +1,285 @@
+// WASAPI exclusive mode output for low latency on Windows. cpal only opens
+// shared mode streams, so this talks to WASAPI directly.
+use super::priority::promote_current_thread;
+use super::stats::STATS;
+use super::synthesis::FunDSPSynth;
+use super::StreamSettings;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tracing::error;
+use wasapi::{Direction, SampleType, ShareMode, WaveFormat};
+
+/// Buffer size to aim for when the user hasn't asked for one
+const DEFAULT_EXCLUSIVE_FRAMES: u32 = 128;
+
+/// Sample formats to try, in order of preference: (sample type, container bits, valid bits)
+const FORMATS: [(SampleType, usize, usize); 3] = [
+    (SampleType::Float, 32, 32),
+    (SampleType::Int, 32, 24),
+    (SampleType::Int, 16, 16),
+];
+
+/// What the device agreed to
+pub struct ExclusiveConfig {
+    pub device_name: String,
+    pub sample_rate: u32,
+    pub buffer_size: u32,
+    pub channels: u16,
+}
+
+/// An exclusive mode stream, rendered on its own thread. WASAPI objects
+/// aren't Send, so everything lives on that thread.
+pub struct ExclusiveStream {
+    running: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ExclusiveStream {
+    pub fn play(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// The device stays claimed while paused, we just send it silence
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ExclusiveStream {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Open the named device (or the default device) in exclusive mode
+pub fn open(
+    synth: &Arc<Mutex<FunDSPSynth>>,
+    settings: &StreamSettings,
+    callback_frames: Arc<AtomicU32>,
+    failed: Arc<AtomicBool>,
+    realtime: Arc<AtomicBool>,
+) -> Result<(ExclusiveStream, ExclusiveConfig), String> {
+    let running = Arc::new(AtomicBool::new(true));
+    let paused = Arc::new(AtomicBool::new(false));
+    let (started, start_result) = mpsc::channel();
+
+    let synth = synth.clone();
+    let settings = settings.clone();
+    let thread_running = running.clone();
+    let thread_paused = paused.clone();
+    let thread = std::thread::spawn(move || {
+        let stream = match Renderer::new(
+            settings.device_name.as_deref(),
+            settings.sample_rate,
+            settings.buffer_size,
+        ) {
+            Ok(stream) => stream,
+            Err(e) => {
+                let _ = started.send(Err(e.to_string()));
+                return;
+            }
+        };
+        let _ = started.send(Ok(stream.config()));
+
+        if settings.realtime_priority {
+            let promoted = promote_current_thread(stream.frames as u32, stream.sample_rate);
+            realtime.store(promoted, Ordering::Relaxed);
+        }
+
+        if let Err(e) = stream.run(&synth, &thread_running, &thread_paused, &callback_frames) {
+            error!("WASAPI exclusive stream error: {}", e);
+            failed.store(true, Ordering::Relaxed);
+        }
+    });
+
+    match start_result.recv() {
+        Ok(Ok(config)) => Ok((
+            ExclusiveStream {
+                running,
+                paused,
+                thread: Some(thread),
+            },
+            config,
+        )),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err("WASAPI exclusive stream thread exited".to_string()),
+    }
+}
+
+/// The WASAPI side of an exclusive stream, owned by the render thread
+struct Renderer {
+    device_name: String,
+    audio_client: wasapi::AudioClient,
+    render_client: wasapi::AudioRenderClient,
+    event: wasapi::Handle,
+    format: WaveFormat,
+    sample_type: SampleType,
+    sample_rate: u32,
+    channels: usize,
+    frames: usize,
+}
+
+impl Renderer {
+    fn new(
+        device_name: Option<&str>,
+        sample_rate: Option<u32>,
+        buffer_size: Option<u32>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Ignore the result, this fails harmlessly if COM is already initialised on this thread
+        let _ = wasapi::initialize_mta();
+
+        let device = match device_name {
+            None => wasapi::get_default_device(&Direction::Render)?,
+            Some(name) => {
+                wasapi::DeviceCollection::new(&Direction::Render)?.get_device_with_name(name)?
+            }
+        };
+        let device_name = device.get_friendlyname()?;
+        let mut audio_client = device.get_iaudioclient()?;
+
+        let sample_rate = sample_rate.unwrap_or(48000);
+        let channels = 2;
+        let (format, sample_type) = FORMATS
+            .iter()
+            .map(|(sample_type, bits, valid_bits)| {
+                let format = WaveFormat::new(
+                    *bits,
+                    *valid_bits,
+                    sample_type,
+                    sample_rate as usize,
+                    channels,
+                    None,
+                );
+                (format, sample_type.clone())
+            })
+            .find(|(format, _)| {
+                // Exclusive mode never suggests an alternative, it either takes the format or not
+                matches!(
+                    audio_client.is_supported(format, &ShareMode::Exclusive),
+                    Ok(None)
+                )
+            })
+            .ok_or_else(|| {
+                format!(
+                    "{} doesn't support exclusive mode at {} Hz",
+                    device_name, sample_rate
+                )
+            })?;
+
+        // Periods are in 100ns units
+        let (_, min_period) = audio_client.get_periods()?;
+        let frames = buffer_size.unwrap_or(DEFAULT_EXCLUSIVE_FRAMES) as i64;
+        let period = (frames * 10_000_000 / sample_rate as i64).max(min_period);
+        audio_client.initialize_client(
+            &format,
+            period,
+            &Direction::Render,
+            &ShareMode::Exclusive,
+            false,
+        )?;
+
+        let event = audio_client.set_get_eventhandle()?;
+        let frames = audio_client.get_bufferframecount()? as usize;
+        let render_client = audio_client.get_audiorenderclient()?;
+
+        Ok(Renderer {
+            device_name,
+            audio_client,
+            render_client,
+            event,
+            format,
+            sample_type,
+            sample_rate,
+            channels,
+            frames,
+        })
+    }
+
+    fn config(&self) -> ExclusiveConfig {
+        ExclusiveConfig {
+            device_name: self.device_name.clone(),
+            sample_rate: self.sample_rate,
+            buffer_size: self.frames as u32,
+            channels: self.channels as u16,
+        }
+    }
+
+    fn run(
+        &self,
+        synth: &Arc<Mutex<FunDSPSynth>>,
+        running: &AtomicBool,
+        paused: &AtomicBool,
+        callback_frames: &AtomicU32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let block_align = self.format.get_blockalign() as usize;
+        let mut samples = vec![0.0f32; self.frames * self.channels];
+        let mut bytes = vec![0u8; self.frames * block_align];
+
+        // Prime the device with silence before starting
+        self.render_client
+            .write_to_device(self.frames, block_align, &bytes, None)?;
+        self.audio_client.start_stream()?;
+
+        while running.load(Ordering::Relaxed) {
+            // In exclusive event mode the device asks for a whole buffer each time
+            self.event.wait_for_event(1000)?;
+            callback_frames.store(self.frames as u32, Ordering::Relaxed);
+
+            // Never block the render thread, output silence on contention
+            let rendered = !paused.load(Ordering::Relaxed)
+                && match synth.try_lock() {
+                    Ok(mut synth_guard) => {
+                        synth_guard.fill_interleaved(&mut samples, self.channels);
+                        true
+                    }
+                    Err(_) => {
+                        STATS.record_lock_contention();
+                        false
+                    }
+                };
+            if !rendered {
+                samples.fill(0.0);
+            }
+
+            encode_samples(
+                &samples,
+                &self.sample_type,
+                block_align / self.channels,
+                &mut bytes,
+            );
+            self.render_client
+                .write_to_device(self.frames, block_align, &bytes, None)?;
+        }
+
+        self.audio_client.stop_stream()?;
+        Ok(())
+    }
+}
+
+/// Convert float samples to the device's sample format
+fn encode_samples(
+    samples: &[f32],
+    sample_type: &SampleType,
+    bytes_per_sample: usize,
+    out: &mut [u8],
+) {
+    for (sample, out) in samples.iter().zip(out.chunks_exact_mut(bytes_per_sample)) {
+        let sample = sample.clamp(-1.0, 1.0);
+        match (sample_type, bytes_per_sample) {
+            (SampleType::Float, _) => out.copy_from_slice(&sample.to_le_bytes()),
+            // 24 bit samples in a 32 bit container are left aligned
+            (SampleType::Int, 4) => {
+                out.copy_from_slice(&(((sample * 8_388_607.0) as i32) << 8).to_le_bytes())
+            }
+            (SampleType::Int, _) => {
+                out.copy_from_slice(&((sample * 32_767.0) as i16).to_le_bytes())
+            }
+        }
+    }
+}
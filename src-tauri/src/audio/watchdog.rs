@@ -0,0 +1,105 @@
+// Output health checks. Catches NaN / Inf, a stuck DC level, or silence
+// while a note is held, so the synth can reset itself rather than stay broken
+use serde::Serialize;
+
+/// A sample this far past zero counts as sound
+const SILENCE_LEVEL: f32 = 1.0e-7;
+/// Output varying less than this within a block, away from zero, is stuck
+const DC_FLATNESS: f32 = 1.0e-5;
+const DC_LEVEL: f32 = 0.01;
+/// How long a stuck or silent output lasts before the watchdog steps in
+const DC_TIMEOUT_SECS: f32 = 0.5;
+const SILENCE_TIMEOUT_SECS: f32 = 2.0;
+
+/// What the watchdog found wrong with the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchdogFault {
+    NonFinite,
+    Dc,
+    Silence,
+}
+
+impl WatchdogFault {
+    const ALL: [WatchdogFault; 3] = [
+        WatchdogFault::NonFinite,
+        WatchdogFault::Dc,
+        WatchdogFault::Silence,
+    ];
+
+    /// For keeping in an atomic. 0 is reserved for "none"
+    pub fn to_code(self) -> u32 {
+        self as u32 + 1
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.get((code as usize).checked_sub(1)?).copied()
+    }
+}
+
+pub struct Watchdog {
+    sample_rate: f32,
+    dc_secs: f32,
+    silent_secs: f32,
+}
+
+impl Watchdog {
+    pub fn new(sample_rate: f32) -> Self {
+        Watchdog {
+            sample_rate,
+            dc_secs: 0.0,
+            silent_secs: 0.0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Check a rendered block. `expect_sound` says whether a note is held
+    /// that should be audible
+    pub fn check(
+        &mut self,
+        left: &[f32],
+        right: &[f32],
+        expect_sound: bool,
+    ) -> Option<WatchdogFault> {
+        let secs = left.len() as f32 / self.sample_rate;
+        let mut peak = 0.0f32;
+        let mut stuck = true;
+        for channel in [left, right] {
+            let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+            for &sample in channel {
+                if !sample.is_finite() {
+                    self.clear();
+                    return Some(WatchdogFault::NonFinite);
+                }
+                min = min.min(sample);
+                max = max.max(sample);
+            }
+            peak = peak.max(max.abs()).max(min.abs());
+            stuck &= max - min < DC_FLATNESS && min.abs() > DC_LEVEL;
+        }
+
+        self.dc_secs = if stuck { self.dc_secs + secs } else { 0.0 };
+        self.silent_secs = if expect_sound && peak < SILENCE_LEVEL {
+            self.silent_secs + secs
+        } else {
+            0.0
+        };
+        if self.dc_secs > DC_TIMEOUT_SECS {
+            self.clear();
+            Some(WatchdogFault::Dc)
+        } else if self.silent_secs > SILENCE_TIMEOUT_SECS {
+            self.clear();
+            Some(WatchdogFault::Silence)
+        } else {
+            None
+        }
+    }
+
+    fn clear(&mut self) {
+        self.dc_secs = 0.0;
+        self.silent_secs = 0.0;
+    }
+}
@@ -0,0 +1,99 @@
+// WAV file helpers
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::BufWriter;
+
+/// Sample format of a WAV file we write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavFormat {
+    Int16,
+    Int24,
+    Float32,
+}
+
+/// Writes stereo frames to a WAV file a frame at a time, converting to
+/// integer samples where needed
+pub struct StereoWavWriter {
+    writer: WavWriter<BufWriter<File>>,
+    format: WavFormat,
+}
+
+impl StereoWavWriter {
+    pub fn create(path: &str, sample_rate: u32, format: WavFormat) -> Result<Self, String> {
+        let (bits_per_sample, sample_format) = match format {
+            WavFormat::Int16 => (16, SampleFormat::Int),
+            WavFormat::Int24 => (24, SampleFormat::Int),
+            WavFormat::Float32 => (32, SampleFormat::Float),
+        };
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        };
+        let writer = WavWriter::create(path, spec)
+            .map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        Ok(StereoWavWriter { writer, format })
+    }
+
+    pub fn write_frame(&mut self, left: f32, right: f32) -> Result<(), String> {
+        for sample in [left, right] {
+            match self.format {
+                WavFormat::Int16 => self.writer.write_sample(to_int(sample, 16) as i16),
+                WavFormat::Int24 => self.writer.write_sample(to_int(sample, 24)),
+                WavFormat::Float32 => self.writer.write_sample(sample),
+            }
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn finalize(self) -> Result<(), String> {
+        self.writer.finalize().map_err(|e| e.to_string())
+    }
+}
+
+/// Scale a -1.0 to 1.0 sample to a `bits` bit integer, clipping anything outside
+pub fn to_int(sample: f32, bits: u32) -> i32 {
+    let max = ((1i64 << (bits - 1)) - 1) as f32;
+    (sample.clamp(-1.0, 1.0) * max).round() as i32
+}
+
+/// Write stereo frames to a WAV file
+pub fn write_stereo_wav(
+    path: &str,
+    sample_rate: u32,
+    frames: &[(f32, f32)],
+    format: WavFormat,
+) -> Result<(), String> {
+    let mut writer = StereoWavWriter::create(path, sample_rate, format)?;
+    for &(left, right) in frames {
+        writer.write_frame(left, right)?;
+    }
+    writer.finalize()
+}
+
+/// Read back a stereo WAV file written by `StereoWavWriter`
+pub fn read_stereo_wav(path: &str) -> Result<(u32, Vec<(f32, f32)>), String> {
+    let mut reader =
+        hound::WavReader::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let spec = reader.spec();
+    if spec.channels != 2 {
+        return Err(format!("{} isn't stereo", path));
+    }
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        SampleFormat::Int => {
+            let scale = 1.0 / (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()
+        }
+    }
+    .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    Ok((
+        spec.sample_rate,
+        samples.chunks_exact(2).map(|s| (s[0], s[1])).collect(),
+    ))
+}
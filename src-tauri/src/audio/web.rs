@@ -0,0 +1,505 @@
+// Web Audio backend. There's no stream for us to own in the browser: an
+// AudioWorklet calls `harphonium_render` for each render quantum and we fill
+// its channel buffers from the same FunDSPSynth the apps use.
+use super::stats::STATS;
+use super::synthesis::FunDSPSynth;
+use super::{
+    initialize_audio, queue_audio_event, AudioConfigInfo, AudioDeviceInfo, AudioEvent, DelayMode,
+    DrumKind, Effect, EffectOrder, EnvelopeMode, GainStage, InstrumentMode, LfoShape, LfoTarget,
+    NotePriority, ReverbType, Smoothing, StealPolicy, VoicePanMode, Waveform,
+};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{error, warn};
+use wasm_bindgen::prelude::*;
+
+/// The synth, shared with the audio engine so queued events reach it
+static SYNTH: OnceLock<Arc<Mutex<FunDSPSynth>>> = OnceLock::new();
+/// Sample rate of the AudioContext, passed in by `harphonium_init`
+static SAMPLE_RATE: AtomicU32 = AtomicU32::new(48000);
+/// Frames in the most recent render quantum (128 in current browsers)
+static RENDER_FRAMES: AtomicU32 = AtomicU32::new(0);
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // Scratch buffer for deinterleaving, so rendering doesn't allocate once warmed up
+    static SCRATCH: RefCell<Vec<(f32, f32)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Stands in for the platform stream controllers. The browser owns the
+/// output, so most of the stream settings aren't ours to change.
+pub struct StreamController;
+
+impl StreamController {
+    pub fn select_device(&self, _name: Option<String>) -> Result<(), String> {
+        Err("Output device selection is handled by the browser".to_string())
+    }
+
+    pub fn select_host(&self, _name: Option<String>) -> Result<(), String> {
+        Err("Audio host selection is not supported in the browser".to_string())
+    }
+
+    pub fn list_devices(&self) -> Result<Vec<AudioDeviceInfo>, String> {
+        Err("Output device selection is handled by the browser".to_string())
+    }
+
+    pub fn list_hosts(&self) -> Result<Vec<String>, String> {
+        Ok(vec!["Web Audio".to_string()])
+    }
+
+    pub fn set_sample_rate(&self, _sample_rate: Option<u32>) -> Result<(), String> {
+        Err("Sample rate is set by the AudioContext".to_string())
+    }
+
+    pub fn set_buffer_size(&self, _buffer_size: Option<u32>) -> Result<(), String> {
+        Err("Buffer size is set by the AudioContext".to_string())
+    }
+
+    pub fn set_exclusive_mode(&self, _exclusive: bool) -> Result<(), String> {
+        Err("Exclusive mode is not supported in the browser".to_string())
+    }
+
+    pub fn set_realtime_priority(&self, _enabled: bool) -> Result<(), String> {
+        Err("Audio thread priority is managed by the browser".to_string())
+    }
+
+    pub fn get_config(&self) -> Option<AudioConfigInfo> {
+        let frames = RENDER_FRAMES.load(Ordering::Relaxed);
+        Some(AudioConfigInfo {
+            host_name: "Web Audio".to_string(),
+            device_name: "Browser audio output".to_string(),
+            sample_rate: SAMPLE_RATE.load(Ordering::Relaxed),
+            engine_sample_rate: SAMPLE_RATE.load(Ordering::Relaxed),
+            buffer_size: if frames > 0 { Some(frames) } else { None },
+            channels: 2,
+            exclusive_mode: false,
+            realtime_priority: false,
+        })
+    }
+
+    /// Output silence until resumed. Suspending the AudioContext itself is up to the page
+    pub fn pause(&self) -> Result<(), String> {
+        PAUSED.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        PAUSED.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn shutdown(&self) -> Result<(), String> {
+        self.pause()
+    }
+}
+
+pub fn start_audio_stream(
+    synth: Arc<Mutex<FunDSPSynth>>,
+) -> Result<StreamController, Box<dyn std::error::Error>> {
+    if let Ok(mut s) = synth.lock() {
+        s.set_sample_rate(SAMPLE_RATE.load(Ordering::Relaxed) as f32);
+    }
+    SYNTH
+        .set(synth)
+        .map_err(|_| "Web audio backend already started")?;
+    Ok(StreamController)
+}
+
+fn queue_event(event: AudioEvent) {
+    if let Err(e) = queue_audio_event(event) {
+        error!("Error handling web audio event: {}", e);
+    }
+}
+
+/// Create the synth. Call once from the worklet with the global `sampleRate`
+#[wasm_bindgen]
+pub fn harphonium_init(sample_rate: f32) -> Result<(), JsValue> {
+    SAMPLE_RATE.store(sample_rate as u32, Ordering::Relaxed);
+    initialize_audio().map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Render one quantum into the worklet's output channels
+#[wasm_bindgen]
+pub fn harphonium_render(left: &mut [f32], right: &mut [f32]) {
+    let frames = left.len().min(right.len());
+    RENDER_FRAMES.store(frames as u32, Ordering::Relaxed);
+
+    let synth = match SYNTH.get() {
+        Some(synth) if !PAUSED.load(Ordering::Relaxed) => synth,
+        _ => {
+            left.fill(0.0);
+            right.fill(0.0);
+            return;
+        }
+    };
+
+    SCRATCH.with(|scratch| {
+        let mut scratch = scratch.borrow_mut();
+        scratch.resize(frames, (0.0, 0.0));
+        match synth.try_lock() {
+            Ok(mut synth_guard) => synth_guard.fill_stereo(&mut scratch),
+            Err(_) => {
+                STATS.record_lock_contention();
+                scratch.fill((0.0, 0.0));
+            }
+        }
+        for (i, &(l, r)) in scratch.iter().enumerate() {
+            left[i] = l;
+            right[i] = r;
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_note_on(note_id: u32, frequency: f32, velocity: f32) {
+    queue_event(AudioEvent::PlayNote {
+        note_id,
+        frequency,
+        velocity,
+    });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_note_off(note_id: u32) {
+    queue_event(AudioEvent::NoteOff { note_id });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_note_off_all() {
+    queue_event(AudioEvent::NoteOffAll);
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_frequency(frequency: f32) {
+    queue_event(AudioEvent::SetFrequency { frequency });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_waveform(waveform: &str) {
+    match Waveform::from_str(waveform) {
+        Some(waveform) => queue_event(AudioEvent::SetWaveform { waveform }),
+        None => warn!("Unknown waveform: {}", waveform),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_unison_voices(voices: usize) {
+    queue_event(AudioEvent::SetUnisonVoices { voices });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_unison_detune(detune: f32) {
+    queue_event(AudioEvent::SetUnisonDetune { detune });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_unison_spread(spread: f32) {
+    queue_event(AudioEvent::SetUnisonSpread { spread });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_ring_mod_mix(mix: f32) {
+    queue_event(AudioEvent::SetRingModMix { mix });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_ring_mod_ratio(ratio: f32) {
+    queue_event(AudioEvent::SetRingModRatio { ratio });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_vibrato_rate(rate: f32) {
+    queue_event(AudioEvent::SetVibratoRate { rate });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_vibrato_depth(depth: f32) {
+    queue_event(AudioEvent::SetVibratoDepth { depth });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_vibrato_delay(delay: f32) {
+    queue_event(AudioEvent::SetVibratoDelay { delay });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_filter_lfo_rate(rate: f32) {
+    queue_event(AudioEvent::SetFilterLfoRate { rate });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_filter_lfo_depth(depth: f32) {
+    queue_event(AudioEvent::SetFilterLfoDepth { depth });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_filter_lfo_sync(beats: f32) {
+    queue_event(AudioEvent::SetFilterLfoSync { beats });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_tempo(bpm: f32) {
+    queue_event(AudioEvent::SetTempo { bpm });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_lfo_shape(lfo: &str, shape: &str) {
+    match (LfoTarget::from_str(lfo), LfoShape::from_str(shape)) {
+        (Some(lfo), Some(shape)) => queue_event(AudioEvent::SetLfoShape { lfo, shape }),
+        _ => warn!("Unknown LFO or shape: {} {}", lfo, shape),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_envelope_delay(delay: f32) {
+    queue_event(AudioEvent::SetEnvelopeDelay { delay });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_envelope_hold(hold: f32) {
+    queue_event(AudioEvent::SetEnvelopeHold { hold });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_envelope_mode(mode: &str) {
+    match EnvelopeMode::from_str(mode) {
+        Some(mode) => queue_event(AudioEvent::SetEnvelopeMode { mode }),
+        None => warn!("Unknown envelope mode: {}", mode),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_filter_velocity_amount(amount: f32) {
+    queue_event(AudioEvent::SetFilterVelocityAmount { amount });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_mono(enabled: bool) {
+    queue_event(AudioEvent::SetMono { enabled });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_note_priority(priority: &str) {
+    match NotePriority::from_str(priority) {
+        Some(priority) => queue_event(AudioEvent::SetNotePriority { priority }),
+        None => warn!("Unknown note priority: {}", priority),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_frequency_smoothing(up: f32, down: f32) {
+    queue_event(AudioEvent::SetFrequencySmoothing {
+        smoothing: Smoothing::new(up, down),
+    });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_instrument_mode(mode: &str) {
+    match InstrumentMode::from_str(mode) {
+        Some(mode) => queue_event(AudioEvent::SetInstrumentMode { mode }),
+        None => warn!("Unknown instrument mode: {}", mode),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_steal_policy(policy: &str) {
+    match StealPolicy::from_str(policy) {
+        Some(policy) => queue_event(AudioEvent::SetStealPolicy { policy }),
+        None => warn!("Unknown steal policy: {}", policy),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_voice_spread(spread: f32) {
+    queue_event(AudioEvent::SetVoiceSpread { spread });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_voice_pan_mode(mode: &str) {
+    match VoicePanMode::from_str(mode) {
+        Some(mode) => queue_event(AudioEvent::SetVoicePanMode { mode }),
+        None => warn!("Unknown voice pan mode: {}", mode),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_stereo_width(width: f32) {
+    queue_event(AudioEvent::SetStereoWidth { width });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_limiter_attack(attack: f32) {
+    queue_event(AudioEvent::SetLimiterAttack { attack });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_limiter_release(release: f32) {
+    queue_event(AudioEvent::SetLimiterRelease { release });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_limiter_ceiling(ceiling: f32) {
+    queue_event(AudioEvent::SetLimiterCeiling { ceiling });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_output_ceiling(ceiling: f32) {
+    queue_event(AudioEvent::SetOutputCeiling { ceiling });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_limiter_bypass(bypass: bool) {
+    queue_event(AudioEvent::SetLimiterBypass { bypass });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_soft_clip_drive(drive: f32) {
+    queue_event(AudioEvent::SetSoftClipDrive { drive });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_gate_smoothing(up: f32, down: f32) {
+    queue_event(AudioEvent::SetGateSmoothing {
+        smoothing: Smoothing::new(up, down),
+    });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_envelope_smoothing(up: f32, down: f32) {
+    queue_event(AudioEvent::SetEnvelopeSmoothing {
+        smoothing: Smoothing::new(up, down),
+    });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_effect_order(order: Vec<String>) {
+    let mut effects = Vec::with_capacity(order.len());
+    for name in &order {
+        match Effect::from_str(name) {
+            Some(effect) => effects.push(effect),
+            None => warn!("Unknown effect: {}", name),
+        }
+    }
+    queue_event(AudioEvent::SetEffectOrder {
+        order: EffectOrder::new(&effects),
+    });
+}
+
+/// `reverb_type` is "room", "hall", "plate" or "shimmer"
+#[wasm_bindgen]
+pub fn harphonium_set_reverb_type(slot: usize, reverb_type: &str) {
+    match ReverbType::from_str(reverb_type) {
+        Some(reverb_type) => queue_event(AudioEvent::SetReverbType { slot, reverb_type }),
+        None => warn!("Unknown reverb type: {}", reverb_type),
+    }
+}
+
+/// `effect` is its name in the effect order, e.g. "filter"
+#[wasm_bindgen]
+pub fn harphonium_set_effect_mix(effect: &str, mix: f32) {
+    match Effect::from_str(effect) {
+        Some(effect) => queue_event(AudioEvent::SetEffectMix { effect, mix }),
+        None => warn!("Unknown effect: {}", effect),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_soft_clip(enabled: bool) {
+    queue_event(AudioEvent::SetSoftClip { enabled });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_master_volume_db(db: f32) {
+    queue_event(AudioEvent::SetMasterVolumeDb { db });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_gain_trim(stage: &str, db: f32) {
+    match GainStage::from_str(stage) {
+        Some(stage) => queue_event(AudioEvent::SetGainTrim { stage, db }),
+        None => warn!("Unknown gain stage: {}", stage),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_strum(frequencies: Vec<f32>) {
+    queue_event(AudioEvent::Strum { frequencies });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_trigger_drum(drum: &str, velocity: f32) {
+    match DrumKind::from_str(drum) {
+        Some(drum) => queue_event(AudioEvent::TriggerDrum { drum, velocity }),
+        None => warn!("Unknown drum: {}", drum),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_master_volume(volume: f32) {
+    queue_event(AudioEvent::SetMasterVolume { volume });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_attack(attack: f32) {
+    queue_event(AudioEvent::SetAttack { attack });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_decay(decay: f32) {
+    queue_event(AudioEvent::SetDecay { decay });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_sustain(sustain: f32) {
+    queue_event(AudioEvent::SetSustain { sustain });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_release(release: f32) {
+    queue_event(AudioEvent::SetRelease { release });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_delay_time(delay_time: f32) {
+    queue_event(AudioEvent::SetDelayTime { delay_time });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_delay_feedback(delay_feedback: f32) {
+    queue_event(AudioEvent::SetDelayFeedback { delay_feedback });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_delay_mix(delay_mix: f32) {
+    queue_event(AudioEvent::SetDelayMix { delay_mix });
+}
+
+/// `mode` is "digital" or "tape"
+#[wasm_bindgen]
+pub fn harphonium_set_delay_mode(mode: &str) {
+    match DelayMode::from_str(mode) {
+        Some(mode) => queue_event(AudioEvent::SetDelayMode { mode }),
+        None => warn!("Unknown delay mode: {}", mode),
+    }
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_delay_low_cut(low_cut: f32) {
+    queue_event(AudioEvent::SetDelayLowCut { low_cut });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_delay_high_cut(high_cut: f32) {
+    queue_event(AudioEvent::SetDelayHighCut { high_cut });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_filter_cutoff(cutoff: f32) {
+    queue_event(AudioEvent::SetFilterCutoff { cutoff });
+}
+
+#[wasm_bindgen]
+pub fn harphonium_set_filter_resonance(resonance: f32) {
+    queue_event(AudioEvent::SetFilterResonance { resonance });
+}
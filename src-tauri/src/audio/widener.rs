@@ -0,0 +1,53 @@
+// Mid/side stereo width control for the master bus
+use fundsp::hacker::{shared, AudioNode, Frame, U2};
+use std::f32::consts::TAU;
+
+/// Below this the side signal is never widened, so the bass stays centred
+const BASS_CUTOFF_HZ: f32 = 120.0;
+
+/// Scales the side (L - R) signal by `width`: 0.0 is mono, 1.0 leaves the
+/// signal alone, 2.0 is twice as wide. The mid signal is never touched, so
+/// a mono fold down sounds the same at any width
+#[derive(Clone)]
+pub struct WidenerNode {
+    width: shared::Shared,
+    /// One pole low pass state, splitting the side signal around the bass cutoff
+    side_low: f32,
+    coefficient: f32,
+}
+
+impl WidenerNode {
+    pub fn new(width: shared::Shared) -> Self {
+        let mut node = WidenerNode {
+            width,
+            side_low: 0.0,
+            coefficient: 0.0,
+        };
+        node.set_sample_rate(48000.0);
+        node
+    }
+}
+
+impl AudioNode for WidenerNode {
+    const ID: u64 = 0x4861_7270_5769_6465;
+    type Inputs = U2;
+    type Outputs = U2;
+
+    fn reset(&mut self) {
+        self.side_low = 0.0;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.coefficient = 1.0 - (-TAU * BASS_CUTOFF_HZ / sample_rate as f32).exp();
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let width = self.width.value();
+        let mid = 0.5 * (input[0] + input[1]);
+        let side = 0.5 * (input[0] - input[1]);
+        self.side_low += self.coefficient * (side - self.side_low);
+        let side_high = side - self.side_low;
+        let side = self.side_low * width.min(1.0) + side_high * width;
+        [mid + side, mid - side].into()
+    }
+}
@@ -1,356 +1,1956 @@
 // src-tauri/src/commands.rs
 // All Tauri command functions live here and are imported by both lib.rs and main.rs
 
-use crate::audio::{handle_audio_event, queue_audio_event, AudioEvent, AudioEventResult, Waveform};
+use crate::audio::{edit_arrangement, Arrangement, SongEntry, Step};
+use crate::audio::{
+    handle_audio_event, queue_audio_event, AudioConfigInfo, AudioDeviceInfo, AudioError,
+    AudioEvent, AudioEventResult, AudioStatsInfo, ControllerSettings, DelayMode, DrumKind,
+    DspLoadInfo, Effect, EffectOrder, EngineMode, EnvelopeMode, EventQueueConfig, ExportFormat,
+    GainStage, GamepadMapping, InstrumentMode, JamSettings, JamStatus, KeySplit, KeyZone, Layer,
+    LayerMix, LfoShape, LfoTarget, MidiDeviceInfo, ModDestination, NotePriority, OscSettings,
+    Patch, PitchTrackingSettings, QwertySettings, RecordingOptions, RecordingStatus, ReverbType,
+    SlotEffect, SlotEffectInfo, SlotPatch, Smoothing, SpectrumInfo, StealPolicy, StrumSettings,
+    TunerReading, Unison, VoicePanMode, Waveform, ZoneParam,
+};
+use crate::http_api::HttpApiSettings;
+use crate::presets::{Preset, PresetBank, PresetInfo};
+use crate::project::Project;
+use tauri::Manager;
 
-/// Play a note (piano mode)
-#[tauri::command]
-pub async fn play_note(frequency: f32) {
-    match queue_audio_event(AudioEvent::PlayNote { frequency }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error handling audio event: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+/// Wrap `event` to apply at frame `at` on the synth clock, if given
+fn timed(event: AudioEvent, at: Option<u64>) -> AudioEvent {
+    match at {
+        Some(frame) => AudioEvent::At {
+            frame,
+            event: Box::new(event),
+        },
+        None => event,
+    }
+}
+
+/// Note ids from here up belong to the other inputs (touch, MIDI, game
+/// controllers, the computer keyboard, pitch tracking, strums)
+const RESERVED_NOTE_IDS: u32 = 0x7ffa_0000;
+
+/// A note id from the UI, 0 if not given. Fails for ids in the reserved
+/// range, which would play or release another input's notes
+fn ui_note_id(note_id: Option<u32>) -> Result<u32, AudioError> {
+    match note_id.unwrap_or(0) {
+        note_id if note_id >= RESERVED_NOTE_IDS => Err(AudioError::InvalidValue {
+            kind: "note id",
+            value: note_id.to_string(),
+        }),
+        note_id => Ok(note_id),
     }
 }
 
+/// Play a note (piano mode). Velocity defaults to full if not given. Notes
+/// without an id share note id 0, so they behave monophonically. With `at`
+/// (a frame, see `get_sample_clock`) the note starts exactly then, rather
+/// than at the start of the next buffer. Ids must be below 0x7ffa_0000
+#[tauri::command]
+pub async fn play_note(
+    frequency: f32,
+    velocity: Option<f32>,
+    note_id: Option<u32>,
+    at: Option<u64>,
+) -> Result<(), AudioError> {
+    let velocity = velocity.unwrap_or(1.0);
+    queue_audio_event(timed(
+        AudioEvent::PlayNote {
+            note_id: ui_note_id(note_id)?,
+            frequency,
+            velocity,
+        },
+        at,
+    ))
+}
+
 /// Set the frequency, for violin / fretless mode
 #[tauri::command]
-pub async fn set_frequency(frequency: f32) {
-    match queue_audio_event(AudioEvent::SetFrequency { frequency }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error handling audio event: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
-    }
+pub async fn set_frequency(frequency: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetFrequency { frequency })
 }
 
+/// Release a note, at frame `at` if given (see `play_note`)
 #[tauri::command]
-pub async fn note_off() {
-    match queue_audio_event(AudioEvent::NoteOff) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error handling audio event: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
-    }
+pub async fn note_off(note_id: Option<u32>, at: Option<u64>) -> Result<(), AudioError> {
+    queue_audio_event(timed(
+        AudioEvent::NoteOff {
+            note_id: ui_note_id(note_id)?,
+        },
+        at,
+    ))
 }
 
+/// Release every sounding note
 #[tauri::command]
-pub async fn set_master_volume(volume: f32) {
-    match queue_audio_event(AudioEvent::SetMasterVolume { volume }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error handling audio event: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
-    }
+pub async fn note_off_all() -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::NoteOffAll)
+}
+
+#[tauri::command]
+pub async fn set_master_volume(volume: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetMasterVolume { volume })
 }
 
 #[tauri::command]
-pub async fn get_master_volume() -> f32 {
+pub async fn get_master_volume() -> Result<f32, AudioError> {
     // audio::get_master_volume()
-    match handle_audio_event(AudioEvent::GetMasterVolume) {
-        AudioEventResult::ValueF32(volume) => volume,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting master volume: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+    match handle_audio_event(AudioEvent::GetMasterVolume)? {
+        AudioEventResult::ValueF32(volume) => Ok(volume),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
+/// Switch waveform. An unknown name leaves the current waveform alone and
+/// the error lists the valid names
 #[tauri::command]
-pub async fn set_waveform(waveform: String) {
-    match queue_audio_event(AudioEvent::SetWaveform {
-        waveform: Waveform::from_str(&waveform).unwrap(),
-    }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting waveform: {}", e);
+pub async fn set_waveform(waveform: String) -> Result<(), AudioError> {
+    let waveform = match Waveform::from_str(&waveform) {
+        Some(waveform) => waveform,
+        None => {
+            return Err(AudioError::InvalidWaveform {
+                value: waveform,
+                valid: Waveform::ALL.iter().map(|w| w.as_str()).collect(),
+            })
         }
-        _ => {
-            eprintln!("Unexpected result");
-        }
-    }
+    };
+    queue_audio_event(AudioEvent::SetWaveform { waveform })
 }
 
 #[tauri::command]
-pub async fn get_waveform() -> String {
-    match handle_audio_event(AudioEvent::GetWaveform) {
-        AudioEventResult::ValueWaveform(waveform) => waveform.as_str().to_string(),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting waveform: {}", e);
-            String::new() // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            String::new() // Return a default value on unexpected result
-        }
+pub async fn get_waveform() -> Result<String, AudioError> {
+    match handle_audio_event(AudioEvent::GetWaveform)? {
+        AudioEventResult::ValueWaveform(waveform) => Ok(waveform.as_str().to_string()),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
 #[tauri::command]
-pub async fn set_attack(attack: f32) {
-    match queue_audio_event(AudioEvent::SetAttack { attack }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting attack: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+pub async fn set_attack(attack: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetAttack { attack })
+}
+
+#[tauri::command]
+pub async fn get_attack() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetAttack)? {
+        AudioEventResult::ValueF32(attack) => Ok(attack),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
 #[tauri::command]
-pub async fn get_attack() -> f32 {
-    match handle_audio_event(AudioEvent::GetAttack) {
-        AudioEventResult::ValueF32(attack) => attack,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting attack: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+pub async fn set_decay(decay: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetDecay { decay })
+}
+
+#[tauri::command]
+pub async fn get_decay() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetDecay)? {
+        AudioEventResult::ValueF32(decay) => Ok(decay),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
 #[tauri::command]
-pub async fn set_decay(decay: f32) {
-    match queue_audio_event(AudioEvent::SetDecay { decay }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting decay: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+pub async fn set_sustain(sustain: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetSustain { sustain })
+}
+
+#[tauri::command]
+pub async fn get_sustain() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetSustain)? {
+        AudioEventResult::ValueF32(sustain) => Ok(sustain),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
 #[tauri::command]
-pub async fn get_decay() -> f32 {
-    match handle_audio_event(AudioEvent::GetDecay) {
-        AudioEventResult::ValueF32(decay) => decay,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting decay: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+pub async fn set_release(release: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetRelease { release })
+}
+
+#[tauri::command]
+pub async fn get_release() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetRelease)? {
+        AudioEventResult::ValueF32(release) => Ok(release),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
 #[tauri::command]
-pub async fn set_sustain(sustain: f32) {
-    match queue_audio_event(AudioEvent::SetSustain { sustain }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting sustain: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+pub async fn set_delay_time(delay_time: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetDelayTime { delay_time })
+}
+
+#[tauri::command]
+pub async fn get_delay_time() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetDelayTime)? {
+        AudioEventResult::ValueF32(delay_time) => Ok(delay_time),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
 #[tauri::command]
-pub async fn get_sustain() -> f32 {
-    match handle_audio_event(AudioEvent::GetSustain) {
-        AudioEventResult::ValueF32(sustain) => sustain,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting sustain: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+pub async fn set_delay_feedback(delay_feedback: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetDelayFeedback { delay_feedback })
+}
+
+#[tauri::command]
+pub async fn get_delay_feedback() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetDelayFeedback)? {
+        AudioEventResult::ValueF32(delay_feedback) => Ok(delay_feedback),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
 #[tauri::command]
-pub async fn set_release(release: f32) {
-    match queue_audio_event(AudioEvent::SetRelease { release }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting release: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+pub async fn set_delay_mix(delay_mix: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetDelayMix { delay_mix })
+}
+
+#[tauri::command]
+pub async fn get_delay_mix() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetDelayMix)? {
+        AudioEventResult::ValueF32(delay_mix) => Ok(delay_mix),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
+/// "digital" for clean repeats, "tape" for wow, flutter and saturation
 #[tauri::command]
-pub async fn get_release() -> f32 {
-    match handle_audio_event(AudioEvent::GetRelease) {
-        AudioEventResult::ValueF32(release) => release,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting release: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+pub async fn set_delay_mode(mode: String) -> Result<(), AudioError> {
+    let mode = DelayMode::from_str(&mode).ok_or(AudioError::InvalidValue {
+        kind: "delay mode",
+        value: mode,
+    })?;
+    queue_audio_event(AudioEvent::SetDelayMode { mode })
+}
+
+#[tauri::command]
+pub async fn get_delay_mode() -> Result<String, AudioError> {
+    match handle_audio_event(AudioEvent::GetDelayMode)? {
+        AudioEventResult::ValueDelayMode(mode) => Ok(mode.as_str().to_string()),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
+/// High pass in the delay's feedback path, Hz (20, off, to 2000)
 #[tauri::command]
-pub async fn set_delay_time(delay_time: f32) {
-    match queue_audio_event(AudioEvent::SetDelayTime { delay_time }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting delay time: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+pub async fn set_delay_low_cut(low_cut: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetDelayLowCut { low_cut })
+}
+
+#[tauri::command]
+pub async fn get_delay_low_cut() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetDelayLowCut)? {
+        AudioEventResult::ValueF32(low_cut) => Ok(low_cut),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
+/// Low pass in the delay's feedback path, Hz (500 to 20000, off)
 #[tauri::command]
-pub async fn get_delay_time() -> f32 {
-    match handle_audio_event(AudioEvent::GetDelayTime) {
-        AudioEventResult::ValueF32(delay_time) => delay_time,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting delay time: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+pub async fn set_delay_high_cut(high_cut: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetDelayHighCut { high_cut })
+}
+
+#[tauri::command]
+pub async fn get_delay_high_cut() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetDelayHighCut)? {
+        AudioEventResult::ValueF32(high_cut) => Ok(high_cut),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
 #[tauri::command]
-pub async fn set_delay_feedback(delay_feedback: f32) {
-    match queue_audio_event(AudioEvent::SetDelayFeedback { delay_feedback }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting delay feedback: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+pub async fn set_filter_cutoff(cutoff: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetFilterCutoff { cutoff })
+}
+
+#[tauri::command]
+pub async fn get_filter_cutoff() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetFilterCutoff)? {
+        AudioEventResult::ValueF32(cutoff) => Ok(cutoff),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
 #[tauri::command]
-pub async fn get_delay_feedback() -> f32 {
-    match handle_audio_event(AudioEvent::GetDelayFeedback) {
-        AudioEventResult::ValueF32(delay_feedback) => delay_feedback,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting delay feedback: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+pub async fn set_filter_resonance(resonance: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetFilterResonance { resonance })
+}
+
+#[tauri::command]
+pub async fn get_filter_resonance() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetFilterResonance)? {
+        AudioEventResult::ValueF32(resonance) => Ok(resonance),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
+/// Offset a parameter for notes within a pitch range, applied at note-on
 #[tauri::command]
-pub async fn set_delay_mix(delay_mix: f32) {
-    match queue_audio_event(AudioEvent::SetDelayMix { delay_mix }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting delay mix: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+pub async fn set_key_zone(
+    min_frequency: f32,
+    max_frequency: f32,
+    param: String,
+    offset: f32,
+) -> Result<(), AudioError> {
+    let param = ZoneParam::from_str(&param).ok_or(AudioError::InvalidValue {
+        kind: "key zone parameter",
+        value: param,
+    })?;
+    let zone = KeyZone {
+        min_frequency,
+        max_frequency,
+        param,
+        offset,
+    };
+    queue_audio_event(AudioEvent::SetKeyZone { zone })
+}
+
+#[tauri::command]
+pub async fn clear_key_zones() -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::ClearKeyZones)
+}
+
+/// List the audio output devices
+#[tauri::command]
+pub async fn list_audio_devices() -> Result<Vec<AudioDeviceInfo>, AudioError> {
+    crate::audio::list_audio_devices()
+}
+
+/// Switch audio output to the named device, or back to the default device if no name is given
+#[tauri::command]
+pub async fn select_audio_device(name: Option<String>) -> Result<(), AudioError> {
+    crate::audio::select_audio_device(name)
+}
+
+/// Set the stream sample rate in Hz, or the device default if not given
+#[tauri::command]
+pub async fn set_sample_rate(sample_rate: Option<u32>) -> Result<(), AudioError> {
+    crate::audio::set_stream_sample_rate(sample_rate)
+}
+
+/// Set the stream buffer size in frames, or the device default if not given
+#[tauri::command]
+pub async fn set_buffer_size(buffer_size: Option<u32>) -> Result<(), AudioError> {
+    crate::audio::set_stream_buffer_size(buffer_size)
+}
+
+/// Report the sample rate, buffer size and channels actually obtained from the device
+#[tauri::command]
+pub async fn get_audio_config() -> Result<AudioConfigInfo, AudioError> {
+    crate::audio::get_audio_config()
+}
+
+/// Pause audio output, e.g. to save battery while backgrounded
+#[tauri::command]
+pub async fn pause_audio() -> Result<(), AudioError> {
+    crate::audio::pause_audio()
+}
+
+/// Resume audio output after pause_audio or shutdown_audio
+#[tauri::command]
+pub async fn resume_audio() -> Result<(), AudioError> {
+    crate::audio::resume_audio()
+}
+
+/// Close the audio stream and release the device
+#[tauri::command]
+pub async fn shutdown_audio() -> Result<(), AudioError> {
+    crate::audio::shutdown_audio()
+}
+
+/// List the audio hosts available on this system (e.g. ALSA, JACK)
+#[tauri::command]
+pub async fn list_audio_hosts() -> Result<Vec<String>, AudioError> {
+    crate::audio::list_audio_hosts()
+}
+
+/// Switch to the named audio host, or back to the platform default if no name is given
+#[tauri::command]
+pub async fn select_audio_host(name: Option<String>) -> Result<(), AudioError> {
+    crate::audio::select_audio_host(name)
+}
+
+/// Ask for exclusive, low latency access to the output device. Check
+/// `get_audio_config` to see whether the device allowed it
+#[tauri::command]
+pub async fn set_exclusive_mode(exclusive: bool) -> Result<(), AudioError> {
+    crate::audio::set_stream_exclusive_mode(exclusive)
+}
+
+/// Toggle real-time priority for the audio callback thread
+#[tauri::command]
+pub async fn set_realtime_priority(enabled: bool) -> Result<(), AudioError> {
+    crate::audio::set_stream_realtime_priority(enabled)
+}
+
+/// Underrun, lock contention and queue overflow counts since startup. The
+/// same numbers are pushed periodically as `audio-stats` events
+#[tauri::command]
+pub async fn get_audio_stats() -> AudioStatsInfo {
+    crate::audio::get_audio_stats()
+}
+
+#[tauri::command]
+pub async fn reset_audio_stats() {
+    crate::audio::reset_audio_stats();
+}
+
+/// Rendering time as a fraction of buffer time, smoothed, plus the peak since
+/// the last `reset_audio_stats`. Also pushed as `dsp-load` events
+#[tauri::command]
+pub async fn get_dsp_load() -> DspLoadInfo {
+    crate::audio::get_dsp_load()
+}
+
+/// Queue a note ahead of time: it starts at `start_time` on the transport
+/// clock (see `get_transport_time`) and is released `duration` seconds later.
+/// Timing is kept by the synth, so sequenced notes don't pick up IPC jitter
+#[tauri::command]
+pub async fn schedule_note(
+    frequency: f32,
+    velocity: f32,
+    start_time: f64,
+    duration: f32,
+) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::ScheduleNote {
+        frequency,
+        velocity,
+        start_time,
+        duration,
+    })
+}
+
+/// Seconds of audio played since startup, the clock `schedule_note` runs on
+#[tauri::command]
+pub async fn get_transport_time() -> f64 {
+    crate::audio::get_transport_time()
+}
+
+/// Frames rendered since startup. Timestamps (`at`) for notes are frames on this clock
+#[tauri::command]
+pub async fn get_sample_clock() -> u64 {
+    crate::audio::get_sample_clock()
+}
+
+/// Latest oscilloscope frame (recent output, downsampled). The same frames
+/// are pushed as `oscilloscope` events at about 30fps while audio is playing
+#[tauri::command]
+pub async fn get_oscilloscope() -> Vec<f32> {
+    crate::audio::get_oscilloscope()
+}
+
+/// Magnitude spectrum (dB) of the most recent output
+#[tauri::command]
+pub async fn get_spectrum() -> Result<SpectrumInfo, AudioError> {
+    crate::audio::get_spectrum()
+}
+
+/// Snapshot of every sound parameter
+#[tauri::command]
+pub async fn get_patch() -> Result<Patch, AudioError> {
+    match handle_audio_event(AudioEvent::GetPatch)? {
+        AudioEventResult::ValuePatch(patch) => Ok(patch),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
+/// Set every sound parameter at once
 #[tauri::command]
-pub async fn get_delay_mix() -> f32 {
-    match handle_audio_event(AudioEvent::GetDelayMix) {
-        AudioEventResult::ValueF32(delay_mix) => delay_mix,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting delay mix: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+pub async fn load_patch(patch: Patch) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::LoadPatch { patch })
+}
+
+/// Load the preset bank, apply `change` to it and save it back
+fn update_bank(
+    app: &tauri::AppHandle,
+    change: impl FnOnce(&mut PresetBank) -> Result<(), String>,
+) -> Result<(), AudioError> {
+    let mut bank = PresetBank::load(app).map_err(AudioError::Preset)?;
+    change(&mut bank).map_err(AudioError::Preset)?;
+    bank.save(app).map_err(AudioError::Preset)
+}
+
+/// Save the current sound, keyboard split and layer as a preset, replacing
+/// any preset with the same name
+#[tauri::command]
+pub async fn save_preset(
+    app: tauri::AppHandle,
+    name: String,
+    author: Option<String>,
+    category: Option<String>,
+) -> Result<(), AudioError> {
+    let patch = get_patch().await?;
+    let split = get_key_split().await?;
+    let layer = get_layer().await?;
+    update_bank(&app, |bank| {
+        bank.store(name, author, category, patch, split, layer);
+        Ok(())
+    })
+}
+
+/// Load a preset's patch, and its keyboard split and layer or the lack of them
+async fn apply_preset(preset: &Preset) -> Result<(), AudioError> {
+    load_patch(preset.patch).await?;
+    match preset.split {
+        Some(split) => queue_audio_event(AudioEvent::SetKeySplit { split })?,
+        None => queue_audio_event(AudioEvent::ClearKeySplit)?,
+    }
+    match preset.layer {
+        Some(layer) => queue_audio_event(AudioEvent::SetLayer { layer }),
+        None => queue_audio_event(AudioEvent::ClearLayer),
     }
 }
 
 #[tauri::command]
-pub async fn set_filter_cutoff(cutoff: f32) {
-    match queue_audio_event(AudioEvent::SetFilterCutoff { cutoff }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting filter cutoff: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+pub async fn load_preset(app: tauri::AppHandle, name: String) -> Result<(), AudioError> {
+    let bank = PresetBank::load(&app).map_err(AudioError::Preset)?;
+    apply_preset(bank.get(&name).map_err(AudioError::Preset)?).await
+}
+
+/// Presets in bank order, with their metadata
+#[tauri::command]
+pub async fn list_presets(app: tauri::AppHandle) -> Result<Vec<PresetInfo>, AudioError> {
+    Ok(PresetBank::load(&app).map_err(AudioError::Preset)?.list())
+}
+
+#[tauri::command]
+pub async fn rename_preset(
+    app: tauri::AppHandle,
+    name: String,
+    new_name: String,
+) -> Result<(), AudioError> {
+    update_bank(&app, |bank| bank.rename(&name, new_name))
+}
+
+#[tauri::command]
+pub async fn delete_preset(app: tauri::AppHandle, name: String) -> Result<(), AudioError> {
+    update_bank(&app, |bank| bank.delete(&name))
+}
+
+#[tauri::command]
+pub async fn duplicate_preset(
+    app: tauri::AppHandle,
+    name: String,
+    new_name: String,
+) -> Result<(), AudioError> {
+    update_bank(&app, |bank| bank.duplicate(&name, new_name))
+}
+
+/// Move a preset to a new position in the bank
+#[tauri::command]
+pub async fn move_preset(
+    app: tauri::AppHandle,
+    name: String,
+    index: usize,
+) -> Result<(), AudioError> {
+    update_bank(&app, |bank| bank.move_to(&name, index))
+}
+
+/// Presets that ship with the app, with their metadata
+#[tauri::command]
+pub async fn list_factory_presets() -> Result<Vec<PresetInfo>, AudioError> {
+    Ok(PresetBank::factory().map_err(AudioError::Preset)?.list())
+}
+
+#[tauri::command]
+pub async fn load_factory_preset(name: String) -> Result<(), AudioError> {
+    let bank = PresetBank::factory().map_err(AudioError::Preset)?;
+    apply_preset(bank.get(&name).map_err(AudioError::Preset)?).await
+}
+
+/// Replace the current sound with a random variation and return it. `amount`
+/// (0.0 to 1.0, default 1.0) is how far to stray from the current patch;
+/// parameters named in `locked_params` are left alone
+#[tauri::command]
+pub async fn randomize_patch(
+    amount: Option<f32>,
+    locked_params: Option<Vec<String>>,
+) -> Result<Patch, AudioError> {
+    let patch = get_patch()
+        .await?
+        .randomized(amount.unwrap_or(1.0), &locked_params.unwrap_or_default());
+    load_patch(patch).await?;
+    Ok(patch)
+}
+
+/// Start recording every note and parameter event, so the performance can
+/// be exported or re-rendered later
+#[tauri::command]
+pub async fn start_capture() -> Result<(), AudioError> {
+    crate::audio::start_capture()
+}
+
+/// Stop recording. Returns the number of events captured
+#[tauri::command]
+pub async fn stop_capture() -> Result<usize, AudioError> {
+    crate::audio::stop_capture()
+}
+
+#[tauri::command]
+pub async fn export_performance(path: String) -> Result<(), AudioError> {
+    crate::audio::export_capture(&path)
+}
+
+/// Load an exported performance. Returns its number of events
+#[tauri::command]
+pub async fn import_performance(path: String) -> Result<usize, AudioError> {
+    crate::audio::import_capture(&path)
+}
+
+fn parse_export_format(format: String) -> Result<ExportFormat, AudioError> {
+    ExportFormat::from_str(&format).ok_or(AudioError::InvalidValue {
+        kind: "export format",
+        value: format,
+    })
+}
+
+/// Re-render the last captured (or imported) performance to an audio file,
+/// at 96kHz unless another sample rate is given. `format` is "wav16",
+/// "wav24", "wav_float" (the default), "flac" or "ogg"
+#[tauri::command]
+pub async fn render_performance(
+    path: String,
+    sample_rate: Option<u32>,
+    format: Option<String>,
+) -> Result<(), AudioError> {
+    let format = format
+        .map(parse_export_format)
+        .transpose()?
+        .unwrap_or_default();
+    crate::audio::render_capture(&path, sample_rate.unwrap_or(96000), format)
+}
+
+/// Export the notes of the last captured performance as a standard MIDI file
+#[tauri::command]
+pub async fn export_midi(path: String) -> Result<(), AudioError> {
+    crate::audio::export_capture_midi(&path)
+}
+
+/// Load a WAV file and play it across the keyboard with the sampler
+/// waveform. `root_frequency` is the pitch it was recorded at (default
+/// middle C); `loop_start` and `loop_end` are in frames
+#[tauri::command]
+pub async fn load_sample(
+    path: String,
+    root_frequency: Option<f32>,
+    loop_start: Option<usize>,
+    loop_end: Option<usize>,
+) -> Result<(), AudioError> {
+    let loop_points = match (loop_start, loop_end) {
+        (Some(start), Some(end)) => Some((start, end)),
+        _ => None,
+    };
+    crate::audio::load_sample(&path, root_frequency.unwrap_or(261.63), loop_points)
+}
+
+/// Presets in a SoundFont (.sf2) file
+#[tauri::command]
+pub async fn list_soundfont_presets(path: String) -> Result<Vec<String>, AudioError> {
+    crate::audio::list_soundfont_presets(&path)
+}
+
+/// Play a SoundFont preset through the sampler waveform
+#[tauri::command]
+pub async fn load_soundfont_preset(path: String, preset: String) -> Result<(), AudioError> {
+    crate::audio::load_soundfont_preset(&path, &preset)
+}
+
+/// Play a drum hit: `drum_id` is "kick", "snare" or "hat"
+#[tauri::command]
+pub async fn trigger_drum(drum_id: String, velocity: f32) -> Result<(), AudioError> {
+    let drum = DrumKind::from_str(&drum_id).ok_or(AudioError::InvalidValue {
+        kind: "drum",
+        value: drum_id,
+    })?;
+    queue_audio_event(AudioEvent::TriggerDrum { drum, velocity })
+}
+
+/// Strum (glissando) across `frequencies` in order. Timing is done by the
+/// synth, so a whole drag across the strings is one call
+#[tauri::command]
+pub async fn strum(frequencies: Vec<f32>) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::Strum { frequencies })
+}
+
+#[tauri::command]
+pub async fn set_strum_settings(settings: StrumSettings) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetStrumSettings { settings })
+}
+
+#[tauri::command]
+pub async fn get_strum_settings() -> Result<StrumSettings, AudioError> {
+    match handle_audio_event(AudioEvent::GetStrumSettings)? {
+        AudioEventResult::ValueStrumSettings(settings) => Ok(settings),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
+/// Latch (drone) mode: notes keep sounding after they're released, until
+/// they're played again or all notes are stopped
 #[tauri::command]
-pub async fn get_filter_cutoff() -> f32 {
-    match handle_audio_event(AudioEvent::GetFilterCutoff) {
-        AudioEventResult::ValueF32(cutoff) => cutoff,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting filter cutoff: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+pub async fn set_latch(enabled: bool) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetLatch { enabled })
+}
+
+#[tauri::command]
+pub async fn get_latch() -> Result<bool, AudioError> {
+    match handle_audio_event(AudioEvent::GetLatch)? {
+        AudioEventResult::ValueBool(enabled) => Ok(enabled),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
+/// Hold (freeze): notes sounding when hold is turned on keep sounding until
+/// it's turned off, however they're released in the meantime
 #[tauri::command]
-pub async fn set_filter_resonance(resonance: f32) {
-    match queue_audio_event(AudioEvent::SetFilterResonance { resonance }) {
-        AudioEventResult::Ok => (),
-        AudioEventResult::Err(e) => {
-            eprintln!("Error setting filter resonance: {}", e);
-        }
-        _ => {
-            eprintln!("Unexpected result");
-        }
+pub async fn hold(enabled: bool) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetHold { enabled })
+}
+
+#[tauri::command]
+pub async fn get_hold() -> Result<bool, AudioError> {
+    match handle_audio_event(AudioEvent::GetHold)? {
+        AudioEventResult::ValueBool(enabled) => Ok(enabled),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
 
+/// Number of stacked oscillator copies per note (1 to 8)
 #[tauri::command]
-pub async fn get_filter_resonance() -> f32 {
-    match handle_audio_event(AudioEvent::GetFilterResonance) {
-        AudioEventResult::ValueF32(resonance) => resonance,
-        AudioEventResult::Err(e) => {
-            eprintln!("Error getting filter resonance: {}", e);
-            0.0 // Return a default value on error
-        }
-        _ => {
-            eprintln!("Unexpected result");
-            0.0 // Return a default value on unexpected result
-        }
+pub async fn set_unison_voices(voices: usize) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetUnisonVoices { voices })
+}
+
+/// Detune of the outermost unison copies, in cents
+#[tauri::command]
+pub async fn set_unison_detune(detune: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetUnisonDetune { detune })
+}
+
+/// Stereo width of the unison copies (0.0 to 1.0)
+#[tauri::command]
+pub async fn set_unison_spread(spread: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetUnisonSpread { spread })
+}
+
+#[tauri::command]
+pub async fn get_unison() -> Result<Unison, AudioError> {
+    match handle_audio_event(AudioEvent::GetUnison)? {
+        AudioEventResult::ValueUnison(unison) => Ok(unison),
+        _ => Err(AudioError::UnexpectedResult),
     }
 }
+
+/// Ring modulation amount (0.0 = off, 1.0 = fully ring modulated)
+#[tauri::command]
+pub async fn set_ring_mod_mix(mix: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetRingModMix { mix })
+}
+
+#[tauri::command]
+pub async fn get_ring_mod_mix() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetRingModMix)? {
+        AudioEventResult::ValueF32(mix) => Ok(mix),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Ring modulator frequency as a multiple of the note frequency
+#[tauri::command]
+pub async fn set_ring_mod_ratio(ratio: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetRingModRatio { ratio })
+}
+
+#[tauri::command]
+pub async fn get_ring_mod_ratio() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetRingModRatio)? {
+        AudioEventResult::ValueF32(ratio) => Ok(ratio),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+#[tauri::command]
+pub async fn set_vibrato_rate(rate: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetVibratoRate { rate })
+}
+
+#[tauri::command]
+pub async fn get_vibrato_rate() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetVibratoRate)? {
+        AudioEventResult::ValueF32(rate) => Ok(rate),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+#[tauri::command]
+pub async fn set_vibrato_depth(depth: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetVibratoDepth { depth })
+}
+
+#[tauri::command]
+pub async fn get_vibrato_depth() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetVibratoDepth)? {
+        AudioEventResult::ValueF32(depth) => Ok(depth),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+#[tauri::command]
+pub async fn set_vibrato_delay(delay: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetVibratoDelay { delay })
+}
+
+#[tauri::command]
+pub async fn get_vibrato_delay() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetVibratoDelay)? {
+        AudioEventResult::ValueF32(delay) => Ok(delay),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Filter LFO speed in Hz, used when not synced to the tempo
+#[tauri::command]
+pub async fn set_filter_lfo_rate(rate: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetFilterLfoRate { rate })
+}
+
+#[tauri::command]
+pub async fn get_filter_lfo_rate() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetFilterLfoRate)? {
+        AudioEventResult::ValueF32(rate) => Ok(rate),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// How far the filter LFO sweeps the cutoff, in octaves
+#[tauri::command]
+pub async fn set_filter_lfo_depth(depth: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetFilterLfoDepth { depth })
+}
+
+#[tauri::command]
+pub async fn get_filter_lfo_depth() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetFilterLfoDepth)? {
+        AudioEventResult::ValueF32(depth) => Ok(depth),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Sync the filter LFO to the tempo: one cycle per `beats` beats, or 0 to run free
+#[tauri::command]
+pub async fn set_filter_lfo_sync(beats: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetFilterLfoSync { beats })
+}
+
+#[tauri::command]
+pub async fn get_filter_lfo_sync() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetFilterLfoSync)? {
+        AudioEventResult::ValueF32(beats) => Ok(beats),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+#[tauri::command]
+pub async fn set_tempo(bpm: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetTempo { bpm })
+}
+
+#[tauri::command]
+pub async fn get_tempo() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetTempo)? {
+        AudioEventResult::ValueF32(bpm) => Ok(bpm),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Choose the shape of an LFO: `lfo` is "vibrato" or "filter", `shape` one of
+/// "sine", "triangle", "square", "saw" or "sample_hold"
+#[tauri::command]
+pub async fn set_lfo_shape(lfo: String, shape: String) -> Result<(), AudioError> {
+    let lfo = LfoTarget::from_str(&lfo).ok_or(AudioError::InvalidValue {
+        kind: "LFO",
+        value: lfo,
+    })?;
+    let shape = LfoShape::from_str(&shape).ok_or(AudioError::InvalidValue {
+        kind: "LFO shape",
+        value: shape,
+    })?;
+    queue_audio_event(AudioEvent::SetLfoShape { lfo, shape })
+}
+
+#[tauri::command]
+pub async fn get_lfo_shape(lfo: String) -> Result<String, AudioError> {
+    let lfo = LfoTarget::from_str(&lfo).ok_or(AudioError::InvalidValue {
+        kind: "LFO",
+        value: lfo,
+    })?;
+    match handle_audio_event(AudioEvent::GetLfoShape { lfo })? {
+        AudioEventResult::ValueLfoShape(shape) => Ok(shape.as_str().to_string()),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Envelope delay before the attack, in seconds (DAHDSR modes only)
+#[tauri::command]
+pub async fn set_envelope_delay(delay: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetEnvelopeDelay { delay })
+}
+
+#[tauri::command]
+pub async fn get_envelope_delay() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetEnvelopeDelay)? {
+        AudioEventResult::ValueF32(delay) => Ok(delay),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Envelope hold at full level after the attack, in seconds (DAHDSR modes only)
+#[tauri::command]
+pub async fn set_envelope_hold(hold: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetEnvelopeHold { hold })
+}
+
+#[tauri::command]
+pub async fn get_envelope_hold() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetEnvelopeHold)? {
+        AudioEventResult::ValueF32(hold) => Ok(hold),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Choose the envelope: "adsr", "dahdsr" or "looping"
+#[tauri::command]
+pub async fn set_envelope_mode(mode: String) -> Result<(), AudioError> {
+    let mode = EnvelopeMode::from_str(&mode).ok_or(AudioError::InvalidValue {
+        kind: "envelope mode",
+        value: mode,
+    })?;
+    queue_audio_event(AudioEvent::SetEnvelopeMode { mode })
+}
+
+#[tauri::command]
+pub async fn get_envelope_mode() -> Result<String, AudioError> {
+    match handle_audio_event(AudioEvent::GetEnvelopeMode)? {
+        AudioEventResult::ValueEnvelopeMode(mode) => Ok(mode.as_str().to_string()),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Octaves the filter cutoff follows note velocity by; negative inverts it
+#[tauri::command]
+pub async fn set_filter_velocity_amount(amount: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetFilterVelocityAmount { amount })
+}
+
+#[tauri::command]
+pub async fn get_filter_velocity_amount() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetFilterVelocityAmount)? {
+        AudioEventResult::ValueF32(amount) => Ok(amount),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Mono (legato) mode: one note at a time, sliding between held notes
+#[tauri::command]
+pub async fn set_mono(enabled: bool) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetMono { enabled })
+}
+
+#[tauri::command]
+pub async fn get_mono() -> Result<bool, AudioError> {
+    match handle_audio_event(AudioEvent::GetMono)? {
+        AudioEventResult::ValueBool(enabled) => Ok(enabled),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Which held note sounds in mono mode: "last", "lowest" or "highest"
+#[tauri::command]
+pub async fn set_note_priority(priority: String) -> Result<(), AudioError> {
+    let priority = NotePriority::from_str(&priority).ok_or(AudioError::InvalidValue {
+        kind: "note priority",
+        value: priority,
+    })?;
+    queue_audio_event(AudioEvent::SetNotePriority { priority })
+}
+
+#[tauri::command]
+pub async fn get_note_priority() -> Result<String, AudioError> {
+    match handle_audio_event(AudioEvent::GetNotePriority)? {
+        AudioEventResult::ValueNotePriority(priority) => Ok(priority.as_str().to_string()),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// How voices are stolen when all are in use: "oldest", "quietest" or "same_pitch"
+#[tauri::command]
+pub async fn set_steal_policy(policy: String) -> Result<(), AudioError> {
+    let policy = StealPolicy::from_str(&policy).ok_or(AudioError::InvalidValue {
+        kind: "steal policy",
+        value: policy,
+    })?;
+    queue_audio_event(AudioEvent::SetStealPolicy { policy })
+}
+
+#[tauri::command]
+pub async fn get_steal_policy() -> Result<String, AudioError> {
+    match handle_audio_event(AudioEvent::GetStealPolicy)? {
+        AudioEventResult::ValueStealPolicy(policy) => Ok(policy.as_str().to_string()),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Spread notes across the stereo field (0.0 = all centred, 1.0 = full width)
+#[tauri::command]
+pub async fn set_voice_spread(spread: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetVoiceSpread { spread })
+}
+
+#[tauri::command]
+pub async fn get_voice_spread() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetVoiceSpread)? {
+        AudioEventResult::ValueF32(spread) => Ok(spread),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// How voice spread places notes: "round_robin" or "pitch"
+#[tauri::command]
+pub async fn set_voice_pan_mode(mode: String) -> Result<(), AudioError> {
+    let mode = VoicePanMode::from_str(&mode).ok_or(AudioError::InvalidValue {
+        kind: "voice pan mode",
+        value: mode,
+    })?;
+    queue_audio_event(AudioEvent::SetVoicePanMode { mode })
+}
+
+#[tauri::command]
+pub async fn get_voice_pan_mode() -> Result<String, AudioError> {
+    match handle_audio_event(AudioEvent::GetVoicePanMode)? {
+        AudioEventResult::ValueVoicePanMode(mode) => Ok(mode.as_str().to_string()),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Master bus stereo width: 0.0 is mono, 1.0 unchanged, 2.0 extra wide
+#[tauri::command]
+pub async fn set_stereo_width(width: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetStereoWidth { width })
+}
+
+#[tauri::command]
+pub async fn get_stereo_width() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetStereoWidth)? {
+        AudioEventResult::ValueF32(width) => Ok(width),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Master limiter attack time in seconds
+#[tauri::command]
+pub async fn set_limiter_attack(attack: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetLimiterAttack { attack })
+}
+
+#[tauri::command]
+pub async fn get_limiter_attack() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetLimiterAttack)? {
+        AudioEventResult::ValueF32(attack) => Ok(attack),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Master limiter release time in seconds
+#[tauri::command]
+pub async fn set_limiter_release(release: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetLimiterRelease { release })
+}
+
+#[tauri::command]
+pub async fn get_limiter_release() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetLimiterRelease)? {
+        AudioEventResult::ValueF32(release) => Ok(release),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Master limiter ceiling in dB (-24.0 to 0.0)
+#[tauri::command]
+pub async fn set_limiter_ceiling(ceiling: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetLimiterCeiling { ceiling })
+}
+
+#[tauri::command]
+pub async fn get_limiter_ceiling() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetLimiterCeiling)? {
+        AudioEventResult::ValueF32(ceiling) => Ok(ceiling),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Hearing protection: the loudest the output can get, in dB (-40.0 to 0.0).
+/// Applies whatever the master volume, preset or limiter settings, and is
+/// kept when presets load
+#[tauri::command]
+pub async fn set_output_ceiling(ceiling: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetOutputCeiling { ceiling })
+}
+
+#[tauri::command]
+pub async fn get_output_ceiling() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetOutputCeiling)? {
+        AudioEventResult::ValueF32(ceiling) => Ok(ceiling),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Take the master limiter out of the signal path
+#[tauri::command]
+pub async fn set_limiter_bypass(bypass: bool) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetLimiterBypass { bypass })
+}
+
+#[tauri::command]
+pub async fn get_limiter_bypass() -> Result<bool, AudioError> {
+    match handle_audio_event(AudioEvent::GetLimiterBypass)? {
+        AudioEventResult::ValueBool(bypass) => Ok(bypass),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Make insert slot `slot` a reverb: "room", "hall", "plate" or "shimmer".
+/// Size, time and damping start at the type's and can be changed after
+#[tauri::command]
+pub async fn set_reverb_type(slot: usize, reverb_type: String) -> Result<(), AudioError> {
+    let reverb_type = ReverbType::from_str(&reverb_type).ok_or(AudioError::InvalidValue {
+        kind: "reverb type",
+        value: reverb_type,
+    })?;
+    effect_slot(slot).await?;
+    queue_audio_event(AudioEvent::SetReverbType { slot, reverb_type })
+}
+
+/// The type of the reverb in insert slot `slot`, None if it isn't a reverb
+#[tauri::command]
+pub async fn get_reverb_type(slot: usize) -> Result<Option<String>, AudioError> {
+    Ok(effect_slot(slot)
+        .await?
+        .reverb_type()
+        .map(|reverb_type| reverb_type.as_str().to_string()))
+}
+
+fn parse_effect(name: String) -> Result<Effect, AudioError> {
+    Effect::from_str(&name).ok_or(AudioError::InvalidValue {
+        kind: "effect",
+        value: name,
+    })
+}
+
+/// Order of the effects, first to last, e.g. ["filter", "delay"]. Effects
+/// left out keep their place after the ones given
+#[tauri::command]
+pub async fn set_effect_order(order: Vec<String>) -> Result<(), AudioError> {
+    let effects = order
+        .into_iter()
+        .map(parse_effect)
+        .collect::<Result<Vec<_>, _>>()?;
+    queue_audio_event(AudioEvent::SetEffectOrder {
+        order: EffectOrder::new(&effects),
+    })
+}
+
+#[tauri::command]
+pub async fn get_effect_order() -> Result<EffectOrder, AudioError> {
+    match handle_audio_event(AudioEvent::GetEffectOrder)? {
+        AudioEventResult::ValueEffectOrder(order) => Ok(order),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Effect types an insert slot can host, with their parameters and ranges
+#[tauri::command]
+pub async fn list_slot_effects() -> Vec<SlotEffectInfo> {
+    crate::audio::list_slot_effects()
+}
+
+async fn effect_slot(slot: usize) -> Result<SlotPatch, AudioError> {
+    match handle_audio_event(AudioEvent::GetEffectSlot { slot })? {
+        AudioEventResult::ValueEffectSlot(slot) => Ok(slot),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Put an effect in insert slot `slot` (0 to 3, "slot_0" to "slot_3" in
+/// the effect order), e.g. "reverb", or "none" to empty it. Its parameters
+/// start at their defaults
+#[tauri::command]
+pub async fn set_slot_effect(slot: usize, effect: String) -> Result<(), AudioError> {
+    let effect = SlotEffect::from_name(&effect).ok_or(AudioError::InvalidValue {
+        kind: "slot effect",
+        value: effect,
+    })?;
+    effect_slot(slot).await?;
+    queue_audio_event(AudioEvent::SetSlotEffect { slot, effect })
+}
+
+/// Set a parameter, by name, of the effect in insert slot `slot`
+#[tauri::command]
+pub async fn set_slot_param(slot: usize, param: String, value: f32) -> Result<(), AudioError> {
+    let effect = effect_slot(slot).await?.effect;
+    let param = effect.param_index(&param).ok_or(AudioError::InvalidValue {
+        kind: "slot parameter",
+        value: format!("{} {}", effect.name(), param),
+    })?;
+    queue_audio_event(AudioEvent::SetSlotParam { slot, param, value })
+}
+
+/// The effect in insert slot `slot` and its parameters by name
+#[tauri::command]
+pub async fn get_effect_slot(slot: usize) -> Result<SlotPatch, AudioError> {
+    effect_slot(slot).await
+}
+
+/// Wet/dry mix of an effect, by its name in the effect order ("filter",
+/// "delay", "slot_0" ...), from 0.0 (all dry) to 1.0 (all wet)
+#[tauri::command]
+pub async fn set_effect_mix(effect: String, mix: f32) -> Result<(), AudioError> {
+    let effect = parse_effect(effect)?;
+    queue_audio_event(AudioEvent::SetEffectMix { effect, mix })
+}
+
+#[tauri::command]
+pub async fn get_effect_mix(effect: String) -> Result<f32, AudioError> {
+    let effect = parse_effect(effect)?;
+    match handle_audio_event(AudioEvent::GetEffectMix { effect })? {
+        AudioEventResult::ValueF32(mix) => Ok(mix),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Soft clipper drive in dB (0.0 to 24.0)
+#[tauri::command]
+pub async fn set_soft_clip_drive(drive: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetSoftClipDrive { drive })
+}
+
+#[tauri::command]
+pub async fn get_soft_clip_drive() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetSoftClipDrive)? {
+        AudioEventResult::ValueF32(drive) => Ok(drive),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Soft clipping ahead of the limiter, for hot patches
+#[tauri::command]
+pub async fn set_soft_clip(enabled: bool) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetSoftClip { enabled })
+}
+
+#[tauri::command]
+pub async fn get_soft_clip() -> Result<bool, AudioError> {
+    match handle_audio_event(AudioEvent::GetSoftClip)? {
+        AudioEventResult::ValueBool(enabled) => Ok(enabled),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Master volume in dB: 0.0 is full volume, -60.0 or below is silent
+#[tauri::command]
+pub async fn set_master_volume_db(db: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetMasterVolumeDb { db })
+}
+
+#[tauri::command]
+pub async fn get_master_volume_db() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetMasterVolumeDb)? {
+        AudioEventResult::ValueF32(db) => Ok(db),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Gain trim in dB for a stage of the signal path: "oscillator", "pre_filter" or "post_fx"
+#[tauri::command]
+pub async fn set_gain_trim(stage: String, db: f32) -> Result<(), AudioError> {
+    let stage = GainStage::from_str(&stage).ok_or(AudioError::InvalidValue {
+        kind: "gain stage",
+        value: stage,
+    })?;
+    queue_audio_event(AudioEvent::SetGainTrim { stage, db })
+}
+
+#[tauri::command]
+pub async fn get_gain_trim(stage: String) -> Result<f32, AudioError> {
+    let stage = GainStage::from_str(&stage).ok_or(AudioError::InvalidValue {
+        kind: "gain stage",
+        value: stage,
+    })?;
+    match handle_audio_event(AudioEvent::GetGainTrim { stage })? {
+        AudioEventResult::ValueF32(db) => Ok(db),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Recent log lines, oldest first, for attaching to bug reports
+#[tauri::command]
+pub async fn get_logs() -> Vec<String> {
+    crate::logging::recent_logs()
+}
+
+/// Set log levels in `RUST_LOG` syntax, e.g. "info,harphonium_lib::audio=debug"
+#[tauri::command]
+pub async fn set_log_filter(filter: String) -> Result<(), String> {
+    crate::logging::set_filter(&filter)
+}
+
+/// Quantize live notes to the next 1/`subdivision` of a beat (1 for beats,
+/// 4 for sixteenths, up to 16), counted from where the sequencer started, so
+/// playing along stays in time. 0 turns it off
+#[tauri::command]
+pub async fn set_quantize(subdivision: u32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetQuantize { subdivision })
+}
+
+#[tauri::command]
+pub async fn get_quantize() -> Result<u32, AudioError> {
+    match handle_audio_event(AudioEvent::GetQuantize)? {
+        AudioEventResult::ValueU32(subdivision) => Ok(subdivision),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Add an empty pattern of `length` steps, returning its index. Steps are
+/// sixteenths (4 per beat) unless `steps_per_beat` says otherwise
+#[tauri::command]
+pub async fn add_pattern(
+    name: String,
+    length: usize,
+    steps_per_beat: Option<u32>,
+) -> Result<usize, AudioError> {
+    edit_arrangement(|arrangement| {
+        arrangement.add_pattern(name, length, steps_per_beat.unwrap_or(4))
+    })
+}
+
+/// Remove a pattern. Song entries playing it are removed too, and patterns
+/// chained to it loop instead
+#[tauri::command]
+pub async fn remove_pattern(pattern: usize) -> Result<(), AudioError> {
+    edit_arrangement(|arrangement| arrangement.remove_pattern(pattern))
+}
+
+#[tauri::command]
+pub async fn set_pattern_length(pattern: usize, length: usize) -> Result<(), AudioError> {
+    edit_arrangement(|arrangement| arrangement.set_pattern_length(pattern, length))
+}
+
+/// Set one step of a pattern. No `frequency` makes it a rest
+#[tauri::command]
+pub async fn set_pattern_step(
+    pattern: usize,
+    step: usize,
+    frequency: Option<f32>,
+    velocity: f32,
+    gate: f32,
+) -> Result<(), AudioError> {
+    edit_arrangement(|arrangement| {
+        arrangement.set_step(
+            pattern,
+            step,
+            Step {
+                frequency,
+                velocity,
+                gate,
+            },
+        )
+    })
+}
+
+/// Chain `pattern` to play `next` when it ends. No `next` loops it
+#[tauri::command]
+pub async fn set_pattern_next(pattern: usize, next: Option<usize>) -> Result<(), AudioError> {
+    edit_arrangement(|arrangement| arrangement.set_next(pattern, next))
+}
+
+/// Set the song: patterns to play in order, each repeated some number of times
+#[tauri::command]
+pub async fn set_song(entries: Vec<SongEntry>) -> Result<(), AudioError> {
+    edit_arrangement(|arrangement| arrangement.set_song(entries))
+}
+
+/// In song mode the sequencer plays the song once from the top, rather than
+/// following pattern chains
+#[tauri::command]
+pub async fn set_song_mode(enabled: bool) -> Result<(), AudioError> {
+    edit_arrangement(|arrangement| {
+        arrangement.song_mode = enabled;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub async fn get_arrangement() -> Result<Arrangement, AudioError> {
+    crate::audio::get_arrangement()
+}
+
+/// Replace every pattern and the song at once
+#[tauri::command]
+pub async fn set_arrangement(arrangement: Arrangement) -> Result<(), AudioError> {
+    edit_arrangement(|current| current.replace(arrangement))
+}
+
+/// Start the sequencer at the synth's tempo, from `pattern` (default the
+/// first) or, in song mode, from the top of the song
+#[tauri::command]
+pub async fn sequencer_play(pattern: Option<usize>) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SequencerPlay {
+        pattern: pattern.unwrap_or(0),
+    })
+}
+
+#[tauri::command]
+pub async fn sequencer_stop() -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SequencerStop)
+}
+
+/// Save the current sound, tempo and arrangement as the project
+#[tauri::command]
+pub async fn save_project(app: tauri::AppHandle) -> Result<(), AudioError> {
+    let project = Project::new(
+        get_patch().await?,
+        get_tempo().await?,
+        crate::audio::get_arrangement()?,
+    );
+    project.save(&app).map_err(AudioError::Project)
+}
+
+/// Restore the saved project. Returns false if there isn't one
+#[tauri::command]
+pub async fn load_project(app: tauri::AppHandle) -> Result<bool, AudioError> {
+    let project = match Project::load(&app).map_err(AudioError::Project)? {
+        Some(project) => project,
+        None => return Ok(false),
+    };
+    queue_audio_event(AudioEvent::SequencerStop)?;
+    load_patch(project.patch).await?;
+    set_tempo(project.tempo).await?;
+    set_arrangement(project.arrangement).await?;
+    Ok(true)
+}
+
+/// List the audio input devices
+#[tauri::command]
+pub async fn list_input_devices() -> Result<Vec<AudioDeviceInfo>, AudioError> {
+    crate::audio::list_input_devices()
+}
+
+/// Run an input device (the default if no name is given) through the delay,
+/// filter and effects, so Harphonium can be used as an effects processor.
+/// The device has to support the output's sample rate
+#[tauri::command]
+pub async fn start_audio_input(device: Option<String>) -> Result<(), AudioError> {
+    crate::audio::start_audio_input(device)
+}
+
+#[tauri::command]
+pub async fn stop_audio_input() -> Result<(), AudioError> {
+    crate::audio::stop_audio_input()
+}
+
+/// Level of the audio input going into the effects (0.0 to 2.0)
+#[tauri::command]
+pub async fn set_input_level(level: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetInputLevel { level })
+}
+
+#[tauri::command]
+pub async fn get_input_level() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetInputLevel)? {
+        AudioEventResult::ValueF32(level) => Ok(level),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Detect the pitch of the audio input, for tuning an instrument. While on,
+/// readings are also pushed as `tuner` events (null when there's no clear pitch)
+#[tauri::command]
+pub async fn set_tuner_enabled(enabled: bool) {
+    crate::audio::set_tuner_enabled(enabled)
+}
+
+/// The note the tuner hears and how many cents off it is, or null if
+/// there's no clear pitch (or the tuner or audio input is off)
+#[tauri::command]
+pub async fn get_tuner_reading() -> Option<TunerReading> {
+    crate::audio::get_tuner_reading()
+}
+
+/// Play the synth from the audio input: its pitch sets the oscillator
+/// frequency (gliding between notes) and its level opens the gate, so
+/// singing or whistling becomes a synth line. Needs the audio input running
+#[tauri::command]
+pub async fn set_pitch_tracking(settings: PitchTrackingSettings) {
+    crate::audio::set_pitch_tracking(settings)
+}
+
+#[tauri::command]
+pub async fn get_pitch_tracking() -> PitchTrackingSettings {
+    crate::audio::get_pitch_tracking()
+}
+
+/// "synth" plays the voices with the audio input mixed in; "vocoder" runs the
+/// voices through a vocoder with the audio input as the modulator
+#[tauri::command]
+pub async fn set_engine_mode(mode: String) -> Result<(), AudioError> {
+    let mode = EngineMode::from_str(&mode).ok_or(AudioError::InvalidValue {
+        kind: "engine mode",
+        value: mode,
+    })?;
+    queue_audio_event(AudioEvent::SetEngineMode { mode })
+}
+
+#[tauri::command]
+pub async fn get_engine_mode() -> Result<String, AudioError> {
+    match handle_audio_event(AudioEvent::GetEngineMode)? {
+        AudioEventResult::ValueEngineMode(mode) => Ok(mode.as_str().to_string()),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Number of vocoder bands, 4 to 32
+#[tauri::command]
+pub async fn set_vocoder_bands(bands: u32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetVocoderBands { bands })
+}
+
+#[tauri::command]
+pub async fn get_vocoder_bands() -> Result<u32, AudioError> {
+    match handle_audio_event(AudioEvent::GetVocoderBands)? {
+        AudioEventResult::ValueU32(bands) => Ok(bands),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Shift the vocoder's formants, -12 to 12 semitones
+#[tauri::command]
+pub async fn set_formant_shift(semitones: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetFormantShift { semitones })
+}
+
+#[tauri::command]
+pub async fn get_formant_shift() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetFormantShift)? {
+        AudioEventResult::ValueF32(semitones) => Ok(semitones),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// How much the audio input's level moves `destination`: "filter_cutoff"
+/// (-1.0 to 1.0, up to 4 octaves either way) or "amplitude" (0.0 to 1.0, the
+/// voices follow the input's level)
+#[tauri::command]
+pub async fn set_input_envelope_amount(destination: String, amount: f32) -> Result<(), AudioError> {
+    let destination = ModDestination::from_str(&destination).ok_or(AudioError::InvalidValue {
+        kind: "mod destination",
+        value: destination,
+    })?;
+    queue_audio_event(AudioEvent::SetInputEnvelopeAmount {
+        destination,
+        amount,
+    })
+}
+
+#[tauri::command]
+pub async fn get_input_envelope_amount(destination: String) -> Result<f32, AudioError> {
+    let destination = ModDestination::from_str(&destination).ok_or(AudioError::InvalidValue {
+        kind: "mod destination",
+        value: destination,
+    })?;
+    match handle_audio_event(AudioEvent::GetInputEnvelopeAmount { destination })? {
+        AudioEventResult::ValueF32(amount) => Ok(amount),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Record the output to a numbered WAV take, returning its path. Takes go
+/// in the app's "recordings" folder unless `options.directory` is set.
+/// Progress comes as `recording-status` events
+#[tauri::command]
+pub async fn start_recording(
+    app: tauri::AppHandle,
+    mut options: RecordingOptions,
+) -> Result<String, AudioError> {
+    if options.directory.is_empty() {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| AudioError::Recording(e.to_string()))?;
+        options.directory = dir.join("recordings").to_string_lossy().into_owned();
+    }
+    crate::audio::start_recording(options)
+}
+
+#[tauri::command]
+pub async fn stop_recording() -> Result<(), AudioError> {
+    crate::audio::stop_recording()
+}
+
+#[tauri::command]
+pub async fn get_recording_status() -> RecordingStatus {
+    crate::audio::get_recording_status()
+}
+
+/// Save audio somewhere the user can get at it: through the save dialog on
+/// desktop, or the share sheet on Android. Exports the recorded `take` (a
+/// path from `start_recording`) if given, otherwise renders the last captured
+/// performance at `sample_rate` (96kHz by default). `format` re-encodes it,
+/// see `render_performance`. Returns false if the user cancelled
+#[tauri::command]
+pub async fn export_audio(
+    app: tauri::AppHandle,
+    take: Option<String>,
+    format: Option<String>,
+    sample_rate: Option<u32>,
+) -> Result<bool, AudioError> {
+    let format = format.map(parse_export_format).transpose()?;
+    let file = crate::export::prepare(&app, take.as_deref(), format, sample_rate.unwrap_or(96000))?;
+    crate::export::deliver(&app, &file)
+}
+
+/// Start logging every event sent to the synth to a JSONL file, for
+/// reproducing glitches with `replay_event_log`
+#[tauri::command]
+pub async fn start_event_log(path: String) -> Result<(), AudioError> {
+    crate::audio::start_event_log(&path)
+}
+
+/// Stop logging events. Returns the number logged
+#[tauri::command]
+pub async fn stop_event_log() -> Result<usize, AudioError> {
+    crate::audio::stop_event_log()
+}
+
+/// Play an event log back through the synth with its original timing.
+/// Returns its number of events; playback carries on in the background
+#[tauri::command]
+pub async fn replay_event_log(path: String) -> Result<usize, AudioError> {
+    crate::audio::replay_event_log(&path)
+}
+
+/// Switch play mode: "keyboard", "fretless" or "harp". Each sets mono,
+/// note priority, voice stealing, quantize and strum to suit it
+#[tauri::command]
+pub async fn set_instrument_mode(mode: String) -> Result<(), AudioError> {
+    let mode = InstrumentMode::from_str(&mode).ok_or(AudioError::InvalidValue {
+        kind: "instrument mode",
+        value: mode,
+    })?;
+    queue_audio_event(AudioEvent::SetInstrumentMode { mode })
+}
+
+#[tauri::command]
+pub async fn get_instrument_mode() -> Result<String, AudioError> {
+    match handle_audio_event(AudioEvent::GetInstrumentMode)? {
+        AudioEventResult::ValueInstrumentMode(mode) => Ok(mode.as_str().to_string()),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// How quickly notes follow pitch changes, as halfway times in seconds for
+/// rising (`up`) and falling (`down`) pitch. Longer hides finger jitter in
+/// fretless mode, at the cost of audible slides
+#[tauri::command]
+pub async fn set_frequency_smoothing(smoothing: Smoothing) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetFrequencySmoothing { smoothing })
+}
+
+#[tauri::command]
+pub async fn get_frequency_smoothing() -> Result<Smoothing, AudioError> {
+    match handle_audio_event(AudioEvent::GetFrequencySmoothing)? {
+        AudioEventResult::ValueSmoothing(smoothing) => Ok(smoothing),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Smoothing of the gate into the envelope, as halfway times in seconds
+/// (up to 20ms). Longer suppresses clicks when notes retrigger; shorter
+/// keeps attacks snappy
+#[tauri::command]
+pub async fn set_gate_smoothing(smoothing: Smoothing) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetGateSmoothing { smoothing })
+}
+
+#[tauri::command]
+pub async fn get_gate_smoothing() -> Result<Smoothing, AudioError> {
+    match handle_audio_event(AudioEvent::GetGateSmoothing)? {
+        AudioEventResult::ValueSmoothing(smoothing) => Ok(smoothing),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Smoothing of the envelope's output, as halfway times in seconds (up to 10ms)
+#[tauri::command]
+pub async fn set_envelope_smoothing(smoothing: Smoothing) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetEnvelopeSmoothing { smoothing })
+}
+
+#[tauri::command]
+pub async fn get_envelope_smoothing() -> Result<Smoothing, AudioError> {
+    match handle_audio_event(AudioEvent::GetEnvelopeSmoothing)? {
+        AudioEventResult::ValueSmoothing(smoothing) => Ok(smoothing),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Split the keyboard: notes below `frequency` play `lower_patch` on
+/// `lower_voices` voices of their own, the rest play the current patch.
+/// Only the lower patch's waveform, envelope and unison are used
+#[tauri::command]
+pub async fn set_key_split(
+    frequency: f32,
+    lower_voices: usize,
+    lower_patch: Patch,
+) -> Result<(), AudioError> {
+    let split = KeySplit {
+        frequency,
+        lower_voices,
+        lower: lower_patch,
+    };
+    queue_audio_event(AudioEvent::SetKeySplit { split })
+}
+
+#[tauri::command]
+pub async fn clear_key_split() -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::ClearKeySplit)
+}
+
+/// The keyboard split, or None if the whole keyboard plays the patch
+#[tauri::command]
+pub async fn get_key_split() -> Result<Option<KeySplit>, AudioError> {
+    match handle_audio_event(AudioEvent::GetKeySplit)? {
+        AudioEventResult::ValueKeySplit(split) => Ok(split),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Layer `patch` over the current sound on every note. Levels default to
+/// 1.0 and pans to the centre
+#[tauri::command]
+pub async fn set_layer(
+    patch: Patch,
+    mix: Option<LayerMix>,
+    main_mix: Option<LayerMix>,
+) -> Result<(), AudioError> {
+    let layer = Layer {
+        patch,
+        mix: mix.unwrap_or_default(),
+        main_mix: main_mix.unwrap_or_default(),
+    };
+    queue_audio_event(AudioEvent::SetLayer { layer })
+}
+
+/// Level and pan of the layer, and of the main patch under it
+#[tauri::command]
+pub async fn set_layer_mix(mix: LayerMix, main_mix: LayerMix) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetLayerMix { mix, main_mix })
+}
+
+#[tauri::command]
+pub async fn clear_layer() -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::ClearLayer)
+}
+
+/// The layered patch and mix, or None if only the main patch plays
+#[tauri::command]
+pub async fn get_layer() -> Result<Option<Layer>, AudioError> {
+    match handle_audio_event(AudioEvent::GetLayer)? {
+        AudioEventResult::ValueLayer(layer) => Ok(layer),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// A computer keyboard key went down or up, by physical key as in
+/// KeyboardEvent.code. The home row plays the white keys from A, the row
+/// above the black keys; Z and X shift the octave, C and V the velocity.
+/// Returns whether the key is mapped, so the window can swallow it
+#[tauri::command]
+pub async fn qwerty_key(code: String, pressed: bool) -> Result<bool, AudioError> {
+    crate::audio::qwerty_key(&code, pressed)
+}
+
+/// Release every note held from the computer keyboard, e.g. when the window
+/// loses focus and won't see the keys come up
+#[tauri::command]
+pub async fn qwerty_release_all() -> Result<(), AudioError> {
+    crate::audio::qwerty_release_all()
+}
+
+#[tauri::command]
+pub async fn set_qwerty_settings(settings: QwertySettings) {
+    crate::audio::set_qwerty_settings(settings)
+}
+
+#[tauri::command]
+pub async fn get_qwerty_settings() -> QwertySettings {
+    crate::audio::get_qwerty_settings()
+}
+
+/// Bend every voice's pitch, from -1.0 (down by the pitch bend range) to
+/// 1.0 (up by it). 0 is unbent
+#[tauri::command]
+pub async fn set_pitch_bend(bend: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetPitchBend { bend })
+}
+
+/// Choose what a game controller's buttons and axes do. Buttons play a note
+/// or a chord, axes bend the pitch or sweep the filter. Fails, keeping the
+/// old mapping, if it names a button or axis that doesn't exist
+#[tauri::command]
+pub async fn set_gamepad_mapping(mapping: GamepadMapping) -> Result<(), AudioError> {
+    crate::audio::set_gamepad_mapping(mapping)
+}
+
+#[tauri::command]
+pub async fn get_gamepad_mapping() -> GamepadMapping {
+    crate::audio::get_gamepad_mapping()
+}
+
+/// The MIDI inputs plugged in now and which are connected. The list is also
+/// pushed as a `midi-devices-changed` event whenever it changes
+#[tauri::command]
+pub async fn list_midi_inputs() -> Vec<MidiDeviceInfo> {
+    crate::audio::list_midi_inputs()
+}
+
+/// Play notes from a MIDI input, alongside any already connected
+#[tauri::command]
+pub async fn connect_midi_input(name: String) -> Result<(), AudioError> {
+    crate::audio::connect_midi_input(&name)
+}
+
+#[tauri::command]
+pub async fn disconnect_midi_input(name: String) -> Result<(), AudioError> {
+    crate::audio::disconnect_midi_input(&name)
+}
+
+/// Whether MIDI inputs are connected as soon as they're plugged in (the default)
+#[tauri::command]
+pub async fn set_midi_auto_connect(enabled: bool) {
+    crate::audio::set_midi_auto_connect(enabled)
+}
+
+#[tauri::command]
+pub async fn get_midi_auto_connect() -> bool {
+    crate::audio::get_midi_auto_connect()
+}
+
+/// Look for Bluetooth MIDI controllers for a few seconds. On Android they're
+/// listed (and connected, with auto-connect) as they're found; elsewhere
+/// they're paired in the system settings and this fails saying so
+#[tauri::command]
+pub async fn scan_bluetooth_midi() -> Result<(), AudioError> {
+    crate::audio::scan_bluetooth_midi()
+}
+
+/// How far a full pitch bend reaches, from MIDI or `set_pitch_bend`, in
+/// semitones (1 to 24, default 2)
+#[tauri::command]
+pub async fn set_pitch_bend_range(semitones: f32) -> Result<(), AudioError> {
+    queue_audio_event(AudioEvent::SetPitchBendRange { semitones })
+}
+
+#[tauri::command]
+pub async fn get_pitch_bend_range() -> Result<f32, AudioError> {
+    match handle_audio_event(AudioEvent::GetPitchBendRange)? {
+        AudioEventResult::ValueF32(semitones) => Ok(semitones),
+        _ => Err(AudioError::UnexpectedResult),
+    }
+}
+
+/// Controller mode: send notes played here, and pitch, filter and volume
+/// changes, out over MIDI on a channel of your choosing, to drive a hardware
+/// synth. Optionally mutes the internal synth. Fails if the MIDI output
+/// can't be opened (desktop only for now)
+#[tauri::command]
+pub async fn set_controller_settings(settings: ControllerSettings) -> Result<(), AudioError> {
+    crate::audio::set_controller_settings(settings)
+}
+
+#[tauri::command]
+pub async fn get_controller_settings() -> ControllerSettings {
+    crate::audio::get_controller_settings()
+}
+
+#[tauri::command]
+pub async fn list_midi_outputs() -> Vec<String> {
+    crate::audio::list_midi_outputs()
+}
+
+/// Mirror notes and parameter changes to an OSC destination over UDP, for
+/// lighting rigs and visualizers. Notes go to /harphonium/note_on and
+/// /harphonium/note_off, parameters to e.g. /harphonium/filter_cutoff
+#[tauri::command]
+pub async fn set_osc_settings(settings: OscSettings) -> Result<(), AudioError> {
+    crate::audio::set_osc_settings(settings)
+}
+
+#[tauri::command]
+pub async fn get_osc_settings() -> OscSettings {
+    crate::audio::get_osc_settings()
+}
+
+/// Jam with other devices on the network. A master broadcasts its tempo and
+/// sequencer start/stop; followers in the same session play along. Fails if
+/// the UDP port can't be opened
+#[tauri::command]
+pub async fn set_jam_settings(settings: JamSettings) -> Result<(), AudioError> {
+    crate::audio::set_jam_settings(settings)
+}
+
+#[tauri::command]
+pub async fn get_jam_settings() -> JamSettings {
+    crate::audio::get_jam_settings()
+}
+
+/// Role, the master being followed if any, and the shared tempo and transport
+#[tauri::command]
+pub async fn get_jam_status() -> JamStatus {
+    crate::audio::get_jam_status()
+}
+
+/// Start or stop the local HTTP control API, which offers presets, the
+/// patch, notes and the sequencer as REST endpoints. Localhost only unless
+/// `allow_remote` is set, which needs a `token`. Fails if the port can't be
+/// opened
+#[tauri::command]
+pub async fn set_http_api_settings(
+    app: tauri::AppHandle,
+    settings: HttpApiSettings,
+) -> Result<(), AudioError> {
+    crate::http_api::set_http_api_settings(app, settings)
+}
+
+#[tauri::command]
+pub async fn get_http_api_settings() -> HttpApiSettings {
+    crate::http_api::http_api_settings()
+}
+
+/// Size the event queue and choose what happens when it's full. Saved for
+/// the next startup, as the running queue can't change
+#[tauri::command]
+pub async fn set_event_queue_config(config: EventQueueConfig) -> Result<(), AudioError> {
+    crate::audio::set_event_queue_config(config)
+}
+
+/// The event queue settings in use, which may not be the saved ones until
+/// the app restarts
+#[tauri::command]
+pub async fn get_event_queue_config() -> EventQueueConfig {
+    crate::audio::get_event_queue_config()
+}
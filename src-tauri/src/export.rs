@@ -0,0 +1,120 @@
+// src-tauri/src/export.rs
+// Getting rendered audio and recorded takes off the device. The file is
+// prepared in the app's cache directory, then handed to the save dialog on
+// desktop or the share sheet on Android
+
+use crate::audio::{self, AudioError, ExportFormat};
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+/// Name offered for an offline render of the last performance
+const PERFORMANCE_NAME: &str = "Harphonium performance";
+
+fn export_error(e: impl std::fmt::Display) -> AudioError {
+    AudioError::Export(e.to_string())
+}
+
+/// Write the audio to export into the cache directory, returning its path.
+/// With `take`, that recording (re-encoded if `format` is given); otherwise
+/// the last captured performance rendered at `sample_rate`
+pub fn prepare(
+    app: &tauri::AppHandle,
+    take: Option<&str>,
+    format: Option<ExportFormat>,
+    sample_rate: u32,
+) -> Result<PathBuf, AudioError> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(export_error)?
+        .join("exports");
+    std::fs::create_dir_all(&dir).map_err(export_error)?;
+    match take {
+        Some(take) => {
+            let take = Path::new(take);
+            let name = take
+                .file_stem()
+                .ok_or_else(|| export_error(format!("Not a file: {}", take.display())))?;
+            match format {
+                Some(format) => {
+                    let path = dir.join(name).with_extension(format.extension());
+                    audio::convert_audio(take, &path, format)?;
+                    Ok(path)
+                }
+                None => {
+                    let path = dir.join(take.file_name().unwrap());
+                    std::fs::copy(take, &path).map_err(|e| {
+                        export_error(format!("Failed to copy {}: {}", take.display(), e))
+                    })?;
+                    Ok(path)
+                }
+            }
+        }
+        None => {
+            let format = format.unwrap_or_default();
+            let path = dir.join(format!("{}.{}", PERFORMANCE_NAME, format.extension()));
+            audio::render_capture(&path.to_string_lossy(), sample_rate, format)?;
+            Ok(path)
+        }
+    }
+}
+
+/// MIME type to share a file as, from its extension
+#[cfg(target_os = "android")]
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("flac") => "audio/flac",
+        Some("ogg") => "audio/ogg",
+        _ => "audio/wav",
+    }
+}
+
+/// Ask where to save `file`, and move it there. Returns false if the user cancelled
+#[cfg(not(target_os = "android"))]
+pub fn deliver(app: &tauri::AppHandle, file: &Path) -> Result<bool, AudioError> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let extension = file.extension().and_then(|e| e.to_str()).unwrap_or("wav");
+    let chosen = app
+        .dialog()
+        .file()
+        .set_file_name(file.file_name().unwrap().to_string_lossy())
+        .add_filter("Audio", &[extension])
+        .blocking_save_file();
+    let dest = match chosen {
+        Some(dest) => dest.into_path().map_err(export_error)?,
+        None => {
+            let _ = std::fs::remove_file(file);
+            return Ok(false);
+        }
+    };
+    // Not a rename, the destination is often on another drive
+    std::fs::copy(file, &dest)
+        .map_err(|e| export_error(format!("Failed to save {}: {}", dest.display(), e)))?;
+    let _ = std::fs::remove_file(file);
+    Ok(true)
+}
+
+/// Open the share sheet for `file`, so it can be sent to another app, saved
+/// to Drive etc. The file stays in the cache for the receiving app to read
+#[cfg(target_os = "android")]
+pub fn deliver(_app: &tauri::AppHandle, file: &Path) -> Result<bool, AudioError> {
+    use jni::objects::{JObject, JValue};
+
+    let context = ndk_context::android_context();
+    let vm = unsafe { jni::JavaVM::from_raw(context.vm().cast()) }.map_err(export_error)?;
+    let mut env = vm.attach_current_thread().map_err(export_error)?;
+    let activity = unsafe { JObject::from_raw(context.context().cast()) };
+    let path = env
+        .new_string(file.to_string_lossy())
+        .map_err(export_error)?;
+    let mime_type = env.new_string(mime_type(file)).map_err(export_error)?;
+    env.call_method(
+        &activity,
+        "shareAudioFile",
+        "(Ljava/lang/String;Ljava/lang/String;)V",
+        &[JValue::Object(&path), JValue::Object(&mime_type)],
+    )
+    .map_err(export_error)?;
+    Ok(true)
+}
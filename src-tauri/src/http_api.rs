@@ -0,0 +1,432 @@
+// src-tauri/src/http_api.rs
+// Local HTTP control API, off unless turned on: REST endpoints for presets,
+// the patch, notes and the sequencer, so home automation or a streaming
+// setup can change the sound and trigger notes. Requests run the same
+// commands as the UI. Listens on localhost only unless told otherwise, and
+// turns away anything from a browser page (an Origin header) or addressed
+// to another host name, so a web page can't reach it through the browser
+//
+//   GET  /presets                 the user's presets
+//   POST /presets/{name}          load one
+//   GET  /factory-presets         presets that ship with the app
+//   POST /factory-presets/{name}  load one
+//   GET  /patch                   the current sound
+//   POST /patch                   change some of it, e.g. {"filter_cutoff": 800}
+//   POST /notes                   play a note: {"frequency", "velocity", "duration"}
+//   POST /events                  queue a parameter change, as the frontend sends
+//                                 them (see `allowed_event` for which)
+//   GET  /transport               {"tempo"}
+//   POST /transport/play          start the sequencer, optionally {"pattern"}
+//   POST /transport/stop
+//   POST /transport/tempo         {"bpm"}
+
+use crate::audio::{get_transport_time, queue_audio_event, AudioError, AudioEvent, Patch};
+use crate::commands;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{info, warn};
+
+/// Bodies are a few numbers or a patch, never more than this
+const MAX_BODY: u64 = 64 * 1024;
+/// How long the server waits for a request before checking it's still wanted
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    /// Listen on every interface rather than just this machine. Needs a
+    /// `token`, as anyone on the network can then reach it
+    pub allow_remote: bool,
+    /// When set, requests must send it as "Authorization: Bearer <token>"
+    pub token: Option<String>,
+}
+
+impl Default for HttpApiSettings {
+    fn default() -> Self {
+        HttpApiSettings {
+            enabled: false,
+            port: 7400,
+            allow_remote: false,
+            token: None,
+        }
+    }
+}
+
+struct HttpApi {
+    settings: HttpApiSettings,
+    running: Arc<AtomicBool>,
+    /// One per address listened on
+    threads: Vec<JoinHandle<()>>,
+}
+
+static HTTP_API: Mutex<Option<HttpApi>> = Mutex::new(None);
+
+#[derive(Deserialize)]
+struct NoteRequest {
+    frequency: f32,
+    #[serde(default = "default_velocity")]
+    velocity: f32,
+    /// Seconds until the note is released
+    #[serde(default = "default_duration")]
+    duration: f32,
+}
+
+fn default_velocity() -> f32 {
+    0.8
+}
+
+fn default_duration() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Default)]
+struct PlayRequest {
+    pattern: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct TempoRequest {
+    bpm: f32,
+}
+
+/// A failed request: the status to answer with and the error for the body
+struct Failure(u16, AudioError);
+
+impl From<AudioError> for Failure {
+    fn from(e: AudioError) -> Self {
+        let status = match e {
+            AudioError::InvalidValue { .. }
+            | AudioError::InvalidWaveform { .. }
+            | AudioError::Preset(_)
+            | AudioError::Sequencer(_) => 400,
+            _ => 500,
+        };
+        Failure(status, e)
+    }
+}
+
+fn bad_request(kind: &'static str, value: impl ToString) -> Failure {
+    Failure(
+        400,
+        AudioError::InvalidValue {
+            kind,
+            value: value.to_string(),
+        },
+    )
+}
+
+fn body<T: serde::de::DeserializeOwned>(request: &mut Request) -> Result<T, Failure> {
+    let mut text = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY)
+        .read_to_string(&mut text)
+        .map_err(|e| bad_request("request body", e))?;
+    // An empty body is an empty object, for requests whose fields are all optional
+    let text = if text.trim().is_empty() { "{}" } else { &text };
+    serde_json::from_str(text).map_err(|e| bad_request("request body", e))
+}
+
+/// "/presets/My%20Pad" -> "My Pad"
+fn path_name(segment: &str) -> Result<String, Failure> {
+    percent_encoding::percent_decode_str(segment)
+        .decode_utf8()
+        .map(|name| name.into_owned())
+        .map_err(|_| bad_request("name", segment))
+}
+
+/// Merge the fields given into the current patch
+fn change_patch(changes: serde_json::Map<String, Value>) -> Result<(), Failure> {
+    let patch = tauri::async_runtime::block_on(commands::get_patch())?;
+    let mut fields = match serde_json::to_value(patch) {
+        Ok(Value::Object(fields)) => fields,
+        _ => return Err(AudioError::UnexpectedResult.into()),
+    };
+    for (key, value) in changes {
+        if !fields.contains_key(&key) {
+            return Err(bad_request("parameter", key));
+        }
+        fields.insert(key, value);
+    }
+    let patch: Patch =
+        serde_json::from_value(Value::Object(fields)).map_err(|e| bad_request("patch", e))?;
+    tauri::async_runtime::block_on(commands::load_patch(patch))?;
+    Ok(())
+}
+
+/// Play a note now and release it `duration` seconds later. The synth
+/// keeps the time, as it does for sequenced notes
+fn play_note(note: NoteRequest) -> Result<(), Failure> {
+    if !note.frequency.is_finite() || note.frequency <= 0.0 || !note.duration.is_finite() {
+        return Err(bad_request("note", format!("{} Hz", note.frequency)));
+    }
+    queue_audio_event(AudioEvent::ScheduleNote {
+        frequency: note.frequency,
+        velocity: note.velocity.clamp(0.0, 1.0),
+        start_time: get_transport_time(),
+        duration: note.duration.clamp(0.0, 60.0),
+    })?;
+    Ok(())
+}
+
+/// Events `POST /events` takes: parameter changes the synth clamps itself,
+/// and releasing every note. Anything else has an endpoint or a command
+/// that checks it first, or isn't for outside use
+fn allowed_event(event: &AudioEvent) -> bool {
+    matches!(
+        event,
+        AudioEvent::SetMasterVolume { .. }
+            | AudioEvent::SetMasterVolumeDb { .. }
+            | AudioEvent::SetWaveform { .. }
+            | AudioEvent::SetAttack { .. }
+            | AudioEvent::SetDecay { .. }
+            | AudioEvent::SetSustain { .. }
+            | AudioEvent::SetRelease { .. }
+            | AudioEvent::SetDelayTime { .. }
+            | AudioEvent::SetDelayFeedback { .. }
+            | AudioEvent::SetDelayMix { .. }
+            | AudioEvent::SetFilterCutoff { .. }
+            | AudioEvent::SetFilterResonance { .. }
+            | AudioEvent::SetFilterLfoRate { .. }
+            | AudioEvent::SetFilterLfoDepth { .. }
+            | AudioEvent::SetVibratoRate { .. }
+            | AudioEvent::SetVibratoDepth { .. }
+            | AudioEvent::SetUnisonVoices { .. }
+            | AudioEvent::SetUnisonDetune { .. }
+            | AudioEvent::SetUnisonSpread { .. }
+            | AudioEvent::SetRingModMix { .. }
+            | AudioEvent::SetRingModRatio { .. }
+            | AudioEvent::SetStereoWidth { .. }
+            | AudioEvent::SetVoiceSpread { .. }
+            | AudioEvent::SetPitchBend { .. }
+            | AudioEvent::SetTempo { .. }
+            | AudioEvent::NoteOffAll
+    )
+}
+
+fn queue_event(event: AudioEvent) -> Result<(), Failure> {
+    if !allowed_event(&event) {
+        return Err(forbidden("event", format!("{:?}", event)));
+    }
+    Ok(queue_audio_event(event)?)
+}
+
+/// Compare without stopping at the first difference, so the time taken
+/// doesn't give away how much of a guessed token was right
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn forbidden(kind: &'static str, value: impl ToString) -> Failure {
+    Failure(
+        403,
+        AudioError::InvalidValue {
+            kind,
+            value: value.to_string(),
+        },
+    )
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+/// Turn away requests from web pages, and any without the token if there
+/// is one. Browsers always send an Origin with a page's POSTs and cross-site
+/// requests, and the Host check stops a rebound DNS name reaching localhost
+fn check_access(settings: &HttpApiSettings, request: &Request) -> Result<(), Failure> {
+    if let Some(origin) = header(request, "Origin") {
+        return Err(forbidden("origin", origin));
+    }
+    if !settings.allow_remote {
+        let host = header(request, "Host").unwrap_or("");
+        let name = match host.rsplit_once(':') {
+            Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+            _ => host,
+        };
+        if !matches!(name, "localhost" | "127.0.0.1" | "[::1]") {
+            return Err(forbidden("host", host));
+        }
+    }
+    if let Some(token) = &settings.token {
+        let given = header(request, "Authorization").and_then(|a| a.strip_prefix("Bearer "));
+        if !given.map_or(false, |given| same_token(given, token)) {
+            return Err(Failure(
+                401,
+                AudioError::InvalidValue {
+                    kind: "token",
+                    value: String::new(),
+                },
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn route(app: &tauri::AppHandle, request: &mut Request) -> Result<Value, Failure> {
+    use tauri::async_runtime::block_on;
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let path = url.split('?').next().unwrap_or("");
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let ok = json!({ "ok": true });
+    match (&method, segments.as_slice()) {
+        (Method::Get, ["presets"]) => Ok(json!(block_on(commands::list_presets(app.clone()))?)),
+        (Method::Post, ["presets", name]) => {
+            block_on(commands::load_preset(app.clone(), path_name(name)?))?;
+            Ok(ok)
+        }
+        (Method::Get, ["factory-presets"]) => {
+            Ok(json!(block_on(commands::list_factory_presets())?))
+        }
+        (Method::Post, ["factory-presets", name]) => {
+            block_on(commands::load_factory_preset(path_name(name)?))?;
+            Ok(ok)
+        }
+        (Method::Get, ["patch"]) => Ok(json!(block_on(commands::get_patch())?)),
+        (Method::Post, ["patch"]) => {
+            change_patch(body(request)?)?;
+            Ok(ok)
+        }
+        (Method::Post, ["notes"]) => {
+            play_note(body(request)?)?;
+            Ok(ok)
+        }
+        (Method::Post, ["events"]) => {
+            queue_event(body(request)?)?;
+            Ok(ok)
+        }
+        (Method::Get, ["transport"]) => Ok(json!({ "tempo": block_on(commands::get_tempo())? })),
+        (Method::Post, ["transport", "play"]) => {
+            let play: PlayRequest = body(request)?;
+            block_on(commands::sequencer_play(play.pattern))?;
+            Ok(ok)
+        }
+        (Method::Post, ["transport", "stop"]) => {
+            block_on(commands::sequencer_stop())?;
+            Ok(ok)
+        }
+        (Method::Post, ["transport", "tempo"]) => {
+            let tempo: TempoRequest = body(request)?;
+            block_on(commands::set_tempo(tempo.bpm))?;
+            Ok(ok)
+        }
+        _ => Err(Failure(
+            404,
+            AudioError::InvalidValue {
+                kind: "endpoint",
+                value: format!("{} {}", method, path),
+            },
+        )),
+    }
+}
+
+fn respond(app: &tauri::AppHandle, settings: &HttpApiSettings, mut request: Request) {
+    let result = check_access(settings, &request).and_then(|_| route(app, &mut request));
+    let (status, value) = match result {
+        Ok(value) => (200, value),
+        Err(Failure(status, e)) => (status, json!(e)),
+    };
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("static header is valid");
+    let response = Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        warn!("Failed to answer HTTP API request: {}", e);
+    }
+}
+
+fn listen(host: &str, port: u16) -> Result<Server, AudioError> {
+    let server = Server::http((host, port))
+        .map_err(|e| AudioError::Device(format!("HTTP API on {}: {}", host, e)))?;
+    info!("🌐 HTTP API listening on {} port {}", host, port);
+    Ok(server)
+}
+
+fn run(server: Server, app: tauri::AppHandle, settings: HttpApiSettings, running: Arc<AtomicBool>) {
+    while running.load(Ordering::Relaxed) {
+        match server.recv_timeout(RECEIVE_TIMEOUT) {
+            Ok(Some(request)) => respond(&app, &settings, request),
+            Ok(None) => {}
+            Err(e) => {
+                warn!("HTTP API stopped: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Start, stop or move the server. Fails, leaving it off, if the port can't
+/// be opened. Remote requests without a token fail first, changing nothing
+pub fn set_http_api_settings(
+    app: tauri::AppHandle,
+    mut settings: HttpApiSettings,
+) -> Result<(), AudioError> {
+    // An empty token would let anything through
+    settings.token = settings.token.filter(|token| !token.is_empty());
+    if settings.enabled && settings.allow_remote && settings.token.is_none() {
+        return Err(AudioError::InvalidValue {
+            kind: "HTTP API token",
+            value: "none, which remote access needs".to_string(),
+        });
+    }
+    let old = HTTP_API.lock().unwrap().take();
+    if let Some(mut old) = old {
+        old.running.store(false, Ordering::Relaxed);
+        for thread in old.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+    let running = Arc::new(AtomicBool::new(true));
+    let mut api = HttpApi {
+        settings: settings.clone(),
+        running: running.clone(),
+        threads: Vec::new(),
+    };
+    if settings.enabled {
+        let mut servers = Vec::new();
+        if settings.allow_remote {
+            servers.push(listen("0.0.0.0", settings.port)?);
+        } else {
+            servers.push(listen("127.0.0.1", settings.port)?);
+            // Local clients may resolve localhost to either; IPv6 is a bonus
+            match listen("::1", settings.port) {
+                Ok(server) => servers.push(server),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        for server in servers {
+            let (app, settings, running) = (app.clone(), settings.clone(), running.clone());
+            api.threads.push(std::thread::spawn(move || {
+                run(server, app, settings, running)
+            }));
+        }
+    }
+    *HTTP_API.lock().unwrap() = Some(api);
+    Ok(())
+}
+
+pub fn http_api_settings() -> HttpApiSettings {
+    match HTTP_API.lock().unwrap().as_ref() {
+        Some(api) => api.settings.clone(),
+        None => HttpApiSettings::default(),
+    }
+}
@@ -0,0 +1,260 @@
+// src-tauri/src/jni_bridge.rs
+// JNI entry points for the Android native touch layer. These queue events
+// directly onto the audio thread, bypassing Tauri IPC for expression parameters.
+
+use crate::audio::{queue_audio_event, AudioEvent};
+use jni::objects::{JByteArray, JObject, JString};
+use jni::sys::{jboolean, jfloat, jint};
+use jni::JNIEnv;
+use tracing::error;
+
+/// Note ids for touches, offset by the pointer id so they stay clear of the
+/// UI's own ids and every other input's
+const JNI_NOTE_ID_BASE: u32 = 0x7ffa_0000;
+/// `nativePlayNote`'s one note, above any pointer id Android hands out
+const JNI_PLAY_NOTE_ID: u32 = JNI_NOTE_ID_BASE + 0xffff;
+
+/// The note id for a touch, or None for a pointer id out of range (Android
+/// never gives negative ones, but it's coming across FFI)
+fn touch_note_id(pointer_id: jint) -> Option<u32> {
+    let id = u32::try_from(pointer_id).ok().filter(|&id| id < 0xffff);
+    if id.is_none() {
+        error!("Ignoring touch with pointer id {}", pointer_id);
+    }
+    id.map(|id| JNI_NOTE_ID_BASE + id)
+}
+
+fn queue_event(event: AudioEvent) {
+    if let Err(e) = queue_audio_event(event) {
+        error!("Error handling JNI audio event: {}", e);
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativePlayNote(
+    _env: JNIEnv,
+    _this: JObject,
+    frequency: jfloat,
+) {
+    queue_event(AudioEvent::PlayNote {
+        note_id: JNI_PLAY_NOTE_ID,
+        frequency,
+        velocity: 1.0,
+    });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeNoteOn(
+    _env: JNIEnv,
+    _this: JObject,
+    pointer_id: jint,
+    frequency: jfloat,
+    velocity: jfloat,
+) {
+    // Pointer ids are reused by Android once lifted, so they work as note ids
+    if let Some(note_id) = touch_note_id(pointer_id) {
+        queue_event(AudioEvent::PlayNote {
+            note_id,
+            frequency,
+            velocity,
+        });
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeNoteOff(
+    _env: JNIEnv,
+    _this: JObject,
+    pointer_id: jint,
+) {
+    if let Some(note_id) = touch_note_id(pointer_id) {
+        queue_event(AudioEvent::NoteOff { note_id });
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeStopNote(
+    _env: JNIEnv,
+    _this: JObject,
+) {
+    queue_event(AudioEvent::NoteOffAll);
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetFrequency(
+    _env: JNIEnv,
+    _this: JObject,
+    frequency: jfloat,
+) {
+    queue_event(AudioEvent::SetFrequency { frequency });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetMasterVolume(
+    _env: JNIEnv,
+    _this: JObject,
+    volume: jfloat,
+) {
+    queue_event(AudioEvent::SetMasterVolume { volume });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetAttack(
+    _env: JNIEnv,
+    _this: JObject,
+    attack: jfloat,
+) {
+    queue_event(AudioEvent::SetAttack { attack });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetDecay(
+    _env: JNIEnv,
+    _this: JObject,
+    decay: jfloat,
+) {
+    queue_event(AudioEvent::SetDecay { decay });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetSustain(
+    _env: JNIEnv,
+    _this: JObject,
+    sustain: jfloat,
+) {
+    queue_event(AudioEvent::SetSustain { sustain });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetRelease(
+    _env: JNIEnv,
+    _this: JObject,
+    release: jfloat,
+) {
+    queue_event(AudioEvent::SetRelease { release });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetDelayTime(
+    _env: JNIEnv,
+    _this: JObject,
+    delay_time: jfloat,
+) {
+    queue_event(AudioEvent::SetDelayTime { delay_time });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetDelayFeedback(
+    _env: JNIEnv,
+    _this: JObject,
+    delay_feedback: jfloat,
+) {
+    queue_event(AudioEvent::SetDelayFeedback { delay_feedback });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetDelayMix(
+    _env: JNIEnv,
+    _this: JObject,
+    delay_mix: jfloat,
+) {
+    queue_event(AudioEvent::SetDelayMix { delay_mix });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetFilterCutoff(
+    _env: JNIEnv,
+    _this: JObject,
+    cutoff: jfloat,
+) {
+    queue_event(AudioEvent::SetFilterCutoff { cutoff });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeSetFilterResonance(
+    _env: JNIEnv,
+    _this: JObject,
+    resonance: jfloat,
+) {
+    queue_event(AudioEvent::SetFilterResonance { resonance });
+}
+
+// MIDI devices opened by the activity (see BleMidi.kt and UsbMidi.kt).
+// Their bytes go to the same parser as desktop MIDI input
+
+fn get_string(env: &mut JNIEnv, s: &JString) -> Option<String> {
+    match env.get_string(s) {
+        Ok(s) => Some(s.into()),
+        Err(e) => {
+            error!("Bad string from JNI: {}", e);
+            None
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeMidiDeviceAdded(
+    mut env: JNIEnv,
+    _this: JObject,
+    id: JString,
+    name: JString,
+) {
+    if let (Some(id), Some(name)) = (get_string(&mut env, &id), get_string(&mut env, &name)) {
+        crate::audio::midi_device_added(id, name);
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeMidiDeviceRemoved(
+    mut env: JNIEnv,
+    _this: JObject,
+    id: JString,
+) {
+    if let Some(id) = get_string(&mut env, &id) {
+        crate::audio::midi_device_removed(id);
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeMidiDeviceFailed(
+    mut env: JNIEnv,
+    _this: JObject,
+    id: JString,
+) {
+    if let Some(id) = get_string(&mut env, &id) {
+        crate::audio::midi_device_failed(id);
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeMidiData(
+    env: JNIEnv,
+    _this: JObject,
+    source: jint,
+    data: JByteArray,
+    offset: jint,
+    count: jint,
+) {
+    let mut bytes = vec![0i8; count.max(0) as usize];
+    if let Err(e) = env.get_byte_array_region(&data, offset, &mut bytes) {
+        error!("Bad MIDI data from JNI: {}", e);
+        return;
+    }
+    let bytes: Vec<u8> = bytes.into_iter().map(|b| b as u8).collect();
+    crate::audio::midi_data(source as u32, &bytes);
+}
+
+/// A USB device the activity has opened, to be read through AMidi
+#[no_mangle]
+pub extern "system" fn Java_uk_co_joebutton_harphonium_MainActivity_nativeOpenUsbMidi(
+    mut env: JNIEnv,
+    _this: JObject,
+    id: JString,
+    source: jint,
+    device: JObject,
+) -> jboolean {
+    match get_string(&mut env, &id) {
+        Some(id) => crate::audio::open_usb_midi(&mut env, id, source as u32, &device) as jboolean,
+        None => 0,
+    }
+}
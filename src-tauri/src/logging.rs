@@ -0,0 +1,124 @@
+// Logging through `tracing`: lines go to stdout (logcat on Android) and to a
+// ring buffer of recent lines the UI can fetch, so problems reported from
+// phones can be debugged without a cable attached
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Recent lines kept for `recent_logs`
+const LOG_BUFFER_LINES: usize = 500;
+/// Levels used when `RUST_LOG` isn't set
+const DEFAULT_FILTER: &str = "info";
+
+static LOG_BUFFER: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Install the logger. Levels come from `RUST_LOG` if it's set
+pub fn init() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let (filter, handle) = reload::Layer::new(filter);
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(RingBufferLayer);
+    #[cfg(target_os = "android")]
+    let result = registry.with(LogcatLayer).try_init();
+    #[cfg(not(target_os = "android"))]
+    let result = registry.with(tracing_subscriber::fmt::layer()).try_init();
+    if result.is_ok() {
+        let _ = FILTER_HANDLE.set(handle);
+    }
+}
+
+/// Change the log levels, in `RUST_LOG` syntax, e.g. "info,harphonium_lib::audio=debug"
+pub fn set_filter(directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(directives)
+        .map_err(|e| format!("Invalid log filter {}: {}", directives, e))?;
+    FILTER_HANDLE
+        .get()
+        .ok_or_else(|| "Logging not initialized".to_string())?
+        .reload(filter)
+        .map_err(|e| format!("Failed to set log filter: {}", e))
+}
+
+/// The most recent log lines, oldest first
+pub fn recent_logs() -> Vec<String> {
+    LOG_BUFFER
+        .lock()
+        .map(|buffer| buffer.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Collects an event's message and any other fields into one line
+struct LineVisitor(String);
+
+impl Visit for LineVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+fn format_line(event: &Event<'_>) -> String {
+    let mut visitor = LineVisitor(String::new());
+    event.record(&mut visitor);
+    visitor.0
+}
+
+struct RingBufferLayer;
+
+impl<S: Subscriber> Layer<S> for RingBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let line = format!(
+            "{} {}: {}",
+            metadata.level(),
+            metadata.target(),
+            format_line(event)
+        );
+        if let Ok(mut buffer) = LOG_BUFFER.lock() {
+            if buffer.len() == LOG_BUFFER_LINES {
+                buffer.pop_front();
+            }
+            buffer.push_back(line);
+        }
+    }
+}
+
+/// Forwards log lines to logcat, where stdout doesn't go
+#[cfg(target_os = "android")]
+struct LogcatLayer;
+
+#[cfg(target_os = "android")]
+impl<S: Subscriber> Layer<S> for LogcatLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        use android_log_sys::{__android_log_write, LogPriority};
+        use std::ffi::CString;
+        use tracing::Level;
+
+        let metadata = event.metadata();
+        let priority = match *metadata.level() {
+            Level::ERROR => LogPriority::ERROR,
+            Level::WARN => LogPriority::WARN,
+            Level::INFO => LogPriority::INFO,
+            Level::DEBUG => LogPriority::DEBUG,
+            Level::TRACE => LogPriority::VERBOSE,
+        };
+        let text = format!("{}: {}", metadata.target(), format_line(event)).replace('\0', "");
+        let (tag, text) = match (CString::new("harphonium"), CString::new(text)) {
+            (Ok(tag), Ok(text)) => (tag, text),
+            _ => return,
+        };
+        unsafe {
+            __android_log_write(priority as std::os::raw::c_int, tag.as_ptr(), text.as_ptr());
+        }
+    }
+}
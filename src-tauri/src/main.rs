@@ -3,13 +3,22 @@
 
 mod audio;
 mod commands;
+mod export;
+mod http_api;
+mod logging;
+mod presets;
+mod project;
 
 fn main() {
+    logging::init();
     tauri::Builder::default()
-        .setup(|_app| {
+        .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            audio::set_app_handle(app.handle().clone());
+
             // Initialize audio engine
             if let Err(e) = audio::initialize_audio() {
-                eprintln!("Failed to initialize audio: {}", e);
+                tracing::error!("Failed to initialize audio: {}", e);
                 // Continue anyway - the app can still work without audio for UI development
             }
             Ok(())
@@ -18,6 +27,7 @@ fn main() {
             commands::play_note,
             commands::set_frequency,
             commands::note_off,
+            commands::note_off_all,
             commands::set_master_volume,
             commands::get_master_volume,
             commands::set_waveform,
@@ -40,6 +50,218 @@ fn main() {
             commands::get_filter_cutoff,
             commands::set_filter_resonance,
             commands::get_filter_resonance,
+            commands::set_key_zone,
+            commands::clear_key_zones,
+            commands::list_audio_devices,
+            commands::select_audio_device,
+            commands::set_sample_rate,
+            commands::set_buffer_size,
+            commands::get_audio_config,
+            commands::pause_audio,
+            commands::resume_audio,
+            commands::shutdown_audio,
+            commands::list_audio_hosts,
+            commands::select_audio_host,
+            commands::set_exclusive_mode,
+            commands::set_realtime_priority,
+            commands::get_audio_stats,
+            commands::reset_audio_stats,
+            commands::get_dsp_load,
+            commands::get_oscilloscope,
+            commands::get_spectrum,
+            commands::get_patch,
+            commands::load_patch,
+            commands::save_preset,
+            commands::load_preset,
+            commands::list_presets,
+            commands::rename_preset,
+            commands::delete_preset,
+            commands::duplicate_preset,
+            commands::move_preset,
+            commands::list_factory_presets,
+            commands::load_factory_preset,
+            commands::randomize_patch,
+            commands::start_capture,
+            commands::stop_capture,
+            commands::export_performance,
+            commands::import_performance,
+            commands::render_performance,
+            commands::export_midi,
+            commands::load_sample,
+            commands::list_soundfont_presets,
+            commands::load_soundfont_preset,
+            commands::trigger_drum,
+            commands::strum,
+            commands::set_strum_settings,
+            commands::get_strum_settings,
+            commands::set_latch,
+            commands::get_latch,
+            commands::hold,
+            commands::get_hold,
+            commands::set_unison_voices,
+            commands::set_unison_detune,
+            commands::set_unison_spread,
+            commands::get_unison,
+            commands::set_ring_mod_mix,
+            commands::get_ring_mod_mix,
+            commands::set_ring_mod_ratio,
+            commands::get_ring_mod_ratio,
+            commands::set_vibrato_rate,
+            commands::get_vibrato_rate,
+            commands::set_vibrato_depth,
+            commands::get_vibrato_depth,
+            commands::set_vibrato_delay,
+            commands::get_vibrato_delay,
+            commands::set_filter_lfo_rate,
+            commands::get_filter_lfo_rate,
+            commands::set_filter_lfo_depth,
+            commands::get_filter_lfo_depth,
+            commands::set_filter_lfo_sync,
+            commands::get_filter_lfo_sync,
+            commands::set_tempo,
+            commands::get_tempo,
+            commands::set_lfo_shape,
+            commands::get_lfo_shape,
+            commands::set_envelope_delay,
+            commands::get_envelope_delay,
+            commands::set_envelope_hold,
+            commands::get_envelope_hold,
+            commands::set_envelope_mode,
+            commands::get_envelope_mode,
+            commands::set_filter_velocity_amount,
+            commands::get_filter_velocity_amount,
+            commands::set_mono,
+            commands::get_mono,
+            commands::set_note_priority,
+            commands::get_note_priority,
+            commands::set_steal_policy,
+            commands::get_steal_policy,
+            commands::set_voice_spread,
+            commands::get_voice_spread,
+            commands::set_voice_pan_mode,
+            commands::get_voice_pan_mode,
+            commands::set_stereo_width,
+            commands::get_stereo_width,
+            commands::set_limiter_attack,
+            commands::get_limiter_attack,
+            commands::set_limiter_release,
+            commands::get_limiter_release,
+            commands::set_limiter_ceiling,
+            commands::get_limiter_ceiling,
+            commands::set_limiter_bypass,
+            commands::get_limiter_bypass,
+            commands::set_soft_clip_drive,
+            commands::get_soft_clip_drive,
+            commands::set_soft_clip,
+            commands::get_soft_clip,
+            commands::set_master_volume_db,
+            commands::get_master_volume_db,
+            commands::set_gain_trim,
+            commands::get_gain_trim,
+            commands::get_logs,
+            commands::set_log_filter,
+            commands::get_sample_clock,
+            commands::schedule_note,
+            commands::get_transport_time,
+            commands::add_pattern,
+            commands::remove_pattern,
+            commands::set_pattern_length,
+            commands::set_pattern_step,
+            commands::set_pattern_next,
+            commands::set_song,
+            commands::set_song_mode,
+            commands::get_arrangement,
+            commands::set_arrangement,
+            commands::sequencer_play,
+            commands::sequencer_stop,
+            commands::save_project,
+            commands::load_project,
+            commands::set_quantize,
+            commands::get_quantize,
+            commands::list_input_devices,
+            commands::start_audio_input,
+            commands::stop_audio_input,
+            commands::set_input_level,
+            commands::get_input_level,
+            commands::set_tuner_enabled,
+            commands::get_tuner_reading,
+            commands::set_pitch_tracking,
+            commands::get_pitch_tracking,
+            commands::set_engine_mode,
+            commands::get_engine_mode,
+            commands::set_vocoder_bands,
+            commands::get_vocoder_bands,
+            commands::set_formant_shift,
+            commands::get_formant_shift,
+            commands::set_input_envelope_amount,
+            commands::get_input_envelope_amount,
+            commands::start_recording,
+            commands::stop_recording,
+            commands::get_recording_status,
+            commands::export_audio,
+            commands::start_event_log,
+            commands::stop_event_log,
+            commands::replay_event_log,
+            commands::set_instrument_mode,
+            commands::get_instrument_mode,
+            commands::set_frequency_smoothing,
+            commands::get_frequency_smoothing,
+            commands::set_gate_smoothing,
+            commands::get_gate_smoothing,
+            commands::set_envelope_smoothing,
+            commands::get_envelope_smoothing,
+            commands::set_key_split,
+            commands::clear_key_split,
+            commands::get_key_split,
+            commands::set_layer,
+            commands::set_layer_mix,
+            commands::clear_layer,
+            commands::get_layer,
+            commands::qwerty_key,
+            commands::qwerty_release_all,
+            commands::set_qwerty_settings,
+            commands::get_qwerty_settings,
+            commands::set_pitch_bend,
+            commands::set_gamepad_mapping,
+            commands::get_gamepad_mapping,
+            commands::list_midi_inputs,
+            commands::connect_midi_input,
+            commands::disconnect_midi_input,
+            commands::set_midi_auto_connect,
+            commands::get_midi_auto_connect,
+            commands::scan_bluetooth_midi,
+            commands::set_pitch_bend_range,
+            commands::get_pitch_bend_range,
+            commands::set_controller_settings,
+            commands::get_controller_settings,
+            commands::list_midi_outputs,
+            commands::set_osc_settings,
+            commands::get_osc_settings,
+            commands::set_jam_settings,
+            commands::get_jam_settings,
+            commands::get_jam_status,
+            commands::set_http_api_settings,
+            commands::get_http_api_settings,
+            commands::set_output_ceiling,
+            commands::get_output_ceiling,
+            commands::set_effect_order,
+            commands::get_effect_order,
+            commands::list_slot_effects,
+            commands::set_slot_effect,
+            commands::set_slot_param,
+            commands::get_effect_slot,
+            commands::set_effect_mix,
+            commands::get_effect_mix,
+            commands::set_reverb_type,
+            commands::get_reverb_type,
+            commands::set_delay_low_cut,
+            commands::get_delay_low_cut,
+            commands::set_delay_high_cut,
+            commands::get_delay_high_cut,
+            commands::set_delay_mode,
+            commands::get_delay_mode,
+            commands::set_event_queue_config,
+            commands::get_event_queue_config,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
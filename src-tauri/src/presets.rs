@@ -0,0 +1,206 @@
+// src-tauri/src/presets.rs
+// The user's preset bank, stored as JSON in the app data directory, and the
+// factory bank compiled into the binary
+
+use crate::audio::{KeySplit, Layer, Patch};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+
+/// Version of the on-disk bank format. Bump this and add a migration in
+/// `PresetBank::load` when the layout changes
+const BANK_VERSION: u32 = 1;
+const BANK_FILE: &str = "presets/bank.json";
+
+/// Factory patches, in the same format as the user's bank
+const FACTORY_BANK: &[u8] = include_bytes!("../presets/factory.json");
+
+/// Everything about a preset except the sound itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresetInfo {
+    pub name: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Seconds since the Unix epoch
+    pub created: u64,
+    pub modified: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    #[serde(flatten)]
+    pub info: PresetInfo,
+    pub patch: Patch,
+    /// The keyboard split saved with the patch. Presets without one play
+    /// the whole keyboard with the patch
+    #[serde(default)]
+    pub split: Option<KeySplit>,
+    /// A second patch layered over this one, if any
+    #[serde(default)]
+    pub layer: Option<Layer>,
+}
+
+/// An ordered list of presets. The order is the user's, see `move_preset`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresetBank {
+    pub version: u32,
+    pub presets: Vec<Preset>,
+}
+
+impl Default for PresetBank {
+    fn default() -> Self {
+        PresetBank {
+            version: BANK_VERSION,
+            presets: Vec::new(),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn bank_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(BANK_FILE))
+}
+
+impl PresetBank {
+    /// Load the user's bank, or an empty one if nothing has been saved yet
+    pub fn load(app: &tauri::AppHandle) -> Result<Self, String> {
+        let path = bank_path(app)?;
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let bank: PresetBank = serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        if bank.version > BANK_VERSION {
+            return Err(format!(
+                "Preset bank version {} is newer than this app supports ({})",
+                bank.version, BANK_VERSION
+            ));
+        }
+        Ok(bank)
+    }
+
+    /// The presets that ship with the app
+    pub fn factory() -> Result<Self, String> {
+        serde_json::from_slice(FACTORY_BANK)
+            .map_err(|e| format!("Failed to parse factory presets: {}", e))
+    }
+
+    /// Write the bank out. Goes via a temporary file so a crash can't leave it half written
+    pub fn save(&self, app: &tauri::AppHandle) -> Result<(), String> {
+        let path = bank_path(app)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+    }
+
+    fn position(&self, name: &str) -> Result<usize, String> {
+        self.presets
+            .iter()
+            .position(|p| p.info.name == name)
+            .ok_or_else(|| format!("No preset named {}", name))
+    }
+
+    fn ensure_free(&self, name: &str) -> Result<(), String> {
+        if self.presets.iter().any(|p| p.info.name == name) {
+            return Err(format!("A preset named {} already exists", name));
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<PresetInfo> {
+        self.presets.iter().map(|p| p.info.clone()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Preset, String> {
+        self.position(name).map(|i| &self.presets[i])
+    }
+
+    /// Store a patch, split and layer under `name`, replacing any preset of
+    /// that name (but keeping its place in the bank and its creation date)
+    pub fn store(
+        &mut self,
+        name: String,
+        author: Option<String>,
+        category: Option<String>,
+        patch: Patch,
+        split: Option<KeySplit>,
+        layer: Option<Layer>,
+    ) {
+        let now = now();
+        match self.position(&name) {
+            Ok(i) => {
+                let preset = &mut self.presets[i];
+                preset.info.author = author;
+                preset.info.category = category;
+                preset.info.modified = now;
+                preset.patch = patch;
+                preset.split = split;
+                preset.layer = layer;
+            }
+            Err(_) => self.presets.push(Preset {
+                info: PresetInfo {
+                    name,
+                    author,
+                    category,
+                    created: now,
+                    modified: now,
+                },
+                patch,
+                split,
+                layer,
+            }),
+        }
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: String) -> Result<(), String> {
+        let i = self.position(name)?;
+        self.ensure_free(&new_name)?;
+        self.presets[i].info.name = new_name;
+        self.presets[i].info.modified = now();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        let i = self.position(name)?;
+        self.presets.remove(i);
+        Ok(())
+    }
+
+    /// Copy a preset to `new_name`, placed straight after the original
+    pub fn duplicate(&mut self, name: &str, new_name: String) -> Result<(), String> {
+        let i = self.position(name)?;
+        self.ensure_free(&new_name)?;
+        let now = now();
+        let mut copy = self.presets[i].clone();
+        copy.info.name = new_name;
+        copy.info.created = now;
+        copy.info.modified = now;
+        self.presets.insert(i + 1, copy);
+        Ok(())
+    }
+
+    /// Move a preset to `index` in the bank (clamped to the end)
+    pub fn move_to(&mut self, name: &str, index: usize) -> Result<(), String> {
+        let i = self.position(name)?;
+        let preset = self.presets.remove(i);
+        let index = index.min(self.presets.len());
+        self.presets.insert(index, preset);
+        Ok(())
+    }
+}
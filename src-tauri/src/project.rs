@@ -0,0 +1,69 @@
+// src-tauri/src/project.rs
+// The user's project: the current sound plus the sequencer's patterns and
+// song, stored as JSON in the app data directory
+
+use crate::audio::{Arrangement, Patch};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::Manager;
+
+/// Version of the on-disk project format. Bump this and add a migration in
+/// `Project::load` when the layout changes
+const PROJECT_VERSION: u32 = 1;
+const PROJECT_FILE: &str = "project.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    pub patch: Patch,
+    pub tempo: f32,
+    #[serde(default)]
+    pub arrangement: Arrangement,
+}
+
+fn project_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(PROJECT_FILE))
+}
+
+impl Project {
+    pub fn new(patch: Patch, tempo: f32, arrangement: Arrangement) -> Self {
+        Project {
+            version: PROJECT_VERSION,
+            patch,
+            tempo,
+            arrangement,
+        }
+    }
+
+    /// Load the saved project, or None if nothing has been saved yet
+    pub fn load(app: &tauri::AppHandle) -> Result<Option<Self>, String> {
+        let path = project_path(app)?;
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let project: Project = serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        if project.version > PROJECT_VERSION {
+            return Err(format!(
+                "Project version {} is newer than this app supports ({})",
+                project.version, PROJECT_VERSION
+            ));
+        }
+        Ok(Some(project))
+    }
+
+    /// Write the project out. Goes via a temporary file so a crash can't leave it half written
+    pub fn save(&self, app: &tauri::AppHandle) -> Result<(), String> {
+        let path = project_path(app)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+    }
+}
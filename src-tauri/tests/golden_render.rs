@@ -0,0 +1,113 @@
+// Golden render tests: every performance log in tests/golden is rendered
+// offline and compared with the WAV of the same name next to it, so DSP
+// changes can't quietly change the sound. A missing reference fails the
+// test rather than being recorded, or every fresh checkout would pass. Record
+// new references, or re-record them all after a deliberate change to the
+// sound, with UPDATE_GOLDEN=1 cargo test --test golden_render, and commit them
+use harphonium_lib::{render_performance, PerformanceLog};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::path::{Path, PathBuf};
+
+const SAMPLE_RATE: u32 = 48000;
+/// Rendered after the last event, so releases and echoes are covered
+const TAIL_SECS: f32 = 1.0;
+/// Largest difference allowed in any sample. Far below anything audible,
+/// but above float rounding differences between platforms
+const TOLERANCE: f32 = 1e-4;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn read_reference(path: &Path) -> Vec<(f32, f32)> {
+    let mut reader = WavReader::open(path).unwrap();
+    let samples: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+    samples.chunks_exact(2).map(|s| (s[0], s[1])).collect()
+}
+
+fn write_reference(path: &Path, frames: &[(f32, f32)]) {
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    let mut writer = WavWriter::create(path, spec).unwrap();
+    for &(left, right) in frames {
+        writer.write_sample(left).unwrap();
+        writer.write_sample(right).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+/// Describe how `rendered` differs from `reference`, or None if it doesn't
+fn compare(rendered: &[(f32, f32)], reference: &[(f32, f32)]) -> Option<String> {
+    if rendered.len() != reference.len() {
+        return Some(format!(
+            "{} frames rendered, reference has {}",
+            rendered.len(),
+            reference.len()
+        ));
+    }
+    let (frame, difference) = rendered
+        .iter()
+        .zip(reference)
+        .map(|(a, b)| (a.0 - b.0).abs().max((a.1 - b.1).abs()))
+        .enumerate()
+        .fold(
+            (0, 0.0),
+            |worst, (i, d)| if d > worst.1 { (i, d) } else { worst },
+        );
+    (difference > TOLERANCE).then(|| {
+        format!(
+            "differs by {:.6} at frame {} ({:.3}s)",
+            difference,
+            frame,
+            frame as f32 / SAMPLE_RATE as f32
+        )
+    })
+}
+
+#[test]
+fn golden_renders() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut logs: Vec<PathBuf> = std::fs::read_dir(golden_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |e| e == "json"))
+        .collect();
+    logs.sort();
+    assert!(!logs.is_empty(), "No performance logs in tests/golden");
+
+    let mut failures = Vec::new();
+    for path in logs {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let log: PerformanceLog =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let rendered = render_performance(&log, SAMPLE_RATE, TAIL_SECS).unwrap();
+        assert!(
+            rendered.iter().all(|(l, r)| l.is_finite() && r.is_finite()),
+            "{}: non-finite output",
+            name
+        );
+
+        let reference = path.with_extension("wav");
+        if update {
+            write_reference(&reference, &rendered);
+            eprintln!("Recorded reference for {}", name);
+            continue;
+        }
+        if !reference.exists() {
+            failures.push(format!("{}: no reference WAV", name));
+            continue;
+        }
+        if let Some(difference) = compare(&rendered, &read_reference(&reference)) {
+            failures.push(format!("{}: {}", name, difference));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "Renders changed or references missing (record with UPDATE_GOLDEN=1 if that's intended):\n{}",
+        failures.join("\n")
+    );
+}
@@ -4,6 +4,7 @@ import Keyboard from './components/Keyboard';
 import KeyboardTab from './components/KeyboardTab';
 import SynthTab from './components/SynthTab';
 import EffectsTab from './components/EffectsTab';
+import { errorMessage } from './errors';
 import './App.css';
 
 type TabType = 'synth' | 'keyboard' | 'effects' | 'info';
@@ -50,7 +51,7 @@ function App() {
       await invoke('play_note', { frequency: frequency });
       setSynthState(`🔊 Playing: ${freqStr} Hz`);
     } catch (error) {
-      setSynthState(`Error: ${error}`);
+      setSynthState(`Error: ${errorMessage(error)}`);
     }
   }
 
@@ -64,7 +65,7 @@ function App() {
       await invoke('note_off');
       setSynthState('🔇 No keys down');
     } catch (error) {
-      setSynthState(`Error: ${error}`);
+      setSynthState(`Error: ${errorMessage(error)}`);
     }
   }
 
@@ -0,0 +1,13 @@
+// Commands fail with an AudioError, serialized as { kind, message }.
+// Anything else that's thrown is shown as it is.
+export function errorMessage(error: unknown): string {
+  if (
+    typeof error === 'object' &&
+    error !== null &&
+    'message' in error &&
+    typeof error.message === 'string'
+  ) {
+    return error.message;
+  }
+  return String(error);
+}